@@ -2,9 +2,11 @@ use heck::*;
 use std::collections::{BTreeSet, HashMap, HashSet};
 use std::mem;
 use wit_bindgen_gen_core::wit_parser::abi::{
-    AbiVariant, Bindgen, Bitcast, Instruction, LiftLower, WasmType, WitxInstruction,
+    AbiVariant, Bindgen, Bitcast, Instruction, LiftLower, WasmSignature, WasmType, WitxInstruction,
+};
+use wit_bindgen_gen_core::{
+    global_getter, wide_flags, wit_parser::*, Direction, Files, Generator, Ns, Unsupported,
 };
-use wit_bindgen_gen_core::{wit_parser::*, Direction, Files, Generator, Ns};
 
 #[derive(Default)]
 pub struct C {
@@ -31,7 +33,12 @@ pub struct C {
     // at the end.
     types: HashMap<TypeId, wit_bindgen_gen_core::Source>,
 
+    // `#define`s for constants, which are printed after all types.
+    constants: wit_bindgen_gen_core::Source,
+
     needs_string: bool,
+    needs_async_import: bool,
+    needs_async_export: bool,
 }
 
 struct Func {
@@ -65,6 +72,12 @@ struct CSig {
     params: Vec<(bool, String)>,
     ret: Return,
     retptrs: Vec<String>,
+    // Results of an `async` function, passed to its completion callback (for
+    // imports) or its `*_done` function (for exports), and whether each is
+    // passed by pointer.
+    async_results: Vec<(bool, String)>,
+    // The full signature of the `*_done` function of an `async` export.
+    done_sig: Option<String>,
 }
 
 #[derive(Debug)]
@@ -108,13 +121,38 @@ impl C {
     fn print_sig(&mut self, iface: &Interface, func: &Function) -> CSig {
         let name = format!(
             "{}_{}",
-            iface.name.to_snake_case(),
+            iface.module().to_snake_case(),
             func.name.to_snake_case()
         );
         self.names.insert(&name).expect("duplicate symbols");
-        let start = self.src.header.len();
+        let mut start = self.src.header.len();
+
+        // Asynchronous functions never return anything directly, instead their
+        // results are delivered through a callback.
+        let ret = if func.is_async {
+            Return {
+                splat_tuple: false,
+                scalar: None,
+                retptrs: Vec::new(),
+            }
+        } else {
+            self.classify_ret(iface, func)
+        };
+
+        // Async imports take a completion callback which is invoked with the
+        // results of the function, so print its type first.
+        let mut async_results = Vec::new();
+        if func.is_async && self.in_import {
+            let callback = format!("{}_callback_t", name);
+            self.names.insert(&callback).expect("duplicate symbols");
+            self.src.h("typedef void (*");
+            self.src.h(&callback);
+            self.src.h(")(void *ctx");
+            async_results = self.print_async_results(iface, func);
+            self.src.h(");\n");
+            start = self.src.header.len();
+        }
 
-        let ret = self.classify_ret(iface, func);
         match &ret.scalar {
             None => self.src.h("void"),
             Some(Scalar::OptionBool(_id)) => self.src.h("bool"),
@@ -150,7 +188,15 @@ impl C {
             self.src.h(&name);
             retptrs.push(name);
         }
-        if func.params.len() == 0 && ret.retptrs.len() == 0 {
+        if func.is_async {
+            if func.params.len() > 0 {
+                self.src.h(", ");
+            }
+            if self.in_import {
+                self.src.h(&format!("{}_callback_t callback, ", name));
+            }
+            self.src.h("void *ctx");
+        } else if func.params.len() == 0 && ret.retptrs.len() == 0 {
             self.src.h("void");
         }
         self.src.h(")");
@@ -158,13 +204,48 @@ impl C {
         let sig = self.src.header[start..].to_string();
         self.src.h(";\n");
 
+        // Async exports are completed by calling their `*_done` function with
+        // the `ctx` they were invoked with and the results of the call.
+        let mut done_sig = None;
+        if func.is_async && !self.in_import {
+            let done = format!("{}_done", name);
+            self.names.insert(&done).expect("duplicate symbols");
+            let start = self.src.header.len();
+            self.src.h("void ");
+            self.src.h(&done);
+            self.src.h("(void *ctx");
+            async_results = self.print_async_results(iface, func);
+            self.src.h(")");
+            done_sig = Some(self.src.header[start..].to_string());
+            self.src.h(";\n");
+        }
+
         CSig {
             sig,
             name,
             params,
             ret,
             retptrs,
+            async_results,
+            done_sig,
+        }
+    }
+
+    fn print_async_results(&mut self, iface: &Interface, func: &Function) -> Vec<(bool, String)> {
+        let mut results = Vec::new();
+        for (i, (_, ty)) in func.results.iter().enumerate() {
+            self.src.h(", ");
+            self.print_ty(iface, ty);
+            self.src.h(" ");
+            let pointer = self.is_arg_by_pointer(iface, ty);
+            if pointer {
+                self.src.h("*");
+            }
+            let name = format!("ret{}", i);
+            self.src.h(&name);
+            results.push((pointer, name));
         }
+        results
     }
 
     fn is_arg_by_pointer(&self, iface: &Interface, ty: &Type) -> bool {
@@ -485,7 +566,7 @@ impl C {
     }
 
     fn print_namespace(&mut self, iface: &Interface) {
-        self.src.h(&iface.name.to_snake_case());
+        self.src.h(&iface.module().to_snake_case());
         self.src.h("_");
     }
 
@@ -716,7 +797,13 @@ impl Generator for C {
         self.sizes.fill(variant, iface);
         self.in_import = variant == AbiVariant::GuestImport;
 
-        for func in iface.functions.iter() {
+        let getters = iface.globals.iter().map(global_getter).collect::<Vec<_>>();
+        for func in iface.functions.iter().chain(&getters) {
+            // Results of async imports are passed as parameters to a
+            // callback, so they don't need space in the return area.
+            if func.is_async && variant == AbiVariant::GuestImport {
+                continue;
+            }
             let sig = iface.wasm_signature(variant, func);
             if let Some(results) = sig.retptr {
                 self.i64_return_pointer_area_size =
@@ -750,7 +837,7 @@ impl Generator for C {
             for (i, field) in record.fields.iter().enumerate() {
                 self.src.h(&format!(
                     "#define {}_{}_{} (1 << {})\n",
-                    iface.name.to_shouty_snake_case(),
+                    iface.module().to_shouty_snake_case(),
                     name.to_shouty_snake_case(),
                     field.name.to_shouty_snake_case(),
                     i,
@@ -830,7 +917,7 @@ impl Generator for C {
         for (i, case) in variant.cases.iter().enumerate() {
             self.src.h(&format!(
                 "#define {}_{}_{} {}\n",
-                iface.name.to_shouty_snake_case(),
+                iface.module().to_shouty_snake_case(),
                 name.to_shouty_snake_case(),
                 case.name.to_shouty_snake_case(),
                 i,
@@ -929,8 +1016,38 @@ impl Generator for C {
             .insert(id, mem::replace(&mut self.src.header, prev));
     }
 
+    fn const_(&mut self, iface: &Interface, constant: &Constant) {
+        let mut ty = &constant.ty;
+        while let Type::Id(id) = ty {
+            match &iface.types[*id].kind {
+                TypeDefKind::Type(t) => ty = t,
+                _ => break,
+            }
+        }
+        let value = match ty {
+            Type::U64 => format!("{}ULL", constant.value),
+            // The literal `9223372036854775808` doesn't fit in an `int64_t`.
+            Type::S64 if constant.value == i64::MIN.into() => {
+                "(-9223372036854775807LL - 1)".to_string()
+            }
+            Type::S64 => format!("{}LL", constant.value),
+            _ => constant.value.to_string(),
+        };
+
+        let prev = mem::take(&mut self.src.header);
+        self.docs(&constant.docs);
+        self.src.h(&format!(
+            "#define {}_{} ((",
+            iface.module().to_shouty_snake_case(),
+            constant.name.to_shouty_snake_case(),
+        ));
+        self.print_ty(iface, &constant.ty);
+        self.src.h(&format!(") {})\n", value));
+        let src = mem::replace(&mut self.src.header, prev);
+        self.constants.push_str(&src);
+    }
+
     fn import(&mut self, iface: &Interface, func: &Function) {
-        assert!(!func.is_async, "async not supported yet");
         let prev = mem::take(&mut self.src);
         let sig = iface.wasm_signature(AbiVariant::GuestImport, func);
 
@@ -939,18 +1056,15 @@ impl Generator for C {
         // signature.
         self.src.c(&format!(
             "__attribute__((import_module(\"{}\"), import_name(\"{}\")))\n",
-            iface.name, func.name
+            iface.module(),
+            func.name
         ));
         let import_name = self.names.tmp(&format!(
             "__wasm_import_{}_{}",
-            iface.name.to_snake_case(),
+            iface.module().to_snake_case(),
             func.name.to_snake_case()
         ));
-        match sig.results.len() {
-            0 => self.src.c("void"),
-            1 => self.src.c(wasm_type(sig.results[0])),
-            _ => unimplemented!("multi-value return not supported"),
-        }
+        self.src.c(wasm_return_type(&sig));
         self.src.c(" ");
         self.src.c(&import_name);
         self.src.c("(");
@@ -965,6 +1079,24 @@ impl Generator for C {
         }
         self.src.c(");\n");
 
+        // Async imports are completed by the host calling a function through
+        // the function table with the results of the call, so forward-declare
+        // that function here. Its body is generated as part of the lifting of
+        // results below.
+        let callback_name = if func.is_async {
+            let name = self.names.tmp(&format!("{}_done", import_name));
+            self.src.c(&format!("static void {}(int32_t", name));
+            for ty in sig.retptr.as_ref().unwrap() {
+                self.src.c(", ");
+                self.src.c(wasm_type(*ty));
+            }
+            self.src.c(");\n");
+            self.needs_async_import = true;
+            Some(name)
+        } else {
+            None
+        };
+
         // Print the public facing signature into the header, and since that's
         // what we are defining also print it into the C file.
         let c_sig = self.print_sig(iface, func);
@@ -972,6 +1104,11 @@ impl Generator for C {
         self.src.c(" {\n");
 
         let mut f = FunctionBindgen::new(self, c_sig, &import_name);
+        f.async_callback = callback_name;
+        if func.is_async {
+            f.locals.insert("callback").unwrap();
+            f.locals.insert("ctx").unwrap();
+        }
         for (pointer, param) in f.sig.params.iter() {
             f.locals.insert(param).unwrap();
 
@@ -998,13 +1135,12 @@ impl Generator for C {
 
         let src = mem::replace(&mut self.src, prev);
         self.funcs
-            .entry(iface.name.to_string())
+            .entry(iface.module().to_string())
             .or_insert(Vec::new())
             .push(Func { src });
     }
 
     fn export(&mut self, iface: &Interface, func: &Function) {
-        assert!(!func.is_async, "async not supported yet");
         let prev = mem::take(&mut self.src);
         let sig = iface.wasm_signature(AbiVariant::GuestExport, func);
 
@@ -1016,20 +1152,16 @@ impl Generator for C {
         // canonical ABI.
         self.src.c(&format!(
             "__attribute__((export_name(\"{}\")))\n",
-            func.name
+            iface.export_name(&func.name)
         ));
         let import_name = self.names.tmp(&format!(
             "__wasm_export_{}_{}",
-            iface.name.to_snake_case(),
+            iface.module().to_snake_case(),
             func.name.to_snake_case()
         ));
 
         let mut f = FunctionBindgen::new(self, c_sig, &import_name);
-        match sig.results.len() {
-            0 => f.gen.src.c("void"),
-            1 => f.gen.src.c(wasm_type(sig.results[0])),
-            _ => unimplemented!("multi-value return not supported"),
-        }
+        f.gen.src.c(wasm_return_type(&sig));
         f.gen.src.c(" ");
         f.gen.src.c(&import_name);
         f.gen.src.c("(");
@@ -1045,6 +1177,13 @@ impl Generator for C {
             f.gen.src.c("void");
         }
         f.gen.src.c(") {\n");
+        if func.is_async {
+            f.gen.needs_async_export = true;
+            f.locals.insert("ctx").unwrap();
+            for (_, name) in f.sig.async_results.iter() {
+                f.locals.insert(name).unwrap();
+            }
+        }
 
         // Perform all lifting/lowering and append it to our src.
         iface.call(
@@ -1059,11 +1198,24 @@ impl Generator for C {
 
         let src = mem::replace(&mut self.src, prev);
         self.funcs
-            .entry(iface.name.to_string())
+            .entry(iface.module().to_string())
             .or_insert(Vec::new())
             .push(Func { src });
     }
 
+    fn nested(
+        &mut self,
+        _iface: &Interface,
+        nested: &Interface,
+        dir: Direction,
+        files: &mut Files,
+    ) {
+        // Nested interfaces get their own header and source file, with all
+        // symbols prefixed by their full path.
+        let mut gen = self.opts.build();
+        gen.generate_one(nested, dir, files);
+    }
+
     fn finish_one(&mut self, iface: &Interface, files: &mut Files) {
         self.src.h(&format!(
             "\
@@ -1077,20 +1229,20 @@ impl Generator for C {
                 #include <stdint.h>
                 #include <stdbool.h>
             ",
-            iface.name.to_shouty_snake_case(),
+            iface.module().to_shouty_snake_case(),
         ));
         self.src.c(&format!(
             "\
                 #include <stdlib.h>
                 #include <{}.h>
             ",
-            iface.name.to_kebab_case(),
+            iface.module().to_kebab_case(),
         ));
 
         self.print_intrinsics();
 
         for (_, resource) in iface.resources.iter() {
-            let ns = iface.name.to_snake_case();
+            let ns = iface.module().to_snake_case();
             let name = resource.name.to_snake_case();
             self.src.h(&format!(
                 "
@@ -1205,7 +1357,7 @@ impl Generator for C {
                     void {0}_string_dup({0}_string_t *ret, const char *s);
                     void {0}_string_free({0}_string_t *ret);
                 ",
-                iface.name.to_snake_case(),
+                iface.module().to_snake_case(),
             ));
             self.src.c("#include <string.h>\n");
             self.src.c(&format!(
@@ -1227,7 +1379,7 @@ impl Generator for C {
                         ret->len = 0;
                     }}
                 ",
-                iface.name.to_snake_case(),
+                iface.module().to_snake_case(),
             ));
         }
 
@@ -1244,6 +1396,8 @@ impl Generator for C {
             }
         }
 
+        self.src.h(&mem::take(&mut self.constants));
+
         if self.i64_return_pointer_area_size > 0 {
             self.src.c(&format!(
                 "static int64_t RET_AREA[{}];\n",
@@ -1251,6 +1405,22 @@ impl Generator for C {
             ));
         }
 
+        if self.needs_async_import {
+            self.src.c("
+                typedef struct {
+                    void (*callback)(void);
+                    void *ctx;
+                } __wasm_async_import_t;
+            ");
+        }
+
+        if self.needs_async_export {
+            self.src.c("
+                __attribute__((import_module(\"canonical_abi\"), import_name(\"async_export_done\")))
+                void __wasm_async_export_done(int32_t ctx, int32_t ptr);
+            ");
+        }
+
         for (_module, funcs) in mem::take(&mut self.funcs) {
             for func in funcs {
                 self.src.h(&func.src.header);
@@ -1266,14 +1436,18 @@ impl Generator for C {
         self.src.h("#endif\n");
 
         files.push(
-            &format!("{}.c", iface.name.to_kebab_case()),
+            &format!("{}.c", iface.module().to_kebab_case()),
             self.src.src.as_bytes(),
         );
         files.push(
-            &format!("{}.h", iface.name.to_kebab_case()),
+            &format!("{}.h", iface.module().to_kebab_case()),
             self.src.header.as_bytes(),
         );
     }
+
+    fn unsupported(&self, iface: &Interface, _dir: Direction) -> Vec<Unsupported> {
+        wide_flags(iface)
+    }
 }
 
 struct FunctionBindgen<'a> {
@@ -1288,6 +1462,7 @@ struct FunctionBindgen<'a> {
     payloads: Vec<String>,
     params: Vec<String>,
     wasm_return: Option<String>,
+    async_callback: Option<String>,
 }
 
 impl<'a> FunctionBindgen<'a> {
@@ -1303,6 +1478,7 @@ impl<'a> FunctionBindgen<'a> {
             payloads: Vec::new(),
             params: Vec::new(),
             wasm_return: None,
+            async_callback: None,
         }
     }
 
@@ -1330,6 +1506,32 @@ impl<'a> FunctionBindgen<'a> {
         ));
     }
 
+    fn call_async_export(&mut self, args: &str, results: &mut Vec<String>) {
+        // The user's function is handed the context of this call, which it
+        // later passes to the `*_done` function along with the results...
+        let ctx = self.params.last().unwrap().clone();
+        self.src.push_str(&format!("{}({}", self.sig.name, args));
+        if args.len() > 0 {
+            self.src.push_str(", ");
+        }
+        self.src.push_str(&format!("(void*) {});\n", ctx));
+
+        // ... and the rest of this function, lowering the results and
+        // signaling completion to the host, is the body of that `*_done`
+        // function.
+        let done_sig = self.sig.done_sig.clone().unwrap();
+        self.src.indent(1);
+        self.src.push_str(&format!("}}\n\n{} {{\n", done_sig));
+        for (byref, name) in self.sig.async_results.iter() {
+            if *byref {
+                results.push(format!("*{}", name));
+            } else {
+                results.push(name.clone());
+            }
+        }
+        *self.params.last_mut().unwrap() = "(int32_t) ctx".to_string();
+    }
+
     fn store_in_retptrs(&mut self, operands: &[String]) {
         if self.sig.ret.splat_tuple {
             assert_eq!(operands.len(), 1);
@@ -1484,7 +1686,7 @@ impl Bindgen for FunctionBindgen<'_> {
             | Instruction::HandleOwnedFromI32 { ty, .. } => {
                 results.push(format!(
                     "({}_{}_t){{ {} }}",
-                    iface.name.to_snake_case(),
+                    iface.module().to_snake_case(),
                     iface.resources[*ty].name.to_snake_case(),
                     operands[0],
                 ));
@@ -1694,7 +1896,7 @@ impl Bindgen for FunctionBindgen<'_> {
                         self.src.push_str(&format!(" {} = ", ret));
                         results.push(ret);
                     }
-                    _ => unimplemented!(),
+                    _ => unreachable!("multiple results are returned through memory"),
                 }
                 self.src.push_str(self.func_to_call);
                 self.src.push_str("(");
@@ -1723,6 +1925,10 @@ impl Bindgen for FunctionBindgen<'_> {
                         args.push_str(op);
                     }
                 }
+                if func.is_async {
+                    self.call_async_export(&args, results);
+                    return;
+                }
                 match &self.sig.ret.scalar {
                     None => {
                         let mut retptrs = Vec::new();
@@ -1837,6 +2043,78 @@ impl Bindgen for FunctionBindgen<'_> {
                     }
                 }
             }
+            Instruction::CallWasmAsyncImport { results: tys, .. } => {
+                // Stash the user's callback and context in a heap allocation
+                // which is passed to the import as its context...
+                let state = self.locals.tmp("state");
+                self.src.push_str(&format!(
+                    "\
+                        __wasm_async_import_t *{state} = malloc(sizeof(__wasm_async_import_t));
+                        {state}->callback = (void (*)(void)) callback;
+                        {state}->ctx = ctx;
+                    ",
+                    state = state,
+                ));
+                let callback = self.async_callback.clone().unwrap();
+                self.src.push_str(&format!("{}(", self.func_to_call));
+                for op in operands.iter() {
+                    self.src.push_str(op);
+                    self.src.push_str(", ");
+                }
+                self.src
+                    .push_str(&format!("(int32_t) &{}, (int32_t) {});\n", callback, state));
+
+                // ... and then the rest of this function, lifting the results
+                // and invoking the user's callback, is the body of the function
+                // the host calls on completion.
+                let arg = self.locals.tmp("arg");
+                self.src.indent(1);
+                self.src
+                    .push_str(&format!("}}\n\nstatic void {}(int32_t {}", callback, arg));
+                for ty in tys.iter() {
+                    let name = self.locals.tmp("arg");
+                    self.src.push_str(&format!(", {} {}", wasm_type(*ty), name));
+                    results.push(name);
+                }
+                let state = self.locals.tmp("state");
+                self.src.push_str(&format!(
+                    ") {{
+                        __wasm_async_import_t *{state} = (__wasm_async_import_t*) {arg};
+                        {name}_callback_t callback = ({name}_callback_t) {state}->callback;
+                        void *ctx = {state}->ctx;
+                        free({state});
+                    ",
+                    state = state,
+                    arg = arg,
+                    name = self.sig.name,
+                ));
+            }
+
+            Instruction::ReturnAsyncExport { .. } => {
+                self.src.push_str(&format!(
+                    "__wasm_async_export_done({}, {});\n",
+                    operands[0], operands[1]
+                ));
+            }
+
+            Instruction::Return { func, .. } if func.is_async => {
+                let mut args = String::from("ctx");
+                let async_results = self.sig.async_results.clone();
+                for (i, (op, (byref, _))) in operands.iter().zip(async_results).enumerate() {
+                    args.push_str(", ");
+                    if byref {
+                        let name = self.locals.tmp("ret");
+                        let ty = self.gen.type_string(iface, &func.results[i].1);
+                        self.src.push_str(&format!("{} {} = {};\n", ty, name, op));
+                        args.push_str("&");
+                        args.push_str(&name);
+                    } else {
+                        args.push_str(op);
+                    }
+                }
+                self.src.push_str(&format!("callback({});\n", args));
+            }
+
             Instruction::Return { .. } if self.gen.in_import => match self.sig.ret.scalar {
                 None => self.store_in_retptrs(operands),
                 Some(Scalar::Type(_)) => {
@@ -1941,6 +2219,17 @@ fn wasm_type(ty: WasmType) -> &'static str {
     }
 }
 
+// Functions with multiple results have them returned through memory, via the
+// `retptrs` of their `CSig`, rather than with wasm multi-value, so at most one
+// result ever shows up in the raw wasm signature.
+fn wasm_return_type(sig: &WasmSignature) -> &'static str {
+    match sig.results.len() {
+        0 => "void",
+        1 => wasm_type(sig.results[0]),
+        _ => unreachable!("multiple results are returned through memory"),
+    }
+}
+
 fn int_repr(ty: Int) -> &'static str {
     match ty {
         Int::U8 => "uint8_t",
@@ -6,9 +6,6 @@ mod imports {
     test_helpers::codegen_c_import!(
         // ...
         "*.wit"
-
-        // TODO: implement async support
-        "!async-functions.wit"
     );
 }
 
@@ -16,9 +13,6 @@ mod exports {
     test_helpers::codegen_c_export!(
         "*.wit"
 
-        // TODO: implement async support
-        "!async-functions.wit"
-
         // TODO: these use push/pull buffer in exports which isn't implemented
         // yet
         "!wasi-next.wit"
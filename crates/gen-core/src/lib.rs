@@ -7,8 +7,10 @@ use wit_parser::*;
 
 pub use wit_parser;
 mod ns;
+mod support;
 
 pub use ns::Ns;
+pub use support::{unsupported, wide_flags, Unsupported};
 
 /// This is the direction from the user's perspective. Are we importing
 /// functions to call, or defining functions and exporting them to be called?
@@ -93,12 +95,61 @@ pub trait Generator {
         ty: &Type,
         docs: &Docs,
     );
-    // fn const_(&mut self, iface: &Interface, name: &str, ty: &str, val: u64, docs: &Docs);
+
+    /// Generates bindings for a constant.
+    ///
+    /// Constants have no representation in the canonical ABI, so generators
+    /// which have no way of expressing them can ignore them.
+    fn const_(&mut self, iface: &Interface, constant: &Constant) {
+        drop((iface, constant));
+    }
+
     fn import(&mut self, iface: &Interface, func: &Function);
     fn export(&mut self, iface: &Interface, func: &Function);
 
+    /// Generates bindings for a global.
+    ///
+    /// Globals are accessed through a function named after the global which
+    /// takes no parameters and returns its value, so by default this
+    /// generates bindings for that function with `import` or `export`.
+    fn global(&mut self, iface: &Interface, global: &Global, dir: Direction) {
+        let getter = global_getter(global);
+        match dir {
+            Direction::Import => self.import(iface, &getter),
+            Direction::Export => self.export(iface, &getter),
+        }
+    }
+
+    /// Generates bindings for `nested`, an interface declared within `iface`.
+    ///
+    /// This is called for each of `iface`'s nested interfaces once the rest
+    /// of `iface` has been generated. Generators typically generate `nested`
+    /// with a fresh generator of their own and emit the result as a module or
+    /// namespace within `iface`'s bindings, or as separate `files`.
+    fn nested(
+        &mut self,
+        _iface: &Interface,
+        nested: &Interface,
+        _dir: Direction,
+        _files: &mut Files,
+    ) {
+        panic!("nested interface `{}` is not supported", nested.name);
+    }
+
     fn finish_one(&mut self, iface: &Interface, files: &mut Files);
 
+    /// Returns each item declared directly in `iface` that this generator
+    /// can't generate bindings for in the direction `dir`.
+    ///
+    /// Generating bindings for these items panics, so tools such as
+    /// `wit-bindgen check` use this to report them up front instead. The
+    /// items of nested interfaces are reported by calling this for each of
+    /// them, as [`unsupported`] does.
+    fn unsupported(&self, iface: &Interface, dir: Direction) -> Vec<Unsupported> {
+        drop((iface, dir));
+        Vec::new()
+    }
+
     fn finish_all(&mut self, files: &mut Files) {
         drop(files);
     }
@@ -132,9 +183,9 @@ pub trait Generator {
             self.type_resource(iface, id);
         }
 
-        // for c in module.constants() {
-        //     self.const_(&c.name, &c.ty, c.value, &c.docs);
-        // }
+        for c in iface.constants.iter() {
+            self.const_(iface, c);
+        }
 
         for f in iface.functions.iter() {
             match dir {
@@ -143,6 +194,14 @@ pub trait Generator {
             }
         }
 
+        for g in iface.globals.iter() {
+            self.global(iface, g, dir);
+        }
+
+        for (_, nested) in iface.interfaces.iter() {
+            self.nested(iface, nested, dir, files);
+        }
+
         self.finish_one(iface, files)
     }
 
@@ -161,6 +220,22 @@ pub trait Generator {
     }
 }
 
+/// Returns the function through which `global` is accessed, which has the
+/// same name as the global and returns its value.
+pub fn global_getter(global: &Global) -> Function {
+    Function {
+        abi: Abi::Canonical,
+        is_async: false,
+        docs: global.docs.clone(),
+        attrs: global.attrs.clone(),
+        name: global.name.clone(),
+        kind: FunctionKind::Freestanding,
+        params: Vec::new(),
+        results: vec![(String::new(), global.ty)],
+        span: global.span.clone(),
+    }
+}
+
 #[derive(Default)]
 pub struct Types {
     type_info: HashMap<TypeId, TypeInfo>,
@@ -216,6 +291,9 @@ impl Types {
             }
             self.maybe_set_preview1_dtor(iface, f);
         }
+        for g in iface.globals.iter() {
+            self.set_param_result_ty(iface, &g.ty, false, true);
+        }
     }
 
     fn maybe_set_preview1_dtor(&mut self, iface: &Interface, f: &Function) {
@@ -453,7 +531,8 @@ impl From<Source> for String {
 
 #[cfg(test)]
 mod tests {
-    use super::{Generator, Source};
+    use super::{wide_flags, Generator, Source};
+    use wit_parser::Interface;
 
     #[test]
     fn simple_append() {
@@ -505,4 +584,17 @@ mod tests {
     fn generator_is_object_safe() {
         fn _assert(_: &dyn Generator) {}
     }
+
+    #[test]
+    fn wide_flags_over_64() {
+        let flags = |name: &str, n: usize| {
+            let names = (0..n).map(|i| format!("b{}", i)).collect::<Vec<_>>();
+            format!("flags {} {{ {} }}\n", name, names.join(", "))
+        };
+        let src = flags("narrow", 64) + &flags("wide", 65);
+        let iface = Interface::parse("x", &src).unwrap();
+        let found = wide_flags(&iface);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].item, "wide");
+    }
 }
@@ -0,0 +1,47 @@
+use crate::{Direction, Generator};
+use wit_parser::*;
+
+/// A construct in an interface that a generator can't produce bindings for.
+#[derive(Debug, Clone)]
+pub struct Unsupported {
+    /// The name of the type, resource, function, or nested interface which
+    /// can't be generated.
+    pub item: String,
+    /// Where `item` is declared.
+    pub span: Span,
+    /// Why `item` can't be generated.
+    pub message: String,
+}
+
+/// Returns everything in `iface`, and in the interfaces nested within it,
+/// that `gen` can't generate bindings for in the direction `dir`.
+pub fn unsupported(gen: &dyn Generator, iface: &Interface, dir: Direction) -> Vec<Unsupported> {
+    let mut ret = gen.unsupported(iface, dir);
+    for (_, nested) in iface.interfaces.iter() {
+        ret.extend(unsupported(gen, nested, dir));
+    }
+    ret
+}
+
+/// Returns each flags type in `iface` with too many flags to fit in a 64-bit
+/// integer, which is the largest representation generators support.
+pub fn wide_flags(iface: &Interface) -> Vec<Unsupported> {
+    let mut ret = Vec::new();
+    for (_, ty) in iface.types.iter() {
+        let (name, record) = match (&ty.name, &ty.kind) {
+            (Some(name), TypeDefKind::Record(r)) if r.is_flags() => (name, r),
+            _ => continue,
+        };
+        if iface.flags_repr(record).is_none() {
+            ret.push(Unsupported {
+                item: name.clone(),
+                span: ty.span.clone(),
+                message: format!(
+                    "flags with more than 64 members aren't supported, found {}",
+                    record.fields.len()
+                ),
+            });
+        }
+    }
+    ret
+}
@@ -4,7 +4,9 @@ use std::mem;
 use wit_bindgen_gen_core::wit_parser::abi::{
     AbiVariant, Bindgen, Bitcast, Instruction, LiftLower, WasmType, WitxInstruction,
 };
-use wit_bindgen_gen_core::{wit_parser::*, Direction, Files, Generator};
+use wit_bindgen_gen_core::{
+    wide_flags, wit_parser::*, Direction, Files, Generator, Types, Unsupported,
+};
 
 #[derive(Default)]
 pub struct Js {
@@ -254,19 +256,35 @@ impl Js {
     }
 
     fn docs(&mut self, docs: &Docs) {
-        let docs = match &docs.contents {
-            Some(docs) => docs,
-            None => return,
-        };
+        self.docs_and_attrs(docs, &Attributes::default());
+    }
+
+    fn docs_and_attrs(&mut self, docs: &Docs, attrs: &Attributes) {
+        let deprecated = attrs.deprecated();
+        if docs.contents.is_none() && deprecated.is_none() {
+            return;
+        }
         self.src.ts("/**\n");
-        for line in docs.lines() {
-            self.src.ts(&format!(" * {}\n", line));
+        if let Some(docs) = &docs.contents {
+            for line in docs.lines() {
+                self.src.ts(&format!(" * {}\n", line));
+            }
+        }
+        match deprecated {
+            Some("") => self.src.ts(" * @deprecated\n"),
+            Some(msg) => {
+                // The message can't end the comment early, or span lines
+                // which aren't part of it.
+                let msg = msg.replace("*/", "*\\/").replace('\n', "\n * ");
+                self.src.ts(&format!(" * @deprecated {}\n", msg));
+            }
+            None => {}
         }
         self.src.ts(" */\n");
     }
 
     fn ts_func(&mut self, iface: &Interface, func: &Function) {
-        self.docs(&func.docs);
+        self.docs_and_attrs(&func.docs, &func.attrs);
 
         let mut name_printed = false;
         if let FunctionKind::Static { .. } = &func.kind {
@@ -380,12 +398,12 @@ impl Generator for Js {
     fn type_record(
         &mut self,
         iface: &Interface,
-        _id: TypeId,
+        id: TypeId,
         name: &str,
         record: &Record,
         docs: &Docs,
     ) {
-        self.docs(docs);
+        self.docs_and_attrs(docs, &iface.types[id].attrs);
         if record.is_tuple() {
             self.src
                 .ts(&format!("export type {} = ", name.to_camel_case()));
@@ -442,12 +460,12 @@ impl Generator for Js {
     fn type_variant(
         &mut self,
         iface: &Interface,
-        _id: TypeId,
+        id: TypeId,
         name: &str,
         variant: &Variant,
         docs: &Docs,
     ) {
-        self.docs(docs);
+        self.docs_and_attrs(docs, &iface.types[id].attrs);
         if variant.is_bool() {
             self.src.ts(&format!(
                 "export type {} = boolean;\n",
@@ -514,16 +532,16 @@ impl Generator for Js {
         }
     }
 
-    fn type_alias(&mut self, iface: &Interface, _id: TypeId, name: &str, ty: &Type, docs: &Docs) {
-        self.docs(docs);
+    fn type_alias(&mut self, iface: &Interface, id: TypeId, name: &str, ty: &Type, docs: &Docs) {
+        self.docs_and_attrs(docs, &iface.types[id].attrs);
         self.src
             .ts(&format!("export type {} = ", name.to_camel_case()));
         self.print_ty(iface, ty);
         self.src.ts(";\n");
     }
 
-    fn type_list(&mut self, iface: &Interface, _id: TypeId, name: &str, ty: &Type, docs: &Docs) {
-        self.docs(docs);
+    fn type_list(&mut self, iface: &Interface, id: TypeId, name: &str, ty: &Type, docs: &Docs) {
+        self.docs_and_attrs(docs, &iface.types[id].attrs);
         self.src
             .ts(&format!("export type {} = ", name.to_camel_case()));
         self.print_list(iface, ty);
@@ -549,12 +567,12 @@ impl Generator for Js {
     fn type_push_buffer(
         &mut self,
         iface: &Interface,
-        _id: TypeId,
+        id: TypeId,
         name: &str,
         ty: &Type,
         docs: &Docs,
     ) {
-        self.docs(docs);
+        self.docs_and_attrs(docs, &iface.types[id].attrs);
         self.src
             .ts(&format!("export type {} = ", name.to_camel_case()));
         self.print_buffer(iface, true, ty);
@@ -564,12 +582,12 @@ impl Generator for Js {
     fn type_pull_buffer(
         &mut self,
         iface: &Interface,
-        _id: TypeId,
+        id: TypeId,
         name: &str,
         ty: &Type,
         docs: &Docs,
     ) {
-        self.docs(docs);
+        self.docs_and_attrs(docs, &iface.types[id].attrs);
         self.src
             .ts(&format!("export type {} = ", name.to_camel_case()));
         self.print_buffer(iface, false, ty);
@@ -650,6 +668,23 @@ impl Generator for Js {
         dst.push((func.name.to_string(), src));
     }
 
+    fn const_(&mut self, _iface: &Interface, constant: &Constant) {
+        self.docs_and_attrs(&constant.docs, &constant.attrs);
+        let suffix = if self.sizes.size(&constant.ty) == 8 {
+            "n"
+        } else {
+            ""
+        };
+        let src = format!(
+            "export const {} = {}{};\n",
+            constant.name.to_shouty_snake_case(),
+            constant.value,
+            suffix,
+        );
+        self.src.js(&src);
+        self.src.ts(&src);
+    }
+
     // As with `abi_variant` above, we're generating host-side bindings here
     // so a user "import" uses the "export" ABI variant on the inside of
     // this `Generator` implementation.
@@ -750,6 +785,20 @@ impl Generator for Js {
         }
     }
 
+    fn nested(
+        &mut self,
+        _iface: &Interface,
+        nested: &Interface,
+        dir: Direction,
+        files: &mut Files,
+    ) {
+        // Nested interfaces get their own module, named after their full
+        // path, which shares `intrinsics.js` with everything else.
+        let mut gen = self.opts.clone().build();
+        gen.generate_one(nested, dir, files);
+        self.all_intrinsics.extend(gen.all_intrinsics);
+    }
+
     fn finish_one(&mut self, iface: &Interface, files: &mut Files) {
         for (module, funcs) in mem::take(&mut self.guest_imports) {
             // TODO: `module.exports` vs `export function`
@@ -773,7 +822,7 @@ impl Generator for Js {
             ));
             self.src.js(&format!(
                 "if (!(\"{0}\" in imports)) imports[\"{0}\"] = {{}};\n",
-                module,
+                iface.module(),
             ));
 
             self.src
@@ -786,7 +835,7 @@ impl Generator for Js {
             {
                 self.src.js(&format!(
                     "imports[\"{}\"][\"{}\"] = {};\n",
-                    module,
+                    iface.module(),
                     name,
                     src.js.trim(),
                 ));
@@ -1141,13 +1190,36 @@ impl Generator for Js {
         self.src.ts(&exports.ts);
 
         let src = mem::take(&mut self.src);
-        let name = iface.name.to_kebab_case();
+        let name = iface.module().to_kebab_case();
         files.push(&format!("{}.js", name), src.js.as_bytes());
         if !self.opts.no_typescript {
             files.push(&format!("{}.d.ts", name), src.ts.as_bytes());
         }
     }
 
+    fn unsupported(&self, iface: &Interface, dir: Direction) -> Vec<Unsupported> {
+        let mut ret = wide_flags(iface);
+        // Buffers can't yet be passed to wasm, only received from it.
+        if dir == Direction::Import {
+            let mut types = Types::default();
+            types.analyze(iface);
+            for func in iface.functions.iter() {
+                let has_buffer = func.params.iter().any(|(_, ty)| {
+                    let info = types.type_info(iface, ty);
+                    info.has_push_buffer || info.has_pull_buffer
+                });
+                if has_buffer {
+                    ret.push(Unsupported {
+                        item: func.name.clone(),
+                        span: func.span.clone(),
+                        message: "passing buffers to wasm isn't supported".to_string(),
+                    });
+                }
+            }
+        }
+        ret
+    }
+
     fn finish_all(&mut self, files: &mut Files) {
         assert!(self.src.ts.is_empty());
         assert!(self.src.js.is_empty());
@@ -1957,7 +2029,7 @@ impl Bindgen for FunctionBindgen<'_> {
                 self.bind_results(sig.results.len(), results);
                 self.src.js(&self.src_object);
                 self.src.js("._exports['");
-                self.src.js(&name);
+                self.src.js(&iface.export_name(name));
                 self.src.js("'](");
                 self.src.js(&operands.join(", "));
                 self.src.js(");\n");
@@ -2011,7 +2083,7 @@ impl Bindgen for FunctionBindgen<'_> {
                 self.src.js("(promise_ctx, _prev => {\n");
                 self.src.js(&self.src_object);
                 self.src.js("._exports['");
-                self.src.js(&name);
+                self.src.js(&iface.export_name(name));
                 self.src.js("'](");
                 for op in operands {
                     self.src.js(op);
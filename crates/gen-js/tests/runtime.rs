@@ -5,6 +5,7 @@ use std::process::Command;
 use wit_bindgen_gen_core::Generator;
 
 test_helpers::runtime_tests!("ts");
+test_helpers::roundtrip_tests_js!();
 
 fn execute(name: &str, wasm: &Path, ts: &Path, imports: &Path, exports: &Path) {
     let mut dir = PathBuf::from(env!("OUT_DIR"));
@@ -26,14 +27,25 @@ fn execute(name: &str, wasm: &Path, ts: &Path, imports: &Path, exports: &Path) {
     for (file, contents) in files.iter() {
         fs::write(dir.join(file), contents).unwrap();
     }
+    fs::copy(ts, dir.join("host.ts")).unwrap();
+
+    run_host(&dir, wasm);
+}
 
+fn roundtrip(_name: &str, dir: &Path, wasm: &Path) {
+    println!("OUT_DIR = {:?}", dir);
+    run_host(dir, wasm);
+}
+
+/// Compiles the `host.ts` in `dir`, next to its bindings, and runs it with
+/// `wasm`.
+fn run_host(dir: &Path, wasm: &Path) {
     let (cmd, args) = if cfg!(windows) {
         ("cmd.exe", &["/c", "npx.cmd"] as &[&str])
     } else {
         ("npx", &[] as &[&str])
     };
 
-    fs::copy(ts, dir.join("host.ts")).unwrap();
     fs::copy("tests/helpers.d.ts", dir.join("helpers.d.ts")).unwrap();
     fs::copy("tests/helpers.js", dir.join("helpers.js")).unwrap();
     let config = dir.join("tsconfig.json");
@@ -74,7 +86,7 @@ fn execute(name: &str, wasm: &Path, ts: &Path, imports: &Path, exports: &Path) {
     fs::write(dir.join("package.json"), "{\"type\":\"module\"}").unwrap();
     let mut path = Vec::new();
     path.push(env::current_dir().unwrap());
-    path.push(dir.clone());
+    path.push(dir.to_path_buf());
     println!("{:?}", std::env::join_paths(&path));
     run(Command::new("node")
         .arg("--experimental-wasi-unstable-preview1")
@@ -0,0 +1,156 @@
+//! A generator which emits each interface as a JSON document, for tools
+//! outside of Rust to consume.
+//!
+//! The schema of the document is described in `wit_parser`'s `serde`
+//! support.
+
+use wit_bindgen_gen_core::{wit_parser, Direction, Files, Generator};
+use wit_parser::*;
+
+#[derive(Default)]
+pub struct Json {
+    opts: Opts,
+}
+
+#[derive(Default, Debug, Clone)]
+#[cfg_attr(feature = "structopt", derive(structopt::StructOpt))]
+pub struct Opts {
+    /// Emit JSON on a single line rather than pretty-printing it.
+    #[cfg_attr(feature = "structopt", structopt(long))]
+    pub compact: bool,
+
+    /// Include where each item is declared, which names files by the paths
+    /// they were parsed from.
+    #[cfg_attr(feature = "structopt", structopt(long))]
+    pub spans: bool,
+}
+
+impl Opts {
+    pub fn build(&self) -> Json {
+        let mut r = Json::new();
+        r.opts = self.clone();
+        r
+    }
+}
+
+impl Json {
+    pub fn new() -> Json {
+        Json::default()
+    }
+}
+
+impl Generator for Json {
+    // Everything is serialized at once in `finish_one`, so the per-item hooks
+    // have nothing to do.
+
+    fn type_record(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _record: &Record,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_variant(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _variant: &Variant,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_resource(&mut self, _iface: &Interface, _ty: ResourceId) {}
+
+    fn type_alias(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_list(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_pointer(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _const_: bool,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_builtin(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_push_buffer(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_pull_buffer(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn import(&mut self, _iface: &Interface, _func: &Function) {}
+
+    fn export(&mut self, _iface: &Interface, _func: &Function) {}
+
+    fn global(&mut self, _iface: &Interface, _global: &Global, _dir: Direction) {}
+
+    fn nested(
+        &mut self,
+        _iface: &Interface,
+        _nested: &Interface,
+        _dir: Direction,
+        _files: &mut Files,
+    ) {
+        // Nested interfaces are part of their parent's document.
+    }
+
+    fn finish_one(&mut self, iface: &Interface, files: &mut Files) {
+        let mut json = match (self.opts.compact, self.opts.spans) {
+            (false, false) => serde_json::to_string_pretty(iface),
+            (false, true) => serde_json::to_string_pretty(&WithSpans(iface)),
+            (true, false) => serde_json::to_string(iface),
+            (true, true) => serde_json::to_string(&WithSpans(iface)),
+        }
+        .unwrap();
+        json.push('\n');
+        files.push(&format!("{}.json", iface.name), json.as_bytes());
+    }
+}
@@ -0,0 +1,88 @@
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::path::Path;
+use wit_bindgen_gen_core::wit_parser::{Interface, SERIALIZATION_VERSION};
+
+mod json {
+    test_helpers::codegen_json!("*.wit");
+}
+
+fn verify(dir: &str, name: &str) {
+    let contents = fs::read(Path::new(dir).join(format!("{}.json", name))).unwrap();
+    let json: Value = serde_json::from_slice(&contents).unwrap();
+    assert_eq!(json["version"], SERIALIZATION_VERSION);
+    assert_eq!(json["name"], name);
+    verify_interface(&json);
+}
+
+/// Checks that every type in `iface`, and its nested interfaces, is listed at
+/// its own index and that every reference to a type is to one that exists.
+fn verify_interface(iface: &Value) {
+    let types = iface["types"].as_array().map(|t| &t[..]).unwrap_or(&[]);
+    for (i, ty) in types.iter().enumerate() {
+        assert_eq!(ty["index"], i);
+    }
+    for field in ["resources", "types", "functions", "globals", "constants"] {
+        verify_type_refs(&iface[field], types.len());
+    }
+    if let Some(nested) = iface["interfaces"].as_array() {
+        nested.iter().for_each(verify_interface);
+    }
+}
+
+fn verify_type_refs(value: &Value, ntypes: usize) {
+    match value {
+        Value::Object(map) => {
+            if let Some(Value::Number(index)) = map.get("type") {
+                let index = index.as_u64().unwrap();
+                assert!(index < ntypes as u64, "type {} doesn't exist", index);
+            }
+            map.values().for_each(|v| verify_type_refs(v, ntypes));
+        }
+        Value::Array(list) => list.iter().for_each(|v| verify_type_refs(v, ntypes)),
+        _ => {}
+    }
+}
+
+#[test]
+fn constants_to_value() {
+    let iface = Interface::parse_file("../../tests/codegen/constants.wit").unwrap();
+    let json = serde_json::to_value(&iface).unwrap();
+    let value = |name: &str| {
+        json["constants"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|c| c["name"] == name)
+            .unwrap()["value"]
+            .clone()
+    };
+    assert_eq!(value("first-fd"), 3);
+    assert_eq!(value("max-u64"), u64::MAX);
+    assert_eq!(value("min-s64"), i64::MIN);
+}
+
+/// Compares the JSON of each codegen test with a checked-in copy in
+/// `tests/golden`, so that changes to the schema are deliberate. Run with
+/// `BLESS=1` to update the copies.
+#[test]
+fn golden() {
+    for name in ["nested", "resource"] {
+        let iface = Interface::parse_file(format!("../../tests/codegen/{}.wit", name)).unwrap();
+        let json = serde_json::to_string_pretty(&iface).unwrap() + "\n";
+        let golden = Path::new("tests/golden").join(format!("{}.json", name));
+        if env::var_os("BLESS").is_some() {
+            fs::write(&golden, json).unwrap();
+            continue;
+        }
+        let expected = fs::read_to_string(&golden).unwrap_or_else(|e| {
+            panic!(
+                "failed to read {}: {}\nthis can be fixed with BLESS=1",
+                golden.display(),
+                e
+            )
+        });
+        assert_eq!(expected, json, "{} is out of date", golden.display());
+    }
+}
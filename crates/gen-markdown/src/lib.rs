@@ -11,7 +11,10 @@ pub struct Markdown {
     opts: Opts,
     sizes: SizeAlign,
     hrefs: HashMap<String, String>,
+    constants: usize,
     funcs: usize,
+    globals: usize,
+    interfaces: usize,
     types: usize,
 }
 
@@ -306,6 +309,29 @@ impl Generator for Markdown {
         self.type_alias(iface, id, name, &Type::Id(id), docs);
     }
 
+    fn const_(&mut self, iface: &Interface, constant: &Constant) {
+        if self.constants == 0 {
+            self.src.push_str("# Constants\n\n");
+        }
+        self.constants += 1;
+
+        self.src.push_str("----\n\n");
+        self.src.push_str(&format!(
+            "#### <a href=\"#{0}\" name=\"{0}\"></a> `{1}`: ",
+            constant.name.to_snake_case(),
+            constant.name,
+        ));
+        self.hrefs.insert(
+            constant.name.clone(),
+            format!("#{}", constant.name.to_snake_case()),
+        );
+        self.print_ty(iface, &constant.ty, false);
+        self.src.push_str(&format!(" = `{}`", constant.value));
+        self.src.push_str("\n\n");
+        self.docs(&constant.docs);
+        self.src.push_str("\n");
+    }
+
     fn import(&mut self, iface: &Interface, func: &Function) {
         if self.funcs == 0 {
             self.src.push_str("# Functions\n\n");
@@ -358,7 +384,58 @@ impl Generator for Markdown {
         self.import(iface, func);
     }
 
-    fn finish_one(&mut self, _iface: &Interface, files: &mut Files) {
+    fn global(&mut self, iface: &Interface, global: &Global, _dir: Direction) {
+        if self.globals == 0 {
+            self.src.push_str("# Globals\n\n");
+        }
+        self.globals += 1;
+
+        self.src.push_str("----\n\n");
+        self.src.push_str(&format!(
+            "#### <a href=\"#{0}\" name=\"{0}\"></a> `{1}`: ",
+            global.name.to_snake_case(),
+            global.name,
+        ));
+        self.hrefs.insert(
+            global.name.clone(),
+            format!("#{}", global.name.to_snake_case()),
+        );
+        self.print_ty(iface, &global.ty, false);
+        self.src.push_str("\n\n");
+        self.docs(&global.docs);
+        self.src.push_str("\n");
+    }
+
+    fn nested(
+        &mut self,
+        _iface: &Interface,
+        nested: &Interface,
+        dir: Direction,
+        files: &mut Files,
+    ) {
+        if self.interfaces == 0 {
+            self.src.push_str("# Interfaces\n\n");
+        }
+        self.interfaces += 1;
+
+        // Nested interfaces are documented in their own files, so only link
+        // to them from here.
+        let file = nested.module().to_kebab_case();
+        self.src.push_str("----\n\n");
+        self.src.push_str(&format!(
+            "#### <a href=\"#{0}\" name=\"{0}\"></a> [`{1}`]({2}.md)\n\n",
+            nested.name.to_snake_case(),
+            nested.name,
+            file,
+        ));
+        self.docs(&nested.docs);
+        self.src.push_str("\n");
+
+        let mut gen = self.opts.build();
+        gen.generate_one(nested, dir, files);
+    }
+
+    fn finish_one(&mut self, iface: &Interface, files: &mut Files) {
         let parser = Parser::new(&self.src);
         let mut events = Vec::new();
         for event in parser {
@@ -376,7 +453,11 @@ impl Generator for Markdown {
         let mut html_output = String::new();
         html::push_html(&mut html_output, events.into_iter());
 
-        files.push("bindings.md", self.src.as_bytes());
-        files.push("bindings.html", html_output.as_bytes());
+        let name = match &iface.module {
+            Some(module) => module.to_kebab_case(),
+            None => "bindings".to_string(),
+        };
+        files.push(&format!("{}.md", name), self.src.as_bytes());
+        files.push(&format!("{}.html", name), html_output.as_bytes());
     }
 }
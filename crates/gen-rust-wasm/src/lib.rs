@@ -6,9 +6,13 @@ use std::process::{Command, Stdio};
 use wit_bindgen_gen_core::wit_parser::abi::{
     AbiVariant, Bindgen, Instruction, LiftLower, WasmType, WitxInstruction,
 };
-use wit_bindgen_gen_core::{wit_parser::*, Direction, Files, Generator, Source, TypeInfo, Types};
+use wit_bindgen_gen_core::{
+    global_getter, wide_flags, wit_parser::*, Direction, Files, Generator, Source, TypeInfo, Types,
+    Unsupported,
+};
 use wit_bindgen_gen_rust::{
-    int_repr, wasm_type, FnSig, RustFunctionGenerator, RustGenerator, TypeMode,
+    has_deprecated, int_repr, mock_module, to_rust_ident, wasm_type, FnSig, MockFunction,
+    RustFunctionGenerator, RustGenerator, TypeMode,
 };
 
 #[derive(Default)]
@@ -22,6 +26,8 @@ pub struct RustWasm {
     trait_name: String,
     i64_return_pointer_area_size: usize,
     sizes: SizeAlign,
+    nesting: usize,
+    mocks: Vec<MockFunction>,
 }
 
 #[derive(Default, Debug, Clone)]
@@ -51,6 +57,11 @@ pub struct Opts {
     /// to a re-export in another crate.
     #[cfg_attr(feature = "structopt", structopt(skip))]
     pub crate_alias: Option<String>,
+
+    /// Whether imports are implemented with mocks, in a `mock` module, when
+    /// not compiling to wasm, so code calling them can be tested natively.
+    #[cfg_attr(feature = "structopt", structopt(long))]
+    pub mock: bool,
 }
 
 #[derive(Default)]
@@ -72,6 +83,45 @@ impl RustWasm {
         RustWasm::default()
     }
 
+    /// Returns the path from the bindings being generated to the module that
+    /// `export!` was invoked in, where the implementations of exports live.
+    fn super_(&self) -> String {
+        "super::".repeat(self.nesting + 1)
+    }
+
+    /// Returns the mock to generate for the import `func`, or `None` if it
+    /// can't be mocked.
+    fn mock_function(
+        &mut self,
+        iface: &Interface,
+        func: &Function,
+        param_mode: TypeMode,
+    ) -> Option<MockFunction> {
+        let prev = mem::take(&mut self.src);
+        let mut params = Vec::new();
+        for (_, ty) in func.params.iter() {
+            let info = self.types.type_info(iface, ty);
+            if info.has_push_buffer || info.has_pull_buffer {
+                self.src = prev;
+                return None;
+            }
+            self.print_ty(iface, ty, param_mode);
+            params.push(mem::take(&mut self.src).into());
+        }
+        self.print_results(iface, func);
+        let ret = mem::replace(&mut self.src, prev).into();
+        let ret_is_clone = func
+            .results
+            .iter()
+            .all(|(_, ty)| !self.types.type_info(iface, ty).has_handle);
+        Some(MockFunction {
+            name: to_rust_ident(&func.name),
+            params,
+            ret,
+            ret_is_clone,
+        })
+    }
+
     fn abi_variant(dir: Direction) -> AbiVariant {
         // This generator uses the obvious direction to ABI variant mapping.
         match dir {
@@ -103,6 +153,10 @@ impl RustGenerator for RustWasm {
         !self.in_import
     }
 
+    fn nesting(&self) -> usize {
+        self.nesting
+    }
+
     fn handle_wrapper(&self) -> Option<&'static str> {
         if self.in_import {
             None
@@ -201,10 +255,14 @@ impl RustGenerator for RustWasm {
         } else {
             // Buffers in exports are represented with special types from the
             // library support crate since they're wrappers around
-            // externally-provided handles.
+            // externally-provided handles. Pull buffers are read by us, so
+            // they're "in" buffers, and push buffers are "out" buffers.
             self.push_str("wit_bindgen_rust::exports::");
-            self.push_str(prefix);
+            self.push_str(if push { "Out" } else { "In" });
             self.push_str("Buffer");
+            if iface.all_bits_valid(ty) {
+                self.push_str("Raw");
+            }
             self.push_str("<");
             self.push_str(lt);
             self.push_str(", ");
@@ -220,15 +278,23 @@ impl Generator for RustWasm {
         self.in_import = variant == AbiVariant::GuestImport;
         self.types.analyze(iface);
         self.trait_name = iface.name.to_camel_case();
+        if self.nesting > 0 {
+            self.rustdoc(&iface.docs);
+            self.src.push_str("pub ");
+        }
         self.src
             .push_str(&format!("mod {} {{\n", iface.name.to_snake_case()));
+        if has_deprecated(iface) {
+            self.src.push_str("#![allow(deprecated)]\n");
+        }
 
         if let Some(alias) = &self.opts.crate_alias {
             self.src
                 .push_str(&format!("use {} as wit_bindgen_rust;\n", alias));
         }
 
-        for func in iface.functions.iter() {
+        let getters = iface.globals.iter().map(global_getter).collect::<Vec<_>>();
+        for func in iface.functions.iter().chain(&getters) {
             let sig = iface.wasm_signature(variant, func);
             if let Some(results) = sig.retptr {
                 self.i64_return_pointer_area_size =
@@ -250,6 +316,7 @@ impl Generator for RustWasm {
             self.src
                 .push_str("wit_bindgen_rust::bitflags::bitflags! {\n");
             self.rustdoc(docs);
+            self.rust_deprecated(&iface.types[id].attrs);
             let repr = iface
                 .flags_repr(record)
                 .expect("unsupported number of flags");
@@ -334,7 +401,7 @@ impl Generator for RustWasm {
             ";
             self.src.push_str(&format!(
                 "
-                    unsafe impl wit_bindgen_rust::HandleType for super::{ty} {{
+                    unsafe impl wit_bindgen_rust::HandleType for {sup}{ty} {{
                         #[inline]
                         fn clone(_val: i32) -> i32 {{
                             {panic_not_wasm}
@@ -362,7 +429,7 @@ impl Generator for RustWasm {
                         }}
                     }}
 
-                    unsafe impl wit_bindgen_rust::LocalHandle for super::{ty} {{
+                    unsafe impl wit_bindgen_rust::LocalHandle for {sup}{ty} {{
                         #[inline]
                         fn new(_val: i32) -> i32 {{
                             {panic_not_wasm}
@@ -392,8 +459,8 @@ impl Generator for RustWasm {
 
                     const _: () = {{
                         #[export_name = \"{ns}canonical_abi_drop_{name}\"]
-                        extern \"C\" fn drop(ty: Box<super::{ty}>) {{
-                            <super::{iface} as {iface}>::drop_{name_snake}(*ty)
+                        extern \"C\" fn drop(ty: Box<{sup}{ty}>) {{
+                            <{sup}{imp} as {iface}>::drop_{name_snake}(*ty)
                         }}
                     }};
                 ",
@@ -401,24 +468,28 @@ impl Generator for RustWasm {
                 name = iface.resources[ty].name,
                 name_snake = iface.resources[ty].name.to_snake_case(),
                 iface = iface.name.to_camel_case(),
+                imp = impl_name(iface),
                 ns = self.opts.symbol_namespace,
+                sup = self.super_(),
                 panic_not_wasm = panic,
             ));
-            let trait_ = self
-                .traits
-                .entry(iface.name.to_camel_case())
-                .or_insert(Trait::default());
-            trait_.methods.push(format!(
+            let method = format!(
                 "
                     /// An optional callback invoked when a handle is finalized
                     /// and destroyed.
-                    fn drop_{}(val: super::{}) {{
+                    fn drop_{}(val: {}{}) {{
                         drop(val);
                     }}
                 ",
                 iface.resources[ty].name.to_snake_case(),
+                self.super_(),
                 iface.resources[ty].name.to_camel_case(),
-            ));
+            );
+            self.traits
+                .entry(iface.name.to_camel_case())
+                .or_insert(Trait::default())
+                .methods
+                .push(method);
             return;
         }
 
@@ -426,6 +497,7 @@ impl Generator for RustWasm {
         let name = &resource.name;
 
         self.rustdoc(&resource.docs);
+        self.rust_deprecated(&resource.attrs);
         self.src.push_str("#[derive(Debug)]\n");
         self.src.push_str("#[repr(transparent)]\n");
         self.src
@@ -465,39 +537,61 @@ impl Generator for RustWasm {
                 name.to_camel_case(),
             ));
         } else {
+            // Mocked handles don't refer to anything, so there's nothing to
+            // drop when not compiling to wasm.
+            let cfg = if self.opts.mock {
+                "#[cfg(target_arch = \"wasm32\")]"
+            } else {
+                ""
+            };
             self.src.push_str(&format!(
                 "{{
                     fn drop(&mut self) {{
-                        #[link(wasm_import_module = \"canonical_abi\")]
-                        extern \"C\" {{
-                            #[link_name = \"resource_drop_{}\"]
-                            fn close(fd: i32);
-                        }}
-                        unsafe {{
-                            close(self.0);
+                        {}
+                        {{
+                            #[link(wasm_import_module = \"canonical_abi\")]
+                            extern \"C\" {{
+                                #[link_name = \"resource_drop_{}\"]
+                                fn close(fd: i32);
+                            }}
+                            unsafe {{
+                                close(self.0);
+                            }}
                         }}
                     }}
                 }}\n",
-                name,
+                cfg, name,
             ));
         }
 
         self.src.push_str("impl Clone for ");
         self.src.push_str(&name.to_camel_case());
+        let cfg = if self.opts.mock {
+            "#[cfg(not(target_arch = \"wasm32\"))]
+            {
+                return Self(self.0);
+            }
+            #[cfg(target_arch = \"wasm32\")]"
+        } else {
+            ""
+        };
         self.src.push_str(&format!(
             "{{
                 fn clone(&self) -> Self {{
-                    #[link(wasm_import_module = \"canonical_abi\")]
-                    extern \"C\" {{
-                        #[link_name = \"resource_clone_{}\"]
-                        fn clone(val: i32) -> i32;
-                    }}
-                    unsafe {{
-                        Self(clone(self.0))
+                    {}
+                    {{
+                        #[link(wasm_import_module = \"canonical_abi\")]
+                        extern \"C\" {{
+                            #[link_name = \"resource_clone_{}\"]
+                            fn clone(val: i32) -> i32;
+                        }}
+                        unsafe {{
+                            Self(clone(self.0))
+                        }}
                     }}
                 }}
             }}\n",
-            name,
+            cfg, name,
         ));
     }
 
@@ -557,16 +651,9 @@ impl Generator for RustWasm {
         self.print_typedef_buffer(iface, id, false, ty, docs);
     }
 
-    // fn const_(&mut self, name: &Id, ty: &Id, val: u64, docs: &str) {
-    //     self.rustdoc(docs);
-    //     self.src.push_str(&format!(
-    //         "pub const {}_{}: {} = {};\n",
-    //         ty.to_shouty_snake_case(),
-    //         name.to_shouty_snake_case(),
-    //         ty.to_camel_case(),
-    //         val
-    //     ));
-    // }
+    fn const_(&mut self, iface: &Interface, constant: &Constant) {
+        self.print_const(iface, constant);
+    }
 
     fn import(&mut self, iface: &Interface, func: &Function) {
         let is_dtor = self.types.is_preview1_dtor_func(func);
@@ -592,8 +679,35 @@ impl Generator for RustWasm {
             sig.self_arg = Some("&self".to_string());
             sig.self_is_first_param = true;
         }
+        let mock = if self.opts.mock {
+            Some(self.mock_function(iface, func, param_mode))
+        } else {
+            None
+        };
         let params = self.print_signature(iface, func, param_mode, &sig);
         self.src.push_str("{\n");
+        if let Some(mock) = &mock {
+            self.src
+                .push_str("#[cfg(not(target_arch = \"wasm32\"))]\n{\n");
+            match mock {
+                Some(mock) => self.src.push_str(&format!(
+                    "return mock::{}::call({});\n",
+                    mock.name,
+                    params.join(", ")
+                )),
+                None => {
+                    for param in params.iter() {
+                        self.src.push_str(&format!("let _ = {};\n", param));
+                    }
+                    self.src.push_str(&format!(
+                        "panic!(\"`{}` can't be mocked since it uses buffers\");\n",
+                        func.name
+                    ));
+                }
+            }
+            self.src
+                .push_str("}\n#[cfg(target_arch = \"wasm32\")]\n{\n");
+        }
         if !is_dtor {
             self.src.push_str("unsafe {\n");
         }
@@ -619,6 +733,9 @@ impl Generator for RustWasm {
         if !is_dtor {
             self.src.push_str("}\n");
         }
+        if mock.is_some() {
+            self.src.push_str("}\n");
+        }
         self.src.push_str("}\n");
 
         match &func.kind {
@@ -627,6 +744,7 @@ impl Generator for RustWasm {
                 self.src.push_str("}\n");
             }
         }
+        self.mocks.extend(mock.flatten());
     }
 
     fn export(&mut self, iface: &Interface, func: &Function) {
@@ -635,7 +753,7 @@ impl Generator for RustWasm {
 
         self.src.push_str("#[export_name = \"");
         self.src.push_str(&self.opts.symbol_namespace);
-        self.src.push_str(&func.name);
+        self.src.push_str(&iface.export_name(&func.name));
         self.src.push_str("\"]\n");
         self.src.push_str("unsafe extern \"C\" fn __wit_bindgen_");
         self.src.push_str(&rust_name);
@@ -692,6 +810,12 @@ impl Generator for RustWasm {
         let mut sig = FnSig::default();
         sig.private = true;
         sig.async_ = func.is_async;
+        if func.params.iter().any(|(_, t)| {
+            let info = self.types.type_info(iface, t);
+            info.has_push_buffer || info.has_pull_buffer
+        }) {
+            sig.generics = Some("<'a>".to_string());
+        }
         match &func.kind {
             FunctionKind::Freestanding => {}
             FunctionKind::Static { .. } => sig.use_item_name = true,
@@ -718,6 +842,30 @@ impl Generator for RustWasm {
         dst.push(mem::replace(&mut self.src, prev).into());
     }
 
+    fn nested(
+        &mut self,
+        _iface: &Interface,
+        nested: &Interface,
+        dir: Direction,
+        _files: &mut Files,
+    ) {
+        // Nested interfaces are generated as a public module within this one,
+        // and the whole thing is formatted at once at the end.
+        let mut gen = RustWasm {
+            opts: Opts {
+                rustfmt: false,
+                ..self.opts.clone()
+            },
+            nesting: self.nesting + 1,
+            ..RustWasm::new()
+        };
+        let mut files = Files::default();
+        gen.generate_one(nested, dir, &mut files);
+        for (_, contents) in files.iter() {
+            self.src.push_str(std::str::from_utf8(contents).unwrap());
+        }
+    }
+
     fn finish_one(&mut self, iface: &Interface, files: &mut Files) {
         let mut src = mem::take(&mut self.src);
 
@@ -751,6 +899,17 @@ impl Generator for RustWasm {
             }
         }
 
+        let mocks = mem::take(&mut self.mocks);
+        if !mocks.is_empty() {
+            src.push_str(&format!(
+                "/// Mocks of the functions imported from `{}`, which are called in\n\
+                 /// place of the imports when not compiling to wasm.\n\
+                 #[cfg(not(target_arch = \"wasm32\"))]\n",
+                iface.name,
+            ));
+            src.push_str(&mock_module(&mocks, None));
+        }
+
         if self.i64_return_pointer_area_size > 0 {
             src.push_str(&format!(
                 "static mut RET_AREA: [i64; {0}] = [0; {0}];\n",
@@ -786,6 +945,20 @@ impl Generator for RustWasm {
 
         files.push("bindings.rs", src.as_bytes());
     }
+
+    fn unsupported(&self, iface: &Interface, _dir: Direction) -> Vec<Unsupported> {
+        wide_flags(iface)
+    }
+}
+
+/// Returns the name of the type in the module that `export!` was invoked in
+/// which implements `iface`'s exports.
+///
+/// Nested interfaces are qualified with the names of the interfaces they're
+/// nested in, like `OuterInner`, so that interfaces of the same name nested in
+/// different parents don't clash.
+fn impl_name(iface: &Interface) -> String {
+    iface.module().to_camel_case()
 }
 
 struct FunctionBindgen<'a> {
@@ -1313,8 +1486,8 @@ impl Bindgen for FunctionBindgen<'_> {
 
             // Never used due to the call modes that this binding generator
             // uses
-            Instruction::BufferLowerHandle { .. } => unimplemented!(),
-            Instruction::BufferLiftPtrLen { .. } => unimplemented!(),
+            Instruction::BufferLowerHandle { .. } => unreachable!(),
+            Instruction::BufferLiftPtrLen { .. } => unreachable!(),
 
             Instruction::BufferLowerPtrLen { push, ty } => {
                 let block = self.blocks.pop().unwrap();
@@ -1364,9 +1537,9 @@ impl Bindgen for FunctionBindgen<'_> {
                 let size = self.gen.sizes.size(ty);
                 let mut result = String::from("wit_bindgen_rust::exports::");
                 if *push {
-                    result.push_str("Push");
+                    result.push_str("Out");
                 } else {
-                    result.push_str("Pull");
+                    result.push_str("In");
                 }
                 result.push_str("Buffer");
                 if iface.all_bits_valid(ty) {
@@ -1478,21 +1651,24 @@ impl Bindgen for FunctionBindgen<'_> {
 
             Instruction::CallWasmAsyncExport { .. } => unreachable!(),
 
-            Instruction::CallInterface { module, func } => {
+            Instruction::CallInterface { module: _, func } => {
                 self.let_results(func.results.len(), results);
                 match &func.kind {
                     FunctionKind::Freestanding => {
                         self.push_str(&format!(
-                            "<super::{m} as {m}>::{}",
+                            "<{s}{i} as {m}>::{}",
                             func.name.to_snake_case(),
-                            m = module.to_camel_case()
+                            s = self.gen.super_(),
+                            i = impl_name(iface),
+                            m = iface.name.to_camel_case()
                         ));
                     }
                     FunctionKind::Static { resource, name }
                     | FunctionKind::Method { resource, name } => {
                         self.push_str(&format!(
-                            "<super::{r} as {r}>::{}",
+                            "<{s}{r} as {r}>::{}",
                             name.to_snake_case(),
+                            s = self.gen.super_(),
                             r = iface.resources[*resource].name.to_camel_case(),
                         ));
                     }
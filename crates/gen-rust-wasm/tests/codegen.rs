@@ -24,10 +24,84 @@ mod imports {
 mod exports {
     test_helpers::codegen_rust_wasm_export!(
         "*.wit"
-
-        // TODO: these use push/pull buffer which isn't implemented in the test
-        // generator just yet
-        "!wasi-next.wit"
-        "!host.wit"
     );
 }
+
+mod mocks {
+    wit_bindgen_rust::import!({
+        src["host"]: "
+            add: function(a: u32, b: u32) -> u32
+            log: function(msg: string)
+            resource counter {
+                static new: function() -> counter
+                get: function() -> u32
+            }
+        ",
+        mock,
+    });
+
+    use host::{mock, Counter};
+
+    #[test]
+    fn expectations() {
+        mock::add::expect(3);
+        mock::add::expect_with(|a, b| a * b);
+        assert_eq!(mock::add::pending(), 2);
+        assert_eq!(host::add(1, 2), 3);
+        assert_eq!(host::add(2, 4), 8);
+        assert_eq!(mock::add::pending(), 0);
+        assert_eq!(mock::add::calls(), ["add(1, 2)", "add(2, 4)"]);
+    }
+
+    #[test]
+    fn defaults() {
+        mock::add::returning(5);
+        mock::add::expect(1);
+        assert_eq!(host::add(0, 0), 1);
+        assert_eq!(host::add(0, 0), 5);
+        assert_eq!(host::add(0, 0), 5);
+
+        let logged = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let log = logged.clone();
+        mock::log::returning_with(move |msg| log.borrow_mut().push(msg.to_string()));
+        host::log("a");
+        host::log("b");
+        assert_eq!(*logged.borrow(), ["a", "b"]);
+        assert_eq!(mock::log::calls(), ["log(\"a\")", "log(\"b\")"]);
+
+        mock::reset();
+        assert!(mock::add::calls().is_empty());
+        assert!(mock::log::calls().is_empty());
+    }
+
+    #[test]
+    fn resources() {
+        mock::counter_new::expect_with(|| unsafe { Counter::from_raw(7) });
+        mock::counter_get::returning_with(|counter| counter.clone().into_raw() as u32);
+        let counter = Counter::new();
+        assert_eq!(counter.get(), 7);
+        assert_eq!(mock::counter_get::calls().len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "unexpected call to `add`")]
+    fn unexpected_call() {
+        host::add(1, 2);
+    }
+}
+
+mod multiple_mocks {
+    wit_bindgen_rust::import!({
+        paths: ["../../tests/codegen/simple-functions.wit", "../../tests/codegen/records.wit"],
+        mock,
+    });
+
+    #[test]
+    fn separate_modules() {
+        simple_functions::mock::f4::expect(1);
+        assert_eq!(simple_functions::f4(), 1);
+        records::mock::tuple_arg::expect(());
+        records::tuple_arg(('a', 1));
+        assert_eq!(records::mock::tuple_arg::calls(), ["tuple_arg(('a', 1))"]);
+    }
+}
@@ -39,6 +39,13 @@ pub trait RustGenerator {
         false
     }
 
+    /// Returns how many modules deep the bindings currently being generated
+    /// are nested within the outermost module of the bindings, which is
+    /// nonzero for nested interfaces.
+    fn nesting(&self) -> usize {
+        0
+    }
+
     fn rustdoc(&mut self, docs: &Docs) {
         let docs = match &docs.contents {
             Some(docs) => docs,
@@ -51,6 +58,31 @@ pub trait RustGenerator {
         }
     }
 
+    /// Prints `#[deprecated]` for items marked `@deprecated`.
+    fn rust_deprecated(&mut self, attrs: &Attributes) {
+        match attrs.deprecated() {
+            Some("") => self.push_str("#[deprecated]\n"),
+            Some(msg) => self.push_str(&format!("#[deprecated(note = {:?})]\n", msg)),
+            None => {}
+        }
+    }
+
+    /// Prints the derives requested with `@rust(derive = "Trait")`, in
+    /// addition to those which are always generated.
+    fn rust_derives(&mut self, attrs: &Attributes) {
+        let derives = attrs
+            .list
+            .iter()
+            .filter(|a| a.name == "rust")
+            .flat_map(|a| a.key_values())
+            .filter(|(key, _)| *key == "derive")
+            .map(|(_, derive)| derive)
+            .collect::<Vec<_>>();
+        if !derives.is_empty() {
+            self.push_str(&format!("#[derive({})]\n", derives.join(", ")));
+        }
+    }
+
     fn rustdoc_params(&mut self, docs: &[(String, Type)], header: &str) {
         drop((docs, header));
         // let docs = docs
@@ -112,6 +144,7 @@ pub trait RustGenerator {
         self.rustdoc(&func.docs);
         self.rustdoc_params(&func.params, "Parameters");
         self.rustdoc_params(&func.results, "Return");
+        self.rust_deprecated(&func.attrs);
 
         if !sig.private {
             self.push_str("pub ");
@@ -197,7 +230,9 @@ pub trait RustGenerator {
                     None => "",
                 };
                 if self.handle_in_super() {
-                    self.push_str("super::");
+                    for _ in 0..=self.nesting() {
+                        self.push_str("super::");
+                    }
                 }
                 if let Some((proj, _)) = self.handle_projection() {
                     self.push_str(proj);
@@ -350,9 +385,12 @@ pub trait RustGenerator {
             TypeMode::AllBorrowed(s) | TypeMode::HandlesBorrowed(s) | TypeMode::LeafBorrowed(s) => {
                 s
             }
-            TypeMode::Owned => unimplemented!(),
+            // Buffers are never actually owned since they're always a view
+            // into the caller's storage, so owned buffers are still tied to
+            // the lifetime of the call.
+            TypeMode::Owned => "'a",
         };
-        if iface.all_bits_valid(ty) {
+        if iface.all_bits_valid(ty) && mode != TypeMode::Owned {
             self.print_borrowed_slice(iface, push, ty, lt)
         } else {
             self.print_lib_buffer(iface, push, ty, mode, lt)
@@ -434,6 +472,7 @@ pub trait RustGenerator {
         for (name, mode) in self.modes_of(iface, id) {
             let lt = self.lifetime_for(&info, mode);
             self.rustdoc(docs);
+            self.rust_deprecated(&iface.types[id].attrs);
             if record.is_tuple() {
                 self.push_str(&format!("pub type {}", name));
                 self.print_generics(&info, lt, true);
@@ -452,6 +491,7 @@ pub trait RustGenerator {
                 } else if !info.has_handle {
                     self.push_str("#[derive(Clone)]\n");
                 }
+                self.rust_derives(&iface.types[id].attrs);
                 self.push_str(&format!("pub struct {}", name));
                 self.print_generics(&info, lt, true);
                 self.push_str(" {\n");
@@ -503,6 +543,7 @@ pub trait RustGenerator {
 
         for (name, mode) in self.modes_of(iface, id) {
             self.rustdoc(docs);
+            self.rust_deprecated(&iface.types[id].attrs);
             let lt = self.lifetime_for(&info, mode);
             if variant.is_bool() {
                 self.push_str(&format!("pub type {} = bool;\n", name));
@@ -541,6 +582,7 @@ pub trait RustGenerator {
             } else if !info.has_handle {
                 self.push_str("#[derive(Clone)]\n");
             }
+            self.rust_derives(&iface.types[id].attrs);
             self.push_str(&format!("pub enum {}", name.to_camel_case()));
             self.print_generics(&info, lt, true);
             self.push_str("{\n");
@@ -661,6 +703,7 @@ pub trait RustGenerator {
         let info = self.info(id);
         for (name, mode) in self.modes_of(iface, id) {
             self.rustdoc(docs);
+            self.rust_deprecated(&iface.types[id].attrs);
             self.push_str(&format!("pub type {}", name));
             let lt = self.lifetime_for(&info, mode);
             self.print_generics(&info, lt, true);
@@ -675,6 +718,7 @@ pub trait RustGenerator {
         for (name, mode) in self.modes_of(iface, id) {
             let lt = self.lifetime_for(&info, mode);
             self.rustdoc(docs);
+            self.rust_deprecated(&iface.types[id].attrs);
             self.push_str(&format!("pub type {}", name));
             self.print_generics(&info, lt, true);
             self.push_str(" = ");
@@ -683,6 +727,17 @@ pub trait RustGenerator {
         }
     }
 
+    fn print_const(&mut self, iface: &Interface, constant: &Constant) {
+        self.rustdoc(&constant.docs);
+        self.rust_deprecated(&constant.attrs);
+        self.push_str(&format!(
+            "pub const {}: ",
+            constant.name.to_shouty_snake_case()
+        ));
+        self.print_ty(iface, &constant.ty, TypeMode::Owned);
+        self.push_str(&format!(" = {};\n", constant.value));
+    }
+
     fn print_typedef_buffer(
         &mut self,
         iface: &Interface,
@@ -695,6 +750,7 @@ pub trait RustGenerator {
         for (name, mode) in self.modes_of(iface, id) {
             let lt = self.lifetime_for(&info, mode);
             self.rustdoc(docs);
+            self.rust_deprecated(&iface.types[id].attrs);
             self.push_str(&format!("pub type {}", name));
             self.print_generics(&info, lt, true);
             self.push_str(" = ");
@@ -749,6 +805,7 @@ pub trait RustGenerator {
             {
                 Some(s)
             }
+            TypeMode::Owned if info.has_pull_buffer || info.has_push_buffer => Some("'a"),
             _ => None,
         }
     }
@@ -1049,6 +1106,28 @@ impl TypeInfoExt for TypeInfo {
     }
 }
 
+/// Returns whether anything in `iface` is marked `@deprecated`, in which case
+/// its bindings need to allow the use of deprecated items.
+pub fn has_deprecated(iface: &Interface) -> bool {
+    iface
+        .types
+        .iter()
+        .any(|(_, t)| t.attrs.deprecated().is_some())
+        || iface
+            .resources
+            .iter()
+            .any(|(_, r)| r.attrs.deprecated().is_some())
+        || iface
+            .functions
+            .iter()
+            .any(|f| f.attrs.deprecated().is_some())
+        || iface.globals.iter().any(|g| g.attrs.deprecated().is_some())
+        || iface
+            .constants
+            .iter()
+            .any(|c| c.attrs.deprecated().is_some())
+}
+
 pub fn case_name(id: &str) -> String {
     if id.chars().next().unwrap().is_alphabetic() {
         id.to_camel_case()
@@ -1074,3 +1153,167 @@ pub fn bitcast(casts: &[Bitcast], operands: &[String], results: &mut Vec<String>
         });
     }
 }
+
+/// A function to generate a mock of with [`mock_module`].
+pub struct MockFunction {
+    /// The name of the function, which is also the name of its mock's module.
+    pub name: String,
+    /// The types of the function's parameters.
+    pub params: Vec<String>,
+    /// The type returned by the function.
+    pub ret: String,
+    /// Whether `ret` implements `Clone`, which is needed to return the same
+    /// value from every call.
+    pub ret_is_clone: bool,
+}
+
+/// Returns the source of a `mock` module containing a module for each of
+/// `funcs`, through which tests queue up and inspect calls to it.
+///
+/// Mocks are implemented by closures returning `Result<_, error>` if `error`
+/// is given. Their state is per-thread so that tests running in parallel
+/// don't see each other's calls.
+pub fn mock_module(funcs: &[MockFunction], error: Option<&str>) -> String {
+    let mut src = String::from("pub mod mock {\n");
+    src.push_str("/// Resets the mock of every function.\n");
+    src.push_str("pub fn reset() {\n");
+    for func in funcs {
+        src.push_str(&format!("{}::reset();\n", func.name));
+    }
+    src.push_str("}\n");
+
+    for func in funcs {
+        let ret = match error {
+            Some(error) => format!("Result<{}, {}>", func.ret, error),
+            None => func.ret.clone(),
+        };
+        let ok = |val: &str| match error {
+            Some(_) => format!("Ok({})", val),
+            None => val.to_string(),
+        };
+        let tys = func.params.join(", ");
+        let params = func
+            .params
+            .iter()
+            .enumerate()
+            .map(|(i, ty)| format!("arg{}: {}", i, ty))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let args = (0..func.params.len())
+            .map(|i| format!("arg{}", i))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let ignored = vec!["_"; func.params.len()].join(", ");
+        let fmt = vec!["{:?}"; func.params.len()].join(", ");
+        let returning = if func.ret_is_clone {
+            format!(
+                "
+                    /// Sets what calls to `{name}` return once there are no
+                    /// queued calls left.
+                    pub fn returning(ret: {fret}) {{
+                        returning_with(move |{ignored}| {ret})
+                    }}
+                ",
+                name = func.name,
+                fret = func.ret,
+                ignored = ignored,
+                ret = ok("ret.clone()"),
+            )
+        } else {
+            String::new()
+        };
+        src.push_str(&format!(
+            "
+                pub mod {name} {{
+                    #[allow(unused_imports)]
+                    use super::super::*;
+
+                    #[derive(Default)]
+                    struct Mock {{
+                        expectations: std::collections::VecDeque<Box<dyn FnOnce({tys}) -> {ret}>>,
+                        default: Option<Box<dyn FnMut({tys}) -> {ret}>>,
+                        calls: Vec<String>,
+                    }}
+
+                    thread_local! {{
+                        static MOCK: std::cell::RefCell<Mock> = Default::default();
+                    }}
+
+                    /// Queues a call to `{name}` which returns `ret`.
+                    pub fn expect(ret: {fret}) {{
+                        expect_with(move |{ignored}| {ok_ret})
+                    }}
+
+                    /// Queues a call to `{name}` which is implemented by
+                    /// `imp`, for example to check its arguments.
+                    ///
+                    /// Queued calls are made in order, after which calls use
+                    /// the default set with `returning`, if any.
+                    pub fn expect_with(imp: impl FnOnce({tys}) -> {ret} + 'static) {{
+                        MOCK.with(|m| m.borrow_mut().expectations.push_back(Box::new(imp)));
+                    }}
+
+                    {returning}
+
+                    /// Sets the implementation of calls to `{name}` once there
+                    /// are no queued calls left.
+                    pub fn returning_with(imp: impl FnMut({tys}) -> {ret} + 'static) {{
+                        MOCK.with(|m| m.borrow_mut().default = Some(Box::new(imp)));
+                    }}
+
+                    /// Returns the calls made to `{name}` so far, with their
+                    /// arguments formatted with `Debug`.
+                    pub fn calls() -> Vec<String> {{
+                        MOCK.with(|m| m.borrow().calls.clone())
+                    }}
+
+                    /// Returns the number of queued calls to `{name}` which
+                    /// haven't been made yet.
+                    pub fn pending() -> usize {{
+                        MOCK.with(|m| m.borrow().expectations.len())
+                    }}
+
+                    /// Forgets the queued calls, the default and the calls
+                    /// made to `{name}`.
+                    pub fn reset() {{
+                        MOCK.with(|m| *m.borrow_mut() = Mock::default());
+                    }}
+
+                    #[doc(hidden)]
+                    pub fn call({params}) -> {ret} {{
+                        let expectation = MOCK.with(|m| {{
+                            let mut m = m.borrow_mut();
+                            m.calls.push(format!(\"{name}({fmt})\", {args}));
+                            m.expectations.pop_front()
+                        }});
+                        if let Some(imp) = expectation {{
+                            return imp({args});
+                        }}
+                        // The default is taken out while it runs in case it
+                        // calls this function again.
+                        let mut imp = MOCK
+                            .with(|m| m.borrow_mut().default.take())
+                            .unwrap_or_else(|| panic!(\"unexpected call to `{name}`\"));
+                        let ret = imp({args});
+                        MOCK.with(|m| {{
+                            m.borrow_mut().default.get_or_insert(imp);
+                        }});
+                        ret
+                    }}
+                }}
+            ",
+            name = func.name,
+            tys = tys,
+            ret = ret,
+            fret = func.ret,
+            ignored = ignored,
+            ok_ret = ok("ret"),
+            returning = returning,
+            params = params,
+            args = args,
+            fmt = fmt,
+        ));
+    }
+    src.push_str("}\n");
+    src
+}
@@ -14,6 +14,7 @@ use std::path::PathBuf;
 use std::{collections::HashMap, mem};
 use wasm_encoder::Instruction;
 use wit_bindgen_gen_core::{
+    global_getter,
     wit_parser::{
         abi::{self, AbiVariant, WasmSignature, WasmType},
         Docs, Function, Interface, Record, ResourceId, SizeAlign, Type, TypeId, Variant,
@@ -906,6 +907,10 @@ impl Generator for SpiderMonkeyWasm<'_> {
             exports.len() <= 1,
             "only one exported interface is currently supported"
         );
+        // Nested interfaces are generated into this same module, so their
+        // functions take up indices alongside everything else.
+        let imports = imports.iter().flat_map(with_nested).collect::<Vec<_>>();
+        let exports = exports.iter().flat_map(with_nested).collect::<Vec<_>>();
         self.num_import_functions =
             Some(u32::try_from(imports.iter().map(|i| i.functions.len()).sum::<usize>()).unwrap());
         self.num_export_functions =
@@ -921,7 +926,8 @@ impl Generator for SpiderMonkeyWasm<'_> {
                     .zip(std::iter::repeat(AbiVariant::GuestExport)),
             )
         {
-            for func in iface.functions.iter() {
+            let getters = iface.globals.iter().map(global_getter).collect::<Vec<_>>();
+            for func in iface.functions.iter().chain(&getters) {
                 let sig = iface.wasm_signature(variant, func);
                 if let Some(results) = sig.retptr {
                     self.i64_return_pointer_area_size =
@@ -1028,14 +1034,14 @@ impl Generator for SpiderMonkeyWasm<'_> {
         let type_index = self.intern_type(wasm_sig.clone());
         let import_fn_index = self.wit_import(self.imports.len());
         self.imports.import(
-            &iface.name,
+            iface.module(),
             Some(&func.name),
             wasm_encoder::EntityType::Function(type_index),
         );
 
         let existing = self
             .import_fn_name_to_index
-            .entry(iface.name.clone())
+            .entry(iface.module().to_string())
             .or_default()
             .insert(
                 func.name.clone(),
@@ -1044,7 +1050,7 @@ impl Generator for SpiderMonkeyWasm<'_> {
         assert!(existing.is_none());
 
         self.function_names
-            .push((import_fn_index, format!("{}.{}", iface.name, func.name)));
+            .push((import_fn_index, format!("{}.{}", iface.module(), func.name)));
 
         let mut bindgen = Bindgen::new(self, &wasm_sig, func, abi::LiftLower::LowerArgsLiftResults);
         iface.call(
@@ -1083,6 +1089,22 @@ impl Generator for SpiderMonkeyWasm<'_> {
         self.export_glue_fns.push((func_encoder, type_index));
     }
 
+    fn nested(
+        &mut self,
+        _iface: &Interface,
+        nested: &Interface,
+        dir: Direction,
+        files: &mut Files,
+    ) {
+        // Imports from nested interfaces are just more functions in this same
+        // module, which JS imports from a module named after their full path.
+        assert!(
+            dir == Direction::Import,
+            "nested exported interfaces are not supported yet"
+        );
+        self.generate_one(nested, dir, files);
+    }
+
     fn finish_one(&mut self, _iface: &Interface, _files: &mut Files) {
         // Nothing to do until wil finish all interfaces and generate our Wasm
         // glue code.
@@ -1272,6 +1294,15 @@ fn convert_ty(ty: WasmType) -> wasm_encoder::ValType {
     }
 }
 
+/// Returns `iface` followed by all of the interfaces nested within it.
+fn with_nested(iface: &Interface) -> Vec<&Interface> {
+    let mut ret = vec![iface];
+    for (_, nested) in iface.interfaces.iter() {
+        ret.extend(with_nested(nested));
+    }
+    ret
+}
+
 struct Bindgen<'a, 'b> {
     gen: &'a mut SpiderMonkeyWasm<'b>,
     sig: &'a WasmSignature,
@@ -4,7 +4,9 @@ use std::mem;
 use wit_bindgen_gen_core::wit_parser::abi::{
     AbiVariant, Bindgen, Bitcast, Instruction, LiftLower, WasmType, WitxInstruction,
 };
-use wit_bindgen_gen_core::{wit_parser::*, Direction, Files, Generator, Ns};
+use wit_bindgen_gen_core::{
+    wide_flags, wit_parser::*, Direction, Files, Generator, Ns, Unsupported,
+};
 
 #[derive(Default)]
 pub struct WasmtimePy {
@@ -29,6 +31,8 @@ pub struct WasmtimePy {
     needs_list_canon_lower: bool,
     needs_push_buffer: bool,
     needs_pull_buffer: bool,
+    needs_buffer_glue: bool,
+    needs_async: bool,
     needs_t_typevar: bool,
     pyimports: BTreeMap<String, Option<BTreeSet<String>>>,
 }
@@ -311,7 +315,7 @@ impl WasmtimePy {
             );
         }
 
-        if iface.resources.len() > 0 {
+        if iface.resources.len() > 0 || self.needs_buffer_glue {
             self.pyimport("typing", "TypeVar");
             self.pyimport("typing", "Generic");
             self.pyimport("typing", "List");
@@ -362,7 +366,26 @@ impl WasmtimePy {
                 ",
             );
         }
-        if self.needs_push_buffer {
+        if self.needs_push_buffer && !self.in_import {
+            self.pyimport("typing", "TypeVar");
+            self.pyimport("typing", "Generic");
+            self.pyimport("typing", "List");
+            self.needs_t_typevar = true;
+            self.src.push_str(
+                "
+                    class PushBuffer(Generic[T]):
+                        capacity: int
+                        items: List[T]
+
+                        def __init__(self, capacity: int) -> None:
+                            self.capacity = capacity
+                            self.items = []
+
+                        def __len__(self) -> int:
+                            return len(self.items)
+                ",
+            )
+        } else if self.needs_push_buffer {
             self.pyimport("typing", "TypeVar");
             self.pyimport("typing", "Generic");
             self.pyimport("typing", "Callable");
@@ -417,6 +440,146 @@ impl WasmtimePy {
                 ",
             )
         }
+        if self.needs_buffer_glue {
+            self.pyimport("typing", "Callable");
+            self.pyimport("typing", "List");
+            self.pyimport("typing", "Sequence");
+            self.src.push_str(
+                "
+                    class InBuffer:
+                        def __init__(self, items: Sequence[Any], size: int, write: Callable[[wasmtime.Storelike, Any, int], None]) -> None:
+                            self.items = items
+                            self.pos = 0
+                            self.size = size
+                            self.write = write
+
+                    class OutBuffer:
+                        def __init__(self, items: List[Any], capacity: int, size: int, read: Callable[[wasmtime.Storelike, int], Any]) -> None:
+                            self.items = items
+                            self.capacity = capacity
+                            self.size = size
+                            self.read = read
+
+                    class BufferGlue:
+                        in_buffers: Slab[InBuffer]
+                        out_buffers: Slab[OutBuffer]
+
+                        def __init__(self) -> None:
+                            self.in_buffers = Slab()
+                            self.out_buffers = Slab()
+
+                        def in_len(self, handle: int) -> int:
+                            b = self.in_buffers.get(handle)
+                            return len(b.items) - b.pos
+
+                        def in_read(self, store: wasmtime.Storelike, handle: int, amt: int, base: int) -> None:
+                            b = self.in_buffers.get(handle)
+                            if amt > len(b.items) - b.pos:
+                                raise IndexError('more items requested from in-buffer than are available')
+                            for item in b.items[b.pos:b.pos + amt]:
+                                b.write(store, item, base)
+                                base += b.size
+                            b.pos += amt
+
+                        def out_len(self, handle: int) -> int:
+                            b = self.out_buffers.get(handle)
+                            return b.capacity - len(b.items)
+
+                        def out_write(self, store: wasmtime.Storelike, handle: int, amt: int, base: int) -> None:
+                            b = self.out_buffers.get(handle)
+                            if amt > b.capacity - len(b.items):
+                                raise IndexError('more items written to out-buffer than are available')
+                            for _ in range(amt):
+                                b.items.append(b.read(store, base))
+                                base += b.size
+                ",
+            );
+        }
+        if self.needs_async {
+            self.pyimport("asyncio", None);
+            self.pyimport("ctypes", None);
+            self.pyimport("typing", "Callable");
+            self.pyimport("typing", "Coroutine");
+            self.pyimport("typing", "Dict");
+            self.pyimport("typing", "Set");
+            self.pyimport("typing", "Tuple");
+            self.pyimport("typing", "cast");
+            // The imports and exports of a module are generated into separate
+            // Python modules, but host coroutines for async imports are only
+            // tied back to the async export awaiting them through this state,
+            // so it's stashed on the store that both are instantiated in.
+            self.src.push_str(
+                "
+                    class _Async:
+                        imports: Set['asyncio.Future[None]']
+                        exports: Dict[int, Tuple[int, 'asyncio.Future[bytes]']]
+                        next_ctx: int
+
+                        def __init__(self) -> None:
+                            self.imports = set()
+                            self.exports = {}
+                            self.next_ctx = 0
+
+                        def spawn_import(self, coro: Coroutine[Any, Any, None]) -> None:
+                            task = asyncio.ensure_future(coro)
+                            self.imports.add(task)
+                            task.add_done_callback(self._import_done)
+
+                        def _import_done(self, task: 'asyncio.Future[None]') -> None:
+                            self.imports.discard(task)
+                            if task.cancelled():
+                                self._fail(asyncio.CancelledError())
+                                return
+                            err = task.exception()
+                            if err is not None:
+                                self._fail(err)
+                            else:
+                                self._check_stalled()
+
+                        async def call_export(self, results: int, call: Callable[[int], Any]) -> bytes:
+                            ctx = self.next_ctx
+                            self.next_ctx += 1
+                            future: 'asyncio.Future[bytes]' = asyncio.get_running_loop().create_future()
+                            self.exports[ctx] = (results, future)
+                            try:
+                                call(ctx)
+                                self._check_stalled()
+                                return await future
+                            finally:
+                                del self.exports[ctx]
+
+                        def export_done(self, ctx: int, mem: wasmtime.Memory, store: wasmtime.Storelike, ptr: int) -> None:
+                            if ctx not in self.exports:
+                                raise IndexError('invalid async export context')
+                            results, future = self.exports[ctx]
+                            if future.done():
+                                raise RuntimeError('async export completed twice')
+                            ptr = ptr & 0xffffffff
+                            if ptr + results * 8 > mem.data_len(store):
+                                raise IndexError('out-of-bounds read')
+                            raw_base = mem.data_ptr(store)
+                            future.set_result(ctypes.string_at(ctypes.addressof(raw_base.contents) + ptr, results * 8))
+
+                        def _check_stalled(self) -> None:
+                            # Nothing can ever complete a pending export at this
+                            # point, so rather than hang forever report an error.
+                            if len(self.imports) == 0:
+                                self._fail(RuntimeError('async export cannot complete: no pending async imports'))
+
+                        def _fail(self, err: BaseException) -> None:
+                            for _, future in self.exports.values():
+                                if not future.done():
+                                    future.set_exception(err)
+
+                    def _async_state(store: wasmtime.Store) -> _Async:
+                        state = getattr(store, '_wit_bindgen_async', None)
+                        if state is None:
+                            state = _Async()
+                            setattr(store, '_wit_bindgen_async', state)
+                        return cast(_Async, state)
+                ",
+            );
+        }
     }
 
     fn type_string(&mut self, iface: &Interface, ty: &Type) -> String {
@@ -523,6 +686,16 @@ impl WasmtimePy {
     }
 
     fn print_buffer(&mut self, iface: &Interface, push: bool, ty: &Type) {
+        // When calling wasm exports the host provides the storage for buffers,
+        // so pull-buffers are any sequence of items and push-buffers are a
+        // host-side list with a fixed capacity for wasm to fill in.
+        if !self.in_import && !push {
+            self.pyimport("typing", "Sequence");
+            self.src.push_str("Sequence[");
+            self.print_ty(iface, ty);
+            self.src.push_str("]");
+            return;
+        }
         if push {
             self.needs_push_buffer = true;
             self.src.push_str("PushBuffer");
@@ -603,6 +776,9 @@ impl WasmtimePy {
                 self.src.push_str("@classmethod\n");
             }
         }
+        if func.is_async {
+            self.src.push_str("async ");
+        }
         self.src.push_str("def ");
         match &func.kind {
             FunctionKind::Method { .. } => self.src.push_str(&func.item_name().to_snake_case()),
@@ -850,7 +1026,9 @@ impl Generator for WasmtimePy {
     // so a user "export" uses the "guest import" ABI variant on the inside of
     // this `Generator` implementation.
     fn export(&mut self, iface: &Interface, func: &Function) {
-        assert!(!func.is_async, "async not supported yet");
+        if func.is_async {
+            self.needs_async = true;
+        }
         let prev = mem::take(&mut self.src);
 
         self.print_sig(iface, func);
@@ -920,6 +1098,15 @@ impl Generator for WasmtimePy {
                 .push_str("assert(isinstance(free, wasmtime.Func))\n");
             locals.insert("free").unwrap();
         }
+
+        if func.is_async {
+            // TODO: hardcoding "__indirect_function_table"
+            self.src
+                .push_str("table = caller[\"__indirect_function_table\"]\n");
+            self.src
+                .push_str("assert(isinstance(table, wasmtime.Table))\n");
+            locals.insert("table").unwrap();
+        }
         self.src.push_str(&src);
         self.deindent();
 
@@ -967,7 +1154,9 @@ impl Generator for WasmtimePy {
     // so a user "import" uses the "export" ABI variant on the inside of
     // this `Generator` implementation.
     fn import(&mut self, iface: &Interface, func: &Function) {
-        assert!(!func.is_async, "async not supported yet");
+        if func.is_async {
+            self.needs_async = true;
+        }
         let prev = mem::take(&mut self.src);
 
         let params = self.print_sig(iface, func);
@@ -1035,7 +1224,9 @@ impl Generator for WasmtimePy {
         if let Some(name) = &needs_free {
             exports.fields.insert(name.clone(), "wasmtime.Func");
         }
-        exports.fields.insert(func.name.clone(), "wasmtime.Func");
+        exports
+            .fields
+            .insert(iface.export_name(&func.name).into_owned(), "wasmtime.Func");
 
         let func_body = mem::replace(&mut self.src, prev);
         let dst = match &func.kind {
@@ -1050,6 +1241,17 @@ impl Generator for WasmtimePy {
         dst.push(func_body);
     }
 
+    fn nested(
+        &mut self,
+        _iface: &Interface,
+        nested: &Interface,
+        dir: Direction,
+        files: &mut Files,
+    ) {
+        let mut gen = self.opts.clone().build();
+        gen.generate_one(nested, dir, files);
+    }
+
     fn finish_one(&mut self, iface: &Interface, files: &mut Files) {
         self.pyimport("typing", "Any");
         self.pyimport("abc", "abstractmethod");
@@ -1188,6 +1390,9 @@ impl Generator for WasmtimePy {
                     r.name.to_camel_case()
                 ));
             }
+            if self.needs_async {
+                self.src.push_str("_async = _async_state(store)\n");
+            }
 
             for func in funcs
                 .freestanding_funcs
@@ -1198,7 +1403,7 @@ impl Generator for WasmtimePy {
                 self.src.push_str(&func.src);
                 self.src.push_str(&format!(
                     "linker.define('{}', '{}', wasmtime.Func(store, ty, {}, access_caller = True))\n",
-                    iface.name,
+                    iface.module(),
                     func.name,
                     func.name.to_snake_case(),
                 ));
@@ -1266,6 +1471,12 @@ impl Generator for WasmtimePy {
                     r.name.to_snake_case(),
                 ));
             }
+            if self.needs_buffer_glue {
+                self.src.push_str("_buffers: BufferGlue\n");
+            }
+            if self.needs_async {
+                self.src.push_str("_async: _Async\n");
+            }
 
             self.src.push_str("def __init__(self, store: wasmtime.Store, linker: wasmtime.Linker, module: wasmtime.Module):\n");
             self.indent();
@@ -1298,6 +1509,43 @@ impl Generator for WasmtimePy {
                     idx = id.index(),
                 ));
             }
+            if self.needs_buffer_glue {
+                self.src.push_str(
+                    "
+                        self._buffers = BufferGlue()
+                        ty1 = wasmtime.FuncType([wasmtime.ValType.i32()], [wasmtime.ValType.i32()])
+                        ty2 = wasmtime.FuncType([wasmtime.ValType.i32(), wasmtime.ValType.i32(), wasmtime.ValType.i32()], [])
+                        def in_len(handle: int) -> int:
+                            return self._buffers.in_len(handle)
+                        linker.define('wit_canonical_buffer_abi', 'in_len', wasmtime.Func(store, ty1, in_len))
+
+                        def in_read(caller: wasmtime.Caller, handle: int, amt: int, base: int) -> None:
+                            self._buffers.in_read(caller, handle, amt, base)
+                        linker.define('wit_canonical_buffer_abi', 'in_read', wasmtime.Func(store, ty2, in_read, access_caller = True))
+
+                        def out_len(handle: int) -> int:
+                            return self._buffers.out_len(handle)
+                        linker.define('wit_canonical_buffer_abi', 'out_len', wasmtime.Func(store, ty1, out_len))
+
+                        def out_write(caller: wasmtime.Caller, handle: int, amt: int, base: int) -> None:
+                            self._buffers.out_write(caller, handle, amt, base)
+                        linker.define('wit_canonical_buffer_abi', 'out_write', wasmtime.Func(store, ty2, out_write, access_caller = True))
+                    ",
+                );
+            }
+            if self.needs_async {
+                self.src.push_str(
+                    "
+                        self._async = _async_state(store)
+                        ty = wasmtime.FuncType([wasmtime.ValType.i32(), wasmtime.ValType.i32()], [])
+                        def async_export_done(caller: wasmtime.Caller, ctx: int, ptr: int) -> None:
+                            memory = caller[\"memory\"]
+                            assert(isinstance(memory, wasmtime.Memory))
+                            self._async.export_done(ctx, memory, caller, ptr)
+                        linker.define('canonical_abi', 'async_export_done', wasmtime.Func(store, ty, async_export_done, access_caller = True))
+                    ",
+                );
+            }
             self.src
                 .push_str("self.instance = linker.instantiate(store, module)\n");
             self.src
@@ -1336,7 +1584,17 @@ impl Generator for WasmtimePy {
             self.deindent();
         }
 
-        files.push("bindings.py", self.src.as_bytes());
+        // Nested interfaces are written to their own module next to
+        // `bindings.py`, named after their full path.
+        let file = match &iface.module {
+            Some(module) => format!("{}.py", module.to_snake_case()),
+            None => "bindings.py".to_string(),
+        };
+        files.push(&file, self.src.as_bytes());
+    }
+
+    fn unsupported(&self, iface: &Interface, _dir: Direction) -> Vec<Unsupported> {
+        wide_flags(iface)
     }
 }
 
@@ -1352,6 +1610,8 @@ struct FunctionBindgen<'a> {
     params: Vec<String>,
     payloads: Vec<String>,
     src_object: String,
+    buffer_handles: Vec<(bool, String)>,
+    async_completion: Option<String>,
 }
 
 impl FunctionBindgen<'_> {
@@ -1375,6 +1635,8 @@ impl FunctionBindgen<'_> {
             params,
             payloads: Vec::new(),
             src_object: "self".to_string(),
+            buffer_handles: Vec::new(),
+            async_completion: None,
         }
     }
 
@@ -2034,54 +2296,64 @@ impl Bindgen for FunctionBindgen<'_> {
                 }
             }
 
-            //    Instruction::BufferLowerHandle { push, ty } => {
-            //        let block = self.blocks.pop().unwrap();
-            //        let size = self.sizes.size(ty);
-            //        let tmp = self.tmp();
-            //        let handle = format!("handle{}", tmp);
-            //        let closure = format!("closure{}", tmp);
-            //        self.needs_buffer_transaction = true;
-            //        if iface.all_bits_valid(ty) {
-            //            let method = if *push { "push_out_raw" } else { "push_in_raw" };
-            //            self.push_str(&format!(
-            //                "let {} = unsafe {{ buffer_transaction.{}({}) }};\n",
-            //                handle, method, operands[0],
-            //            ));
-            //        } else if *push {
-            //            self.closures.push_str(&format!(
-            //                "let {} = |memory: &wasmtime::Memory, base: i32| {{
-            //                    Ok(({}, {}))
-            //                }};\n",
-            //                closure, block, size,
-            //            ));
-            //            self.push_str(&format!(
-            //                "let {} = unsafe {{ buffer_transaction.push_out({}, &{}) }};\n",
-            //                handle, operands[0], closure,
-            //            ));
-            //        } else {
-            //            let start = self.src.len();
-            //            self.print_ty(iface, ty, TypeMode::AllBorrowed("'_"));
-            //            let ty = self.src[start..].to_string();
-            //            self.src.truncate(start);
-            //            self.closures.push_str(&format!(
-            //                "let {} = |memory: &wasmtime::Memory, base: i32, e: {}| {{
-            //                    {};
-            //                    Ok({})
-            //                }};\n",
-            //                closure, ty, block, size,
-            //            ));
-            //            self.push_str(&format!(
-            //                "let {} = unsafe {{ buffer_transaction.push_in({}, &{}) }};\n",
-            //                handle, operands[0], closure,
-            //            ));
-            //        }
-            //        results.push(format!("{}", handle));
-            //    }
+            Instruction::BufferLowerHandle { push, ty } => {
+                let (block, block_results) = self.blocks.pop().unwrap();
+                let base = self.payloads.pop().unwrap();
+                self.gen.needs_buffer_glue = true;
+                let size = self.gen.sizes.size(ty);
+                let handle = self.locals.tmp("handle");
+                let ty = self.gen.type_string(iface, ty);
+                if *push {
+                    assert_eq!(block_results.len(), 1);
+                    let read = self.locals.tmp("read_val");
+                    self.src.push_str(&format!(
+                        "def {}(caller: wasmtime.Storelike, {}: int) -> {}:\n",
+                        read, base, ty,
+                    ));
+                    self.src.indent(2);
+                    self.src.push_str(&block);
+                    self.src.push_str(&format!("return {}\n", block_results[0]));
+                    self.src.deindent(2);
+                    self.src.push_str(&format!(
+                        "{} = {}._buffers.out_buffers.insert(OutBuffer({op}.items, {op}.capacity, {}, {}))\n",
+                        handle,
+                        self.src_object,
+                        size,
+                        read,
+                        op = operands[0],
+                    ));
+                } else {
+                    assert!(block_results.is_empty());
+                    let write = self.locals.tmp("write_val");
+                    self.src.push_str(&format!(
+                        "def {}(caller: wasmtime.Storelike, e: {}, {}: int) -> None:\n",
+                        write, ty, base,
+                    ));
+                    self.src.indent(2);
+                    self.src.push_str(&block);
+                    self.src.deindent(2);
+                    self.src.push_str(&format!(
+                        "{} = {}._buffers.in_buffers.insert(InBuffer({}, {}, {}))\n",
+                        handle, self.src_object, operands[0], size, write,
+                    ));
+                }
+                self.buffer_handles.push((*push, handle.clone()));
+                results.push(handle);
+            }
+
             Instruction::CallWasm {
                 module: _,
                 name,
                 sig,
             } => {
+                // Buffers passed to wasm are only valid for the duration of
+                // the call, so make sure their handles are released however
+                // the call finishes.
+                let buffer_handles = mem::take(&mut self.buffer_handles);
+                if !buffer_handles.is_empty() {
+                    self.src.push_str("try:\n");
+                    self.src.indent(2);
+                }
                 if sig.results.len() > 0 {
                     for i in 0..sig.results.len() {
                         if i > 0 {
@@ -2095,13 +2367,26 @@ impl Bindgen for FunctionBindgen<'_> {
                 }
                 self.src.push_str(&self.src_object);
                 self.src.push_str("._");
-                self.src.push_str(&name.to_snake_case());
+                self.src.push_str(&iface.export_name(name).to_snake_case());
                 self.src.push_str("(caller");
                 if operands.len() > 0 {
                     self.src.push_str(", ");
                 }
                 self.src.push_str(&operands.join(", "));
                 self.src.push_str(")\n");
+                if !buffer_handles.is_empty() {
+                    self.src.deindent(2);
+                    self.src.push_str("finally:\n");
+                    self.src.indent(2);
+                    for (push, handle) in buffer_handles.iter() {
+                        let kind = if *push { "out" } else { "in" };
+                        self.src.push_str(&format!(
+                            "{}._buffers.{}_buffers.remove({})\n",
+                            self.src_object, kind, handle,
+                        ));
+                    }
+                    self.src.deindent(2);
+                }
                 for (ty, name) in sig.results.iter().zip(results.iter()) {
                     let ty = match ty {
                         WasmType::I32 | WasmType::I64 => "int",
@@ -2111,7 +2396,75 @@ impl Bindgen for FunctionBindgen<'_> {
                         .push_str(&format!("assert(isinstance({}, {}))\n", name, ty));
                 }
             }
+            Instruction::CallWasmAsyncExport {
+                module: _,
+                name,
+                params: _,
+                results: wasm_results,
+            } => {
+                // The export reports its results through the
+                // `async_export_done` intrinsic, each stored in an 8-byte slot,
+                // and host coroutines for any async imports it calls are run
+                // while waiting for that.
+                let ctx = self.locals.tmp("ctx");
+                let raw = self.locals.tmp("results");
+                if wasm_results.len() > 0 {
+                    self.src.push_str(&format!("{} = ", raw));
+                }
+                self.src.push_str(&format!(
+                    "await {obj}._async.call_export({n}, lambda {ctx}: {obj}._{name}(caller, ",
+                    obj = self.src_object,
+                    n = wasm_results.len(),
+                    ctx = ctx,
+                    name = iface.export_name(name).to_snake_case(),
+                ));
+                for operand in operands.iter() {
+                    self.src.push_str(operand);
+                    self.src.push_str(", ");
+                }
+                self.src.push_str(&ctx);
+                self.src.push_str("))\n");
+                self.gen.pyimport("struct", None);
+                for (i, ty) in wasm_results.iter().enumerate() {
+                    let (fmt, pyty) = match ty {
+                        WasmType::I32 => ("<i", "int"),
+                        WasmType::I64 => ("<q", "int"),
+                        WasmType::F32 => ("<f", "float"),
+                        WasmType::F64 => ("<d", "float"),
+                    };
+                    let ret = self.locals.tmp("ret");
+                    self.src.push_str(&format!(
+                        "{} = struct.unpack_from('{}', {}, {})[0]\n",
+                        ret,
+                        fmt,
+                        raw,
+                        i * 8,
+                    ));
+                    self.src
+                        .push_str(&format!("assert(isinstance({}, {}))\n", ret, pyty));
+                    results.push(ret);
+                }
+            }
             Instruction::CallInterface { module: _, func } => {
+                // Async imports start the host's coroutine in the background
+                // and complete the call into wasm once it's finished, so
+                // everything from here until `ReturnAsyncImport` is part of
+                // that completion.
+                if func.is_async {
+                    // Arguments are lifted out of wasm memory now, while it's
+                    // still valid, rather than once the coroutine runs.
+                    for operand in operands.iter_mut() {
+                        let tmp = self.locals.tmp("arg");
+                        self.src.push_str(&format!("{} = {}\n", tmp, operand));
+                        *operand = tmp;
+                    }
+                    let complete = self.locals.tmp("complete");
+                    self.src
+                        .push_str(&format!("async def {}() -> None:\n", complete));
+                    self.src.indent(2);
+                    self.src.push_str("caller = store\n");
+                    self.async_completion = Some(complete);
+                }
                 for i in 0..func.results.len() {
                     if i > 0 {
                         self.src.push_str(", ");
@@ -2123,6 +2476,9 @@ impl Bindgen for FunctionBindgen<'_> {
                 if func.results.len() > 0 {
                     self.src.push_str(" = ");
                 }
+                if func.is_async {
+                    self.src.push_str("await ");
+                }
                 match &func.kind {
                     FunctionKind::Freestanding | FunctionKind::Static { .. } => {
                         self.src.push_str(&format!(
@@ -2143,6 +2499,19 @@ impl Bindgen for FunctionBindgen<'_> {
                 self.src.push_str("\n");
             }
 
+            Instruction::ReturnAsyncImport { .. } => {
+                self.src
+                    .push_str(&format!("callback = table.get(store, {})\n", operands[0]));
+                self.src
+                    .push_str("assert(isinstance(callback, wasmtime.Func))\n");
+                self.src
+                    .push_str(&format!("callback(store, {})\n", operands[1..].join(", ")));
+                self.src.deindent(2);
+                let complete = self.async_completion.take().unwrap();
+                self.src
+                    .push_str(&format!("_async.spawn_import({}())\n", complete));
+            }
+
             Instruction::Return { amt, .. } => match amt {
                 0 => {}
                 1 => self.src.push_str(&format!("return {}\n", operands[0])),
@@ -1,32 +1,26 @@
+use std::fs;
 use std::path::Path;
 use std::process::Command;
 
 mod exports {
-    test_helpers::codegen_py_export!(
-        "*.wit"
-
-        // TODO: implement async support
-        "!async-functions.wit"
-    );
+    test_helpers::codegen_py_export!("*.wit");
 }
 
 mod imports {
-    test_helpers::codegen_py_import!(
-        "*.wit"
-
-        // TODO: implement async support
-        "!async-functions.wit"
-
-        // This uses buffers, which we don't support in imports just yet
-        // TODO: should support this
-        "!wasi-next.wit"
-        "!host.wit"
-    );
+    test_helpers::codegen_py_import!("*.wit");
 }
 
 fn verify(dir: &str, _name: &str) {
+    // Nested interfaces are generated into their own modules alongside
+    // `bindings.py`, so check every module that was generated.
+    let mut modules = fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().and_then(|s| s.to_str()) == Some("py"))
+        .collect::<Vec<_>>();
+    modules.sort();
     let output = Command::new("mypy")
-        .arg(Path::new(dir).join("bindings.py"))
+        .args(&modules)
         .arg("--config-file")
         .arg("mypy.ini")
         .output()
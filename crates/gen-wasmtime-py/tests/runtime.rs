@@ -5,6 +5,7 @@ use std::process::Command;
 use wit_bindgen_gen_core::Generator;
 
 test_helpers::runtime_tests!("py");
+test_helpers::roundtrip_tests_py!();
 
 fn execute(name: &str, wasm: &Path, py: &Path, imports: &Path, exports: &Path) {
     let out_dir = PathBuf::from(env!("OUT_DIR"));
@@ -19,13 +20,17 @@ fn execute(name: &str, wasm: &Path, py: &Path, imports: &Path, exports: &Path) {
     // We call `generate_all` with exports from the imports.wit file, and
     // imports from the exports.wit wit file. It's reversed because we're
     // implementing the host side of these APIs.
-    let iface = wit_bindgen_gen_core::wit_parser::Interface::parse_file(imports).unwrap();
-    let mut files = Default::default();
-    wit_bindgen_gen_wasmtime_py::Opts::default()
-        .build()
-        .generate_all(&[], &[iface], &mut files);
-    for (file, contents) in files.iter() {
-        fs::write(dir.join("imports").join(file), contents).unwrap();
+    //
+    // Some tests only exercise exports, so the imports may not exist.
+    if imports.exists() {
+        let iface = wit_bindgen_gen_core::wit_parser::Interface::parse_file(imports).unwrap();
+        let mut files = Default::default();
+        wit_bindgen_gen_wasmtime_py::Opts::default()
+            .build()
+            .generate_all(&[], &[iface], &mut files);
+        for (file, contents) in files.iter() {
+            fs::write(dir.join("imports").join(file), contents).unwrap();
+        }
     }
     fs::write(dir.join("imports").join("__init__.py"), "").unwrap();
 
@@ -39,10 +44,56 @@ fn execute(name: &str, wasm: &Path, py: &Path, imports: &Path, exports: &Path) {
     }
     fs::write(dir.join("exports").join("__init__.py"), "").unwrap();
 
+    // Any other `*.wit` files in the test's directory are exported by only
+    // some guests, so their bindings go in a package named after the file
+    // and the host checks whether the guest implements them.
+    for entry in fs::read_dir(exports.parent().unwrap()).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().and_then(|s| s.to_str()) != Some("wit")
+            || path == imports
+            || path == exports
+        {
+            continue;
+        }
+        let package = path
+            .file_stem()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .replace('-', "_");
+        fs::create_dir_all(&dir.join(&package)).unwrap();
+        let iface = wit_bindgen_gen_core::wit_parser::Interface::parse_file(&path).unwrap();
+        let mut files = Default::default();
+        wit_bindgen_gen_wasmtime_py::Opts::default()
+            .build()
+            .generate_all(&[iface], &[], &mut files);
+        for (file, contents) in files.iter() {
+            fs::write(dir.join(&package).join(file), contents).unwrap();
+        }
+        fs::write(dir.join(&package).join("__init__.py"), "").unwrap();
+    }
+
+    run_host(name, &dir, py, wasm);
+}
+
+fn roundtrip(name: &str, dir: &Path, wasm: &Path) {
+    println!("OUT_DIR = {:?}", dir);
+    run_host(
+        &format!("roundtrip-{}", name),
+        dir,
+        &dir.join("host.py"),
+        wasm,
+    );
+}
+
+/// Type-checks `py`, which imports bindings from `dir`, and runs it with
+/// `wasm`.
+fn run_host(name: &str, dir: &Path, py: &Path, wasm: &Path) {
+    let out_dir = PathBuf::from(env!("OUT_DIR"));
     println!("Running mypy...");
     exec(
         Command::new("mypy")
-            .env("MYPYPATH", &dir)
+            .env("MYPYPATH", dir)
             .arg(py)
             .arg("--cache-dir")
             .arg(out_dir.join("mypycache").join(name)),
@@ -50,7 +101,7 @@ fn execute(name: &str, wasm: &Path, py: &Path, imports: &Path, exports: &Path) {
 
     exec(
         Command::new("python3")
-            .env("PYTHONPATH", &dir)
+            .env("PYTHONPATH", dir)
             .arg(py)
             .arg(wasm),
     );
@@ -7,9 +7,12 @@ use std::str::FromStr;
 use wit_bindgen_gen_core::wit_parser::abi::{
     Abi, AbiVariant, Bindgen, Instruction, LiftLower, WasmType, WitxInstruction,
 };
-use wit_bindgen_gen_core::{wit_parser::*, Direction, Files, Generator, Source, TypeInfo, Types};
+use wit_bindgen_gen_core::{
+    wide_flags, wit_parser::*, Direction, Files, Generator, Source, TypeInfo, Types, Unsupported,
+};
 use wit_bindgen_gen_rust::{
-    int_repr, to_rust_ident, wasm_type, FnSig, RustFunctionGenerator, RustGenerator, TypeMode,
+    has_deprecated, int_repr, mock_module, to_rust_ident, wasm_type, FnSig, MockFunction,
+    RustFunctionGenerator, RustGenerator, TypeMode,
 };
 
 #[derive(Default)]
@@ -18,6 +21,7 @@ pub struct Wasmtime {
     opts: Opts,
     needs_get_memory: bool,
     needs_get_func: bool,
+    needs_get_table: bool,
     needs_char_from_i32: bool,
     needs_invalid_variant: bool,
     needs_validate_flags: bool,
@@ -29,6 +33,7 @@ pub struct Wasmtime {
     needs_custom_error_to_trap: bool,
     needs_custom_error_to_types: BTreeSet<String>,
     all_needed_handles: BTreeSet<String>,
+    has_async_functions: bool,
     exported_resources: BTreeSet<ResourceId>,
     types: Types,
     guest_imports: HashMap<String, Vec<Import>>,
@@ -47,6 +52,7 @@ enum NeededFunction {
 
 struct Import {
     is_async: bool,
+    module: String,
     name: String,
     trait_signature: String,
     num_wasm_params: usize,
@@ -57,6 +63,8 @@ struct Import {
 struct Exports {
     fields: BTreeMap<String, (String, String)>,
     funcs: Vec<String>,
+    mocks: Vec<MockFunction>,
+    mock_funcs: Vec<String>,
 }
 
 #[derive(Default, Debug, Clone)]
@@ -82,6 +90,12 @@ pub struct Opts {
     /// custom trait-defined error. Applicable for import bindings.
     #[cfg_attr(feature = "structopt", structopt(long))]
     pub custom_error: bool,
+
+    /// Whether a mock of each exports struct is generated, along with a
+    /// `mock` module to configure it, so code calling wasm exports can be
+    /// tested without instantiating a module.
+    #[cfg_attr(feature = "structopt", structopt(long))]
+    pub mock: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -195,8 +209,11 @@ impl Wasmtime {
 
     /// Classifies the return value of a function to see if it needs handling
     /// with respect to the `custom_error` configuration option.
+    ///
+    /// Async functions always report errors as traps through their returned
+    /// future, so they're never classified as custom errors.
     fn classify_fn_ret(&mut self, iface: &Interface, f: &Function) -> FunctionRet {
-        if !self.opts.custom_error {
+        if !self.opts.custom_error || f.is_async {
             return FunctionRet::Normal;
         }
 
@@ -223,6 +240,162 @@ impl Wasmtime {
         self.needs_custom_error_to_trap = true;
         FunctionRet::CustomToTrap
     }
+
+    /// Returns the pattern used to destructure the return value of the `get`
+    /// closure passed to `add_to_linker` for imports, depending on whether
+    /// handle tables and async state are part of it.
+    fn import_state_pattern(&self, host: &str, tables: &str, async_: &str) -> String {
+        match (self.all_needed_handles.len() > 0, self.has_async_functions) {
+            (false, false) => host.to_string(),
+            (true, false) => format!("({}, {})", host, tables),
+            (false, true) => format!("({}, {})", host, async_),
+            (true, true) => format!("({}, {}, {})", host, tables, async_),
+        }
+    }
+
+    /// Generates the method of the exports struct's mock which calls `func`'s
+    /// mock, with the same signature as the real method.
+    fn mock_method(&mut self, iface: &Interface, func: &Function, is_async: bool) {
+        let prev = mem::take(&mut self.src);
+        let mut mock_types = Vec::new();
+        for (_, ty) in func.params.iter() {
+            let info = self.types.type_info(iface, ty);
+            if info.has_push_buffer || info.has_pull_buffer {
+                mock_types.clear();
+                break;
+            }
+            self.print_ty(iface, ty, TypeMode::AllBorrowed("'_"));
+            mock_types.push(mem::take(&mut self.src).into());
+        }
+        let can_mock = mock_types.len() == func.params.len();
+
+        let mut sig = FnSig::default();
+        sig.async_ = is_async;
+        sig.self_arg = Some("&self, _caller: impl wasmtime::AsContextMut<Data = T>".to_string());
+        let params = self.print_docs_and_params(iface, func, TypeMode::AllBorrowed("'_"), &sig);
+        self.push_str("-> Result<");
+        self.print_results(iface, func);
+        self.push_str(", wasmtime::Trap> {\n");
+        let name = to_rust_ident(&func.name);
+        if can_mock {
+            self.push_str(&format!("mock::{}::call({})\n", name, params.join(", ")));
+        } else {
+            for param in params.iter() {
+                self.push_str(&format!("let _ = {};\n", param));
+            }
+            self.push_str(&format!(
+                "panic!(\"`{}` can't be mocked since it uses buffers\")\n",
+                func.name,
+            ));
+        }
+        self.push_str("}\n");
+        let method = mem::take(&mut self.src);
+
+        let mock = if can_mock {
+            self.print_results(iface, func);
+            let ret = mem::take(&mut self.src).into();
+            let ret_is_clone = func
+                .results
+                .iter()
+                .all(|(_, ty)| !self.types.type_info(iface, ty).has_handle);
+            Some(MockFunction {
+                name,
+                params: mock_types,
+                ret,
+                ret_is_clone,
+            })
+        } else {
+            None
+        };
+        self.src = prev;
+
+        let exports = self
+            .guest_exports
+            .entry(iface.name.to_string())
+            .or_insert_with(Exports::default);
+        exports.mock_funcs.push(method.into());
+        exports.mocks.extend(mock);
+    }
+
+    /// Generates `{name}Mock`, a mock of the exports struct `name` with the
+    /// same methods, along with the `mock` module configuring it.
+    fn print_exports_mock(&mut self, iface: &Interface, name: &str, exports: &Exports) {
+        self.push_str(&format!(
+            "
+                /// A mock of [`{name}`] with the same methods, which call the
+                /// functions' mocks in the [`mock`] module instead of wasm,
+                /// so code using the exports can be tested without a module.
+                pub struct {name}Mock<T> {{
+                    _marker: std::marker::PhantomData<fn(T)>,
+                }}
+
+                impl<T> Default for {name}Mock<T> {{
+                    fn default() -> Self {{
+                        Self::new()
+                    }}
+                }}
+
+                impl<T> {name}Mock<T> {{
+                    pub fn new() -> Self {{
+                        {name}Mock {{
+                            _marker: std::marker::PhantomData,
+                        }}
+                    }}
+            ",
+            name = name,
+        ));
+        for func in exports.mock_funcs.iter() {
+            self.push_str(func);
+        }
+        let async_fn = if self.opts.async_.is_none() {
+            ""
+        } else {
+            "async "
+        };
+        for r in self.exported_resources.iter() {
+            self.src.push_str(&format!(
+                "
+                    /// Does nothing, since mocked resources don't have
+                    /// destructors.
+                    pub {async}fn drop_{name_snake}(
+                        &self,
+                        _store: impl wasmtime::AsContextMut<Data = T>,
+                        _val: {name_camel},
+                    ) -> Result<(), wasmtime::Trap> {{
+                        Ok(())
+                    }}
+
+                    /// Creates a handle to a mocked resource, to be returned
+                    /// from mocked functions, which is identified by `index`
+                    /// in recorded calls.
+                    pub fn new_{name_snake}(index: u32) -> {name_camel} {{
+                        {name_camel}(wit_bindgen_wasmtime::rt::ResourceIndex::mock(index))
+                    }}
+                ",
+                async = async_fn,
+                name_snake = iface.resources[*r].name.to_snake_case(),
+                name_camel = iface.resources[*r].name.to_camel_case(),
+            ));
+        }
+        self.push_str("}\n");
+
+        self.push_str(&format!(
+            "/// Mocks of the functions exported from `{}`, which are called by
+            /// [`{}Mock`].\n",
+            iface.name, name,
+        ));
+        self.push_str(&mock_module(&exports.mocks, Some("wasmtime::Trap")));
+    }
+
+    /// Returns the projection applied to the result of `get_state` for
+    /// exports to reach the `{Name}Data` structure.
+    fn export_data(&self) -> &'static str {
+        if self.has_async_functions {
+            ".0"
+        } else {
+            ""
+        }
+    }
 }
 
 impl RustGenerator for Wasmtime {
@@ -377,9 +550,14 @@ impl Generator for Wasmtime {
         let variant = Self::abi_variant(dir);
         self.types.analyze(iface);
         self.in_import = variant == AbiVariant::GuestImport;
+        self.has_async_functions = iface.functions.iter().any(|f| f.is_async);
         self.trait_name = iface.name.to_camel_case();
+        self.rustdoc(&iface.docs);
         self.src
             .push_str(&format!("pub mod {} {{\n", iface.name.to_snake_case()));
+        if has_deprecated(iface) {
+            self.src.push_str("#![allow(deprecated)]\n");
+        }
         self.src
             .push_str("#[allow(unused_imports)]\nuse wit_bindgen_wasmtime::{wasmtime, anyhow};\n");
         self.sizes.fill(variant, iface);
@@ -397,6 +575,7 @@ impl Generator for Wasmtime {
             self.src
                 .push_str("wit_bindgen_wasmtime::bitflags::bitflags! {\n");
             self.rustdoc(docs);
+            self.rust_deprecated(&iface.types[id].attrs);
             self.src
                 .push_str(&format!("pub struct {}: ", name.to_camel_case()));
             let repr = iface
@@ -510,6 +689,7 @@ impl Generator for Wasmtime {
         // `i32` to manage the resultt.
         let tyname = name.to_camel_case();
         self.rustdoc(&iface.resources[ty].docs);
+        self.rust_deprecated(&iface.resources[ty].attrs);
         self.src.push_str("#[derive(Debug)]\n");
         self.src.push_str(&format!(
             "pub struct {}(wit_bindgen_wasmtime::rt::ResourceIndex);\n",
@@ -573,22 +753,14 @@ impl Generator for Wasmtime {
         self.print_typedef_buffer(iface, id, false, ty, docs);
     }
 
-    // fn const_(&mut self, name: &Id, ty: &Id, val: u64, docs: &str) {
-    //     self.rustdoc(docs);
-    //     self.src.push_str(&format!(
-    //         "pub const {}_{}: {} = {};\n",
-    //         ty.to_shouty_snake_case(),
-    //         name.to_shouty_snake_case(),
-    //         ty.to_camel_case(),
-    //         val
-    //     ));
-    // }
+    fn const_(&mut self, iface: &Interface, constant: &Constant) {
+        self.print_const(iface, constant);
+    }
 
     // As with `abi_variant` above, we're generating host-side bindings here
     // so a user "export" uses the "guest import" ABI variant on the inside of
     // this `Generator` implementation.
     fn export(&mut self, iface: &Interface, func: &Function) {
-        assert!(!func.is_async, "async not supported yet");
         let prev = mem::take(&mut self.src);
 
         let is_dtor = self.types.is_preview1_dtor_func(func);
@@ -636,7 +808,9 @@ impl Generator for Wasmtime {
 
         let mut fnsig = FnSig::default();
         fnsig.private = true;
-        fnsig.async_ = self.opts.async_.includes(&func.name);
+        // Wit `async` functions return a future themselves, so they're never
+        // additionally `async` in the trait.
+        fnsig.async_ = self.opts.async_.includes(&func.name) && !func.is_async;
         fnsig.self_arg = Some(self_arg);
         self.print_docs_and_params(
             iface,
@@ -651,6 +825,14 @@ impl Generator for Wasmtime {
         // The Rust return type may differ from the wasm return type based on
         // the `custom_error` configuration of this code generator.
         match self.classify_fn_ret(iface, func) {
+            // Async functions return a `'static` future which is completed
+            // outside of this call, and traps are signaled through the future
+            // since the host can't otherwise fail the call.
+            FunctionRet::Normal if func.is_async => {
+                self.push_str(" -> wit_bindgen_wasmtime::BoxFuture<'static, Result<");
+                self.print_results(iface, func);
+                self.push_str(", wasmtime::Trap>>");
+            }
             FunctionRet::Normal => {
                 if func.results.len() > 0 {
                     self.push_str(" -> ");
@@ -712,7 +894,8 @@ impl Generator for Wasmtime {
                     );
                     let _enter = span.enter();
                 ",
-                iface.name, func.name,
+                iface.module(),
+                func.name,
             ));
         }
         self.src.push_str(&closures);
@@ -729,11 +912,18 @@ impl Generator for Wasmtime {
             self.needs_get_func = true;
         }
 
-        if needs_memory || needs_borrow_checker {
+        // Async functions always capture the memory to lower results into
+        // it later, along with the table containing the completion callback.
+        if needs_memory || needs_borrow_checker || func.is_async {
             self.src
                 .push_str("let memory = &get_memory(&mut caller, \"memory\")?;\n");
             self.needs_get_memory = true;
         }
+        if func.is_async {
+            self.src
+                .push_str("let table = get_table(&mut caller, \"__indirect_function_table\")?;\n");
+            self.needs_get_table = true;
+        }
 
         if needs_borrow_checker {
             self.src.push_str(
@@ -745,8 +935,9 @@ impl Generator for Wasmtime {
             self.src.push_str("let host = get(caller.data_mut());\n");
         }
 
-        if self.all_needed_handles.len() > 0 {
-            self.src.push_str("let (host, _tables) = host;\n");
+        let pattern = self.import_state_pattern("host", "_tables", "_async");
+        if pattern != "host" {
+            self.src.push_str(&format!("let {} = host;\n", pattern));
         }
 
         self.src.push_str(&String::from(src));
@@ -763,6 +954,7 @@ impl Generator for Wasmtime {
             .push(Import {
                 is_async,
                 num_wasm_params: sig.params.len(),
+                module: iface.module().to_string(),
                 name: func.name.to_string(),
                 closure,
                 trait_signature,
@@ -773,13 +965,14 @@ impl Generator for Wasmtime {
     // so a user "import" uses the "export" ABI variant on the inside of
     // this `Generator` implementation.
     fn import(&mut self, iface: &Interface, func: &Function) {
-        assert!(!func.is_async, "async not supported yet");
         let prev = mem::take(&mut self.src);
 
         // If anything is asynchronous on exports then everything must be
         // asynchronous, Wasmtime can't intermix async and sync calls because
         // it's unknown whether the wasm module will make an async host call.
-        let is_async = !self.opts.async_.is_none();
+        // Wit `async` functions are additionally always `async` here since
+        // their results are only available after awaiting host imports.
+        let is_async = !self.opts.async_.is_none() || func.is_async;
         let mut sig = FnSig::default();
         sig.async_ = is_async;
         sig.self_arg = Some("&self, mut caller: impl wasmtime::AsContextMut<Data = T>".to_string());
@@ -814,6 +1007,11 @@ impl Generator for Wasmtime {
             ..
         } = f;
 
+        if self.opts.mock && !is_dtor {
+            self.mock_method(iface, func, is_async);
+        }
+
+        let data = self.export_data();
         let exports = self
             .guest_exports
             .entry(iface.name.to_string())
@@ -851,8 +1049,13 @@ impl Generator for Wasmtime {
 
         if needs_buffer_transaction {
             self.needs_buffer_glue = true;
-            self.src
-                .push_str("let mut buffer_transaction = self.buffer_glue.transaction();\n");
+            self.src.push_str(&format!(
+                "let buffer_glue = (self.get_state)(caller.as_context_mut().data_mut()){}
+                    .buffer_glue
+                    .clone();
+                let mut buffer_transaction = buffer_glue.transaction();\n",
+                data,
+            ));
         }
 
         self.src.push_str(&String::from(src));
@@ -883,12 +1086,34 @@ impl Generator for Wasmtime {
                 format!("wasmtime::TypedFunc<{}>", cvt),
                 format!(
                     "instance.get_typed_func::<{}, _>(&mut store, \"{}\")?",
-                    cvt, func.name,
+                    cvt,
+                    iface.export_name(&func.name),
                 ),
             ),
         );
     }
 
+    fn nested(
+        &mut self,
+        _iface: &Interface,
+        nested: &Interface,
+        dir: Direction,
+        _files: &mut Files,
+    ) {
+        // Nested interfaces are generated as a module within this one, and the
+        // whole thing is formatted at once at the end.
+        let mut gen = Opts {
+            rustfmt: false,
+            ..self.opts.clone()
+        }
+        .build();
+        let mut files = Files::default();
+        gen.generate_one(nested, dir, &mut files);
+        for (_, contents) in files.iter() {
+            self.src.push_str(std::str::from_utf8(contents).unwrap());
+        }
+    }
+
     fn finish_one(&mut self, iface: &Interface, files: &mut Files) {
         for (module, funcs) in sorted_iter(&self.guest_imports) {
             let module_camel = module.to_camel_case();
@@ -977,15 +1202,16 @@ impl Generator for Wasmtime {
             let is_async = !self.opts.async_.is_none();
             self.push_str("\npub fn add_to_linker<T, U>(linker: &mut wasmtime::Linker<T>");
             self.push_str(", get: impl Fn(&mut T) -> ");
-            if self.all_needed_handles.is_empty() {
-                self.push_str("&mut U");
-            } else {
-                self.push_str(&format!("(&mut U, &mut {}Tables<U>)", module_camel));
-            }
+            let ret = self.import_state_pattern(
+                "&mut U",
+                &format!("&mut {}Tables<U>", module_camel),
+                "&mut wit_bindgen_wasmtime::Async<T>",
+            );
+            self.push_str(&ret);
             self.push_str("+ Send + Sync + Copy + 'static) -> anyhow::Result<()> \n");
             self.push_str("where U: ");
             self.push_str(&module_camel);
-            if is_async {
+            if is_async || self.has_async_functions {
                 self.push_str(", T: Send,");
             }
             self.push_str("\n{\n");
@@ -995,6 +1221,9 @@ impl Generator for Wasmtime {
             if self.needs_get_func {
                 self.push_str("use wit_bindgen_wasmtime::rt::get_func;\n");
             }
+            if self.needs_get_table {
+                self.push_str("use wit_bindgen_wasmtime::rt::get_table;\n");
+            }
             for f in funcs {
                 let method = if f.is_async {
                     format!("func_wrap{}_async", f.num_wasm_params)
@@ -1003,17 +1232,18 @@ impl Generator for Wasmtime {
                 };
                 self.push_str(&format!(
                     "linker.{}(\"{}\", \"{}\", {})?;\n",
-                    method, module, f.name, f.closure,
+                    method, f.module, f.name, f.closure,
                 ));
             }
             if !self.has_preview1_dtor {
+                let pattern = self.import_state_pattern("host", "tables", "_");
                 for handle in self.all_needed_handles.iter() {
                     self.src.push_str(&format!(
                         "linker.func_wrap(
                             \"canonical_abi\",
                             \"resource_drop_{name}\",
                             move |mut caller: wasmtime::Caller<'_, T>, handle: u32| {{
-                                let (host, tables) = get(caller.data_mut());
+                                let {pattern} = get(caller.data_mut());
                                 let handle = tables
                                     .{snake}_table
                                     .remove(handle)
@@ -1026,6 +1256,7 @@ impl Generator for Wasmtime {
                         )?;\n",
                         name = handle,
                         snake = handle.to_snake_case(),
+                        pattern = pattern,
                     ));
                 }
             }
@@ -1061,14 +1292,22 @@ impl Generator for Wasmtime {
                     r.index()
                 ));
             }
+            if self.needs_buffer_glue {
+                self.push_str("buffer_glue: wit_bindgen_wasmtime::imports::BufferGlue,\n");
+            }
             self.push_str("}\n");
 
             self.push_str("pub struct ");
             self.push_str(&name);
             self.push_str("<T> {\n");
+            let state = if self.has_async_functions {
+                format!("(&mut {}Data, &mut wit_bindgen_wasmtime::Async<T>)", name)
+            } else {
+                format!("&mut {}Data", name)
+            };
             self.push_str(&format!(
-                "get_state: Box<dyn Fn(&mut T) -> &mut {}Data + Send + Sync>,\n",
-                name
+                "get_state: Box<dyn Fn(&mut T) -> {} + Send + Sync>,\n",
+                state
             ));
             for (name, (ty, _)) in exports.fields.iter() {
                 self.push_str(name);
@@ -1076,11 +1315,8 @@ impl Generator for Wasmtime {
                 self.push_str(ty);
                 self.push_str(",\n");
             }
-            // if self.needs_buffer_glue {
-            //     self.push_str("buffer_glue: wit_bindgen_wasmtime::imports::BufferGlue,");
-            // }
             self.push_str("}\n");
-            let bound = if self.opts.async_.is_none() {
+            let bound = if self.opts.async_.is_none() && !self.has_async_functions {
                 ""
             } else {
                 ": Send"
@@ -1100,11 +1336,14 @@ impl Generator for Wasmtime {
                     /// the general store's state.
                     pub fn add_to_linker(
                         linker: &mut wasmtime::Linker<T>,
-                        get_state: impl Fn(&mut T) -> &mut {}Data + Send + Sync + Copy + 'static,
+                        get_state: impl Fn(&mut T) -> {} + Send + Sync + Copy + 'static,
                     ) -> anyhow::Result<()> {{
                 ",
-                name,
+                state,
             ));
+            if self.needs_buffer_glue || self.has_async_functions {
+                self.push_str("use wit_bindgen_wasmtime::rt::get_memory;\n");
+            }
             for r in self.exported_resources.iter() {
                 let (func_wrap, call, wait, prefix, suffix) = if self.opts.async_.is_none() {
                     ("func_wrap", "call", "", "", "")
@@ -1123,7 +1362,7 @@ impl Generator for Wasmtime {
                             \"canonical_abi\",
                             \"resource_drop_{name}\",
                             move |mut caller: wasmtime::Caller<'_, T>, idx: u32| {prefix}{{
-                                let state = get_state(caller.data_mut());
+                                let state = get_state(caller.data_mut()){data};
                                 let resource_idx = state.index_slab{idx}.remove(idx)?;
                                 let wasm = match state.resource_slab{idx}.drop(resource_idx) {{
                                     Some(wasm) => wasm,
@@ -1138,7 +1377,7 @@ impl Generator for Wasmtime {
                             \"canonical_abi\",
                             \"resource_clone_{name}\",
                             move |mut caller: wasmtime::Caller<'_, T>, idx: u32| {{
-                                let state = get_state(caller.data_mut());
+                                let state = get_state(caller.data_mut()){data};
                                 let resource_idx = state.index_slab{idx}.get(idx)?;
                                 state.resource_slab{idx}.clone(resource_idx)?;
                                 Ok(state.index_slab{idx}.insert(resource_idx))
@@ -1148,7 +1387,7 @@ impl Generator for Wasmtime {
                             \"canonical_abi\",
                             \"resource_get_{name}\",
                             move |mut caller: wasmtime::Caller<'_, T>, idx: u32| {{
-                                let state = get_state(caller.data_mut());
+                                let state = get_state(caller.data_mut()){data};
                                 let resource_idx = state.index_slab{idx}.get(idx)?;
                                 Ok(state.resource_slab{idx}.get(resource_idx))
                             }},
@@ -1157,7 +1396,7 @@ impl Generator for Wasmtime {
                             \"canonical_abi\",
                             \"resource_new_{name}\",
                             move |mut caller: wasmtime::Caller<'_, T>, val: i32| {{
-                                let state = get_state(caller.data_mut());
+                                let state = get_state(caller.data_mut()){data};
                                 let resource_idx = state.resource_slab{idx}.insert(val);
                                 Ok(state.index_slab{idx}.insert(resource_idx))
                             }},
@@ -1170,47 +1409,69 @@ impl Generator for Wasmtime {
                     wait = wait,
                     prefix = prefix,
                     suffix = suffix,
+                    data = self.export_data(),
+                ));
+            }
+            if self.needs_buffer_glue {
+                self.push_str(&format!(
+                    "
+                        linker.func_wrap(
+                            \"wit_canonical_buffer_abi\",
+                            \"in_len\",
+                            move |mut caller: wasmtime::Caller<'_, T>, handle: u32| {{
+                                get_state(caller.data_mut()){data}.buffer_glue.in_len(handle)
+                            }},
+                        )?;
+                        linker.func_wrap(
+                            \"wit_canonical_buffer_abi\",
+                            \"in_read\",
+                            move |mut caller: wasmtime::Caller<'_, T>, handle: u32, len: u32, offset: u32| {{
+                                let memory = get_memory(&mut caller, \"memory\")?;
+                                let glue = get_state(caller.data_mut()){data}.buffer_glue.clone();
+                                // Buffers are only pushed by this module's
+                                // exports, whose transactions use this same
+                                // store and data type `T`.
+                                unsafe {{ glue.in_read(handle, &mut caller, &memory, offset, len) }}
+                            }},
+                        )?;
+                        linker.func_wrap(
+                            \"wit_canonical_buffer_abi\",
+                            \"out_len\",
+                            move |mut caller: wasmtime::Caller<'_, T>, handle: u32| {{
+                                get_state(caller.data_mut()){data}.buffer_glue.out_len(handle)
+                            }},
+                        )?;
+                        linker.func_wrap(
+                            \"wit_canonical_buffer_abi\",
+                            \"out_write\",
+                            move |mut caller: wasmtime::Caller<'_, T>, handle: u32, len: u32, offset: u32| {{
+                                let memory = get_memory(&mut caller, \"memory\")?;
+                                let glue = get_state(caller.data_mut()){data}.buffer_glue.clone();
+                                // Buffers are only pushed by this module's
+                                // exports, whose transactions use this same
+                                // store and data type `T`.
+                                unsafe {{ glue.out_write(handle, &mut caller, &memory, offset, len) }}
+                            }},
+                        )?;
+                    ",
+                    data = self.export_data(),
                 ));
             }
-            // if self.needs_buffer_glue {
-            //     self.push_str(
-            //         "
-            //             use wit_bindgen_wasmtime::rt::get_memory;
-
-            //             let buffer_glue = wit_bindgen_wasmtime::imports::BufferGlue::default();
-            //             let g = buffer_glue.clone();
-            //             linker.func(
-            //                 \"wit_canonical_buffer_abi\",
-            //                 \"in_len\",
-            //                 move |handle: u32| g.in_len(handle),
-            //             )?;
-            //             let g = buffer_glue.clone();
-            //             linker.func(
-            //                 \"wit_canonical_buffer_abi\",
-            //                 \"in_read\",
-            //                 move |caller: wasmtime::Caller<'_>, handle: u32, len: u32, offset: u32| {
-            //                     let memory = get_memory(&mut caller, \"memory\")?;
-            //                     g.in_read(handle, &memory, offset, len)
-            //                 },
-            //             )?;
-            //             let g = buffer_glue.clone();
-            //             linker.func(
-            //                 \"wit_canonical_buffer_abi\",
-            //                 \"out_len\",
-            //                 move |handle: u32| g.out_len(handle),
-            //             )?;
-            //             let g = buffer_glue.clone();
-            //             linker.func(
-            //                 \"wit_canonical_buffer_abi\",
-            //                 \"out_write\",
-            //                 move |caller: wasmtime::Caller<'_>, handle: u32, len: u32, offset: u32| {
-            //                     let memory = get_memory(&mut caller, \"memory\")?;
-            //                     g.out_write(handle, &memory, offset, len)
-            //                 },
-            //             )?;
-            //         ",
-            //     );
-            // }
+            if self.has_async_functions {
+                self.push_str(
+                    "
+                        linker.func_wrap(
+                            \"canonical_abi\",
+                            \"async_export_done\",
+                            move |mut caller: wasmtime::Caller<'_, T>, ctx: i32, ptr: i32| {
+                                let memory = get_memory(&mut caller, \"memory\")?;
+                                let (mem, data) = memory.data_and_store_mut(&mut caller);
+                                get_state(data).1.export_done(ctx, mem, ptr)
+                            },
+                        )?;
+                    ",
+                );
+            }
             self.push_str("Ok(())\n");
             self.push_str("}\n");
 
@@ -1239,14 +1500,14 @@ impl Generator for Wasmtime {
                         mut store: impl wasmtime::AsContextMut<Data = T>,
                         module: &wasmtime::Module,
                         linker: &mut wasmtime::Linker<T>,
-                        get_state: impl Fn(&mut T) -> &mut {}Data + Send + Sync + Copy + 'static,
+                        get_state: impl Fn(&mut T) -> {} + Send + Sync + Copy + 'static,
                     ) -> anyhow::Result<(Self, wasmtime::Instance)> {{
                         Self::add_to_linker(linker, get_state)?;
                         let instance = linker.instantiate{}(&mut store, module){}?;
                         Ok((Self::new(store, &instance,get_state)?, instance))
                     }}
                 ",
-                async_fn, name, instantiate, wait,
+                async_fn, state, instantiate, wait,
             ));
 
             self.push_str(&format!(
@@ -1262,10 +1523,10 @@ impl Generator for Wasmtime {
                     pub fn new(
                         mut store: impl wasmtime::AsContextMut<Data = T>,
                         instance: &wasmtime::Instance,
-                        get_state: impl Fn(&mut T) -> &mut {}Data + Send + Sync + Copy + 'static,
+                        get_state: impl Fn(&mut T) -> {} + Send + Sync + Copy + 'static,
                     ) -> anyhow::Result<Self> {{
                 ",
-                name,
+                state,
             ));
             self.push_str("let mut store = store.as_context_mut();\n");
             assert!(!self.needs_get_func);
@@ -1279,12 +1540,13 @@ impl Generator for Wasmtime {
             for r in self.exported_resources.iter() {
                 self.src.push_str(&format!(
                     "
-                        get_state(store.data_mut()).dtor{} = \
+                        get_state(store.data_mut()){}.dtor{} = \
                             Some(instance.get_typed_func::<i32, (), _>(\
                                 &mut store, \
                                 \"canonical_abi_drop_{}\", \
                             )?);\n
                     ",
+                    self.export_data(),
                     r.index(),
                     iface.resources[*r].name,
                 ));
@@ -1325,7 +1587,7 @@ impl Generator for Wasmtime {
                             val: {name_camel},
                         ) -> Result<(), wasmtime::Trap> {{
                             let mut store = store.as_context_mut();
-                            let data = (self.get_state)(store.data_mut());
+                            let data = (self.get_state)(store.data_mut()){data};
                             let wasm = match data.resource_slab{idx}.drop(val.0) {{
                                 Some(val) => val,
                                 None => return Ok(()),
@@ -1340,10 +1602,15 @@ impl Generator for Wasmtime {
                     async = async_fn,
                     call = call,
                     wait = wait,
+                    data = self.export_data(),
                 ));
             }
 
             self.push_str("}\n");
+
+            if self.opts.mock {
+                self.print_exports_mock(iface, &name, exports);
+            }
         }
         self.print_intrinsics();
 
@@ -1377,6 +1644,10 @@ impl Generator for Wasmtime {
 
         files.push("bindings.rs", src.as_bytes());
     }
+
+    fn unsupported(&self, iface: &Interface, _dir: Direction) -> Vec<Unsupported> {
+        wide_flags(iface)
+    }
 }
 
 struct FunctionBindgen<'a> {
@@ -1469,7 +1740,8 @@ impl FunctionBindgen<'_> {
                     self.push_str(
                         "let (caller_memory, data) = memory.data_and_store_mut(&mut caller);\n",
                     );
-                    self.push_str("let (_, _tables) = get(data);\n");
+                    let pattern = self.gen.import_state_pattern("_", "_tables", "_");
+                    self.push_str(&format!("let {} = get(data);\n", pattern));
                 } else {
                     self.push_str("let caller_memory = memory.data_mut(&mut caller);\n");
                 }
@@ -1706,9 +1978,10 @@ impl Bindgen for FunctionBindgen<'_> {
                 self.push_str(&format!(
                     "
                         let obj{tmp} = {op};
-                        (self.get_state)(caller.as_context_mut().data_mut()).resource_slab{idx}.clone(obj{tmp}.0)?;
-                        let handle{tmp} = (self.get_state)(caller.as_context_mut().data_mut()).index_slab{idx}.insert(obj{tmp}.0);
+                        (self.get_state)(caller.as_context_mut().data_mut()){data}.resource_slab{idx}.clone(obj{tmp}.0)?;
+                        let handle{tmp} = (self.get_state)(caller.as_context_mut().data_mut()){data}.index_slab{idx}.insert(obj{tmp}.0);
                     ",
+                    data = self.gen.export_data(),
                     tmp = tmp,
                     idx = ty.index(),
                     op = operands[0],
@@ -1719,8 +1992,9 @@ impl Bindgen for FunctionBindgen<'_> {
             Instruction::HandleOwnedFromI32 { ty } => {
                 let tmp = self.tmp();
                 self.push_str(&format!(
-                    "let handle{} = (self.get_state)(caller.as_context_mut().data_mut()).index_slab{}.remove({} as u32)?;\n",
+                    "let handle{} = (self.get_state)(caller.as_context_mut().data_mut()){}.index_slab{}.remove({} as u32)?;\n",
                     tmp,
+                    self.gen.export_data(),
                     ty.index(),
                     operands[0],
                 ));
@@ -2008,7 +2282,7 @@ impl Bindgen for FunctionBindgen<'_> {
             // Never used due to the call modes that this binding generator
             // uses
             Instruction::BufferLowerPtrLen { .. } => unreachable!(),
-            Instruction::BufferLiftHandle { .. } => unimplemented!(),
+            Instruction::BufferLiftHandle { .. } => unreachable!(),
 
             Instruction::BufferLiftPtrLen { push, ty } => {
                 let block = self.blocks.pop().unwrap();
@@ -2063,8 +2337,11 @@ impl Bindgen for FunctionBindgen<'_> {
                         handle, method, operands[0],
                     ));
                 } else if *push {
+                    // Closures here are invoked while wasm is running, so they
+                    // receive the store context to access memory through
+                    // instead of borrowing the `caller` of this function.
                     self.closures.push_str(&format!(
-                        "let {} = |memory: &wasmtime::Memory, base: i32| {{
+                        "let {} = |mut caller: wasmtime::StoreContextMut<'_, T>, memory: &wasmtime::Memory, base: i32| {{
                             Ok(({}, {}))
                         }};\n",
                         closure, block, size,
@@ -2076,7 +2353,7 @@ impl Bindgen for FunctionBindgen<'_> {
                 } else {
                     let ty = self.type_string(iface, ty, TypeMode::AllBorrowed("'_"));
                     self.closures.push_str(&format!(
-                        "let {} = |memory: &wasmtime::Memory, base: i32, e: {}| {{
+                        "let {} = |mut caller: wasmtime::StoreContextMut<'_, T>, memory: &wasmtime::Memory, base: i32, e: {}| {{
                             {};
                             Ok({})
                         }};\n",
@@ -2127,8 +2404,72 @@ impl Bindgen for FunctionBindgen<'_> {
                 self.caller_memory_available = false; // invalidated by call
             }
 
-            Instruction::CallWasmAsyncImport { .. } => unimplemented!(),
-            Instruction::CallWasmAsyncExport { .. } => unimplemented!(),
+            // Host bindings never call async imports, those are only called
+            // from within wasm.
+            Instruction::CallWasmAsyncImport { .. } => unreachable!(),
+
+            Instruction::CallWasmAsyncExport {
+                module: _,
+                name,
+                params: _,
+                results: wasm_results,
+            } => {
+                // Allocate a context for this call which is passed as the
+                // final argument and later handed back to us through the
+                // `async_export_done` intrinsic along with the results.
+                let tmp = self.tmp();
+                self.push_str(&format!(
+                    "let ctx{} = (self.get_state)(caller.as_context_mut().data_mut()).1.start_export({});\n",
+                    tmp,
+                    wasm_results.len(),
+                ));
+                self.push_str("self.");
+                self.push_str(&to_rust_ident(name));
+                if self.gen.opts.async_.includes(name) {
+                    self.push_str(".call_async(");
+                } else {
+                    self.push_str(".call(");
+                }
+                self.push_str("&mut caller, (");
+                for operand in operands {
+                    self.push_str(operand);
+                    self.push_str(", ");
+                }
+                self.push_str(&format!("ctx{} as i32,))", tmp));
+                if self.gen.opts.async_.includes(name) {
+                    self.push_str(".await");
+                }
+                self.push_str("?;\n");
+
+                // Wait for the export to finish, driving host futures for any
+                // async imports it called in the meantime, and then decode its
+                // results which are each stored in an 8-byte slot.
+                if wasm_results.len() > 0 {
+                    self.push_str(&format!("let results{} = ", tmp));
+                }
+                self.push_str(&format!(
+                    "wit_bindgen_wasmtime::rt::wait_export(
+                        caller.as_context_mut(),
+                        |state| (self.get_state)(state).1,
+                        ctx{},
+                    ).await?;\n",
+                    tmp,
+                ));
+                self.gen.needs_raw_mem = self.gen.needs_raw_mem || wasm_results.len() > 0;
+                for (i, ty) in wasm_results.iter().enumerate() {
+                    let result = format!("result{}_{}", tmp, i);
+                    self.push_str(&format!(
+                        "let {} = results{}.load::<{}>({})?;\n",
+                        result,
+                        tmp,
+                        wasm_type(*ty),
+                        i * 8,
+                    ));
+                    results.push(result);
+                }
+                self.after_call = true;
+                self.caller_memory_available = false; // invalidated by call
+            }
 
             Instruction::CallInterface { module: _, func } => {
                 for (i, operand) in operands.iter().enumerate() {
@@ -2162,10 +2503,34 @@ impl Bindgen for FunctionBindgen<'_> {
                     call.push_str(&format!("param{}, ", i));
                 }
                 call.push_str(")");
-                if self.gen.opts.async_.includes(&func.name) {
+                if self.gen.opts.async_.includes(&func.name) && !func.is_async {
                     call.push_str(".await");
                 }
 
+                if func.is_async {
+                    // The host's future is spawned into the store's `Async`
+                    // state, and once it resolves the results are lowered
+                    // within a completion which has access to the store again.
+                    // Everything from here until `ReturnAsyncImport` is part of
+                    // that completion.
+                    self.push_str(&format!("let future = {};\n", call));
+                    self.push_str(
+                        "let memory = *memory;
+                        _async.spawn_import(async move {
+                            let result = future.await;
+                            wit_bindgen_wasmtime::rt::ImportCompletion::new(
+                                move |mut caller: wasmtime::StoreContextMut<'_, T>| Box::pin(async move {
+                                    #[allow(unused_variables)]
+                                    let memory = &memory;
+                        ",
+                    );
+                    self.let_results(func.results.len(), results);
+                    self.push_str("result?;\n");
+                    self.after_call = true;
+                    self.caller_memory_available = false;
+                    return;
+                }
+
                 self.let_results(func.results.len(), results);
                 match self.gen.classify_fn_ret(iface, func) {
                     FunctionRet::Normal => self.push_str(&call),
@@ -2227,8 +2592,44 @@ impl Bindgen for FunctionBindgen<'_> {
                 }
             }
 
-            Instruction::ReturnAsyncExport { .. } => unimplemented!(),
-            Instruction::ReturnAsyncImport { .. } => unimplemented!(),
+            // Async exports are only returned from within wasm.
+            Instruction::ReturnAsyncExport { .. } => unreachable!(),
+
+            Instruction::ReturnAsyncImport { func, .. } => {
+                // Invoke the wasm completion callback, located in the function
+                // table, with its context and the lowered results. This
+                // finishes the completion opened in `CallInterface`.
+                let sig = iface.wasm_signature(AbiVariant::GuestImport, func);
+                let mut params = "(i32, ".to_string();
+                for ty in sig.retptr.as_ref().unwrap() {
+                    params.push_str(wasm_type(*ty));
+                    params.push_str(", ");
+                }
+                params.push_str(")");
+                let (call, wait) = if self.gen.opts.async_.is_none() {
+                    ("call", "")
+                } else {
+                    ("call_async", ".await")
+                };
+                self.push_str(&format!(
+                    "let callback = table
+                        .get(&mut caller, {cb} as u32)
+                        .and_then(|f| f.funcref().and_then(|f| f.cloned()))
+                        .ok_or_else(|| wasmtime::Trap::new(\"invalid async completion callback\"))?;
+                    callback
+                        .typed::<{params}, (), _>(&caller)?
+                        .{call}(&mut caller, ({args}, )){wait}?;
+                    Ok(())
+                    }}))
+                    }});
+                    Ok(())\n",
+                    cb = operands[0],
+                    params = params,
+                    call = call,
+                    wait = wait,
+                    args = operands[1..].join(", "),
+                ));
+            }
 
             Instruction::I32Load { offset } => results.push(self.load(*offset, "i32", operands)),
             Instruction::I32Load8U { offset } => {
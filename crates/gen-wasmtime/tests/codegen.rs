@@ -10,9 +10,6 @@ mod exports {
         "*.wit"
         "*.witx"
 
-        // TODO: implement async support
-        "!async-functions.wit"
-
         // If you want to exclude a specific test you can include it here with
         // gitignore glob syntax:
         //
@@ -29,9 +26,6 @@ mod imports {
     test_helpers::codegen_wasmtime_import!(
         "*.wit"
 
-        // TODO: implement async support
-        "!async-functions.wit"
-
         // TODO: these use push/pull buffer which isn't implemented in the test
         // generator just yet
         "!wasi-next.wit"
@@ -107,3 +101,43 @@ mod custom_errors {
         custom_error: true,
     });
 }
+
+mod mocks {
+    wit_bindgen_wasmtime::import!({
+        src["x"]: "
+            add: function(a: u32, b: u32) -> u32
+            resource y {
+                static new: function() -> y
+                z: function() -> string
+            }
+        ",
+        mock: true,
+    });
+
+    use x::{mock, XMock};
+
+    #[test]
+    fn mock() -> anyhow::Result<()> {
+        let mut store = wasmtime::Store::<()>::default();
+        let exports = XMock::new();
+
+        mock::add::expect(3);
+        mock::add::expect_with(|_, _| Err(wasmtime::Trap::new("overflow")));
+        mock::add::returning(0);
+        assert_eq!(exports.add(&mut store, 1, 2)?, 3);
+        assert!(exports.add(&mut store, u32::MAX, 1).is_err());
+        assert_eq!(exports.add(&mut store, 2, 2)?, 0);
+        assert_eq!(mock::add::pending(), 0);
+        assert_eq!(
+            mock::add::calls(),
+            ["add(1, 2)", "add(4294967295, 1)", "add(2, 2)"]
+        );
+
+        mock::y_new::expect_with(|| Ok(XMock::<()>::new_y(1)));
+        mock::y_z::returning("z".to_string());
+        let y = exports.y_new(&mut store)?;
+        assert_eq!(exports.y_z(&mut store, &y)?, "z");
+        exports.drop_y(&mut store, y)?;
+        Ok(())
+    }
+}
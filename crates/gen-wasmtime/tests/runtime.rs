@@ -2,6 +2,7 @@ use anyhow::{Context as _, Result};
 use wasmtime::{Config, Engine, Instance, Linker, Module, Store};
 
 test_helpers::runtime_tests_wasmtime!();
+test_helpers::roundtrip_tests_wasmtime!();
 
 fn default_config() -> Result<Config> {
     // Create an engine with caching enabled to assist with iteration in this
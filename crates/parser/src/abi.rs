@@ -1,6 +1,7 @@
 use crate::{
     Function, Int, Interface, Record, RecordKind, ResourceId, Type, TypeDefKind, TypeId, Variant,
 };
+use std::fmt;
 use std::mem;
 
 /// A raw WebAssembly signature with params and results.
@@ -26,6 +27,24 @@ pub enum WasmType {
     // e.g. externref, so we don't need to define them here.
 }
 
+impl WasmType {
+    /// Returns the name of this type in the WebAssembly text format.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WasmType::I32 => "i32",
+            WasmType::I64 => "i64",
+            WasmType::F32 => "f32",
+            WasmType::F64 => "f64",
+        }
+    }
+}
+
+impl fmt::Display for WasmType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 fn unify(a: WasmType, b: WasmType) -> WasmType {
     use WasmType::*;
 
@@ -687,7 +706,7 @@ def_instruction! {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Bitcast {
     // Upcasts
     F32ToF64,
@@ -1289,7 +1308,7 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                         AbiVariant::GuestImport => {
                             assert_eq!(self.stack.len(), sig.params.len() - 2);
                             self.emit(&Instruction::CallWasmAsyncImport {
-                                module: &self.iface.name,
+                                module: self.iface.module(),
                                 name: &func.name,
                                 params: &sig.params,
                                 results: tys,
@@ -1298,7 +1317,7 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                         AbiVariant::GuestExport => {
                             assert_eq!(self.stack.len(), sig.params.len() - 1);
                             self.emit(&Instruction::CallWasmAsyncExport {
-                                module: &self.iface.name,
+                                module: self.iface.module(),
                                 name: &func.name,
                                 params: &sig.params,
                                 results: tys,
@@ -1318,7 +1337,7 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                     // actual wasm function.
                     assert_eq!(self.stack.len(), sig.params.len());
                     self.emit(&Instruction::CallWasm {
-                        module: &self.iface.name,
+                        module: self.iface.module(),
                         name: &func.name,
                         sig: &sig,
                     });
@@ -1388,7 +1407,7 @@ impl<'a, B: Bindgen> Generator<'a, B> {
 
                 // ... and that allows us to call the interface types function
                 self.emit(&Instruction::CallInterface {
-                    module: &self.iface.name,
+                    module: self.iface.module(),
                     func,
                 });
 
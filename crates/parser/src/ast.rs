@@ -2,13 +2,17 @@ use crate::abi::Abi;
 use anyhow::Result;
 use lex::{Span, Token, Tokenizer};
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
 
+mod format;
 mod lex;
 mod resolve;
 
+pub use format::format;
 pub use lex::validate_id;
 
 pub struct Ast<'a> {
@@ -62,9 +66,30 @@ pub struct Resource<'a> {
     values: Vec<(bool, Value<'a>)>,
 }
 
+/// The comments and attributes which precede an item.
 #[derive(Default)]
 struct Docs<'a> {
     docs: Vec<Cow<'a, str>>,
+    attrs: Vec<Attribute<'a>>,
+}
+
+/// `@name` or `@name(args)`, where `args` is kept as written.
+struct Attribute<'a> {
+    name: Id<'a>,
+    args: Option<&'a str>,
+    /// `args` split at each `,` outside of any parentheses or strings.
+    values: Vec<AttributeValue<'a>>,
+    span: Span,
+}
+
+/// An argument of an attribute, written `value` or `key = value`.
+struct AttributeValue<'a> {
+    key: Option<&'a str>,
+    /// The value as written, or the contents of a string literal with its
+    /// escapes replaced.
+    value: Cow<'a, str>,
+    /// Whether `value` was written as a single string literal.
+    string: bool,
 }
 
 pub struct TypeDef<'a> {
@@ -140,9 +165,14 @@ enum ValueKind<'a> {
         results: Vec<(Id<'a>, Type<'a>)>,
     },
     Global(Type<'a>),
+    Const {
+        ty: Type<'a>,
+        value: i128,
+        /// The span of the literal `value`.
+        span: Span,
+    },
 }
 
-#[allow(dead_code)] // TODO
 pub struct Interface<'a> {
     docs: Docs<'a>,
     name: Id<'a>,
@@ -156,20 +186,50 @@ impl<'a> Ast<'a> {
         if lexer.eat(Token::Semicolon)? || lexer.eat(Token::LeftParen)? {
             return Ast::parse_old_witx(input);
         }
-        let mut items = Vec::new();
-        while lexer.clone().next()?.is_some() {
-            let docs = parse_docs(&mut lexer)?;
-            items.push(Item::parse(&mut lexer, docs)?);
+        let mut errors = Vec::new();
+        let items = parse_items(&mut lexer, false, &mut errors);
+        if errors.is_empty() {
+            Ok(Ast { items })
+        } else {
+            Err(Errors(errors).into())
+        }
+    }
+
+    /// Returns the names of the interfaces that `use` items load from other
+    /// files, which is every interface used that isn't nested in this one.
+    pub fn external_uses(&self) -> Vec<&Id<'a>> {
+        let mut nested = HashSet::new();
+        let mut uses = Vec::new();
+        collect(&self.items, &mut nested, &mut uses);
+        uses.retain(|id| !nested.contains(&*id.name));
+        return uses;
+
+        fn collect<'a, 'b>(
+            items: &'b [Item<'a>],
+            nested: &mut HashSet<&'b str>,
+            uses: &mut Vec<&'b Id<'a>>,
+        ) {
+            for item in items {
+                match item {
+                    Item::Use(u) => uses.push(&u.from[0]),
+                    Item::Interface(i) => {
+                        nested.insert(&i.name.name);
+                        collect(&i.items, nested, uses);
+                    }
+                    _ => {}
+                }
+            }
         }
-        Ok(Ast { items })
     }
 
     pub fn resolve(
         &self,
         name: &str,
+        file: &Path,
+        contents: &str,
         map: &HashMap<String, crate::Interface>,
     ) -> Result<crate::Interface> {
-        let mut resolver = resolve::Resolver::default();
+        let mut resolver = resolve::Resolver::new(file, contents);
         let instance = resolver.resolve(name, &self.items, map)?;
         Ok(instance)
     }
@@ -208,7 +268,20 @@ impl<'a> Ast<'a> {
                         name: id(&r.ident),
                         values: Vec::new(),
                     }),
-                    old::DeclSyntax::Const(_) => unimplemented!(),
+                    // witx scopes constants to their type, so their names
+                    // are prefixed with it to keep them distinct here.
+                    old::DeclSyntax::Const(c) => Item::Value(Value {
+                        docs: docs(&d.comments),
+                        name: Id {
+                            name: format!("{}_{}", c.ty.name(), c.name.name()).into(),
+                            span: span(c.name.span()),
+                        },
+                        kind: ValueKind::Const {
+                            ty: Type::Name(id(&c.ty)),
+                            value: c.value.into(),
+                            span: span(c.name.span()),
+                        },
+                    }),
                 },
             };
             items.push(item);
@@ -407,6 +480,7 @@ impl<'a> Ast<'a> {
             let docs = docs.docs();
             Docs {
                 docs: docs.lines().map(|s| format!("//{}\n", s).into()).collect(),
+                attrs: Vec::new(),
             }
         }
 
@@ -445,8 +519,153 @@ impl<'a> Ast<'a> {
     }
 }
 
+/// Parses items up to the end of the input or, if `nested`, up to and including
+/// the `}` closing an interface.
+///
+/// Errors are recorded in `errors` and parsing resumes at the next item after
+/// each one, so that as many errors as possible are found at once.
+fn parse_items<'a>(
+    tokens: &mut Tokenizer<'a>,
+    nested: bool,
+    errors: &mut Vec<anyhow::Error>,
+) -> Vec<Item<'a>> {
+    let mut items = Vec::new();
+    loop {
+        let begin = tokens.clone();
+        let err = match parse_item(tokens, nested, errors) {
+            Ok(Some(item)) => {
+                items.push(item);
+                continue;
+            }
+            Ok(None) => break,
+            Err(e) => e,
+        };
+        let pos = match error_position(&err) {
+            Some(pos) => pos,
+            None => {
+                errors.push(err);
+                break;
+            }
+        };
+        errors.push(err);
+        *tokens = begin;
+        if !recover(tokens, pos, nested, errors) {
+            break;
+        }
+    }
+    return items;
+
+    fn parse_item<'a>(
+        tokens: &mut Tokenizer<'a>,
+        nested: bool,
+        errors: &mut Vec<anyhow::Error>,
+    ) -> Result<Option<Item<'a>>> {
+        let docs = parse_docs(tokens)?;
+        if nested {
+            if tokens.eat(Token::RightBrace)? {
+                return Ok(None);
+            }
+        } else if tokens.clone().next()?.is_none() {
+            return Ok(None);
+        }
+        Item::parse(tokens, docs, errors).map(Some)
+    }
+}
+
+/// Skips from the start of the item that failed to parse with an error at
+/// `pos` to the start of the next item after the error, returning whether
+/// there's anything left to parse.
+///
+/// Keywords which always start an item are recognized anywhere, while `name:`
+/// and attributes are only recognized outside of any brackets of the item
+/// that failed. Any of these must be the first thing on its line.
+fn recover(
+    tokens: &mut Tokenizer<'_>,
+    pos: u32,
+    nested: bool,
+    errors: &mut Vec<anyhow::Error>,
+) -> bool {
+    let mut first = None;
+    let mut parens = 0u32;
+    let mut braces = 0u32;
+    loop {
+        let mut peek = tokens.clone();
+        let (span, token) = match peek.next() {
+            Ok(Some(pair)) => pair,
+            Ok(None) => return false,
+            Err(e) => {
+                // Errors before the one being recovered from can't happen as
+                // the item parsed successfully up to there.
+                if e.position() > pos as usize {
+                    errors.push(e.into());
+                }
+                *tokens = peek;
+                continue;
+            }
+        };
+        let first = *first.get_or_insert(span.start);
+        // Only the first token on a line is considered so that the keywords
+        // and names which caused the error aren't mistaken for a new item.
+        let line_start = tokens.input()[..span.start as usize]
+            .rsplit('\n')
+            .next()
+            .unwrap()
+            .trim()
+            .is_empty();
+        if span.start >= pos && span.start > first && line_start {
+            match token {
+                Token::Use
+                | Token::Type
+                | Token::Flags
+                | Token::Enum
+                | Token::Variant
+                | Token::Record
+                | Token::Union
+                | Token::Resource
+                | Token::Interface
+                | Token::Const => return true,
+                Token::At if parens == 0 && braces == 0 => return true,
+                Token::Id | Token::ExplicitId if parens == 0 && braces == 0 => {
+                    // `name:` starts a value, but `name::` is a `use` path.
+                    let mut after = peek.clone();
+                    if let Ok(Some((_, Token::Colon))) = after.next() {
+                        if !matches!(after.next_raw(), Ok(Some((_, Token::Colon)))) {
+                            return true;
+                        }
+                    }
+                }
+                Token::RightBrace if nested && braces == 0 => return true,
+                _ => {}
+            }
+        }
+        match token {
+            Token::LeftParen => parens += 1,
+            Token::RightParen => parens = parens.saturating_sub(1),
+            Token::LeftBrace => braces += 1,
+            Token::RightBrace => braces = braces.saturating_sub(1),
+            _ => {}
+        }
+        *tokens = peek;
+    }
+}
+
+/// Returns the offset in the input that an error from parsing occurred at.
+fn error_position(err: &anyhow::Error) -> Option<u32> {
+    if let Some(err) = err.downcast_ref::<Error>() {
+        return Some(err.span.start);
+    }
+    if let Some(err) = err.downcast_ref::<lex::Error>() {
+        return Some(u32::try_from(err.position()).unwrap());
+    }
+    None
+}
+
 impl<'a> Item<'a> {
-    fn parse(tokens: &mut Tokenizer<'a>, docs: Docs<'a>) -> Result<Item<'a>> {
+    fn parse(
+        tokens: &mut Tokenizer<'a>,
+        docs: Docs<'a>,
+        errors: &mut Vec<anyhow::Error>,
+    ) -> Result<Item<'a>> {
         match tokens.clone().next()? {
             Some((_span, Token::Use)) => Use::parse(tokens, docs).map(Item::Use),
             Some((_span, Token::Type)) => TypeDef::parse(tokens, docs).map(Item::TypeDef),
@@ -458,7 +677,10 @@ impl<'a> Item<'a> {
             Some((_span, Token::Record)) => TypeDef::parse_record(tokens, docs).map(Item::TypeDef),
             Some((_span, Token::Union)) => TypeDef::parse_union(tokens, docs).map(Item::TypeDef),
             Some((_span, Token::Resource)) => Resource::parse(tokens, docs).map(Item::Resource),
-            Some((_span, Token::Interface)) => Interface::parse(tokens, docs).map(Item::Interface),
+            Some((_span, Token::Interface)) => {
+                Interface::parse(tokens, docs, errors).map(Item::Interface)
+            }
+            Some((_span, Token::Const)) => Value::parse_const(tokens, docs).map(Item::Value),
             Some((_span, Token::Id)) | Some((_span, Token::ExplicitId)) => {
                 Value::parse(tokens, docs).map(Item::Value)
             }
@@ -468,7 +690,8 @@ impl<'a> Item<'a> {
 }
 
 impl<'a> Use<'a> {
-    fn parse(tokens: &mut Tokenizer<'a>, _docs: Docs<'a>) -> Result<Self> {
+    fn parse(tokens: &mut Tokenizer<'a>, docs: Docs<'a>) -> Result<Self> {
+        no_attributes(&docs)?;
         tokens.expect(Token::Use)?;
         let mut names = None;
         loop {
@@ -496,7 +719,7 @@ impl<'a> Use<'a> {
             tokens.expect(Token::RightBrace)?;
         }
         tokens.expect(Token::From_)?;
-        let mut from = vec![parse_id(tokens)?];
+        let mut from = vec![parse_use_path(tokens)?];
         while tokens.eat(Token::Colon)? {
             tokens.expect_raw(Token::Colon)?;
             from.push(parse_id(tokens)?);
@@ -645,7 +868,12 @@ impl<'a> Resource<'a> {
                     break;
                 }
                 let statik = tokens.eat(Token::Static)?;
-                values.push((statik, Value::parse(tokens, docs)?));
+                let value = if let Some((_, Token::Const)) = tokens.clone().next()? {
+                    Value::parse_const(tokens, docs)?
+                } else {
+                    Value::parse(tokens, docs)?
+                };
+                values.push((statik, value));
             }
         }
         Ok(Resource { docs, name, values })
@@ -653,6 +881,22 @@ impl<'a> Resource<'a> {
 }
 
 impl<'a> Value<'a> {
+    /// Parses `const name: type = value`.
+    fn parse_const(tokens: &mut Tokenizer<'a>, docs: Docs<'a>) -> Result<Self> {
+        tokens.expect(Token::Const)?;
+        let name = parse_id(tokens)?;
+        tokens.expect(Token::Colon)?;
+        let ty = Type::parse(tokens)?;
+        tokens.expect(Token::Equals)?;
+        let span = tokens.expect(Token::Integer)?;
+        let value = parse_integer(tokens, span)?;
+        Ok(Value {
+            docs,
+            name,
+            kind: ValueKind::Const { ty, value, span },
+        })
+    }
+
     fn parse(tokens: &mut Tokenizer<'a>, docs: Docs<'a>) -> Result<Self> {
         let name = parse_id(tokens)?;
         tokens.expect(Token::Colon)?;
@@ -733,6 +977,47 @@ fn parse_id<'a>(tokens: &mut Tokenizer<'a>) -> Result<Id<'a>> {
     }
 }
 
+/// Parses the integer literal at `span`, which is at most 64 bits wide.
+fn parse_integer(tokens: &Tokenizer<'_>, span: Span) -> Result<i128> {
+    let text = &tokens.input()[span.start as usize..span.end as usize];
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let digits = digits.replace('_', "");
+    let (radix, digits) = if let Some(hex) = digits.strip_prefix("0x") {
+        (16, hex)
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        (2, binary)
+    } else {
+        (10, &digits[..])
+    };
+    let value = match u64::from_str_radix(digits, radix) {
+        Ok(value) => i128::from(value),
+        Err(_) => {
+            return Err(Error {
+                span,
+                msg: format!("invalid integer `{}`", text),
+            }
+            .into())
+        }
+    };
+    Ok(if negative { -value } else { value })
+}
+
+/// Parses the name of the interface that a `use` item loads, which is either
+/// an identifier or a dotted path such as `dir.module` naming a file within a
+/// directory.
+fn parse_use_path<'a>(tokens: &mut Tokenizer<'a>) -> Result<Id<'a>> {
+    let mut id = parse_id(tokens)?;
+    while tokens.eat(Token::Period)? {
+        let next = parse_id(tokens)?;
+        id.name = format!("{}.{}", id.name, next.name).into();
+        id.span.end = next.span.end;
+    }
+    Ok(id)
+}
+
 fn parse_opt_id<'a>(tokens: &mut Tokenizer<'a>) -> Result<Option<Id<'a>>> {
     let mut other = tokens.clone();
     match other.next()? {
@@ -761,6 +1046,7 @@ fn parse_docs<'a>(tokens: &mut Tokenizer<'a>) -> Result<Docs<'a>> {
         match token {
             Token::Whitespace => {}
             Token::Comment => docs.docs.push(tokens.get_span(span).into()),
+            Token::At => docs.attrs.push(parse_attribute(&mut clone, span)?),
             _ => break,
         };
         *tokens = clone.clone();
@@ -768,6 +1054,96 @@ fn parse_docs<'a>(tokens: &mut Tokenizer<'a>) -> Result<Docs<'a>> {
     Ok(docs)
 }
 
+/// Parses the rest of an attribute whose `@` is at `at`.
+fn parse_attribute<'a>(tokens: &mut Tokenizer<'a>, at: Span) -> Result<Attribute<'a>> {
+    let name = parse_id(tokens)?;
+    let mut end = name.span.end;
+    let mut args = None;
+    let mut values = Vec::new();
+    if let Some((_, Token::LeftParen)) = tokens.clone().next()? {
+        let open = tokens.expect(Token::LeftParen)?;
+        // Arguments can be any tokens with balanced parentheses.
+        let mut depth = 0;
+        let mut value = Vec::new();
+        loop {
+            let token = tokens.next_raw()?;
+            match token {
+                Some((_, Token::Whitespace)) | Some((_, Token::Comment)) => continue,
+                Some((_, Token::Comma)) | Some((_, Token::RightParen)) if depth == 0 => {
+                    values.extend(attribute_value(tokens, &value));
+                    value.clear();
+                }
+                _ => {}
+            }
+            match token {
+                Some((_, Token::LeftParen)) => depth += 1,
+                Some((span, Token::RightParen)) if depth == 0 => {
+                    let text = tokens.get_span(Span {
+                        start: open.end,
+                        end: span.start,
+                    });
+                    args = Some(text.trim());
+                    end = span.end;
+                    break;
+                }
+                Some((_, Token::RightParen)) => depth -= 1,
+                Some((_, Token::Comma)) if depth == 0 => continue,
+                Some(_) => {}
+                None => return Err(err_expected(tokens, "')'", None).into()),
+            }
+            value.extend(token);
+        }
+    }
+    Ok(Attribute {
+        name,
+        args,
+        values,
+        span: Span {
+            start: at.start,
+            end,
+        },
+    })
+}
+
+/// Interprets the tokens of a single argument of an attribute, returning
+/// `None` if there aren't any.
+fn attribute_value<'a>(
+    tokens: &Tokenizer<'a>,
+    value: &[(Span, Token)],
+) -> Option<AttributeValue<'a>> {
+    let (key, value) = match value {
+        [(key, _), (_, Token::Equals), rest @ ..] => (Some(tokens.get_span(*key)), rest),
+        _ => (None, value),
+    };
+    let (value, string) = match value {
+        [(span, Token::Str)] => (tokens.parse_str(*span).into(), true),
+        [(first, _), ..] => {
+            let (last, _) = value.last().unwrap();
+            let span = Span {
+                start: first.start,
+                end: last.end,
+            };
+            (tokens.get_span(span).into(), false)
+        }
+        [] if key.is_some() => ("".into(), false),
+        [] => return None,
+    };
+    Some(AttributeValue { key, value, string })
+}
+
+/// Returns an error if any attributes were written before something which
+/// isn't an item.
+fn no_attributes(docs: &Docs<'_>) -> Result<()> {
+    match docs.attrs.first() {
+        Some(attr) => Err(Error {
+            span: attr.span,
+            msg: "attributes are only allowed on items".to_string(),
+        }
+        .into()),
+        None => Ok(()),
+    }
+}
+
 impl<'a> Type<'a> {
     fn parse(tokens: &mut Tokenizer<'a>) -> Result<Self> {
         match tokens.next()? {
@@ -929,18 +1305,15 @@ impl<'a> Type<'a> {
 }
 
 impl<'a> Interface<'a> {
-    fn parse(tokens: &mut Tokenizer<'a>, docs: Docs<'a>) -> Result<Self> {
+    fn parse(
+        tokens: &mut Tokenizer<'a>,
+        docs: Docs<'a>,
+        errors: &mut Vec<anyhow::Error>,
+    ) -> Result<Self> {
         tokens.expect(Token::Interface)?;
         let name = parse_id(tokens)?;
         tokens.expect(Token::LeftBrace)?;
-        let mut items = Vec::new();
-        loop {
-            let docs = parse_docs(tokens)?;
-            if tokens.eat(Token::RightBrace)? {
-                break;
-            }
-            items.push(Item::parse(tokens, docs)?);
-        }
+        let items = parse_items(tokens, true, errors);
         Ok(Interface { docs, name, items })
     }
 }
@@ -956,6 +1329,7 @@ fn parse_list<'a, T>(
     loop {
         // get docs before we skip them to try to eat the end token
         let docs = parse_docs(tokens)?;
+        no_attributes(&docs)?;
 
         // if we found an end token then we're done
         if tokens.eat(end)? {
@@ -1009,28 +1383,152 @@ impl fmt::Display for Error {
 
 impl std::error::Error for Error {}
 
-pub fn rewrite_error(err: &mut anyhow::Error, file: &str, contents: &str) {
-    #[cfg(feature = "witx-compat")]
-    if let Some(err) = err.downcast_mut::<wast::Error>() {
-        err.set_path(file.as_ref());
-        err.set_text(contents);
-        return;
+/// An error which also points at another location, such as the previous
+/// definition of a name which is defined twice.
+#[derive(Debug)]
+struct RelatedError {
+    error: Error,
+    related: crate::Span,
+    label: String,
+}
+
+impl fmt::Display for RelatedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl std::error::Error for RelatedError {}
+
+/// All of the errors found while parsing, in the order they were found.
+#[derive(Debug)]
+struct Errors(Vec<anyhow::Error>);
+
+impl fmt::Display for Errors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, err) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str("\n")?;
+            }
+            err.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Errors {}
+
+/// Translates byte offsets within a file into `crate::Span`s.
+#[derive(Clone, Default)]
+pub struct SourceLines {
+    file: Option<Arc<Path>>,
+    /// Byte offsets of the start of each line.
+    starts: Vec<u32>,
+}
+
+impl SourceLines {
+    pub fn new(file: &Path, contents: &str) -> SourceLines {
+        let starts = std::iter::once(0)
+            .chain(contents.match_indices('\n').map(|(i, _)| i as u32 + 1))
+            .collect();
+        SourceLines {
+            file: Some(file.into()),
+            starts,
+        }
+    }
+
+    fn span(&self, span: Span) -> crate::Span {
+        let line = match self.starts.binary_search(&span.start) {
+            Ok(i) => i,
+            Err(i) => i.saturating_sub(1),
+        };
+        crate::Span {
+            file: self.file.clone(),
+            start: span.start,
+            end: span.end,
+            line: line as u32 + 1,
+            column: span.start - self.starts.get(line).copied().unwrap_or(0) + 1,
+        }
     }
-    let parse = match err.downcast_mut::<Error>() {
-        Some(err) => err,
-        None => return lex::rewrite_error(err, file, contents),
+}
+
+/// Attaches the location of `id`, the interface named by a `use` item, to
+/// `err` from failing to load that interface.
+pub fn load_error(id: &Id<'_>, err: anyhow::Error) -> anyhow::Error {
+    Error {
+        span: id.span,
+        msg: format!("{:#}", err),
+    }
+    .into()
+}
+
+/// Converts `err`, from parsing or resolving `contents` of `file`, into a
+/// `ParseError` with a diagnostic for each error within it.
+///
+/// For `*.md` files `contents` is the extracted `wit` code while `original` is
+/// the whole file. Errors which don't point into `contents`, such as a failure
+/// to load a dependency, are returned unchanged.
+pub fn rewrite_error(
+    err: anyhow::Error,
+    file: &Path,
+    contents: &str,
+    original: &str,
+) -> anyhow::Error {
+    #[cfg(feature = "witx-compat")]
+    let err = match err.downcast::<wast::Error>() {
+        Ok(mut e) => {
+            e.set_path(file);
+            e.set_text(contents);
+            return e.into();
+        }
+        Err(err) => err,
     };
-    let msg = highlight_err(
-        parse.span.start as usize,
-        Some(parse.span.end as usize),
-        file,
-        contents,
-        &parse.msg,
-    );
-    *err = anyhow::anyhow!("{}", msg);
+    let lines = SourceLines::new(file, contents);
+    let diagnostics = match err.downcast_ref::<Errors>() {
+        Some(errors) => {
+            let mut diagnostics = errors
+                .0
+                .iter()
+                .map(|e| {
+                    diagnostic(e, &lines).unwrap_or_else(|| {
+                        crate::Diagnostic::error(Default::default(), e.to_string())
+                    })
+                })
+                .collect::<Vec<_>>();
+            // Resolution finds errors in several passes over the file, so
+            // they're put back in the order they appear in it.
+            diagnostics.sort_by_key(|d| d.span.start);
+            diagnostics
+        }
+        None => match diagnostic(&err, &lines) {
+            Some(d) => vec![d],
+            None => return err,
+        },
+    };
+    return crate::ParseError::new(diagnostics, original).into();
+
+    fn diagnostic(err: &anyhow::Error, lines: &SourceLines) -> Option<crate::Diagnostic> {
+        if let Some(e) = err.downcast_ref::<Error>() {
+            return Some(crate::Diagnostic::error(lines.span(e.span), &e.msg));
+        }
+        if let Some(e) = err.downcast_ref::<RelatedError>() {
+            let mut d = crate::Diagnostic::error(lines.span(e.error.span), &e.error.msg);
+            d.related.push((e.related.clone(), e.label.clone()));
+            return Some(d);
+        }
+        if let Some(e) = err.downcast_ref::<lex::Error>() {
+            let pos = u32::try_from(e.position()).unwrap();
+            let span = Span {
+                start: pos,
+                end: pos,
+            };
+            return Some(crate::Diagnostic::error(lines.span(span), e.to_string()));
+        }
+        None
+    }
 }
 
-fn highlight_err(
+pub fn highlight_err(
     start: usize,
     end: Option<usize>,
     file: &str,
@@ -0,0 +1,542 @@
+//! A pretty-printer for `*.wit` source which, unlike printing a resolved
+//! `Interface`, preserves comments and the order of items as written.
+//!
+//! The source is parsed with `Ast::parse` first so only valid input is
+//! formatted, and then the raw token stream, including comments and
+//! whitespace, is walked following the same grammar as the parser.
+
+use super::lex::{Span, Token, Tokenizer};
+use super::Ast;
+use anyhow::{bail, Result};
+
+const INDENT: &str = "  ";
+
+pub fn format(input: &str) -> Result<String> {
+    Ast::parse(input)?;
+
+    let mut tokens = Vec::new();
+    let mut lexer = Tokenizer::new(input)?;
+    while let Some(token) = lexer.next_raw()? {
+        tokens.push(token);
+    }
+    if let Some((_, Token::LeftParen)) | Some((_, Token::Semicolon)) = tokens
+        .iter()
+        .find(|(_, t)| !matches!(t, Token::Whitespace | Token::Comment))
+    {
+        bail!("formatting `*.witx` files is not supported");
+    }
+
+    let mut f = Formatter {
+        input: lexer.input(),
+        tokens,
+        pos: 0,
+        out: String::new(),
+        indent: 0,
+        line_start: true,
+        newlines: 0,
+    };
+    f.trivia();
+    while f.peek().is_some() {
+        f.item()?;
+        f.newline();
+        f.trivia();
+    }
+
+    let mut out = f.out.trim_end().to_string();
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+struct Formatter<'a> {
+    input: &'a str,
+    tokens: Vec<(Span, Token)>,
+    pos: usize,
+    out: String,
+    indent: usize,
+    /// Whether nothing has been written on the current output line yet.
+    line_start: bool,
+    /// The number of newlines in the source since the last token or comment.
+    newlines: usize,
+}
+
+impl Formatter<'_> {
+    fn item(&mut self) -> Result<()> {
+        self.attributes()?;
+        match self.peek() {
+            Some(Token::Use) => self.use_(),
+            Some(Token::Const) => self.const_(),
+            Some(Token::Type) => {
+                self.token(Token::Type)?;
+                self.space();
+                self.id()?;
+                self.space();
+                self.token(Token::Equals)?;
+                self.space();
+                self.ty()
+            }
+            Some(Token::Record) => self.typedef(Token::Record, |f| {
+                f.id()?;
+                f.token(Token::Colon)?;
+                f.space();
+                f.ty()
+            }),
+            Some(Token::Flags) => self.typedef(Token::Flags, |f| f.id()),
+            Some(Token::Enum) => self.typedef(Token::Enum, |f| f.id()),
+            Some(Token::Union) => self.typedef(Token::Union, |f| f.ty()),
+            Some(Token::Variant) => self.typedef(Token::Variant, |f| {
+                f.id()?;
+                if f.peek() == Some(Token::LeftParen) {
+                    f.token(Token::LeftParen)?;
+                    f.ty()?;
+                    f.token(Token::RightParen)?;
+                }
+                Ok(())
+            }),
+            Some(Token::Resource) => {
+                self.token(Token::Resource)?;
+                self.space();
+                self.id()?;
+                if self.peek() == Some(Token::LeftBrace) {
+                    self.space();
+                    self.block(false, |f| {
+                        f.attributes()?;
+                        if f.peek() == Some(Token::Static) {
+                            f.token(Token::Static)?;
+                            f.space();
+                        }
+                        if f.peek() == Some(Token::Const) {
+                            return f.const_();
+                        }
+                        f.value()
+                    })?;
+                }
+                Ok(())
+            }
+            Some(Token::Interface) => {
+                self.token(Token::Interface)?;
+                self.space();
+                self.id()?;
+                self.space();
+                self.block(false, |f| f.item())
+            }
+            _ => self.value(),
+        }
+    }
+
+    /// Formats each attribute before an item on a line of its own, keeping
+    /// its arguments as written.
+    fn attributes(&mut self) -> Result<()> {
+        while self.peek() == Some(Token::At) {
+            self.token(Token::At)?;
+            self.id()?;
+            if self.peek() == Some(Token::LeftParen) {
+                self.token(Token::LeftParen)?;
+                let start = self.tokens[self.pos].0.start as usize;
+                let mut depth = 0;
+                let end = loop {
+                    let (span, token) = match self.tokens.get(self.pos) {
+                        Some(token) => *token,
+                        None => bail!("unexpected end of input while formatting"),
+                    };
+                    self.pos += 1;
+                    match token {
+                        Token::LeftParen => depth += 1,
+                        Token::RightParen if depth == 0 => break span.start as usize,
+                        Token::RightParen => depth -= 1,
+                        _ => {}
+                    }
+                };
+                self.newlines = 0;
+                let args = self.input[start..end].trim().to_string();
+                self.write(&args);
+                self.write(")");
+            }
+            self.newline();
+            self.trivia();
+        }
+        Ok(())
+    }
+
+    fn use_(&mut self) -> Result<()> {
+        self.token(Token::Use)?;
+        self.space();
+        if self.peek() == Some(Token::Star) {
+            self.token(Token::Star)?;
+        } else {
+            self.token(Token::LeftBrace)?;
+            self.space();
+            while self.peek() != Some(Token::RightBrace) {
+                self.id()?;
+                if self.peek() == Some(Token::As) {
+                    self.space();
+                    self.token(Token::As)?;
+                    self.space();
+                    self.id()?;
+                }
+                self.separator(Token::RightBrace)?;
+            }
+            self.space();
+            self.token(Token::RightBrace)?;
+        }
+        self.space();
+        self.token(Token::From_)?;
+        self.space();
+        self.id()?;
+        while self.peek() == Some(Token::Period) {
+            self.token(Token::Period)?;
+            self.id()?;
+        }
+        while self.peek() == Some(Token::Colon) {
+            self.token(Token::Colon)?;
+            self.token(Token::Colon)?;
+            self.id()?;
+        }
+        Ok(())
+    }
+
+    fn const_(&mut self) -> Result<()> {
+        self.token(Token::Const)?;
+        self.space();
+        self.id()?;
+        self.token(Token::Colon)?;
+        self.space();
+        self.ty()?;
+        self.space();
+        self.token(Token::Equals)?;
+        self.space();
+        self.token(Token::Integer)
+    }
+
+    fn typedef(&mut self, keyword: Token, case: fn(&mut Self) -> Result<()>) -> Result<()> {
+        self.token(keyword)?;
+        self.space();
+        self.id()?;
+        self.space();
+        self.block(true, case)
+    }
+
+    fn value(&mut self) -> Result<()> {
+        self.id()?;
+        self.token(Token::Colon)?;
+        self.space();
+        if self.peek() == Some(Token::Async) {
+            self.token(Token::Async)?;
+            self.space();
+        }
+        if self.peek() != Some(Token::Function) {
+            return self.ty();
+        }
+        self.token(Token::Function)?;
+        self.list(|f| {
+            f.id()?;
+            f.token(Token::Colon)?;
+            f.space();
+            f.ty()
+        })?;
+        if self.peek() == Some(Token::RArrow) {
+            self.space();
+            self.token(Token::RArrow)?;
+            self.space();
+            if self.peek() == Some(Token::LeftParen) {
+                self.list(|f| f.result())?;
+            } else {
+                self.result()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn result(&mut self) -> Result<()> {
+        if let Some(Token::Id) | Some(Token::ExplicitId) = self.peek() {
+            if self.peek_nth(1) == Some(Token::Colon) {
+                self.id()?;
+                self.token(Token::Colon)?;
+                self.space();
+            }
+        }
+        self.ty()
+    }
+
+    fn ty(&mut self) -> Result<()> {
+        let token = self.peek();
+        match token {
+            Some(Token::Handle) => {
+                self.token(Token::Handle)?;
+                self.space();
+                self.id()
+            }
+            Some(Token::List)
+            | Some(Token::Option_)
+            | Some(Token::Expected)
+            | Some(Token::Tuple)
+            | Some(Token::PushBuffer)
+            | Some(Token::PullBuffer) => {
+                self.next()?;
+                self.token(Token::LessThan)?;
+                while self.peek() != Some(Token::GreaterThan) {
+                    if self.peek() == Some(Token::Underscore) {
+                        self.token(Token::Underscore)?;
+                    } else {
+                        self.ty()?;
+                    }
+                    self.separator(Token::GreaterThan)?;
+                }
+                self.token(Token::GreaterThan)
+            }
+            Some(Token::U8)
+            | Some(Token::U16)
+            | Some(Token::U32)
+            | Some(Token::U64)
+            | Some(Token::S8)
+            | Some(Token::S16)
+            | Some(Token::S32)
+            | Some(Token::S64)
+            | Some(Token::F32)
+            | Some(Token::F64)
+            | Some(Token::Char)
+            | Some(Token::Bool)
+            | Some(Token::String_)
+            | Some(Token::Id)
+            | Some(Token::ExplicitId) => self.next(),
+            other => bail!("unexpected token {:?} while formatting a type", other),
+        }
+    }
+
+    /// Formats a `{ ... }` body with one element per line, where elements are
+    /// separated by commas if `commas` is set.
+    fn block(&mut self, commas: bool, elem: fn(&mut Self) -> Result<()>) -> Result<()> {
+        self.token(Token::LeftBrace)?;
+        if self.peek() == Some(Token::RightBrace) && !self.has_comment(Token::RightBrace) {
+            return self.token(Token::RightBrace);
+        }
+        self.indent += 1;
+        self.newline();
+        loop {
+            self.trivia();
+            if self.peek() == Some(Token::RightBrace) {
+                break;
+            }
+            elem(self)?;
+            if commas {
+                self.write(",");
+                if self.peek() == Some(Token::Comma) {
+                    self.skip()?;
+                }
+            }
+            self.newline();
+        }
+        self.indent -= 1;
+        self.token(Token::RightBrace)
+    }
+
+    /// Formats a parenthesized, comma-separated list, which is kept on one
+    /// line unless it started on a new line in the source.
+    fn list(&mut self, elem: fn(&mut Self) -> Result<()>) -> Result<()> {
+        self.token(Token::LeftParen)?;
+        let vertical = self.source_newline() && self.peek() != Some(Token::RightParen);
+        if !vertical {
+            while self.peek() != Some(Token::RightParen) {
+                elem(self)?;
+                self.separator(Token::RightParen)?;
+            }
+            return self.token(Token::RightParen);
+        }
+        self.indent += 1;
+        self.newline();
+        loop {
+            self.trivia();
+            if self.peek() == Some(Token::RightParen) {
+                break;
+            }
+            elem(self)?;
+            self.write(",");
+            if self.peek() == Some(Token::Comma) {
+                self.skip()?;
+            }
+            self.newline();
+        }
+        self.indent -= 1;
+        self.token(Token::RightParen)
+    }
+
+    /// Consumes the comma after an element of an inline list, writing it
+    /// unless the list ends with `end` next.
+    fn separator(&mut self, end: Token) -> Result<()> {
+        if self.peek() == Some(Token::Comma) {
+            self.skip()?;
+            if self.peek() != Some(end) {
+                self.write(",");
+                self.space();
+            }
+        }
+        Ok(())
+    }
+
+    fn id(&mut self) -> Result<()> {
+        match self.peek() {
+            Some(Token::Id) | Some(Token::ExplicitId) => self.next(),
+            other => bail!("expected an identifier while formatting, found {:?}", other),
+        }
+    }
+
+    fn token(&mut self, expected: Token) -> Result<()> {
+        match self.peek() {
+            Some(t) if t == expected => self.next(),
+            other => bail!(
+                "expected {:?} while formatting, found {:?}",
+                expected,
+                other
+            ),
+        }
+    }
+
+    /// Writes out the next token as it appears in the source.
+    fn next(&mut self) -> Result<()> {
+        self.trivia();
+        let blank_line = self.line_start && self.newlines >= 2;
+        let (span, token) = self.take()?;
+        if blank_line && token != Token::RightBrace {
+            self.blank_line();
+        }
+        let text = &self.input[span.start as usize..span.end as usize];
+        self.write(text);
+        Ok(())
+    }
+
+    /// Consumes the next token without writing it out.
+    fn skip(&mut self) -> Result<()> {
+        self.take()?;
+        Ok(())
+    }
+
+    fn take(&mut self) -> Result<(Span, Token)> {
+        self.trivia();
+        match self.tokens.get(self.pos) {
+            Some(token) => {
+                self.pos += 1;
+                self.newlines = 0;
+                Ok(*token)
+            }
+            None => bail!("unexpected end of input while formatting"),
+        }
+    }
+
+    /// Writes out any comments before the next token, keeping track of how
+    /// many newlines precede it.
+    fn trivia(&mut self) {
+        while let Some((span, token)) = self.tokens.get(self.pos).cloned() {
+            let text = &self.input[span.start as usize..span.end as usize];
+            match token {
+                Token::Whitespace => {
+                    self.newlines += text.matches('\n').count();
+                    self.pos += 1;
+                    continue;
+                }
+                Token::Comment => {}
+                _ => break,
+            }
+            self.pos += 1;
+            let line_comment = text.starts_with("//");
+            let text = text.trim_end();
+
+            if !self.line_start {
+                // A comment in the middle of a line stays there, but a line
+                // comment forces the rest onto the next line.
+                self.space();
+                self.write(text);
+                if line_comment {
+                    self.newline();
+                } else {
+                    self.space();
+                }
+            } else if self.newlines == 0 && self.out.ends_with('\n') && !self.out.trim().is_empty()
+            {
+                // A comment on the same line as the previous token in the
+                // source trails what was written for that token.
+                self.out.pop();
+                self.out.push(' ');
+                self.out.push_str(text);
+                self.out.push('\n');
+            } else {
+                if self.newlines >= 2 {
+                    self.blank_line();
+                }
+                self.write(text);
+                if line_comment || self.source_newline() {
+                    self.newline();
+                } else {
+                    self.space();
+                }
+            }
+            self.newlines = if line_comment { 1 } else { 0 };
+        }
+    }
+
+    /// Returns whether the next token or comment is on a new line in the
+    /// source.
+    fn source_newline(&self) -> bool {
+        match self.tokens.get(self.pos) {
+            Some((span, Token::Whitespace)) => {
+                self.input[span.start as usize..span.end as usize].contains('\n')
+            }
+            Some(_) => self.newlines > 0,
+            None => true,
+        }
+    }
+
+    /// Returns whether there are any comments before the next `expected`
+    /// token.
+    fn has_comment(&self, expected: Token) -> bool {
+        self.tokens[self.pos..]
+            .iter()
+            .take_while(|(_, t)| *t != expected)
+            .any(|(_, t)| *t == Token::Comment)
+    }
+
+    fn peek(&self) -> Option<Token> {
+        self.peek_nth(0)
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<Token> {
+        self.tokens[self.pos..]
+            .iter()
+            .map(|(_, t)| *t)
+            .filter(|t| !matches!(t, Token::Whitespace | Token::Comment))
+            .nth(n)
+    }
+
+    fn write(&mut self, text: &str) {
+        if self.line_start {
+            for _ in 0..self.indent {
+                self.out.push_str(INDENT);
+            }
+            self.line_start = false;
+        }
+        self.out.push_str(text);
+    }
+
+    fn space(&mut self) {
+        if !self.line_start && !self.out.ends_with(' ') {
+            self.out.push(' ');
+        }
+    }
+
+    fn newline(&mut self) {
+        if !self.line_start {
+            while self.out.ends_with(' ') {
+                self.out.pop();
+            }
+            self.out.push('\n');
+            self.line_start = true;
+        }
+    }
+
+    fn blank_line(&mut self) {
+        let after_open = self.out.ends_with("{\n") || self.out.ends_with("(\n");
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") && !after_open {
+            self.out.push('\n');
+        }
+    }
+}
@@ -45,6 +45,10 @@ pub enum Token {
     GreaterThan,
     RArrow,
     Star,
+    Period,
+    At,
+    Integer,
+    Str,
 
     Use,
     Type,
@@ -81,6 +85,7 @@ pub enum Token {
     Interface,
     Tuple,
     Async,
+    Const,
 
     Id,
     ExplicitId,
@@ -136,6 +141,26 @@ impl<'a> Tokenizer<'a> {
         Ok(ret)
     }
 
+    /// Returns the contents of the string literal at `span` with its escapes,
+    /// which were validated when it was lexed, replaced.
+    pub fn parse_str(&self, span: Span) -> String {
+        let token = self.get_span(span);
+        let mut ret = String::new();
+        let mut chars = token[1..token.len() - 1].chars();
+        while let Some(ch) = chars.next() {
+            ret.push(match ch {
+                '\\' => match chars.next() {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some(ch) => ch,
+                    None => unreachable!(),
+                },
+                ch => ch,
+            });
+        }
+        ret
+    }
+
     pub fn parse_explicit_id(&self, span: Span) -> Result<String> {
         let token = self.get_span(span);
         let id_part = token.strip_prefix('%').unwrap();
@@ -202,13 +227,43 @@ impl<'a> Tokenizer<'a> {
             '<' => LessThan,
             '>' => GreaterThan,
             '*' => Star,
+            '.' => Period,
+            '@' => At,
             '-' => {
                 if self.eatc('>') {
                     RArrow
+                } else if self
+                    .chars
+                    .clone()
+                    .next()
+                    .map_or(false, |(_, c)| c.is_ascii_digit())
+                {
+                    self.integer();
+                    Integer
                 } else {
                     return Err(Error::Unexpected(start, '-'));
                 }
             }
+            '0'..='9' => {
+                self.integer();
+                Integer
+            }
+            '"' => {
+                loop {
+                    match self.chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\n')) => return Err(Error::NewlineInString(start)),
+                        Some((_, '\\')) => match self.chars.next() {
+                            Some((_, '"')) | Some((_, '\\')) | Some((_, 'n')) | Some((_, 't')) => {}
+                            Some((pos, ch)) => return Err(Error::InvalidEscape(pos, ch)),
+                            None => return Err(Error::UnterminatedString(start)),
+                        },
+                        Some(_) => {}
+                        None => return Err(Error::UnterminatedString(start)),
+                    }
+                }
+                Str
+            }
             '%' => {
                 let mut iter = self.chars.clone();
                 if let Some((_, ch)) = iter.next() {
@@ -270,6 +325,7 @@ impl<'a> Tokenizer<'a> {
                     "interface" => Interface,
                     "tuple" => Tuple,
                     "async" => Async,
+                    "const" => Const,
                     _ => Id,
                 }
             }
@@ -339,6 +395,19 @@ impl<'a> Tokenizer<'a> {
         }
     }
 
+    /// Consumes the rest of an integer literal, which may be written in hex
+    /// or binary with a `0x` or `0b` prefix and have `_` separators. The
+    /// literal is validated by the parser.
+    fn integer(&mut self) {
+        let mut iter = self.chars.clone();
+        while let Some((_, ch)) = iter.next() {
+            if !ch.is_ascii_alphanumeric() && ch != '_' {
+                break;
+            }
+            self.chars = iter.clone();
+        }
+    }
+
     fn eatc(&mut self, ch: char) -> bool {
         let mut iter = self.chars.clone();
         match iter.next() {
@@ -531,12 +600,35 @@ impl Token {
             PullBuffer => "keyword `pull-buffer`",
             RArrow => "`->`",
             Star => "`*`",
+            Period => "`.`",
+            At => "`@`",
+            Integer => "an integer",
+            Str => "a string",
             As => "keyword `as`",
             From_ => "keyword `from`",
             Static => "keyword `static`",
             Interface => "keyword `interface`",
             Tuple => "keyword `tuple`",
             Async => "keyword `async`",
+            Const => "keyword `const`",
+        }
+    }
+}
+
+impl Error {
+    /// Returns the byte offset in the input that this error occurred at.
+    pub fn position(&self) -> usize {
+        match self {
+            Error::Unexpected(at, _)
+            | Error::UnterminatedComment(at)
+            | Error::Wanted { at, .. }
+            | Error::UnterminatedString(at)
+            | Error::NewlineInString(at)
+            | Error::InvalidCharInString(at, _)
+            | Error::InvalidCharInId(at, _)
+            | Error::IdNotSSNFC(at)
+            | Error::IdPartEmpty(at)
+            | Error::InvalidEscape(at, _) => *at,
         }
     }
 }
@@ -562,27 +654,6 @@ impl fmt::Display for Error {
     }
 }
 
-pub fn rewrite_error(err: &mut anyhow::Error, file: &str, contents: &str) {
-    let lex = match err.downcast_mut::<Error>() {
-        Some(err) => err,
-        None => return,
-    };
-    let pos = match lex {
-        Error::Unexpected(at, _)
-        | Error::UnterminatedComment(at)
-        | Error::Wanted { at, .. }
-        | Error::UnterminatedString(at)
-        | Error::NewlineInString(at)
-        | Error::InvalidCharInString(at, _)
-        | Error::InvalidCharInId(at, _)
-        | Error::IdNotSSNFC(at)
-        | Error::IdPartEmpty(at)
-        | Error::InvalidEscape(at, _) => *at,
-    };
-    let msg = super::highlight_err(pos, None, file, contents, lex);
-    *err = anyhow::anyhow!("{}", msg);
-}
-
 #[test]
 fn test_validate_id() {
     validate_id(0, "apple").unwrap();
@@ -677,7 +748,21 @@ fn test_tokenizer() {
     assert_eq!(collect("%a-a").unwrap(), vec![Token::ExplicitId]);
     assert_eq!(collect("%bool").unwrap(), vec![Token::ExplicitId]);
     assert_eq!(collect("%").unwrap(), vec![Token::ExplicitId]);
+    assert_eq!(collect("\"a b\"").unwrap(), vec![Token::Str]);
+    assert_eq!(collect("\"a\\\"b\"").unwrap(), vec![Token::Str]);
+    assert_eq!(
+        collect("@a(\"b\")").unwrap(),
+        vec![
+            Token::At,
+            Token::Id,
+            Token::LeftParen,
+            Token::Str,
+            Token::RightParen
+        ]
+    );
 
+    assert!(collect("\"a").is_err(), "unterminated string");
+    assert!(collect("\"a\nb\"").is_err(), "newline in string");
     assert!(collect("\u{149}").is_err(), "strongly discouraged");
     assert!(collect("\u{673}").is_err(), "strongly discouraged");
     assert!(collect("\u{17a3}").is_err(), "strongly discouraged");
@@ -1,11 +1,17 @@
-use super::{Error, Item, Span, Value, ValueKind};
+use super::{Error, Errors, Item, RelatedError, SourceLines, Span, Value, ValueKind};
 use crate::*;
 use anyhow::Result;
 use std::collections::{HashMap, HashSet};
 use std::mem;
+use std::path::Path;
 
 #[derive(Default)]
 pub struct Resolver {
+    lines: SourceLines,
+    /// Where each name in `type_lookup` and `resource_lookup` was defined in
+    /// this scope, either by its declaration or by a `use`.
+    type_spans: HashMap<String, Span>,
+    resource_spans: HashMap<String, Span>,
     type_lookup: HashMap<String, TypeId>,
     types: Arena<TypeDef>,
     resource_lookup: HashMap<String, ResourceId>,
@@ -15,8 +21,15 @@ pub struct Resolver {
     anon_types: HashMap<Key, TypeId>,
     functions: Vec<Function>,
     globals: Vec<Global>,
+    constants: Vec<Constant>,
+    /// Errors found so far, which don't stop resolution so that they can all
+    /// be reported at once.
+    errors: Vec<anyhow::Error>,
 }
 
+/// The names and types of the parameters or results of a function.
+type Params = Vec<(String, Type)>;
+
 #[derive(PartialEq, Eq, Hash)]
 enum Key {
     Variant(Vec<(String, Option<Type>)>),
@@ -29,83 +42,184 @@ enum Key {
 }
 
 impl Resolver {
+    pub(super) fn new(file: &Path, contents: &str) -> Resolver {
+        Resolver {
+            lines: SourceLines::new(file, contents),
+            ..Resolver::default()
+        }
+    }
+
     pub(super) fn resolve(
         &mut self,
         name: &str,
         fields: &[Item<'_>],
         deps: &HashMap<String, Interface>,
     ) -> Result<Interface> {
-        // First pull in any names from our dependencies
-        self.process_use(fields, deps)?;
+        let iface = self.resolve_scope(name, None, fields, &|name| deps.get(name));
+        if self.errors.is_empty() {
+            Ok(iface)
+        } else {
+            Err(Errors(mem::take(&mut self.errors)).into())
+        }
+    }
+
+    /// Resolves the interface `name` made up of `fields`, where `outer` looks
+    /// up the interfaces that `use` can refer to from the enclosing scope and
+    /// `module` is the path to a nested interface.
+    ///
+    /// Errors are recorded in `self.errors`, skipping whichever item they were
+    /// found in, so the interface returned is incomplete if there were any.
+    fn resolve_scope<'a>(
+        &mut self,
+        name: &str,
+        module: Option<String>,
+        fields: &[Item<'_>],
+        outer: &dyn Fn(&str) -> Option<&'a Interface>,
+    ) -> Interface {
+        // Nested interfaces are resolved first, each in a scope of its own, so
+        // that names can be `use`d from them both here and in the nested
+        // interfaces which follow them.
+        let mut interfaces = Arena::new();
+        let mut interface_lookup = HashMap::new();
+        for field in fields {
+            let i = match field {
+                Item::Interface(i) => i,
+                _ => continue,
+            };
+            if let Some(prev) = interface_lookup.get(&*i.name.name) {
+                let prev: &Interface = &interfaces[*prev];
+                let err = self.redefined(
+                    i.name.span,
+                    format!("interface {:?} defined twice", i.name.name),
+                    &prev.span,
+                );
+                self.errors.push(err);
+                continue;
+            }
+            let mut iface = {
+                let lookup = |name: &str| match interface_lookup.get(name) {
+                    Some(id) => Some(&interfaces[*id]),
+                    None => outer(name),
+                };
+                let module = format!("{}::{}", module.as_deref().unwrap_or(name), i.name.name);
+                let mut resolver = Resolver {
+                    lines: self.lines.clone(),
+                    ..Resolver::default()
+                };
+                let iface = resolver.resolve_scope(&i.name.name, Some(module), &i.items, &lookup);
+                self.errors.append(&mut resolver.errors);
+                iface
+            };
+            iface.docs = self.docs(&i.docs);
+            iface.attrs = self.attrs(&i.docs);
+            iface.span = self.span(i.name.span);
+            let id = interfaces.alloc(iface);
+            interface_lookup.insert(i.name.name.to_string(), id);
+        }
+
+        // Next pull in any names from our dependencies
+        let lookup = |name: &str| match interface_lookup.get(name) {
+            Some(id) => Some(&interfaces[*id]),
+            None => outer(name),
+        };
+        self.process_use(fields, &lookup);
         // ... then register our own names
-        self.register_names(fields)?;
+        let (typedefs, resources) = self.register_names(fields);
 
         // With all names registered we can now fully expand and translate all
         // types.
-        for field in fields {
-            let t = match field {
-                Item::TypeDef(t) => t,
+        for (i, field) in fields.iter().enumerate() {
+            let (t, id) = match (field, typedefs.get(&i)) {
+                (Item::TypeDef(t), Some(id)) => (t, *id),
                 _ => continue,
             };
-            let id = self.type_lookup[&*t.name.name];
-            let kind = self.resolve_type_def(&t.ty)?;
-            self.types.get_mut(id).unwrap().kind = kind;
+            match self.resolve_type_def(&t.ty) {
+                Ok(kind) => self.types.get_mut(id).unwrap().kind = kind,
+                Err(e) => self.errors.push(e),
+            }
         }
 
         // And finally we can resolve all type references in functions/globals
         // and additionally validate that types thesmelves are not recursive
         let mut valid_types = HashSet::new();
         let mut visiting = HashSet::new();
-        for field in fields {
-            match field {
-                Item::Value(v) => self.resolve_value(v)?,
-                Item::Resource(r) => self.resolve_resource(r)?,
-                Item::TypeDef(t) => {
-                    self.validate_type_not_recursive(
-                        t.name.span,
-                        self.type_lookup[&*t.name.name],
-                        &mut visiting,
-                        &mut valid_types,
-                    )?;
+        for (i, field) in fields.iter().enumerate() {
+            let result = match (field, typedefs.get(&i), resources.get(&i)) {
+                (Item::Value(v), _, _) => self.resolve_value(v),
+                (Item::Resource(r), _, Some(id)) => {
+                    self.resolve_resource(r, *id);
+                    continue;
                 }
+                (Item::TypeDef(t), Some(id), _) => self.validate_type_not_recursive(
+                    t.name.span,
+                    *id,
+                    &mut visiting,
+                    &mut valid_types,
+                ),
                 _ => continue,
+            };
+            if let Err(e) = result {
+                self.errors.push(e);
+                // Any cycle found has been reported now, so the types within
+                // it aren't checked again.
+                valid_types.extend(visiting.drain());
             }
         }
 
-        Ok(Interface {
+        Interface {
             name: name.to_string(),
+            docs: Docs::default(),
+            attrs: Attributes::default(),
+            span: crate::Span::default(),
+            module,
             types: mem::take(&mut self.types),
             type_lookup: mem::take(&mut self.type_lookup),
             resources: mem::take(&mut self.resources),
             resource_lookup: mem::take(&mut self.resource_lookup),
-            interface_lookup: Default::default(),
-            interfaces: Default::default(),
+            interface_lookup,
+            interfaces,
             functions: mem::take(&mut self.functions),
             globals: mem::take(&mut self.globals),
-        })
+            constants: mem::take(&mut self.constants),
+        }
     }
 
     fn process_use<'a>(
         &mut self,
-        fields: &[Item<'a>],
-        deps: &'a HashMap<String, Interface>,
-    ) -> Result<()> {
-        for field in fields {
+        fields: &[Item<'_>],
+        lookup: &dyn Fn(&str) -> Option<&'a Interface>,
+    ) {
+        'uses: for field in fields {
             let u = match field {
                 Item::Use(u) => u,
                 _ => continue,
             };
-            let mut dep = &deps[&*u.from[0].name];
+            let mut dep = match lookup(&u.from[0].name) {
+                Some(dep) => dep,
+                None => {
+                    self.errors.push(
+                        Error {
+                            span: u.from[0].span,
+                            msg: format!("no interface named `{}`", u.from[0].name),
+                        }
+                        .into(),
+                    );
+                    continue;
+                }
+            };
             let mut prev = &*u.from[0].name;
             for name in u.from[1..].iter() {
                 dep = match dep.interface_lookup.get(&*name.name) {
                     Some(i) => &dep.interfaces[*i],
                     None => {
-                        return Err(Error {
-                            span: name.span,
-                            msg: format!("`{}` not defined in `{}`", name.name, prev),
-                        }
-                        .into())
+                        self.errors.push(
+                            Error {
+                                span: name.span,
+                                msg: format!("`{}` not defined in `{}`", name.name, prev),
+                            }
+                            .into(),
+                        );
+                        continue 'uses;
                     }
                 };
                 prev = &*name.name;
@@ -124,40 +238,51 @@ impl Resolver {
 
                         if let Some(id) = dep.resource_lookup.get(&*name.name.name) {
                             let resource = self.copy_resource(&mod_name.name, dep, *id);
-                            self.define_resource(my_name, span, resource)?;
+                            // The resource's type has the same name, and would
+                            // clash too.
+                            if !self.define_resource(my_name, span, resource) {
+                                continue;
+                            }
                             found = true;
                         }
 
                         if let Some(id) = dep.type_lookup.get(&*name.name.name) {
                             let ty = self.copy_type_def(&mod_name.name, dep, *id);
-                            self.define_type(my_name, span, ty)?;
+                            self.define_type(my_name, span, ty);
                             found = true;
                         }
 
                         if !found {
-                            return Err(Error {
-                                span: name.name.span,
-                                msg: "name not defined in submodule".to_string(),
-                            }
-                            .into());
+                            self.errors.push(
+                                Error {
+                                    span: name.name.span,
+                                    msg: "name not defined in submodule".to_string(),
+                                }
+                                .into(),
+                            );
                         }
                     }
                 }
                 None => {
+                    let mut clashes = HashSet::new();
                     for (id, resource) in dep.resources.iter() {
                         let id = self.copy_resource(&mod_name.name, dep, id);
-                        self.define_resource(&resource.name, mod_name.span, id)?;
+                        if !self.define_resource(&resource.name, mod_name.span, id) {
+                            clashes.insert(&*resource.name);
+                        }
                     }
                     let mut names = dep.type_lookup.iter().collect::<Vec<_>>();
                     names.sort(); // produce a stable order by which to add names
                     for (name, id) in names {
+                        if clashes.contains(&**name) {
+                            continue;
+                        }
                         let ty = self.copy_type_def(&mod_name.name, dep, *id);
-                        self.define_type(name, mod_name.span, ty)?;
+                        self.define_type(name, mod_name.span, ty);
                     }
                 }
             }
         }
-        Ok(())
     }
 
     fn copy_resource(&mut self, dep_name: &str, dep: &Interface, r: ResourceId) -> ResourceId {
@@ -169,11 +294,13 @@ impl Resolver {
                 let r = &dep.resources[r];
                 let resource = Resource {
                     docs: r.docs.clone(),
+                    attrs: r.attrs.clone(),
                     name: r.name.clone(),
+                    span: r.span.clone(),
                     foreign_module: Some(
                         r.foreign_module
                             .clone()
-                            .unwrap_or_else(|| dep_name.to_string()),
+                            .unwrap_or_else(|| module_name(dep_name)),
                     ),
                 };
                 resources.alloc(resource)
@@ -188,11 +315,13 @@ impl Resolver {
 
         let ty = TypeDef {
             docs: ty.docs.clone(),
+            attrs: ty.attrs.clone(),
             name: ty.name.clone(),
+            span: ty.span.clone(),
             foreign_module: Some(
                 ty.foreign_module
                     .clone()
-                    .unwrap_or_else(|| dep_name.to_string()),
+                    .unwrap_or_else(|| module_name(dep_name)),
             ),
             kind: match &ty.kind {
                 TypeDefKind::Type(t) => TypeDefKind::Type(self.copy_type(dep_name, dep, *t)),
@@ -204,6 +333,7 @@ impl Resolver {
                             docs: field.docs.clone(),
                             name: field.name.clone(),
                             ty: self.copy_type(dep_name, dep, field.ty),
+                            span: field.span.clone(),
                         })
                         .collect(),
                     kind: r.kind,
@@ -216,6 +346,7 @@ impl Resolver {
                             docs: case.docs.clone(),
                             name: case.name.clone(),
                             ty: case.ty.map(|t| self.copy_type(dep_name, dep, t)),
+                            span: case.span.clone(),
                         })
                         .collect(),
                     tag: v.tag,
@@ -246,78 +377,118 @@ impl Resolver {
         }
     }
 
-    fn register_names(&mut self, fields: &[Item<'_>]) -> Result<()> {
-        let mut values = HashSet::new();
-        for field in fields {
+    /// Defines the names of the types and resources in `fields`, returning
+    /// the ids of those which were defined, by their index in `fields`, so
+    /// that those which clash with an earlier name can be skipped.
+    fn register_names(
+        &mut self,
+        fields: &[Item<'_>],
+    ) -> (HashMap<usize, TypeId>, HashMap<usize, ResourceId>) {
+        let mut values = HashMap::new();
+        let mut typedefs = HashMap::new();
+        let mut resources = HashMap::new();
+        for (i, field) in fields.iter().enumerate() {
             match field {
                 Item::Resource(r) => {
                     let docs = self.docs(&r.docs);
+                    let attrs = self.attrs(&r.docs);
                     let id = self.resources.alloc(Resource {
                         docs,
+                        attrs,
                         name: r.name.name.to_string(),
+                        span: self.span(r.name.span),
                         foreign_module: None,
                     });
-                    self.define_resource(&r.name.name, r.name.span, id)?;
+                    if !self.define_resource(&r.name.name, r.name.span, id) {
+                        continue;
+                    }
+                    resources.insert(i, id);
                     let type_id = self.types.alloc(TypeDef {
                         docs: Docs::default(),
+                        attrs: Attributes::default(),
                         kind: TypeDefKind::Type(Type::Handle(id)),
                         name: None,
+                        span: self.span(r.name.span),
                         foreign_module: None,
                     });
-                    self.define_type(&r.name.name, r.name.span, type_id)?;
+                    self.define_type(&r.name.name, r.name.span, type_id);
                 }
                 Item::TypeDef(t) => {
                     let docs = self.docs(&t.docs);
+                    let attrs = self.attrs(&t.docs);
                     let id = self.types.alloc(TypeDef {
                         docs,
+                        attrs,
                         // a dummy kind is used for now which will get filled in
                         // later with the actual desired contents.
                         kind: TypeDefKind::List(Type::U8),
                         name: Some(t.name.name.to_string()),
+                        span: self.span(t.name.span),
                         foreign_module: None,
                     });
-                    self.define_type(&t.name.name, t.name.span, id)?;
+                    if self.define_type(&t.name.name, t.name.span, id) {
+                        typedefs.insert(i, id);
+                    }
                 }
                 Item::Value(f) => {
-                    if !values.insert(&f.name.name) {
-                        return Err(Error {
-                            span: f.name.span,
-                            msg: format!("{:?} defined twice", f.name.name),
-                        }
-                        .into());
+                    if let Some(prev) = values.insert(&f.name.name, f.name.span) {
+                        let prev = self.span(prev);
+                        let err = self.redefined(
+                            f.name.span,
+                            format!("{:?} defined twice", f.name.name),
+                            &prev,
+                        );
+                        self.errors.push(err);
                     }
                 }
-                Item::Use(_) => {}
-
-                Item::Interface(_) => unimplemented!(),
+                Item::Use(_) | Item::Interface(_) => {}
             }
         }
 
-        Ok(())
+        (typedefs, resources)
     }
 
-    fn define_resource(&mut self, name: &str, span: Span, id: ResourceId) -> Result<()> {
-        if self.resource_lookup.insert(name.to_string(), id).is_some() {
-            Err(Error {
-                span,
-                msg: format!("resource {:?} defined twice", name),
-            }
-            .into())
-        } else {
-            Ok(())
+    /// Defines the resource `name`, returning whether it was defined or
+    /// recording an error if the name is already taken.
+    fn define_resource(&mut self, name: &str, span: Span, id: ResourceId) -> bool {
+        if let Some(prev) = self.resource_spans.get(name) {
+            let prev = self.span(*prev);
+            let err = self.redefined(span, format!("resource {:?} defined twice", name), &prev);
+            self.errors.push(err);
+            return false;
         }
+        self.resource_spans.insert(name.to_string(), span);
+        self.resource_lookup.insert(name.to_string(), id);
+        true
     }
 
-    fn define_type(&mut self, name: &str, span: Span, id: TypeId) -> Result<()> {
-        if self.type_lookup.insert(name.to_string(), id).is_some() {
-            Err(Error {
-                span,
-                msg: format!("type {:?} defined twice", name),
-            }
-            .into())
-        } else {
-            Ok(())
+    /// Defines the type `name`, returning whether it was defined or recording
+    /// an error if the name is already taken.
+    fn define_type(&mut self, name: &str, span: Span, id: TypeId) -> bool {
+        if let Some(prev) = self.type_spans.get(name) {
+            let prev = self.span(*prev);
+            let err = self.redefined(span, format!("type {:?} defined twice", name), &prev);
+            self.errors.push(err);
+            return false;
+        }
+        self.type_spans.insert(name.to_string(), span);
+        self.type_lookup.insert(name.to_string(), id);
+        true
+    }
+
+    /// Creates an error for something defined twice, pointing at the previous
+    /// definition as well if it's known.
+    fn redefined(&self, span: Span, msg: String, prev: &crate::Span) -> anyhow::Error {
+        let error = Error { span, msg };
+        if prev.file.is_none() {
+            return error.into();
+        }
+        RelatedError {
+            error,
+            related: prev.clone(),
+            label: "previous definition here".to_string(),
         }
+        .into()
     }
 
     fn resolve_type_def(&mut self, ty: &super::Type<'_>) -> Result<TypeDefKind> {
@@ -390,9 +561,11 @@ impl Resolver {
                             docs: self.docs(&field.docs),
                             name: field.name.name.to_string(),
                             ty: self.resolve_type(&field.ty)?,
+                            span: self.span(field.name.span),
                         })
                     })
-                    .collect::<Result<Vec<_>>>()?;
+                    .collect::<Vec<_>>();
+                let fields = self.all(fields)?;
                 TypeDefKind::Record(Record {
                     kind: if record.tuple_hint {
                         RecordKind::Tuple
@@ -429,9 +602,11 @@ impl Resolver {
                                 Some(ty) => Some(self.resolve_type(ty)?),
                                 None => None,
                             },
+                            span: self.span(case.name.span),
                         })
                     })
-                    .collect::<Result<Vec<_>>>()?;
+                    .collect::<Vec<_>>();
+                let cases = self.all(cases)?;
                 TypeDefKind::Variant(Variant {
                     tag: match &variant.tag {
                         Some(ty) => self.get_variant_tag(ty),
@@ -475,6 +650,8 @@ impl Resolver {
             kind,
             name: None,
             docs: Docs::default(),
+            attrs: Attributes::default(),
+            span: crate::Span::default(),
             foreign_module: None,
         }))
     }
@@ -508,6 +685,10 @@ impl Resolver {
         Type::Id(*id)
     }
 
+    fn span(&self, span: Span) -> crate::Span {
+        self.lines.span(span)
+    }
+
     fn docs(&mut self, doc: &super::Docs<'_>) -> Docs {
         if doc.docs.is_empty() {
             return Docs { contents: None };
@@ -531,8 +712,61 @@ impl Resolver {
         }
     }
 
+    /// Translates the attributes in `doc`, checking the arguments of those
+    /// which the parser knows about and skipping any which are invalid.
+    fn attrs(&mut self, doc: &super::Docs<'_>) -> Attributes {
+        let mut list = Vec::new();
+        for attr in doc.attrs.iter() {
+            let err = match (&*attr.name.name, attr.args) {
+                ("deprecated", Some(_))
+                    if !matches!(&attr.values[..], [v] if v.key.is_none() && v.string) =>
+                {
+                    Some("`@deprecated` takes an optional message, like `@deprecated(\"use `g` instead\")`")
+                }
+                ("since", None) | ("since", Some("")) => {
+                    Some("`@since` requires a version, like `@since(1.2)`")
+                }
+                _ => None,
+            };
+            if let Some(msg) = err {
+                self.errors.push(
+                    Error {
+                        span: attr.span,
+                        msg: msg.to_string(),
+                    }
+                    .into(),
+                );
+                continue;
+            }
+            if let Some(prev) = list.iter().find(|a: &&Attribute| a.name == attr.name.name) {
+                if attr.name.name == "deprecated" || attr.name.name == "since" {
+                    let prev = prev.span.clone();
+                    let err = self.redefined(
+                        attr.span,
+                        format!("attribute `@{}` given twice", attr.name.name),
+                        &prev,
+                    );
+                    self.errors.push(err);
+                    continue;
+                }
+            }
+            list.push(Attribute {
+                name: attr.name.name.to_string(),
+                args: attr.args.map(|s| s.to_string()),
+                values: attr
+                    .values
+                    .iter()
+                    .map(|v| (v.key.map(|k| k.to_string()), v.value.to_string()))
+                    .collect(),
+                span: self.span(attr.span),
+            });
+        }
+        Attributes { list }
+    }
+
     fn resolve_value(&mut self, value: &Value<'_>) -> Result<()> {
         let docs = self.docs(&value.docs);
+        let attrs = self.attrs(&value.docs);
         match &value.kind {
             ValueKind::Function {
                 is_async,
@@ -540,39 +774,93 @@ impl Resolver {
                 params,
                 results,
             } => {
-                let params = params
-                    .iter()
-                    .map(|(name, ty)| Ok((name.name.to_string(), self.resolve_type(ty)?)))
-                    .collect::<Result<_>>()?;
-                let results = results
-                    .iter()
-                    .map(|(name, ty)| Ok((name.name.to_string(), self.resolve_type(ty)?)))
-                    .collect::<Result<_>>()?;
+                let (params, results) = self.resolve_signature(params, results)?;
                 self.functions.push(Function {
                     abi: *abi,
                     docs,
+                    attrs,
                     name: value.name.name.to_string(),
                     kind: FunctionKind::Freestanding,
                     params,
                     results,
                     is_async: *is_async,
+                    span: self.span(value.name.span),
                 });
             }
             ValueKind::Global(ty) => {
                 let ty = self.resolve_type(ty)?;
                 self.globals.push(Global {
                     docs,
+                    attrs,
+                    name: value.name.name.to_string(),
+                    ty,
+                    span: self.span(value.name.span),
+                });
+            }
+            ValueKind::Const {
+                ty,
+                value: literal,
+                span,
+            } => {
+                let ty = self.resolve_type(ty)?;
+                let (min, max) = match self.int_range(&ty) {
+                    Some(range) => range,
+                    None => {
+                        return Err(Error {
+                            span: value.name.span,
+                            msg: "constants must have an integer type".to_string(),
+                        }
+                        .into())
+                    }
+                };
+                if *literal < min || *literal > max {
+                    return Err(Error {
+                        span: *span,
+                        msg: format!(
+                            "value {} is out of range for this constant, which must be \
+                             between {} and {}",
+                            literal, min, max
+                        ),
+                    }
+                    .into());
+                }
+                self.constants.push(Constant {
+                    docs,
+                    attrs,
                     name: value.name.name.to_string(),
                     ty,
+                    value: *literal,
+                    span: self.span(value.name.span),
                 });
             }
         }
         Ok(())
     }
 
-    fn resolve_resource(&mut self, resource: &super::Resource<'_>) -> Result<()> {
-        let mut names = HashSet::new();
-        let id = self.resource_lookup[&*resource.name.name];
+    /// Returns the range of values of `ty` if it's an integer type or an
+    /// alias of one.
+    fn int_range(&self, ty: &Type) -> Option<(i128, i128)> {
+        let bits = match ty {
+            Type::U8 | Type::S8 => 8,
+            Type::U16 | Type::S16 => 16,
+            Type::U32 | Type::S32 => 32,
+            Type::U64 | Type::S64 => 64,
+            Type::Id(id) => match &self.types[*id].kind {
+                TypeDefKind::Type(t) => return self.int_range(t),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(match ty {
+            Type::S8 | Type::S16 | Type::S32 | Type::S64 => {
+                (-(1 << (bits - 1)), (1 << (bits - 1)) - 1)
+            }
+            _ => (0, (1 << bits) - 1),
+        })
+    }
+
+    fn resolve_resource(&mut self, resource: &super::Resource<'_>, id: ResourceId) {
+        let mut names = HashMap::new();
         for (statik, value) in resource.values.iter() {
             let (abi, is_async, params, results) = match &value.kind {
                 ValueKind::Function {
@@ -582,29 +870,44 @@ impl Resolver {
                     results,
                 } => (*abi, *is_async, params, results),
                 ValueKind::Global(_) => {
-                    return Err(Error {
-                        span: value.name.span,
-                        msg: "globals not allowed in resources".to_string(),
-                    }
-                    .into());
+                    self.errors.push(
+                        Error {
+                            span: value.name.span,
+                            msg: "globals not allowed in resources".to_string(),
+                        }
+                        .into(),
+                    );
+                    continue;
                 }
-            };
-            if !names.insert(&value.name.name) {
-                return Err(Error {
-                    span: value.name.span,
-                    msg: format!("{:?} defined twice in this resource", value.name.name),
+                ValueKind::Const { .. } => {
+                    self.errors.push(
+                        Error {
+                            span: value.name.span,
+                            msg: "constants not allowed in resources".to_string(),
+                        }
+                        .into(),
+                    );
+                    continue;
                 }
-                .into());
+            };
+            if let Some(prev) = names.insert(&value.name.name, value.name.span) {
+                let prev = self.span(prev);
+                let err = self.redefined(
+                    value.name.span,
+                    format!("{:?} defined twice in this resource", value.name.name),
+                    &prev,
+                );
+                self.errors.push(err);
             }
             let docs = self.docs(&value.docs);
-            let mut params = params
-                .iter()
-                .map(|(name, ty)| Ok((name.name.to_string(), self.resolve_type(ty)?)))
-                .collect::<Result<Vec<_>>>()?;
-            let results = results
-                .iter()
-                .map(|(name, ty)| Ok((name.name.to_string(), self.resolve_type(ty)?)))
-                .collect::<Result<_>>()?;
+            let attrs = self.attrs(&value.docs);
+            let (mut params, results) = match self.resolve_signature(params, results) {
+                Ok(signature) => signature,
+                Err(e) => {
+                    self.errors.push(e);
+                    continue;
+                }
+            };
             let kind = if *statik {
                 FunctionKind::Static {
                     resource: id,
@@ -621,13 +924,52 @@ impl Resolver {
                 abi,
                 is_async,
                 docs,
+                attrs,
                 name: format!("{}::{}", resource.name.name, value.name.name),
                 kind,
                 params,
                 results,
+                span: self.span(value.name.span),
             });
         }
-        Ok(())
+    }
+
+    /// Resolves the types of the `params` and `results` of a function.
+    fn resolve_signature(
+        &mut self,
+        params: &[(super::Id<'_>, super::Type<'_>)],
+        results: &[(super::Id<'_>, super::Type<'_>)],
+    ) -> Result<(Params, Params)> {
+        let types = params
+            .iter()
+            .chain(results)
+            .map(|(name, ty)| Ok((name.name.to_string(), self.resolve_type(ty)?)))
+            .collect::<Vec<_>>();
+        let mut params = self.all(types)?;
+        let results = params.split_off(params.len() - results.len());
+        Ok((params, results))
+    }
+
+    /// Returns all of `results` if they're all `Ok`, or otherwise the last
+    /// error having recorded any others, so that an error in one field or
+    /// parameter doesn't hide those in the rest.
+    fn all<T>(&mut self, results: Vec<Result<T>>) -> Result<Vec<T>> {
+        let mut ret = Vec::new();
+        let mut error = None;
+        for result in results {
+            match result {
+                Ok(item) => ret.push(item),
+                Err(e) => {
+                    if let Some(prev) = error.replace(e) {
+                        self.errors.push(prev);
+                    }
+                }
+            }
+        }
+        match error {
+            Some(e) => Err(e),
+            None => Ok(ret),
+        }
     }
 
     fn validate_type_not_recursive(
@@ -685,3 +1027,10 @@ impl Resolver {
         Ok(())
     }
 }
+
+/// Returns the name of the module that items used from `dep_name` are
+/// imported from. Interfaces loaded from a path such as `dir.module` are named
+/// after their file.
+fn module_name(dep_name: &str) -> String {
+    dep_name.rsplit('.').next().unwrap().to_string()
+}
@@ -0,0 +1,736 @@
+//! Checking whether a new version of an interface is compatible with an old
+//! one.
+//!
+//! [`compare`] walks two resolved interfaces side by side and classifies each
+//! difference between them as either [`Compatibility::Compatible`], meaning
+//! that code built against the old interface keeps working, or
+//! [`Compatibility::Breaking`]. Items are matched up by name, so renaming an
+//! item shows up as one item being removed and another added.
+//!
+//! Named types are compared where they're defined rather than everywhere
+//! they're used, so a change to `record point` is reported once for `point`
+//! and not again for every function taking a `point`. Functions whose
+//! canonical ABI changes as a result are still reported though, since their
+//! core wasm signature is what guests actually link against.
+
+use crate::abi::{AbiVariant, WasmSignature, WasmType};
+use crate::{Interface, Record, SizeAlign, Span, Type, TypeDef, TypeDefKind, Variant};
+
+/// A difference between two versions of an interface.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Change {
+    /// The path of the item which changed, such as `f` for a function,
+    /// `point.x` for a field of `point`, `file::open` for a function of the
+    /// resource `file`, or `nested/f` for an item of a nested interface.
+    pub item: String,
+    pub item_kind: ItemKind,
+    pub kind: ChangeKind,
+    pub compatibility: Compatibility,
+    pub message: String,
+    /// Where the item is defined, in the new interface unless it was
+    /// removed.
+    pub span: Span,
+}
+
+impl Change {
+    pub fn is_breaking(&self) -> bool {
+        self.compatibility == Compatibility::Breaking
+    }
+}
+
+/// Whether code built against the old version of an interface keeps working
+/// with the new one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Compatibility {
+    Compatible,
+    Breaking,
+}
+
+/// The sort of item that a `Change` is to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ItemKind {
+    Interface,
+    Resource,
+    Type,
+    Field,
+    Case,
+    Function,
+    Global,
+    Constant,
+}
+
+/// What happened to the item of a `Change`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    /// The item's type, value or signature changed.
+    Changed,
+    /// The item moved relative to its siblings, such as a record field
+    /// being declared in a different position.
+    Reordered,
+    /// The item's in-memory layout, as computed by `SizeAlign`, changed.
+    LayoutChanged,
+    /// The core wasm signature or import module of the item changed.
+    AbiChanged,
+}
+
+impl Compatibility {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Compatibility::Compatible => "compatible",
+            Compatibility::Breaking => "breaking",
+        }
+    }
+}
+
+impl ItemKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ItemKind::Interface => "interface",
+            ItemKind::Resource => "resource",
+            ItemKind::Type => "type",
+            ItemKind::Field => "field",
+            ItemKind::Case => "case",
+            ItemKind::Function => "function",
+            ItemKind::Global => "global",
+            ItemKind::Constant => "constant",
+        }
+    }
+}
+
+impl ChangeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeKind::Added => "added",
+            ChangeKind::Removed => "removed",
+            ChangeKind::Changed => "changed",
+            ChangeKind::Reordered => "reordered",
+            ChangeKind::LayoutChanged => "layout-changed",
+            ChangeKind::AbiChanged => "abi-changed",
+        }
+    }
+}
+
+/// Returns every difference between `old` and `new`, in the order that the
+/// items appear in `old` followed by those only in `new`.
+///
+/// Documentation and attributes aren't compared.
+pub fn compare(old: &Interface, new: &Interface) -> Vec<Change> {
+    let mut changes = Vec::new();
+    Compare::new(old, new, String::new(), &mut changes).interface();
+    changes
+}
+
+struct Compare<'a> {
+    old: &'a Interface,
+    new: &'a Interface,
+    old_sizes: SizeAlign,
+    new_sizes: SizeAlign,
+    prefix: String,
+    changes: &'a mut Vec<Change>,
+}
+
+impl<'a> Compare<'a> {
+    fn new(
+        old: &'a Interface,
+        new: &'a Interface,
+        prefix: String,
+        changes: &'a mut Vec<Change>,
+    ) -> Compare<'a> {
+        // Buffers are the only types whose layout depends on the ABI variant,
+        // and they're laid out the same way in records either way.
+        let mut old_sizes = SizeAlign::default();
+        old_sizes.fill(AbiVariant::GuestImport, old);
+        let mut new_sizes = SizeAlign::default();
+        new_sizes.fill(AbiVariant::GuestImport, new);
+        Compare {
+            old,
+            new,
+            old_sizes,
+            new_sizes,
+            prefix,
+            changes,
+        }
+    }
+
+    fn push(
+        &mut self,
+        item: &str,
+        item_kind: ItemKind,
+        kind: ChangeKind,
+        compatibility: Compatibility,
+        span: &Span,
+        message: String,
+    ) {
+        self.changes.push(Change {
+            item: format!("{}{}", self.prefix, item),
+            item_kind,
+            kind,
+            compatibility,
+            message,
+            span: span.clone(),
+        });
+    }
+
+    fn added(&mut self, item: &str, item_kind: ItemKind, span: &Span) {
+        let message = format!("{} `{}{}` was added", item_kind.as_str(), self.prefix, item);
+        self.push(
+            item,
+            item_kind,
+            ChangeKind::Added,
+            Compatibility::Compatible,
+            span,
+            message,
+        );
+    }
+
+    fn removed(&mut self, item: &str, item_kind: ItemKind, span: &Span) {
+        let message = format!(
+            "{} `{}{}` was removed",
+            item_kind.as_str(),
+            self.prefix,
+            item
+        );
+        self.push(
+            item,
+            item_kind,
+            ChangeKind::Removed,
+            Compatibility::Breaking,
+            span,
+            message,
+        );
+    }
+
+    fn changed(&mut self, item: &str, item_kind: ItemKind, span: &Span, message: String) {
+        self.push(
+            item,
+            item_kind,
+            ChangeKind::Changed,
+            Compatibility::Breaking,
+            span,
+            message,
+        );
+    }
+
+    fn interface(&mut self) {
+        if self.old.module() != self.new.module() {
+            // Nested interfaces are already named by the prefix.
+            let item = match self.prefix.strip_suffix('/') {
+                Some(prefix) => prefix.to_string(),
+                None => self.new.name.clone(),
+            };
+            self.changes.push(Change {
+                item,
+                item_kind: ItemKind::Interface,
+                kind: ChangeKind::AbiChanged,
+                compatibility: Compatibility::Breaking,
+                message: format!(
+                    "import module changed from `{}` to `{}`",
+                    self.old.module(),
+                    self.new.module()
+                ),
+                span: self.new.span.clone(),
+            });
+        }
+        self.resources();
+        self.types();
+        self.functions();
+        self.globals();
+        self.constants();
+        self.interfaces();
+    }
+
+    fn resources(&mut self) {
+        let (old, new) = (self.old, self.new);
+        for (_, resource) in old.resources.iter() {
+            if !new.resource_lookup.contains_key(&resource.name) {
+                self.removed(&resource.name, ItemKind::Resource, &resource.span);
+            }
+        }
+        for (_, resource) in new.resources.iter() {
+            if !old.resource_lookup.contains_key(&resource.name) {
+                self.added(&resource.name, ItemKind::Resource, &resource.span);
+            }
+        }
+    }
+
+    fn types(&mut self) {
+        let (old, new) = (self.old, self.new);
+        for (_, ty) in old.types.iter() {
+            let name = match &ty.name {
+                Some(name) => name,
+                None => continue,
+            };
+            match new.type_lookup.get(name) {
+                Some(id) => self.typedef(name, ty, &new.types[*id]),
+                None => self.removed(name, ItemKind::Type, &ty.span),
+            }
+        }
+        for (_, ty) in new.types.iter() {
+            if let Some(name) = &ty.name {
+                if !old.type_lookup.contains_key(name) {
+                    self.added(name, ItemKind::Type, &ty.span);
+                }
+            }
+        }
+    }
+
+    fn typedef(&mut self, name: &str, old: &TypeDef, new: &TypeDef) {
+        let old_kind = kind_name(&old.kind);
+        let new_kind = kind_name(&new.kind);
+        if old_kind != new_kind {
+            let message = format!("changed from {} to {}", old_kind, new_kind);
+            self.changed(name, ItemKind::Type, &new.span, message);
+            return;
+        }
+        match (&old.kind, &new.kind) {
+            (TypeDefKind::Record(a), TypeDefKind::Record(b)) => self.record(name, a, b),
+            (TypeDefKind::Variant(a), TypeDefKind::Variant(b)) => self.variant(name, a, b),
+            (TypeDefKind::Type(a), TypeDefKind::Type(b))
+            | (TypeDefKind::List(a), TypeDefKind::List(b))
+            | (TypeDefKind::Pointer(a), TypeDefKind::Pointer(b))
+            | (TypeDefKind::ConstPointer(a), TypeDefKind::ConstPointer(b))
+            | (TypeDefKind::PushBuffer(a), TypeDefKind::PushBuffer(b))
+            | (TypeDefKind::PullBuffer(a), TypeDefKind::PullBuffer(b)) => {
+                if !self.same_type(a, b) {
+                    let message = format!(
+                        "changed from `{}` to `{}`",
+                        self.old.type_string(a),
+                        self.new.type_string(b)
+                    );
+                    self.changed(name, ItemKind::Type, &new.span, message);
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn record(&mut self, name: &str, old: &Record, new: &Record) {
+        let mut moved = false;
+        for (i, field) in old.fields.iter().enumerate() {
+            let path = format!("{}.{}", name, field.name);
+            match new.fields.iter().position(|f| f.name == field.name) {
+                Some(j) => {
+                    let new_field = &new.fields[j];
+                    if !self.same_type(&field.ty, &new_field.ty) {
+                        let message = format!(
+                            "type changed from `{}` to `{}`",
+                            self.old.type_string(&field.ty),
+                            self.new.type_string(&new_field.ty)
+                        );
+                        self.changed(&path, ItemKind::Field, &new_field.span, message);
+                    }
+                    moved = moved || i != j;
+                }
+                None => self.removed(&path, ItemKind::Field, &field.span),
+            }
+        }
+        for field in new.fields.iter() {
+            if !old.fields.iter().any(|f| f.name == field.name) {
+                // Unlike most additions this changes the layout of the
+                // record, so it's still breaking.
+                let path = format!("{}.{}", name, field.name);
+                let message = format!("field `{}{}` was added", self.prefix, path);
+                self.push(
+                    &path,
+                    ItemKind::Field,
+                    ChangeKind::Added,
+                    Compatibility::Breaking,
+                    &field.span,
+                    message,
+                );
+            }
+        }
+        let permutation = old.fields.len() == new.fields.len()
+            && old
+                .fields
+                .iter()
+                .all(|f| new.fields.iter().any(|g| g.name == f.name));
+        if !moved || !permutation {
+            return;
+        }
+
+        // All that's left is a permutation of the same fields, which only
+        // matters if it moves fields within the record.
+        let (what, old_pos, new_pos) = if old.is_flags() {
+            let positions = |r: &Record| (0..r.fields.len()).collect::<Vec<_>>();
+            ("bit", positions(old), positions(new))
+        } else {
+            (
+                "offset",
+                self.old_sizes.field_offsets(old),
+                self.new_sizes.field_offsets(new),
+            )
+        };
+        let mut relaid = false;
+        for (i, field) in old.fields.iter().enumerate() {
+            let j = new
+                .fields
+                .iter()
+                .position(|f| f.name == field.name)
+                .unwrap();
+            if old_pos[i] == new_pos[j] {
+                continue;
+            }
+            relaid = true;
+            let path = format!("{}.{}", name, field.name);
+            let message = format!(
+                "moved from {} {} to {} {}",
+                what, old_pos[i], what, new_pos[j]
+            );
+            self.push(
+                &path,
+                ItemKind::Field,
+                ChangeKind::LayoutChanged,
+                Compatibility::Breaking,
+                &new.fields[j].span,
+                message,
+            );
+        }
+        if !relaid {
+            let span = self.new.types[self.new.type_lookup[name]].span.clone();
+            self.push(
+                name,
+                ItemKind::Type,
+                ChangeKind::Reordered,
+                Compatibility::Compatible,
+                &span,
+                "fields were reordered without changing the layout".to_string(),
+            );
+        }
+    }
+
+    fn variant(&mut self, name: &str, old: &Variant, new: &Variant) {
+        for (i, case) in old.cases.iter().enumerate() {
+            let path = format!("{}.{}", name, case.name);
+            let j = match new.cases.iter().position(|c| c.name == case.name) {
+                Some(j) => j,
+                None => {
+                    self.removed(&path, ItemKind::Case, &case.span);
+                    continue;
+                }
+            };
+            let new_case = &new.cases[j];
+            let same = match (&case.ty, &new_case.ty) {
+                (Some(a), Some(b)) => self.same_type(a, b),
+                (None, None) => true,
+                _ => false,
+            };
+            if !same {
+                let message = format!(
+                    "payload changed from {} to {}",
+                    payload_string(self.old, &case.ty),
+                    payload_string(self.new, &new_case.ty)
+                );
+                self.changed(&path, ItemKind::Case, &new_case.span, message);
+            }
+            if i != j {
+                let message = format!("discriminant changed from {} to {}", i, j);
+                self.push(
+                    &path,
+                    ItemKind::Case,
+                    ChangeKind::Reordered,
+                    Compatibility::Breaking,
+                    &new_case.span,
+                    message,
+                );
+            }
+        }
+        for case in new.cases.iter() {
+            if !old.cases.iter().any(|c| c.name == case.name) {
+                // Code matching on the old cases has no way to handle a new
+                // one, so this is breaking too.
+                let path = format!("{}.{}", name, case.name);
+                let message = format!("case `{}{}` was added", self.prefix, path);
+                self.push(
+                    &path,
+                    ItemKind::Case,
+                    ChangeKind::Added,
+                    Compatibility::Breaking,
+                    &case.span,
+                    message,
+                );
+            }
+        }
+    }
+
+    fn functions(&mut self) {
+        let (old, new) = (self.old, self.new);
+        for func in old.functions.iter() {
+            let new_func = match new.functions.iter().find(|f| f.name == func.name) {
+                Some(f) => f,
+                None => {
+                    self.removed(&func.name, ItemKind::Function, &func.span);
+                    continue;
+                }
+            };
+            let name = &func.name;
+            let span = &new_func.span;
+            if func.is_async != new_func.is_async {
+                let message = if new_func.is_async {
+                    "became async".to_string()
+                } else {
+                    "is no longer async".to_string()
+                };
+                self.changed(name, ItemKind::Function, span, message);
+            }
+            self.signature(name, span, "parameter", &func.params, &new_func.params);
+            self.signature(name, span, "result", &func.results, &new_func.results);
+
+            for variant in [AbiVariant::GuestImport, AbiVariant::GuestExport].iter() {
+                let a = old.wasm_signature(*variant, func);
+                let b = new.wasm_signature(*variant, new_func);
+                if a == b {
+                    continue;
+                }
+                let message = format!(
+                    "core wasm signature changed from `{}` to `{}`",
+                    signature_string(&a),
+                    signature_string(&b)
+                );
+                self.push(
+                    name,
+                    ItemKind::Function,
+                    ChangeKind::AbiChanged,
+                    Compatibility::Breaking,
+                    span,
+                    message,
+                );
+                break;
+            }
+        }
+        for func in new.functions.iter() {
+            if !old.functions.iter().any(|f| f.name == func.name) {
+                self.added(&func.name, ItemKind::Function, &func.span);
+            }
+        }
+    }
+
+    /// Compares either the parameters or the results of a function.
+    fn signature(
+        &mut self,
+        name: &str,
+        span: &Span,
+        what: &str,
+        old: &[(String, Type)],
+        new: &[(String, Type)],
+    ) {
+        if old.len() != new.len() {
+            let message = format!(
+                "number of {}s changed from {} to {}",
+                what,
+                old.len(),
+                new.len()
+            );
+            self.changed(name, ItemKind::Function, span, message);
+            return;
+        }
+        for ((old_name, old_ty), (new_name, new_ty)) in old.iter().zip(new) {
+            if !self.same_type(old_ty, new_ty) {
+                let message = format!(
+                    "type of {} `{}` changed from `{}` to `{}`",
+                    what,
+                    old_name,
+                    self.old.type_string(old_ty),
+                    self.new.type_string(new_ty)
+                );
+                self.changed(name, ItemKind::Function, span, message);
+            } else if old_name != new_name {
+                let message = format!("{} `{}` was renamed to `{}`", what, old_name, new_name);
+                self.push(
+                    name,
+                    ItemKind::Function,
+                    ChangeKind::Changed,
+                    Compatibility::Compatible,
+                    span,
+                    message,
+                );
+            }
+        }
+    }
+
+    fn globals(&mut self) {
+        let (old, new) = (self.old, self.new);
+        for global in old.globals.iter() {
+            match new.globals.iter().find(|g| g.name == global.name) {
+                Some(g) => {
+                    if !self.same_type(&global.ty, &g.ty) {
+                        let message = format!(
+                            "type changed from `{}` to `{}`",
+                            old.type_string(&global.ty),
+                            new.type_string(&g.ty)
+                        );
+                        self.changed(&global.name, ItemKind::Global, &g.span, message);
+                    }
+                }
+                None => self.removed(&global.name, ItemKind::Global, &global.span),
+            }
+        }
+        for global in new.globals.iter() {
+            if !old.globals.iter().any(|g| g.name == global.name) {
+                self.added(&global.name, ItemKind::Global, &global.span);
+            }
+        }
+    }
+
+    fn constants(&mut self) {
+        let (old, new) = (self.old, self.new);
+        for constant in old.constants.iter() {
+            let c = match new.constants.iter().find(|c| c.name == constant.name) {
+                Some(c) => c,
+                None => {
+                    self.removed(&constant.name, ItemKind::Constant, &constant.span);
+                    continue;
+                }
+            };
+            if !self.same_type(&constant.ty, &c.ty) {
+                let message = format!(
+                    "type changed from `{}` to `{}`",
+                    old.type_string(&constant.ty),
+                    new.type_string(&c.ty)
+                );
+                self.changed(&constant.name, ItemKind::Constant, &c.span, message);
+            }
+            // Both sides of an interface are expected to agree on the value
+            // of a constant, so changing it is breaking as well.
+            if constant.value != c.value {
+                let message = format!("value changed from {} to {}", constant.value, c.value);
+                self.changed(&constant.name, ItemKind::Constant, &c.span, message);
+            }
+        }
+        for constant in new.constants.iter() {
+            if !old.constants.iter().any(|c| c.name == constant.name) {
+                self.added(&constant.name, ItemKind::Constant, &constant.span);
+            }
+        }
+    }
+
+    fn interfaces(&mut self) {
+        let (old, new) = (self.old, self.new);
+        for (_, iface) in old.interfaces.iter() {
+            match new.interface_lookup.get(&iface.name) {
+                Some(id) => {
+                    let prefix = format!("{}{}/", self.prefix, iface.name);
+                    Compare::new(iface, &new.interfaces[*id], prefix, self.changes).interface();
+                }
+                None => self.removed(&iface.name, ItemKind::Interface, &iface.span),
+            }
+        }
+        for (_, iface) in new.interfaces.iter() {
+            if !old.interface_lookup.contains_key(&iface.name) {
+                self.added(&iface.name, ItemKind::Interface, &iface.span);
+            }
+        }
+    }
+
+    /// Returns whether `old`, from the old interface, is the same type as
+    /// `new`, from the new interface.
+    ///
+    /// Aliases are looked through, and named types are considered the same
+    /// if they have the same name since their definitions are compared
+    /// separately.
+    fn same_type(&self, old: &Type, new: &Type) -> bool {
+        match (unalias(self.old, old), unalias(self.new, new)) {
+            (Type::Handle(a), Type::Handle(b)) => {
+                self.old.resources[a].name == self.new.resources[b].name
+            }
+            (Type::Id(a), Type::Id(b)) => {
+                let (a, b) = (&self.old.types[a], &self.new.types[b]);
+                match (&a.name, &b.name) {
+                    (Some(a), Some(b)) => a == b,
+                    (None, None) => self.same_kind(&a.kind, &b.kind),
+                    _ => false,
+                }
+            }
+            (a, b) => a == b,
+        }
+    }
+
+    fn same_kind(&self, old: &TypeDefKind, new: &TypeDefKind) -> bool {
+        if kind_name(old) != kind_name(new) {
+            return false;
+        }
+        match (old, new) {
+            (TypeDefKind::Record(a), TypeDefKind::Record(b)) => {
+                a.fields.len() == b.fields.len()
+                    && a.fields
+                        .iter()
+                        .zip(&b.fields)
+                        .all(|(a, b)| a.name == b.name && self.same_type(&a.ty, &b.ty))
+            }
+            (TypeDefKind::Variant(a), TypeDefKind::Variant(b)) => {
+                a.cases.len() == b.cases.len()
+                    && a.cases.iter().zip(&b.cases).all(|(a, b)| {
+                        a.name == b.name
+                            && match (&a.ty, &b.ty) {
+                                (Some(a), Some(b)) => self.same_type(a, b),
+                                (None, None) => true,
+                                _ => false,
+                            }
+                    })
+            }
+            (TypeDefKind::Type(a), TypeDefKind::Type(b))
+            | (TypeDefKind::List(a), TypeDefKind::List(b))
+            | (TypeDefKind::Pointer(a), TypeDefKind::Pointer(b))
+            | (TypeDefKind::ConstPointer(a), TypeDefKind::ConstPointer(b))
+            | (TypeDefKind::PushBuffer(a), TypeDefKind::PushBuffer(b))
+            | (TypeDefKind::PullBuffer(a), TypeDefKind::PullBuffer(b)) => self.same_type(a, b),
+            _ => false,
+        }
+    }
+}
+
+fn unalias(iface: &Interface, ty: &Type) -> Type {
+    match ty {
+        Type::Id(id) => match &iface.types[*id].kind {
+            TypeDefKind::Type(t) => unalias(iface, t),
+            _ => *ty,
+        },
+        _ => *ty,
+    }
+}
+
+/// Describes the sort of type `kind` is, where types of different sorts are
+/// never compatible with one another.
+fn kind_name(kind: &TypeDefKind) -> &'static str {
+    match kind {
+        TypeDefKind::Record(r) if r.is_flags() => "flags",
+        TypeDefKind::Record(r) if r.is_tuple() => "a tuple",
+        TypeDefKind::Record(_) => "a record",
+        TypeDefKind::Variant(v) if v.is_bool() => "a bool",
+        TypeDefKind::Variant(v) if v.as_option().is_some() => "an option",
+        TypeDefKind::Variant(v) if v.as_expected().is_some() => "an expected",
+        TypeDefKind::Variant(v) if v.is_enum() => "an enum",
+        TypeDefKind::Variant(v) if v.is_union() => "a union",
+        TypeDefKind::Variant(_) => "a variant",
+        TypeDefKind::List(_) => "a list",
+        TypeDefKind::Pointer(_) => "a pointer",
+        TypeDefKind::ConstPointer(_) => "a const pointer",
+        TypeDefKind::PushBuffer(_) => "a push buffer",
+        TypeDefKind::PullBuffer(_) => "a pull buffer",
+        TypeDefKind::Type(_) => "an alias",
+    }
+}
+
+fn payload_string(iface: &Interface, ty: &Option<Type>) -> String {
+    match ty {
+        Some(ty) => format!("`{}`", iface.type_string(ty)),
+        None => "nothing".to_string(),
+    }
+}
+
+/// Renders `sig` like `(i32, i32) -> i64`.
+fn signature_string(sig: &WasmSignature) -> String {
+    let list = |tys: &[WasmType]| {
+        tys.iter()
+            .map(|t| t.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    match sig.results.len() {
+        0 => format!("({})", list(&sig.params)),
+        1 => format!("({}) -> {}", list(&sig.params), list(&sig.results)),
+        _ => format!("({}) -> ({})", list(&sig.params), list(&sig.results)),
+    }
+}
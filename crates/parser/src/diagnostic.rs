@@ -0,0 +1,101 @@
+use crate::Span;
+use std::fmt;
+
+/// How severe a `Diagnostic` is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem found in a `*.wit` file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Where the problem is.
+    pub span: Span,
+    pub message: String,
+    /// Further explanation which isn't tied to a location.
+    pub notes: Vec<String>,
+    /// Other locations relevant to the problem, each with a label, such as the
+    /// previous definition of a name which is defined twice.
+    pub related: Vec<(Span, String)>,
+}
+
+impl Diagnostic {
+    /// Creates an error with `message` pointing at `span`.
+    pub fn error(span: Span, message: impl Into<String>) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            span,
+            message: message.into(),
+            notes: Vec::new(),
+            related: Vec::new(),
+        }
+    }
+
+    /// Renders this diagnostic in the same style as parse errors, where
+    /// `contents` are the contents of `self.span.file`.
+    ///
+    /// Related spans in the same file are highlighted as well, while those in
+    /// other files are only referred to by their location.
+    pub fn render(&self, contents: &str) -> String {
+        let mut msg = self.span.highlight(contents, &self.message);
+        for (span, label) in self.related.iter() {
+            msg.push('\n');
+            if span.file == self.span.file {
+                msg.push_str(&span.highlight(contents, label));
+            } else if span.file.is_some() {
+                msg.push_str(&format!("{}\n     --> {}", label, span));
+            } else {
+                msg.push_str(label);
+            }
+        }
+        for note in self.notes.iter() {
+            msg.push_str("\nnote: ");
+            msg.push_str(note);
+        }
+        msg
+    }
+}
+
+/// The error returned when a `*.wit` file fails to parse, carrying a
+/// diagnostic for every problem found in it.
+///
+/// This is the error within the `anyhow::Error` returned by `Interface::parse`
+/// and friends when a file is malformed, and can be recovered with
+/// `anyhow::Error::downcast_ref`.
+#[derive(Debug)]
+pub struct ParseError {
+    diagnostics: Vec<Diagnostic>,
+    rendered: String,
+}
+
+impl ParseError {
+    /// Creates an error from `diagnostics`, all of which point into the file
+    /// with `contents`.
+    pub fn new(diagnostics: Vec<Diagnostic>, contents: &str) -> ParseError {
+        let rendered = diagnostics
+            .iter()
+            .map(|d| d.render(contents))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        ParseError {
+            diagnostics,
+            rendered,
+        }
+    }
+
+    /// Returns the problems found, in the order they appear in the file.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.rendered)
+    }
+}
+
+impl std::error::Error for ParseError {}
@@ -0,0 +1,1056 @@
+//! A reference interpreter for the canonical ABI.
+//!
+//! Code generators translate the `Instruction`s that `Interface::call` feeds
+//! them into source code, and mistakes in that translation tend to only show
+//! up once the generated code is run. This module instead executes the
+//! instruction stream directly, moving dynamically-typed [`Value`]s in and out
+//! of a [`LinearMemory`]. It's meant to serve as an executable
+//! specification of the ABI: lowering a value here and then lifting it with a
+//! generator's bindings, or vice versa, should always round-trip.
+//!
+//! [`call_wasm`] plays the role of bindings calling a core wasm function,
+//! lowering interface values into core wasm values, and [`call_interface`]
+//! plays the role of bindings wrapping an implementation of an interface
+//! function for core wasm to call. Only the canonical ABI is supported, and
+//! neither async functions nor buffers are.
+//!
+//! Besides testing, the interpreter can be used by hosts which only learn
+//! about interfaces at runtime, by implementing [`LinearMemory`] for a real
+//! instance's memory.
+
+use crate::abi::{Abi, AbiVariant, Bindgen, Bitcast, Instruction, LiftLower, WasmType};
+use crate::{Function, Interface, SizeAlign, Type, TypeDefKind, TypeId};
+use anyhow::{bail, Result};
+use std::convert::TryFrom;
+use std::mem;
+
+/// An interface types value.
+///
+/// The `c-char` and `usize` types of `*.witx` files are represented as `U8`
+/// and `U32` respectively.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    U8(u8),
+    S8(i8),
+    U16(u16),
+    S16(i16),
+    U32(u32),
+    S32(i32),
+    U64(u64),
+    S64(i64),
+    F32(f32),
+    F64(f64),
+    Char(char),
+    String(String),
+    List(Vec<Value>),
+    /// A record or tuple, with its fields in the order they're declared.
+    Record(Vec<Value>),
+    /// Whether each flag is set, in the order they're declared.
+    Flags(Vec<bool>),
+    /// A `variant`, `enum`, `union`, `option` or `expected`, with the index
+    /// of its case and the payload of that case, if it has one.
+    Variant {
+        case: usize,
+        payload: Option<Box<Value>>,
+    },
+    Handle(u32),
+}
+
+/// A core wasm value.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WasmValue {
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+}
+
+impl WasmValue {
+    pub fn ty(&self) -> WasmType {
+        match self {
+            WasmValue::I32(_) => WasmType::I32,
+            WasmValue::I64(_) => WasmType::I64,
+            WasmValue::F32(_) => WasmType::F32,
+            WasmValue::F64(_) => WasmType::F64,
+        }
+    }
+}
+
+/// The linear memory of a module, which values are lowered into and lifted
+/// out of.
+pub trait LinearMemory {
+    /// Fills `dst` with the bytes at `addr`.
+    fn read(&mut self, addr: i32, dst: &mut [u8]) -> Result<()>;
+
+    /// Overwrites the bytes at `addr` with `src`.
+    fn write(&mut self, addr: i32, src: &[u8]) -> Result<()>;
+
+    /// Allocates `size` bytes aligned to `align`, returning their address.
+    ///
+    /// This is where bindings would call `canonical_abi_realloc`.
+    fn alloc(&mut self, size: usize, align: usize) -> Result<i32>;
+
+    /// Deallocates the `size` bytes at `addr` which were allocated with
+    /// `align` by the other side of a call, after they've been lifted.
+    ///
+    /// This is where bindings would call `canonical_abi_free`.
+    fn free(&mut self, addr: i32, size: usize, align: usize) -> Result<()>;
+}
+
+/// A `LinearMemory` backed by a vector of bytes.
+///
+/// Allocations are appended to the end of the memory, zero-filled, and
+/// nothing is ever freed.
+#[derive(Clone, Debug, Default)]
+pub struct Memory {
+    bytes: Vec<u8>,
+}
+
+impl Memory {
+    pub fn new() -> Memory {
+        Memory::default()
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    fn range(&self, addr: i32, len: usize) -> Option<std::ops::Range<usize>> {
+        let start = addr as u32 as usize;
+        let end = start.checked_add(len)?;
+        if end > self.bytes.len() {
+            return None;
+        }
+        Some(start..end)
+    }
+}
+
+impl LinearMemory for Memory {
+    fn read(&mut self, addr: i32, dst: &mut [u8]) -> Result<()> {
+        match self.range(addr, dst.len()) {
+            Some(range) => {
+                dst.copy_from_slice(&self.bytes[range]);
+                Ok(())
+            }
+            None => bail!(
+                "out of bounds read of {} bytes at {:#x}",
+                dst.len(),
+                addr as u32
+            ),
+        }
+    }
+
+    fn write(&mut self, addr: i32, src: &[u8]) -> Result<()> {
+        match self.range(addr, src.len()) {
+            Some(range) => {
+                self.bytes[range].copy_from_slice(src);
+                Ok(())
+            }
+            None => bail!(
+                "out of bounds write of {} bytes at {:#x}",
+                src.len(),
+                addr as u32
+            ),
+        }
+    }
+
+    fn alloc(&mut self, size: usize, align: usize) -> Result<i32> {
+        let addr = align_to(self.bytes.len(), align.max(1));
+        let end = addr + size;
+        if end > i32::MAX as usize {
+            bail!("out of memory allocating {} bytes", size);
+        }
+        self.bytes.resize(end, 0);
+        Ok(addr as i32)
+    }
+
+    fn free(&mut self, _addr: i32, _size: usize, _align: usize) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl From<Vec<u8>> for Memory {
+    fn from(bytes: Vec<u8>) -> Memory {
+        Memory { bytes }
+    }
+}
+
+/// Calls the core wasm function for `func` with interface values, as the
+/// bindings for calling an imported function (with `AbiVariant::GuestImport`)
+/// or an exported function (with `AbiVariant::GuestExport`) would.
+///
+/// `args` are lowered into core wasm values, writing into `memory` where
+/// necessary, and then `wasm` is called with them in place of the real
+/// function. Its results are then lifted back into interface values.
+pub fn call_wasm<M: LinearMemory>(
+    iface: &Interface,
+    variant: AbiVariant,
+    func: &Function,
+    memory: &mut M,
+    args: &[Value],
+    mut wasm: impl FnMut(&mut M, &[WasmValue]) -> Result<Vec<WasmValue>>,
+) -> Result<Vec<Value>> {
+    if args.len() != func.params.len() {
+        bail!(
+            "`{}` takes {} arguments but {} were given",
+            func.name,
+            func.params.len(),
+            args.len()
+        );
+    }
+    let sig = iface.wasm_signature(variant, func);
+    let args = args.iter().cloned().map(Slot::Value).collect::<Vec<_>>();
+    let mut call = |memory: &mut M, args: Vec<Slot>| {
+        let args = args
+            .into_iter()
+            .map(Slot::into_wasm)
+            .collect::<Result<Vec<_>>>()?;
+        let results = wasm(memory, &args)?;
+        let tys = results.iter().map(|r| r.ty()).collect::<Vec<_>>();
+        if tys != sig.results {
+            bail!(
+                "`{}` should return {:?} but returned {:?}",
+                func.name,
+                sig.results,
+                tys
+            );
+        }
+        Ok(results.into_iter().map(Slot::Wasm).collect())
+    };
+    let results = run(
+        iface,
+        variant,
+        LiftLower::LowerArgsLiftResults,
+        func,
+        memory,
+        &args,
+        &mut call,
+    )?;
+    results.into_iter().map(Slot::into_value).collect()
+}
+
+/// Calls `imp`, an implementation of `func` taking and returning interface
+/// values, with core wasm arguments, as the bindings for defining an imported
+/// function (with `AbiVariant::GuestImport`) or an exported function (with
+/// `AbiVariant::GuestExport`) would.
+///
+/// `args` are lifted into interface values, reading from `memory` where
+/// necessary, and the results of `imp` are lowered back into core wasm
+/// values.
+pub fn call_interface<M: LinearMemory>(
+    iface: &Interface,
+    variant: AbiVariant,
+    func: &Function,
+    memory: &mut M,
+    args: &[WasmValue],
+    mut imp: impl FnMut(&[Value]) -> Result<Vec<Value>>,
+) -> Result<Vec<WasmValue>> {
+    let sig = iface.wasm_signature(variant, func);
+    let tys = args.iter().map(|a| a.ty()).collect::<Vec<_>>();
+    if tys != sig.params {
+        bail!(
+            "`{}` takes {:?} but was given {:?}",
+            func.name,
+            sig.params,
+            tys
+        );
+    }
+    let args = args.iter().cloned().map(Slot::Wasm).collect::<Vec<_>>();
+    let mut call = |_: &mut M, args: Vec<Slot>| {
+        let args = args
+            .into_iter()
+            .map(Slot::into_value)
+            .collect::<Result<Vec<_>>>()?;
+        let results = imp(&args)?;
+        if results.len() != func.results.len() {
+            bail!(
+                "`{}` should return {} values but returned {}",
+                func.name,
+                func.results.len(),
+                results.len()
+            );
+        }
+        Ok(results.into_iter().map(Slot::Value).collect())
+    };
+    let results = run(
+        iface,
+        variant,
+        LiftLower::LiftArgsLowerResults,
+        func,
+        memory,
+        &args,
+        &mut call,
+    )?;
+    results.into_iter().map(Slot::into_wasm).collect()
+}
+
+type Call<'a, M> = dyn FnMut(&mut M, Vec<Slot>) -> Result<Vec<Slot>> + 'a;
+
+fn run<M: LinearMemory>(
+    iface: &Interface,
+    variant: AbiVariant,
+    lift_lower: LiftLower,
+    func: &Function,
+    memory: &mut M,
+    args: &[Slot],
+    call: &mut Call<'_, M>,
+) -> Result<Vec<Slot>> {
+    if func.abi != Abi::Canonical {
+        bail!("`{}` doesn't use the canonical ABI", func.name);
+    }
+    if func.is_async {
+        bail!("`{}` is async, which isn't supported", func.name);
+    }
+
+    let mut sizes = SizeAlign::default();
+    sizes.fill(variant, iface);
+    let mut recorder = Recorder {
+        sizes: &sizes,
+        registers: 0,
+        blocks: vec![Vec::new()],
+        finished: Vec::new(),
+        unsupported: None,
+    };
+    iface.call(variant, lift_lower, func, &mut recorder);
+    if let Some(inst) = recorder.unsupported {
+        bail!("`{}` uses `{}`, which isn't supported", func.name, inst);
+    }
+    let program = Block {
+        stmts: recorder.blocks.pop().unwrap(),
+        results: Vec::new(),
+    };
+
+    let mut exec = Exec {
+        iface,
+        sizes: &sizes,
+        memory,
+        args,
+        call,
+        registers: vec![Slot::Unit; recorder.registers],
+        elems: Vec::new(),
+        payloads: Vec::new(),
+        returned: None,
+    };
+    exec.block(&program)?;
+    Ok(exec.returned.expect("no `return` instruction was executed"))
+}
+
+/// An operand of an instruction, which is either a core wasm value, an
+/// interface value, or nothing in the case of variant payloads for cases
+/// without a payload.
+#[derive(Clone, Debug)]
+enum Slot {
+    Wasm(WasmValue),
+    Value(Value),
+    Unit,
+}
+
+impl Slot {
+    fn into_wasm(self) -> Result<WasmValue> {
+        match self {
+            Slot::Wasm(w) => Ok(w),
+            other => bail!("expected a core wasm value, found {:?}", other),
+        }
+    }
+
+    fn into_value(self) -> Result<Value> {
+        match self {
+            Slot::Value(v) => Ok(v),
+            other => bail!("expected an interface value, found {:?}", other),
+        }
+    }
+}
+
+/// An owned copy of the parts of an `Instruction` needed to execute it.
+///
+/// Instructions which take blocks own them here.
+#[derive(Debug)]
+enum Op {
+    GetArg(usize),
+    I32Const(i32),
+    Bitcasts(Vec<Bitcast>),
+    ConstZero(Vec<WasmType>),
+    Load(Access, i32),
+    Store(Access, i32),
+    Lower(Scalar),
+    Lift(Scalar),
+    ListCanonLower(Type),
+    ListCanonLift {
+        element: Type,
+        free: bool,
+    },
+    ListLower(Type, Block),
+    ListLift {
+        element: Type,
+        free: bool,
+        body: Block,
+    },
+    IterElem,
+    IterBasePointer,
+    RecordLower(usize),
+    RecordLift,
+    FlagsLower {
+        flags: usize,
+        words: usize,
+    },
+    FlagsLower64(usize),
+    FlagsLift(usize),
+    FlagsLift64(usize),
+    VariantPayloadName,
+    VariantLower {
+        is_bool: bool,
+        cases: Vec<Block>,
+    },
+    VariantLift {
+        is_bool: bool,
+        cases: Vec<Block>,
+    },
+    Call,
+    Return,
+    ReturnPointerArea(usize),
+}
+
+/// The width of a load or store.
+#[derive(Clone, Copy, Debug)]
+enum Access {
+    I32,
+    U8,
+    S8,
+    U16,
+    S16,
+    I64,
+    F32,
+    F64,
+}
+
+/// The interface type of a scalar lifting or lowering instruction.
+#[derive(Clone, Copy, Debug)]
+enum Scalar {
+    U8,
+    S8,
+    U16,
+    S16,
+    U32,
+    S32,
+    U64,
+    S64,
+    F32,
+    F64,
+    Char,
+    Handle,
+}
+
+#[derive(Debug)]
+struct Stmt {
+    op: Op,
+    operands: Vec<usize>,
+    results: Vec<usize>,
+}
+
+#[derive(Debug)]
+struct Block {
+    stmts: Vec<Stmt>,
+    /// The registers holding the values this block produces.
+    results: Vec<usize>,
+}
+
+/// Records the instructions emitted for a function as a tree of `Block`s,
+/// where operands are registers numbered in the order they're defined.
+struct Recorder<'a> {
+    sizes: &'a SizeAlign,
+    registers: usize,
+    /// The blocks currently being recorded, innermost last.
+    blocks: Vec<Vec<Stmt>>,
+    /// Blocks which have been finished but not yet claimed by an
+    /// instruction.
+    finished: Vec<Block>,
+    unsupported: Option<String>,
+}
+
+impl Recorder<'_> {
+    fn push(&mut self, op: Op, operands: Vec<usize>, nresults: usize) -> Vec<usize> {
+        let results = (self.registers..self.registers + nresults).collect::<Vec<_>>();
+        self.registers += nresults;
+        self.blocks.last_mut().unwrap().push(Stmt {
+            op,
+            operands,
+            results: results.clone(),
+        });
+        results
+    }
+
+    fn blocks(&mut self, n: usize) -> Vec<Block> {
+        self.finished.split_off(self.finished.len() - n)
+    }
+}
+
+impl Bindgen for Recorder<'_> {
+    type Operand = usize;
+
+    fn emit(
+        &mut self,
+        _iface: &Interface,
+        inst: &Instruction<'_>,
+        operands: &mut Vec<usize>,
+        results: &mut Vec<usize>,
+    ) {
+        use Instruction::*;
+
+        let op = match inst {
+            GetArg { nth } => Op::GetArg(*nth),
+            I32Const { val } => Op::I32Const(*val),
+            Bitcasts { casts } => Op::Bitcasts(casts.to_vec()),
+            ConstZero { tys } => Op::ConstZero(tys.to_vec()),
+
+            I32Load { offset } => Op::Load(Access::I32, *offset),
+            I32Load8U { offset } => Op::Load(Access::U8, *offset),
+            I32Load8S { offset } => Op::Load(Access::S8, *offset),
+            I32Load16U { offset } => Op::Load(Access::U16, *offset),
+            I32Load16S { offset } => Op::Load(Access::S16, *offset),
+            I64Load { offset } => Op::Load(Access::I64, *offset),
+            F32Load { offset } => Op::Load(Access::F32, *offset),
+            F64Load { offset } => Op::Load(Access::F64, *offset),
+            I32Store { offset } => Op::Store(Access::I32, *offset),
+            I32Store8 { offset } => Op::Store(Access::U8, *offset),
+            I32Store16 { offset } => Op::Store(Access::U16, *offset),
+            I64Store { offset } => Op::Store(Access::I64, *offset),
+            F32Store { offset } => Op::Store(Access::F32, *offset),
+            F64Store { offset } => Op::Store(Access::F64, *offset),
+
+            I32FromU8 | I32FromChar8 => Op::Lower(Scalar::U8),
+            I32FromS8 => Op::Lower(Scalar::S8),
+            I32FromU16 => Op::Lower(Scalar::U16),
+            I32FromS16 => Op::Lower(Scalar::S16),
+            I32FromU32 | I32FromUsize => Op::Lower(Scalar::U32),
+            I32FromS32 => Op::Lower(Scalar::S32),
+            I64FromU64 => Op::Lower(Scalar::U64),
+            I64FromS64 => Op::Lower(Scalar::S64),
+            F32FromIf32 => Op::Lower(Scalar::F32),
+            F64FromIf64 => Op::Lower(Scalar::F64),
+            I32FromChar => Op::Lower(Scalar::Char),
+            I32FromBorrowedHandle { .. } | I32FromOwnedHandle { .. } => Op::Lower(Scalar::Handle),
+            U8FromI32 | Char8FromI32 => Op::Lift(Scalar::U8),
+            S8FromI32 => Op::Lift(Scalar::S8),
+            U16FromI32 => Op::Lift(Scalar::U16),
+            S16FromI32 => Op::Lift(Scalar::S16),
+            U32FromI32 | UsizeFromI32 => Op::Lift(Scalar::U32),
+            S32FromI32 => Op::Lift(Scalar::S32),
+            U64FromI64 => Op::Lift(Scalar::U64),
+            S64FromI64 => Op::Lift(Scalar::S64),
+            If32FromF32 => Op::Lift(Scalar::F32),
+            If64FromF64 => Op::Lift(Scalar::F64),
+            CharFromI32 => Op::Lift(Scalar::Char),
+            HandleOwnedFromI32 { .. } | HandleBorrowedFromI32 { .. } => Op::Lift(Scalar::Handle),
+
+            ListCanonLower { element, .. } => Op::ListCanonLower(**element),
+            ListCanonLift { element, free, .. } => Op::ListCanonLift {
+                element: **element,
+                free: free.is_some(),
+            },
+            ListLower { element, .. } => {
+                let body = self.blocks(1).pop().unwrap();
+                Op::ListLower(**element, body)
+            }
+            ListLift { element, free, .. } => Op::ListLift {
+                element: **element,
+                free: free.is_some(),
+                body: self.blocks(1).pop().unwrap(),
+            },
+            IterElem { .. } => Op::IterElem,
+            IterBasePointer => Op::IterBasePointer,
+
+            RecordLower { record, .. } => Op::RecordLower(record.fields.len()),
+            RecordLift { .. } => Op::RecordLift,
+            FlagsLower { record, .. } => Op::FlagsLower {
+                flags: record.fields.len(),
+                words: record.num_i32s(),
+            },
+            FlagsLower64 { record, .. } => Op::FlagsLower64(record.fields.len()),
+            FlagsLift { record, .. } => Op::FlagsLift(record.fields.len()),
+            FlagsLift64 { record, .. } => Op::FlagsLift64(record.fields.len()),
+
+            VariantPayloadName => Op::VariantPayloadName,
+            VariantLower { variant, .. } => Op::VariantLower {
+                is_bool: variant.is_bool(),
+                cases: self.blocks(variant.cases.len()),
+            },
+            VariantLift { variant, .. } => Op::VariantLift {
+                is_bool: variant.is_bool(),
+                cases: self.blocks(variant.cases.len()),
+            },
+
+            CallWasm { .. } | CallInterface { .. } => Op::Call,
+            Return { .. } => Op::Return,
+
+            // Buffers, async functions and the preview1 ABI aren't
+            // supported, and the latter two are rejected before getting
+            // here.
+            other => {
+                if self.unsupported.is_none() {
+                    let name = format!("{:?}", other);
+                    let name = name.split(|c: char| !c.is_alphanumeric()).next();
+                    self.unsupported = name.map(|s| s.to_string());
+                }
+                // Claim the buffer's block so the rest of the recording is
+                // still consistent.
+                if let BufferLowerPtrLen { .. }
+                | BufferLowerHandle { .. }
+                | BufferLiftPtrLen { .. }
+                | BufferLiftHandle { .. } = other
+                {
+                    self.blocks(1);
+                }
+                results.extend(self.push(Op::Return, Vec::new(), inst.results_len()));
+                return;
+            }
+        };
+        results.extend(self.push(op, mem::take(operands), inst.results_len()));
+    }
+
+    fn allocate_typed_space(&mut self, _iface: &Interface, _ty: TypeId) -> usize {
+        unreachable!("only used by the preview1 ABI")
+    }
+
+    fn i64_return_pointer_area(&mut self, amt: usize) -> usize {
+        self.push(Op::ReturnPointerArea(amt), Vec::new(), 1)[0]
+    }
+
+    fn push_block(&mut self) {
+        self.blocks.push(Vec::new());
+    }
+
+    fn finish_block(&mut self, operands: &mut Vec<usize>) {
+        let stmts = self.blocks.pop().unwrap();
+        self.finished.push(Block {
+            stmts,
+            results: mem::take(operands),
+        });
+    }
+
+    fn sizes(&self) -> &SizeAlign {
+        self.sizes
+    }
+
+    fn is_list_canonical(&self, _iface: &Interface, _element: &Type) -> bool {
+        // Every list other than strings is lowered element by element, which
+        // exercises more of the instruction set.
+        false
+    }
+}
+
+/// Executes the program recorded by `Recorder`.
+struct Exec<'a, 'b, M> {
+    iface: &'a Interface,
+    sizes: &'a SizeAlign,
+    memory: &'a mut M,
+    args: &'a [Slot],
+    call: &'a mut Call<'b, M>,
+    registers: Vec<Slot>,
+    /// The element and base pointer of the lists being iterated over,
+    /// innermost last.
+    elems: Vec<(Slot, i32)>,
+    /// The payloads of the variants being lowered, innermost last.
+    payloads: Vec<Slot>,
+    returned: Option<Vec<Slot>>,
+}
+
+impl<M: LinearMemory> Exec<'_, '_, M> {
+    /// Executes `block`, returning the values it produces.
+    fn block(&mut self, block: &Block) -> Result<Vec<Slot>> {
+        for stmt in block.stmts.iter() {
+            let operands = stmt
+                .operands
+                .iter()
+                .map(|r| self.registers[*r].clone())
+                .collect::<Vec<_>>();
+            let results = self.stmt(&stmt.op, operands)?;
+            assert_eq!(results.len(), stmt.results.len(), "{:?}", stmt.op);
+            for (r, value) in stmt.results.iter().zip(results) {
+                self.registers[*r] = value;
+            }
+        }
+        Ok(block
+            .results
+            .iter()
+            .map(|r| self.registers[*r].clone())
+            .collect())
+    }
+
+    fn stmt(&mut self, op: &Op, mut operands: Vec<Slot>) -> Result<Vec<Slot>> {
+        Ok(match op {
+            Op::GetArg(nth) => match self.args.get(*nth) {
+                Some(arg) => vec![arg.clone()],
+                None => bail!("missing argument {}", nth),
+            },
+            Op::I32Const(val) => vec![Slot::Wasm(WasmValue::I32(*val))],
+            Op::Bitcasts(casts) => casts
+                .iter()
+                .zip(operands)
+                .map(|(cast, operand)| Ok(Slot::Wasm(bitcast(*cast, operand.into_wasm()?)?)))
+                .collect::<Result<_>>()?,
+            Op::ConstZero(tys) => tys
+                .iter()
+                .map(|ty| {
+                    Slot::Wasm(match ty {
+                        WasmType::I32 => WasmValue::I32(0),
+                        WasmType::I64 => WasmValue::I64(0),
+                        WasmType::F32 => WasmValue::F32(0.0),
+                        WasmType::F64 => WasmValue::F64(0.0),
+                    })
+                })
+                .collect(),
+
+            Op::Load(access, offset) => {
+                let addr = i32_operand(operands.pop())?;
+                vec![Slot::Wasm(self.load(*access, addr, *offset)?)]
+            }
+            Op::Store(access, offset) => {
+                let addr = i32_operand(operands.pop())?;
+                let value = operands.pop().unwrap().into_wasm()?;
+                self.store(*access, addr, *offset, value)?;
+                Vec::new()
+            }
+
+            Op::Lower(scalar) => {
+                let value = operands.pop().unwrap().into_value()?;
+                vec![Slot::Wasm(lower(*scalar, value)?)]
+            }
+            Op::Lift(scalar) => {
+                let value = operands.pop().unwrap().into_wasm()?;
+                vec![Slot::Value(lift(*scalar, value)?)]
+            }
+
+            Op::ListCanonLower(element) => {
+                let bytes = match (self.is_char(element), operands.pop().unwrap()) {
+                    (true, Slot::Value(Value::String(s))) => s.into_bytes(),
+                    (true, other) => bail!("expected a string, found {:?}", other),
+                    (false, _) => bail!("canonical lists are only supported for strings"),
+                };
+                let ptr = self.memory.alloc(bytes.len(), 1)?;
+                self.memory.write(ptr, &bytes)?;
+                vec![
+                    Slot::Wasm(WasmValue::I32(ptr)),
+                    Slot::Wasm(WasmValue::I32(bytes.len() as i32)),
+                ]
+            }
+            Op::ListCanonLift { element, free } => {
+                if !self.is_char(element) {
+                    bail!("canonical lists are only supported for strings");
+                }
+                let len = i32_operand(operands.pop())?;
+                let ptr = i32_operand(operands.pop())?;
+                self.check_bounds(ptr, len as u32 as usize)?;
+                let mut bytes = vec![0; len as u32 as usize];
+                self.memory.read(ptr, &mut bytes)?;
+                if *free {
+                    self.memory.free(ptr, bytes.len(), 1)?;
+                }
+                match String::from_utf8(bytes) {
+                    Ok(s) => vec![Slot::Value(Value::String(s))],
+                    Err(_) => bail!("invalid utf-8 in string at {:#x}", ptr),
+                }
+            }
+            Op::ListLower(element, body) => {
+                let elems = match operands.pop().unwrap().into_value()? {
+                    Value::List(elems) => elems,
+                    other => bail!("expected a list, found {:?}", other),
+                };
+                let size = self.sizes.size(element);
+                let align = self.sizes.align(element);
+                let ptr = self.memory.alloc(size * elems.len(), align)?;
+                let len = elems.len() as i32;
+                for (i, elem) in elems.into_iter().enumerate() {
+                    let base = ptr + (i * size) as i32;
+                    self.elems.push((Slot::Value(elem), base));
+                    self.block(body)?;
+                    self.elems.pop();
+                }
+                vec![
+                    Slot::Wasm(WasmValue::I32(ptr)),
+                    Slot::Wasm(WasmValue::I32(len)),
+                ]
+            }
+            Op::ListLift {
+                element,
+                free,
+                body,
+            } => {
+                let len = i32_operand(operands.pop())?;
+                let ptr = i32_operand(operands.pop())?;
+                let size = self.sizes.size(element);
+                self.check_bounds(ptr, size * len as u32 as usize)?;
+                let mut elems = Vec::new();
+                for i in 0..len as u32 as usize {
+                    let base = ptr + (i * size) as i32;
+                    self.elems.push((Slot::Unit, base));
+                    let mut results = self.block(body)?;
+                    self.elems.pop();
+                    elems.push(results.pop().unwrap().into_value()?);
+                }
+                if *free {
+                    let align = self.sizes.align(element);
+                    self.memory.free(ptr, size * elems.len(), align)?;
+                }
+                vec![Slot::Value(Value::List(elems))]
+            }
+            Op::IterElem => {
+                let (elem, _) = self.elems.last_mut().unwrap();
+                vec![mem::replace(elem, Slot::Unit)]
+            }
+            Op::IterBasePointer => {
+                let (_, base) = self.elems.last().unwrap();
+                vec![Slot::Wasm(WasmValue::I32(*base))]
+            }
+
+            Op::RecordLower(n) => match operands.pop().unwrap().into_value()? {
+                Value::Record(fields) if fields.len() == *n => {
+                    fields.into_iter().map(Slot::Value).collect()
+                }
+                other => bail!("expected a record with {} fields, found {:?}", n, other),
+            },
+            Op::RecordLift => {
+                let fields = operands
+                    .into_iter()
+                    .map(Slot::into_value)
+                    .collect::<Result<_>>()?;
+                vec![Slot::Value(Value::Record(fields))]
+            }
+            Op::FlagsLower { flags: n, words } => {
+                let flags = flags_operand(operands.pop(), *n)?;
+                let mut words = vec![0u32; *words];
+                for (i, set) in flags.iter().enumerate() {
+                    words[i / 32] |= (*set as u32) << (i % 32);
+                }
+                words
+                    .into_iter()
+                    .map(|w| Slot::Wasm(WasmValue::I32(w as i32)))
+                    .collect()
+            }
+            Op::FlagsLower64(n) => {
+                let flags = flags_operand(operands.pop(), *n)?;
+                let mut bits = 0u64;
+                for (i, set) in flags.iter().enumerate() {
+                    bits |= (*set as u64) << i;
+                }
+                vec![Slot::Wasm(WasmValue::I64(bits as i64))]
+            }
+            Op::FlagsLift(n) => {
+                let words = operands
+                    .into_iter()
+                    .map(|w| i32_operand(Some(w)))
+                    .collect::<Result<Vec<_>>>()?;
+                let flags = (0..*n)
+                    .map(|i| (words[i / 32] as u32) & (1 << (i % 32)) != 0)
+                    .collect();
+                vec![Slot::Value(Value::Flags(flags))]
+            }
+            Op::FlagsLift64(n) => {
+                let bits = match operands.pop().unwrap().into_wasm()? {
+                    WasmValue::I64(bits) => bits as u64,
+                    other => bail!("expected an i64, found {:?}", other),
+                };
+                let flags = (0..*n).map(|i| bits & (1 << i) != 0).collect();
+                vec![Slot::Value(Value::Flags(flags))]
+            }
+
+            Op::VariantPayloadName => vec![self.payloads.last().unwrap().clone()],
+            Op::VariantLower { is_bool, cases } => {
+                let (case, payload) = match (operands.pop().unwrap().into_value()?, is_bool) {
+                    (Value::Bool(b), true) => (b as usize, None),
+                    (Value::Variant { case, payload }, false) => (case, payload),
+                    (other, _) => bail!("expected a variant, found {:?}", other),
+                };
+                let block = match cases.get(case) {
+                    Some(block) => block,
+                    None => bail!("case {} is out of range", case),
+                };
+                let payload = match payload {
+                    Some(payload) => Slot::Value(*payload),
+                    None => Slot::Unit,
+                };
+                self.payloads.push(payload);
+                let results = self.block(block)?;
+                self.payloads.pop();
+                results
+            }
+            Op::VariantLift { is_bool, cases } => {
+                let case = match operands.pop().unwrap().into_wasm()? {
+                    WasmValue::I32(i) => i as u32 as u64,
+                    WasmValue::I64(i) => i as u64,
+                    other => bail!("expected an integer discriminant, found {:?}", other),
+                };
+                let block = match cases.get(case as usize) {
+                    Some(block) => block,
+                    None => bail!("invalid discriminant {}", case),
+                };
+                let payload = match self.block(block)?.pop() {
+                    Some(payload) => Some(Box::new(payload.into_value()?)),
+                    None => None,
+                };
+                vec![Slot::Value(if *is_bool {
+                    Value::Bool(case == 1)
+                } else {
+                    Value::Variant {
+                        case: case as usize,
+                        payload,
+                    }
+                })]
+            }
+
+            Op::Call => (self.call)(self.memory, operands)?,
+            Op::Return => {
+                self.returned = Some(operands);
+                Vec::new()
+            }
+            Op::ReturnPointerArea(amt) => {
+                let ptr = self.memory.alloc(amt * 8, 8)?;
+                vec![Slot::Wasm(WasmValue::I32(ptr))]
+            }
+        })
+    }
+
+    fn load(&mut self, access: Access, addr: i32, offset: i32) -> Result<WasmValue> {
+        let addr = addr.wrapping_add(offset);
+        Ok(match access {
+            Access::I32 => WasmValue::I32(i32::from_le_bytes(self.read(addr)?)),
+            Access::U8 => WasmValue::I32(u8::from_le_bytes(self.read(addr)?).into()),
+            Access::S8 => WasmValue::I32(i8::from_le_bytes(self.read(addr)?).into()),
+            Access::U16 => WasmValue::I32(u16::from_le_bytes(self.read(addr)?).into()),
+            Access::S16 => WasmValue::I32(i16::from_le_bytes(self.read(addr)?).into()),
+            Access::I64 => WasmValue::I64(i64::from_le_bytes(self.read(addr)?)),
+            Access::F32 => WasmValue::F32(f32::from_le_bytes(self.read(addr)?)),
+            Access::F64 => WasmValue::F64(f64::from_le_bytes(self.read(addr)?)),
+        })
+    }
+
+    fn store(&mut self, access: Access, addr: i32, offset: i32, value: WasmValue) -> Result<()> {
+        let addr = addr.wrapping_add(offset);
+        let bytes = match (access, value) {
+            (Access::I32, WasmValue::I32(v)) => v.to_le_bytes().to_vec(),
+            (Access::U8, WasmValue::I32(v)) | (Access::S8, WasmValue::I32(v)) => vec![v as u8],
+            (Access::U16, WasmValue::I32(v)) | (Access::S16, WasmValue::I32(v)) => {
+                (v as u16).to_le_bytes().to_vec()
+            }
+            (Access::I64, WasmValue::I64(v)) => v.to_le_bytes().to_vec(),
+            (Access::F32, WasmValue::F32(v)) => v.to_le_bytes().to_vec(),
+            (Access::F64, WasmValue::F64(v)) => v.to_le_bytes().to_vec(),
+            (access, value) => bail!("cannot store {:?} with a {:?} store", value, access),
+        };
+        self.memory.write(addr, &bytes)
+    }
+
+    fn read<const N: usize>(&mut self, addr: i32) -> Result<[u8; N]> {
+        let mut buf = [0; N];
+        self.memory.read(addr, &mut buf)?;
+        Ok(buf)
+    }
+
+    /// Makes sure that the `len` bytes at `addr` are in bounds before
+    /// anything is allocated for them or any of them are read, rather than
+    /// trusting a bogus length.
+    fn check_bounds(&mut self, addr: i32, len: usize) -> Result<()> {
+        if len == 0 {
+            return Ok(());
+        }
+        let last = u64::from(addr as u32) + len as u64 - 1;
+        if last > u64::from(u32::MAX) || self.memory.read(last as i32, &mut [0]).is_err() {
+            bail!(
+                "list of {} bytes at {:#x} is out of bounds",
+                len,
+                addr as u32
+            );
+        }
+        Ok(())
+    }
+
+    fn is_char(&self, ty: &Type) -> bool {
+        match ty {
+            Type::Char => true,
+            Type::Id(id) => match &self.iface.types[*id].kind {
+                TypeDefKind::Type(t) => self.is_char(t),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+fn i32_operand(operand: Option<Slot>) -> Result<i32> {
+    match operand {
+        Some(Slot::Wasm(WasmValue::I32(i))) => Ok(i),
+        other => bail!("expected an i32, found {:?}", other),
+    }
+}
+
+fn flags_operand(operand: Option<Slot>, n: usize) -> Result<Vec<bool>> {
+    match operand {
+        Some(Slot::Value(Value::Flags(flags))) if flags.len() == n => Ok(flags),
+        other => bail!("expected {} flags, found {:?}", n, other),
+    }
+}
+
+fn lower(scalar: Scalar, value: Value) -> Result<WasmValue> {
+    Ok(match (scalar, value) {
+        (Scalar::U8, Value::U8(v)) => WasmValue::I32(v.into()),
+        (Scalar::S8, Value::S8(v)) => WasmValue::I32(v.into()),
+        (Scalar::U16, Value::U16(v)) => WasmValue::I32(v.into()),
+        (Scalar::S16, Value::S16(v)) => WasmValue::I32(v.into()),
+        (Scalar::U32, Value::U32(v)) => WasmValue::I32(v as i32),
+        (Scalar::S32, Value::S32(v)) => WasmValue::I32(v),
+        (Scalar::U64, Value::U64(v)) => WasmValue::I64(v as i64),
+        (Scalar::S64, Value::S64(v)) => WasmValue::I64(v),
+        (Scalar::F32, Value::F32(v)) => WasmValue::F32(v),
+        (Scalar::F64, Value::F64(v)) => WasmValue::F64(v),
+        (Scalar::Char, Value::Char(v)) => WasmValue::I32(v as i32),
+        (Scalar::Handle, Value::Handle(v)) => WasmValue::I32(v as i32),
+        (scalar, value) => bail!("expected a {:?}, found {:?}", scalar, value),
+    })
+}
+
+fn lift(scalar: Scalar, value: WasmValue) -> Result<Value> {
+    Ok(match (scalar, value) {
+        // Like the instructions that this implements, lifting 8 and 16-bit
+        // integers truncates the upper bits.
+        (Scalar::U8, WasmValue::I32(v)) => Value::U8(v as u8),
+        (Scalar::S8, WasmValue::I32(v)) => Value::S8(v as i8),
+        (Scalar::U16, WasmValue::I32(v)) => Value::U16(v as u16),
+        (Scalar::S16, WasmValue::I32(v)) => Value::S16(v as i16),
+        (Scalar::U32, WasmValue::I32(v)) => Value::U32(v as u32),
+        (Scalar::S32, WasmValue::I32(v)) => Value::S32(v),
+        (Scalar::U64, WasmValue::I64(v)) => Value::U64(v as u64),
+        (Scalar::S64, WasmValue::I64(v)) => Value::S64(v),
+        (Scalar::F32, WasmValue::F32(v)) => Value::F32(v),
+        (Scalar::F64, WasmValue::F64(v)) => Value::F64(v),
+        (Scalar::Char, WasmValue::I32(v)) => match char::try_from(v as u32) {
+            Ok(c) => Value::Char(c),
+            Err(_) => bail!("invalid unicode scalar value {:#x}", v),
+        },
+        (Scalar::Handle, WasmValue::I32(v)) => Value::Handle(v as u32),
+        (scalar, value) => bail!("cannot lift {:?} from {:?}", scalar, value),
+    })
+}
+
+/// Implements `Bitcast` the same way that the Rust generators do.
+fn bitcast(cast: Bitcast, value: WasmValue) -> Result<WasmValue> {
+    use WasmValue::*;
+
+    Ok(match (cast, value) {
+        (Bitcast::None, v) => v,
+        (Bitcast::F32ToF64, F32(v)) => F64(v.into()),
+        (Bitcast::I32ToI64, I32(v)) => I64(v.into()),
+        (Bitcast::F32ToI32, F32(v)) => I32(v.to_bits() as i32),
+        (Bitcast::F64ToI64, F64(v)) => I64(v.to_bits() as i64),
+        (Bitcast::F64ToF32, F64(v)) => F32(v as f32),
+        (Bitcast::I64ToI32, I64(v)) => I32(v as i32),
+        (Bitcast::I32ToF32, I32(v)) => F32(f32::from_bits(v as u32)),
+        (Bitcast::I64ToF64, I64(v)) => F64(f64::from_bits(v as u64)),
+        (Bitcast::F32ToI64, F32(v)) => I64(v.to_bits().into()),
+        (Bitcast::I64ToF32, I64(v)) => F32(f32::from_bits(v as u32)),
+        (cast, value) => bail!("cannot apply {:?} to {:?}", cast, value),
+    })
+}
+
+fn align_to(val: usize, align: usize) -> usize {
+    (val + align - 1) & !(align - 1)
+}
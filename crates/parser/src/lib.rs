@@ -1,13 +1,24 @@
 use anyhow::{anyhow, bail, Context, Result};
 use id_arena::{Arena, Id};
 use pulldown_cmark::{CodeBlockKind, CowStr, Event, Options, Parser, Tag};
+use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 pub mod abi;
 mod ast;
+pub mod compat;
+mod diagnostic;
+pub mod interpret;
+#[cfg(feature = "serde")]
+mod serialize;
 mod sizealign;
+pub use diagnostic::*;
+#[cfg(feature = "serde")]
+pub use serialize::{WithSpans, SERIALIZATION_VERSION};
 pub use sizealign::*;
 
 /// Checks if the given string is a legal identifier in wit.
@@ -16,9 +27,88 @@ pub fn validate_id(s: &str) -> Result<()> {
     Ok(())
 }
 
+/// Formats the `*.wit` source `contents`, preserving its comments.
+///
+/// Items are kept in the order they're written, with one blank line at most
+/// between them. Fields and cases of types are placed one per line, while
+/// function parameters stay on one line unless the first one was on a new
+/// line in `contents`. `filename` is only used in error messages.
+pub fn format(filename: impl AsRef<Path>, contents: &str) -> Result<String> {
+    ast::format(contents).map_err(|e| ast::rewrite_error(e, filename.as_ref(), contents, contents))
+}
+
+/// The location of an item within a `*.wit` file.
+///
+/// Spans of resolved items cover the item's name. Items which weren't parsed
+/// from a file, such as those synthesized by generators or decoded from a
+/// component, have a default span without a `file`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    /// The file that the item was parsed from.
+    pub file: Option<Arc<Path>>,
+    /// The byte offset of the start of the span in the file. For `*.md` files
+    /// this is an offset into the `wit` code extracted from the file.
+    pub start: u32,
+    /// The byte offset of the end of the span (exclusive).
+    pub end: u32,
+    /// The 1-based line that the span starts on.
+    pub line: u32,
+    /// The 1-based byte column that the span starts at.
+    pub column: u32,
+}
+
+impl Span {
+    /// Renders `msg` as a diagnostic pointing at this span in `contents`, the
+    /// contents of `self.file`, in the same style as parse errors.
+    ///
+    /// If this span has no file then `msg` is returned as-is.
+    pub fn highlight(&self, contents: &str, msg: &str) -> String {
+        let file = match &self.file {
+            Some(file) => file,
+            None => return msg.to_string(),
+        };
+        let md_contents;
+        let mut contents = contents;
+        if file.extension().and_then(|s| s.to_str()) == Some("md") {
+            md_contents = unwrap_md(contents);
+            contents = &md_contents[..];
+        }
+        ast::highlight_err(
+            self.start as usize,
+            Some(self.end as usize),
+            &file.display().to_string(),
+            contents,
+            msg,
+        )
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "{}:{}:{}", file.display(), self.line, self.column),
+            None => f.write_str("<unknown>"),
+        }
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct Interface {
     pub name: String,
+    /// Documentation for interfaces nested within another, which is always
+    /// empty for top-level interfaces.
+    pub docs: Docs,
+    /// Attributes of interfaces nested within another, which is always empty
+    /// for top-level interfaces.
+    pub attrs: Attributes,
+    /// Where the name of an interface nested within another is declared,
+    /// which is the default span for top-level interfaces.
+    pub span: Span,
+    /// The core wasm module that the functions of interfaces nested within
+    /// another are imported from, which is the path to the interface such as
+    /// `outer::inner`. This is `None` for top-level interfaces, which use
+    /// `name` instead.
+    pub module: Option<String>,
     pub types: Arena<TypeDef>,
     pub type_lookup: HashMap<String, TypeId>,
     pub resources: Arena<Resource>,
@@ -27,6 +117,7 @@ pub struct Interface {
     pub interface_lookup: HashMap<String, InterfaceId>,
     pub functions: Vec<Function>,
     pub globals: Vec<Global>,
+    pub constants: Vec<Constant>,
 }
 
 pub type TypeId = Id<TypeDef>;
@@ -36,8 +127,11 @@ pub type InterfaceId = Id<Interface>;
 #[derive(Debug)]
 pub struct TypeDef {
     pub docs: Docs,
+    pub attrs: Attributes,
     pub kind: TypeDefKind,
     pub name: Option<String>,
+    /// Where this type is declared; anonymous types have a default span.
+    pub span: Span,
     /// `None` if this type is originally declared in this instance or
     /// otherwise `Some` if it was originally defined in a different module.
     pub foreign_module: Option<String>,
@@ -100,6 +194,7 @@ pub struct Field {
     pub docs: Docs,
     pub name: String,
     pub ty: Type,
+    pub span: Span,
 }
 
 impl Record {
@@ -164,6 +259,7 @@ pub struct Case {
     pub docs: Docs,
     pub name: String,
     pub ty: Option<Type>,
+    pub span: Span,
 }
 
 impl Variant {
@@ -228,10 +324,73 @@ pub struct Docs {
     pub contents: Option<String>,
 }
 
+/// The attributes written before an item, such as `@deprecated` or
+/// `@rust(derive = "Hash")`.
+///
+/// Apart from those documented in `WIT.md`, attributes aren't interpreted by
+/// the parser, so generators are free to define their own.
+#[derive(Clone, Default, Debug)]
+pub struct Attributes {
+    pub list: Vec<Attribute>,
+}
+
+/// A single attribute, written `@name` or `@name(args)`.
+#[derive(Clone, Debug)]
+pub struct Attribute {
+    pub name: String,
+    /// The source text between the parentheses, if there are any.
+    pub args: Option<String>,
+    /// `args` split at each `,` outside of any parentheses or strings, as
+    /// `key = value` or just `value`. Values written as a string literal have
+    /// their quotes removed and their escapes replaced.
+    pub values: Vec<(Option<String>, String)>,
+    pub span: Span,
+}
+
+impl Attributes {
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    /// Returns the attribute called `name`, if there is one.
+    pub fn get(&self, name: &str) -> Option<&Attribute> {
+        self.list.iter().find(|a| a.name == name)
+    }
+
+    /// Returns `Some` with the message given to `@deprecated`, which is empty
+    /// if there isn't one, if the item is deprecated.
+    pub fn deprecated(&self) -> Option<&str> {
+        let attr = self.get("deprecated")?;
+        Some(attr.values.first().map_or("", |(_, msg)| msg.as_str()))
+    }
+
+    /// Returns the version given to `@since`, if there is one.
+    pub fn since(&self) -> Option<&str> {
+        self.get("since")?.args.as_deref()
+    }
+}
+
+impl Attribute {
+    /// Returns the arguments of an attribute written like
+    /// `@name(key = "value", other = "value")` as `(key, value)` pairs. An
+    /// argument without a key is returned as the key, with an empty value.
+    pub fn key_values(&self) -> Vec<(&str, &str)> {
+        self.values
+            .iter()
+            .map(|(key, value)| match key {
+                Some(key) => (key.as_str(), value.as_str()),
+                None => (value.as_str(), ""),
+            })
+            .collect()
+    }
+}
+
 #[derive(Debug)]
 pub struct Resource {
     pub docs: Docs,
+    pub attrs: Attributes,
     pub name: String,
+    pub span: Span,
     /// `None` if this resource is defined within the containing instance,
     /// otherwise `Some` if it's defined in an instance named here.
     pub foreign_module: Option<String>,
@@ -240,8 +399,23 @@ pub struct Resource {
 #[derive(Debug)]
 pub struct Global {
     pub docs: Docs,
+    pub attrs: Attributes,
     pub name: String,
     pub ty: Type,
+    pub span: Span,
+}
+
+/// A named constant, declared with `const name: type = value`.
+#[derive(Debug)]
+pub struct Constant {
+    pub docs: Docs,
+    pub attrs: Attributes,
+    pub name: String,
+    /// The type of the constant, which is an integer type or an alias of one.
+    pub ty: Type,
+    /// The value of the constant, which is always within the range of `ty`.
+    pub value: i128,
+    pub span: Span,
 }
 
 #[derive(Debug)]
@@ -249,10 +423,12 @@ pub struct Function {
     pub abi: abi::Abi,
     pub is_async: bool,
     pub docs: Docs,
+    pub attrs: Attributes,
     pub name: String,
     pub kind: FunctionKind,
     pub params: Vec<(String, Type)>,
     pub results: Vec<(String, Type)>,
+    pub span: Span,
 }
 
 #[derive(Debug)]
@@ -306,12 +482,23 @@ impl Interface {
         })
     }
 
+    /// Parses the `*.wit` file at `path`, loading the interfaces named in its
+    /// `use` items from the directories returned by `search_path`.
     pub fn parse_file(path: impl AsRef<Path>) -> Result<Interface> {
+        Interface::parse_file_with_include_dirs(path, &[])
+    }
+
+    /// Parses the `*.wit` file at `path` like `parse_file`, additionally
+    /// searching `include_dirs` for the interfaces named in `use` items.
+    pub fn parse_file_with_include_dirs(
+        path: impl AsRef<Path>,
+        include_dirs: &[PathBuf],
+    ) -> Result<Interface> {
         let path = path.as_ref();
-        let parent = path.parent().unwrap();
+        let dirs = search_path(path, include_dirs);
         let contents = std::fs::read_to_string(&path)
             .with_context(|| format!("failed to read: {}", path.display()))?;
-        Interface::parse_with(path, &contents, |path| load_fs(parent, path))
+        Interface::parse_with(path, &contents, |name| load_fs(&dirs, name))
     }
 
     pub fn parse_with(
@@ -336,6 +523,7 @@ impl Interface {
         map: &mut HashMap<String, Interface>,
     ) -> Result<Interface> {
         let mut name = filename.file_stem().unwrap();
+        let original = contents;
         let mut contents = contents;
 
         // If we have a ".md" file, it's a wit file wrapped in a markdown file;
@@ -350,43 +538,47 @@ impl Interface {
         }
 
         // Parse the `contents `into an AST
-        let ast = match ast::Ast::parse(contents) {
-            Ok(ast) => ast,
-            Err(mut e) => {
-                let file = filename.display().to_string();
-                ast::rewrite_error(&mut e, &file, contents);
-                return Err(e);
-            }
-        };
+        let ast = ast::Ast::parse(contents)
+            .map_err(|e| ast::rewrite_error(e, filename, contents, original))?;
 
         // Load up any modules into our `map` that have not yet been parsed.
         if !visiting.insert(filename.to_path_buf()) {
             bail!("file `{}` recursively imports itself", filename.display())
         }
-        for item in ast.items.iter() {
-            let u = match item {
-                ast::Item::Use(u) => u,
-                _ => continue,
-            };
-            if map.contains_key(&*u.from[0].name) {
+        for dep in ast.external_uses() {
+            if map.contains_key(&*dep.name) {
                 continue;
             }
-            let (filename, contents) = load(&u.from[0].name)
-                // TODO: insert context here about `u.name.span` and `filename`
-                ?;
+            let (filename, contents) = load(&dep.name).map_err(|e| {
+                ast::rewrite_error(ast::load_error(dep, e), filename, contents, original)
+            })?;
             let instance = Interface::_parse_with(&filename, &contents, load, visiting, map)?;
-            map.insert(u.from[0].name.to_string(), instance);
+            map.insert(dep.name.to_string(), instance);
         }
         visiting.remove(filename);
 
         // and finally resolve everything into our final instance
-        match ast.resolve(name.to_str().unwrap(), map) {
-            Ok(i) => Ok(i),
-            Err(mut e) => {
-                let file = filename.display().to_string();
-                ast::rewrite_error(&mut e, &file, contents);
-                Err(e)
-            }
+        ast.resolve(name.to_str().unwrap(), filename, contents, map)
+            .map_err(|e| ast::rewrite_error(e, filename, contents, original))
+    }
+
+    /// Returns the name of the core wasm module that this interface's
+    /// functions are imported from.
+    pub fn module(&self) -> &str {
+        self.module.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Returns the name of the core wasm export which implements the function
+    /// `name`.
+    ///
+    /// Functions of top-level interfaces are exported under their own name,
+    /// while those of nested interfaces are prefixed with the interface's
+    /// module, as in `outer::inner#func`, so they can't clash with the
+    /// functions of other interfaces.
+    pub fn export_name<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        match &self.module {
+            Some(module) => format!("{}#{}", module, name).into(),
+            None => name.into(),
         }
     }
 
@@ -488,14 +680,120 @@ impl Interface {
             None
         }
     }
+
+    /// Renders `ty` as it would be written in a `*.wit` file, referring to
+    /// named types by their name.
+    pub fn type_string(&self, ty: &Type) -> String {
+        let id = match ty {
+            Type::U8 => return "u8".to_string(),
+            Type::U16 => return "u16".to_string(),
+            Type::U32 => return "u32".to_string(),
+            Type::U64 => return "u64".to_string(),
+            Type::S8 => return "s8".to_string(),
+            Type::S16 => return "s16".to_string(),
+            Type::S32 => return "s32".to_string(),
+            Type::S64 => return "s64".to_string(),
+            Type::F32 => return "f32".to_string(),
+            Type::F64 => return "f64".to_string(),
+            Type::Char => return "char".to_string(),
+            Type::CChar => return "c-char".to_string(),
+            Type::Usize => return "usize".to_string(),
+            Type::Handle(r) => return format!("handle {}", self.resources[*r].name),
+            Type::Id(id) => *id,
+        };
+        match &self.types[id].name {
+            Some(name) => name.clone(),
+            None => self.typedef_string(id),
+        }
+    }
+
+    /// Renders the definition of the type `id`, even if it has a name, as it
+    /// would be written in a `*.wit` file. Records and variants are
+    /// abbreviated to `record { ... }` and `variant { ... }`.
+    pub fn typedef_string(&self, id: TypeId) -> String {
+        match &self.types[id].kind {
+            TypeDefKind::Type(t) => self.type_string(t),
+            TypeDefKind::List(Type::Char) => "string".to_string(),
+            TypeDefKind::List(t) => format!("list<{}>", self.type_string(t)),
+            TypeDefKind::PushBuffer(t) => format!("push-buffer<{}>", self.type_string(t)),
+            TypeDefKind::PullBuffer(t) => format!("pull-buffer<{}>", self.type_string(t)),
+            TypeDefKind::Pointer(t) => format!("*mut {}", self.type_string(t)),
+            TypeDefKind::ConstPointer(t) => format!("*const {}", self.type_string(t)),
+            TypeDefKind::Record(r) if r.is_tuple() => {
+                let fields = r
+                    .fields
+                    .iter()
+                    .map(|f| self.type_string(&f.ty))
+                    .collect::<Vec<_>>();
+                format!("tuple<{}>", fields.join(", "))
+            }
+            TypeDefKind::Record(_) => "record { ... }".to_string(),
+            TypeDefKind::Variant(v) => {
+                if v.is_bool() {
+                    "bool".to_string()
+                } else if let Some(t) = v.as_option() {
+                    format!("option<{}>", self.type_string(t))
+                } else if let Some((ok, err)) = v.as_expected() {
+                    let ok = ok.map_or("_".to_string(), |t| self.type_string(t));
+                    let err = err.map_or("_".to_string(), |t| self.type_string(t));
+                    format!("expected<{}, {}>", ok, err)
+                } else {
+                    "variant { ... }".to_string()
+                }
+            }
+        }
+    }
 }
 
-fn load_fs(root: &Path, name: &str) -> Result<(PathBuf, String)> {
-    // TODO: only read one, not both
-    let wit = root.join(name).with_extension("wit");
-    let witx = root.join(name).with_extension("witx");
-    let contents = fs::read_to_string(&wit)
-        .or_else(|_| fs::read_to_string(&witx))
-        .context(format!("failed to read `{}`", wit.display()))?;
-    Ok((wit, contents))
+/// The environment variable listing directories to search for the interfaces
+/// named in `use` items, in addition to those given to
+/// `Interface::parse_file_with_include_dirs`. It's separated like `PATH`.
+pub const WIT_PATH: &str = "WIT_PATH";
+
+/// Returns the directories that `Interface::parse_file` searches, in order,
+/// for the interfaces used by the file at `path`: the directory containing
+/// `path`, then `include_dirs`, then the directories listed in `WIT_PATH`.
+pub fn search_path(path: &Path, include_dirs: &[PathBuf]) -> Vec<PathBuf> {
+    let mut dirs = vec![path.parent().unwrap_or_else(|| Path::new("")).to_path_buf()];
+    dirs.extend(include_dirs.iter().cloned());
+    if let Some(paths) = std::env::var_os(WIT_PATH) {
+        dirs.extend(std::env::split_paths(&paths).filter(|p| !p.as_os_str().is_empty()));
+    }
+    dirs
+}
+
+/// Returns the files that the interface `name` of a `use` item may be loaded
+/// from, in the order they should be tried.
+///
+/// A dotted name such as `dir.module` refers to `dir/module.wit` within one
+/// of `dirs`, and for each directory `*.wit` files are preferred over
+/// `*.witx` files.
+pub fn interface_paths(dirs: &[PathBuf], name: &str) -> Vec<PathBuf> {
+    let relative = name.split('.').collect::<PathBuf>();
+    let mut paths = Vec::new();
+    for dir in dirs {
+        for ext in ["wit", "witx"].iter() {
+            let mut path = dir.join(&relative).into_os_string();
+            path.push(".");
+            path.push(ext);
+            paths.push(path.into());
+        }
+    }
+    paths
+}
+
+fn load_fs(dirs: &[PathBuf], name: &str) -> Result<(PathBuf, String)> {
+    let paths = interface_paths(dirs, name);
+    for path in paths.iter() {
+        match fs::read_to_string(path) {
+            Ok(contents) => return Ok((path.clone(), contents)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e).context(format!("failed to read `{}`", path.display())),
+        }
+    }
+    let mut msg = format!("interface `{}` not found, tried:", name);
+    for path in paths.iter() {
+        msg.push_str(&format!("\n    {}", path.display()));
+    }
+    bail!("{}", msg)
 }
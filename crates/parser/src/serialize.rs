@@ -0,0 +1,597 @@
+//! Serialization of resolved interfaces, enabled with the `serde` feature.
+//!
+//! Rather than mirroring the in-memory representation, interfaces serialize
+//! to a document with a stable schema meant for consumption by tools outside
+//! of Rust. Its general shape, in JSON, is:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "name": "my-interface",
+//!   "resources": [{ "name": "file" }],
+//!   "types": [{ "index": 0, "name": "point", "kind": "record", "fields": [...] }],
+//!   "functions": [{ "name": "f", "params": [...], "results": [...], "abi": {...} }],
+//!   "globals": [{ "name": "g", "type": "u32" }],
+//!   "constants": [{ "name": "c", "type": "u32", "value": 1 }],
+//!   "interfaces": [{ "name": "nested", "types": [...], ... }]
+//! }
+//! ```
+//!
+//! Wherever a type is referred to it's either a string naming a builtin type,
+//! such as `"u32"`, `{ "handle": "<resource>" }` for a handle to a resource,
+//! or `{ "type": <index> }` for a type in the `types` of the enclosing
+//! interface, which also includes `"name"` if the type is named.
+//!
+//! Types, functions and globals also include their canonical ABI details for
+//! both the `guest-import` and `guest-export` variants: the size and
+//! alignment of types, and the core wasm signature of functions. Items list
+//! their attributes in `attributes`, such as
+//! `{ "name": "since", "args": "1.2" }` for `@since(1.2)`. Fields which would
+//! be empty or absent, such as `docs` or `attributes`, are omitted.
+//!
+//! Where each item is declared, in `span`, is only included when serializing
+//! through [`WithSpans`] since it names files by the paths they were parsed
+//! from, which depend on where the interface was loaded.
+
+use crate::abi::{Abi, AbiVariant, WasmSignature, WasmType};
+use crate::{FunctionKind, Int, RecordKind, SizeAlign, Span, TypeDefKind};
+use serde::{Serialize, Serializer};
+
+/// The version of the serialized schema, which is bumped whenever it changes
+/// in a way that isn't backwards compatible.
+pub const SERIALIZATION_VERSION: u32 = 1;
+
+impl Serialize for crate::Interface {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut iface = Interface::new(self, false);
+        iface.version = Some(SERIALIZATION_VERSION);
+        iface.serialize(serializer)
+    }
+}
+
+/// Serializes an interface like its `Serialize` implementation, additionally
+/// including the `span` of each item as `{ "file", "line", "column" }`.
+pub struct WithSpans<'a>(pub &'a crate::Interface);
+
+impl Serialize for WithSpans<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut iface = Interface::new(self.0, true);
+        iface.version = Some(SERIALIZATION_VERSION);
+        iface.serialize(serializer)
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Interface<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<u32>,
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    docs: Option<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    attributes: Vec<Attribute<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    span: Option<Location>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    module: Option<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    resources: Vec<Resource<'a>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    types: Vec<TypeDef<'a>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    functions: Vec<Function<'a>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    globals: Vec<Global<'a>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    constants: Vec<Constant<'a>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    interfaces: Vec<Interface<'a>>,
+}
+
+#[derive(Serialize)]
+struct Attribute<'a> {
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    args: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct Location {
+    file: String,
+    line: u32,
+    column: u32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Resource<'a> {
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    docs: Option<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    attributes: Vec<Attribute<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    span: Option<Location>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    foreign_module: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct TypeDef<'a> {
+    index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    docs: Option<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    attributes: Vec<Attribute<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    span: Option<Location>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    foreign_module: Option<&'a str>,
+    #[serde(flatten)]
+    kind: TypeKind<'a>,
+    abi: PerVariant<Layout>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+enum TypeKind<'a> {
+    Record {
+        fields: Vec<Field<'a>>,
+    },
+    Flags {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        repr: Option<&'static str>,
+        fields: Vec<Field<'a>>,
+    },
+    Tuple {
+        fields: Vec<Field<'a>>,
+    },
+    Variant {
+        tag: &'static str,
+        cases: Vec<Case<'a>>,
+    },
+    List {
+        element: TypeRef<'a>,
+    },
+    Pointer {
+        element: TypeRef<'a>,
+    },
+    ConstPointer {
+        element: TypeRef<'a>,
+    },
+    PushBuffer {
+        element: TypeRef<'a>,
+    },
+    PullBuffer {
+        element: TypeRef<'a>,
+    },
+    Alias {
+        #[serde(rename = "type")]
+        ty: TypeRef<'a>,
+    },
+}
+
+#[derive(Serialize)]
+struct Field<'a> {
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    docs: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    span: Option<Location>,
+    #[serde(rename = "type")]
+    ty: TypeRef<'a>,
+}
+
+#[derive(Serialize)]
+struct Case<'a> {
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    docs: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    span: Option<Location>,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    ty: Option<TypeRef<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum TypeRef<'a> {
+    Builtin(&'static str),
+    Handle {
+        handle: &'a str,
+    },
+    Id {
+        #[serde(rename = "type")]
+        index: usize,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        name: Option<&'a str>,
+    },
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Function<'a> {
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    docs: Option<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    attributes: Vec<Attribute<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    span: Option<Location>,
+    #[serde(rename = "async", skip_serializing_if = "std::ops::Not::not")]
+    is_async: bool,
+    #[serde(flatten)]
+    kind: FunctionKindRef<'a>,
+    params: Vec<Param<'a>>,
+    results: Vec<Param<'a>>,
+    abi: FunctionAbi,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+enum FunctionKindRef<'a> {
+    Freestanding,
+    Static { resource: &'a str },
+    Method { resource: &'a str },
+}
+
+#[derive(Serialize)]
+struct Param<'a> {
+    name: &'a str,
+    #[serde(rename = "type")]
+    ty: TypeRef<'a>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct FunctionAbi {
+    abi: &'static str,
+    #[serde(flatten)]
+    signatures: PerVariant<Signature>,
+}
+
+#[derive(Serialize)]
+struct Signature {
+    params: Vec<&'static str>,
+    results: Vec<&'static str>,
+    /// The types stored at the return pointer, if one is used.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retptr: Option<Vec<&'static str>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Global<'a> {
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    docs: Option<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    attributes: Vec<Attribute<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    span: Option<Location>,
+    #[serde(rename = "type")]
+    ty: TypeRef<'a>,
+    abi: PerVariant<Layout>,
+}
+
+#[derive(Serialize)]
+struct Constant<'a> {
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    docs: Option<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    attributes: Vec<Attribute<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    span: Option<Location>,
+    #[serde(rename = "type")]
+    ty: TypeRef<'a>,
+    value: ConstantValue,
+}
+
+/// The value of a constant, which is serialized as a 64-bit integer of the
+/// constant's signedness since not every format supports 128-bit integers.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum ConstantValue {
+    Signed(i64),
+    Unsigned(u64),
+}
+
+#[derive(Serialize)]
+struct Layout {
+    size: usize,
+    align: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct PerVariant<T> {
+    guest_import: T,
+    guest_export: T,
+}
+
+/// The sizes of types in both ABI variants.
+struct Sizes {
+    import: SizeAlign,
+    export: SizeAlign,
+}
+
+impl Sizes {
+    fn layout(&self, ty: &crate::Type) -> PerVariant<Layout> {
+        PerVariant {
+            guest_import: Layout {
+                size: self.import.size(ty),
+                align: self.import.align(ty),
+            },
+            guest_export: Layout {
+                size: self.export.size(ty),
+                align: self.export.align(ty),
+            },
+        }
+    }
+}
+
+impl<'a> Interface<'a> {
+    fn new(iface: &'a crate::Interface, spans: bool) -> Interface<'a> {
+        let mut sizes = Sizes {
+            import: SizeAlign::default(),
+            export: SizeAlign::default(),
+        };
+        sizes.import.fill(AbiVariant::GuestImport, iface);
+        sizes.export.fill(AbiVariant::GuestExport, iface);
+
+        Interface {
+            version: None,
+            name: &iface.name,
+            docs: docs(&iface.docs),
+            attributes: attributes(&iface.attrs),
+            span: location(&iface.span, spans),
+            module: iface.module.as_deref(),
+            resources: iface
+                .resources
+                .iter()
+                .map(|(_, r)| Resource {
+                    name: &r.name,
+                    docs: docs(&r.docs),
+                    attributes: attributes(&r.attrs),
+                    span: location(&r.span, spans),
+                    foreign_module: r.foreign_module.as_deref(),
+                })
+                .collect(),
+            types: iface
+                .types
+                .iter()
+                .map(|(id, ty)| TypeDef {
+                    index: id.index(),
+                    name: ty.name.as_deref(),
+                    docs: docs(&ty.docs),
+                    attributes: attributes(&ty.attrs),
+                    span: location(&ty.span, spans),
+                    foreign_module: ty.foreign_module.as_deref(),
+                    kind: type_kind(iface, &ty.kind, spans),
+                    abi: sizes.layout(&crate::Type::Id(id)),
+                })
+                .collect(),
+            functions: iface
+                .functions
+                .iter()
+                .map(|f| Function {
+                    name: &f.name,
+                    docs: docs(&f.docs),
+                    attributes: attributes(&f.attrs),
+                    span: location(&f.span, spans),
+                    is_async: f.is_async,
+                    kind: match &f.kind {
+                        FunctionKind::Freestanding => FunctionKindRef::Freestanding,
+                        FunctionKind::Static { resource, .. } => FunctionKindRef::Static {
+                            resource: &iface.resources[*resource].name,
+                        },
+                        FunctionKind::Method { resource, .. } => FunctionKindRef::Method {
+                            resource: &iface.resources[*resource].name,
+                        },
+                    },
+                    params: params(iface, &f.params),
+                    results: params(iface, &f.results),
+                    abi: FunctionAbi {
+                        abi: match f.abi {
+                            Abi::Preview1 => "preview1",
+                            Abi::Canonical => "canonical",
+                        },
+                        signatures: PerVariant {
+                            guest_import: signature(
+                                iface.wasm_signature(AbiVariant::GuestImport, f),
+                            ),
+                            guest_export: signature(
+                                iface.wasm_signature(AbiVariant::GuestExport, f),
+                            ),
+                        },
+                    },
+                })
+                .collect(),
+            globals: iface
+                .globals
+                .iter()
+                .map(|g| Global {
+                    name: &g.name,
+                    docs: docs(&g.docs),
+                    attributes: attributes(&g.attrs),
+                    span: location(&g.span, spans),
+                    ty: type_ref(iface, &g.ty),
+                    abi: sizes.layout(&g.ty),
+                })
+                .collect(),
+            constants: iface
+                .constants
+                .iter()
+                .map(|c| Constant {
+                    name: &c.name,
+                    docs: docs(&c.docs),
+                    attributes: attributes(&c.attrs),
+                    span: location(&c.span, spans),
+                    ty: type_ref(iface, &c.ty),
+                    value: constant_value(iface, &c.ty, c.value),
+                })
+                .collect(),
+            interfaces: iface
+                .interfaces
+                .iter()
+                .map(|(_, i)| Interface::new(i, spans))
+                .collect(),
+        }
+    }
+}
+
+fn docs(docs: &crate::Docs) -> Option<&str> {
+    docs.contents.as_deref()
+}
+
+fn attributes(attrs: &crate::Attributes) -> Vec<Attribute<'_>> {
+    attrs
+        .list
+        .iter()
+        .map(|a| Attribute {
+            name: &a.name,
+            args: a.args.as_deref(),
+        })
+        .collect()
+}
+
+fn location(span: &Span, spans: bool) -> Option<Location> {
+    if !spans {
+        return None;
+    }
+    let file = span.file.as_ref()?;
+    Some(Location {
+        file: file.display().to_string(),
+        line: span.line,
+        column: span.column,
+    })
+}
+
+fn type_kind<'a>(iface: &'a crate::Interface, kind: &'a TypeDefKind, spans: bool) -> TypeKind<'a> {
+    match kind {
+        TypeDefKind::Record(r) => {
+            let fields = r
+                .fields
+                .iter()
+                .map(|f| Field {
+                    name: &f.name,
+                    docs: docs(&f.docs),
+                    span: location(&f.span, spans),
+                    ty: type_ref(iface, &f.ty),
+                })
+                .collect();
+            match r.kind {
+                RecordKind::Other => TypeKind::Record { fields },
+                RecordKind::Flags(repr) => TypeKind::Flags {
+                    repr: repr.map(int),
+                    fields,
+                },
+                RecordKind::Tuple => TypeKind::Tuple { fields },
+            }
+        }
+        TypeDefKind::Variant(v) => TypeKind::Variant {
+            tag: int(v.tag),
+            cases: v
+                .cases
+                .iter()
+                .map(|c| Case {
+                    name: &c.name,
+                    docs: docs(&c.docs),
+                    span: location(&c.span, spans),
+                    ty: c.ty.as_ref().map(|ty| type_ref(iface, ty)),
+                })
+                .collect(),
+        },
+        TypeDefKind::List(t) => TypeKind::List {
+            element: type_ref(iface, t),
+        },
+        TypeDefKind::Pointer(t) => TypeKind::Pointer {
+            element: type_ref(iface, t),
+        },
+        TypeDefKind::ConstPointer(t) => TypeKind::ConstPointer {
+            element: type_ref(iface, t),
+        },
+        TypeDefKind::PushBuffer(t) => TypeKind::PushBuffer {
+            element: type_ref(iface, t),
+        },
+        TypeDefKind::PullBuffer(t) => TypeKind::PullBuffer {
+            element: type_ref(iface, t),
+        },
+        TypeDefKind::Type(t) => TypeKind::Alias {
+            ty: type_ref(iface, t),
+        },
+    }
+}
+
+fn type_ref<'a>(iface: &'a crate::Interface, ty: &crate::Type) -> TypeRef<'a> {
+    use crate::Type;
+    match ty {
+        Type::U8 => TypeRef::Builtin("u8"),
+        Type::U16 => TypeRef::Builtin("u16"),
+        Type::U32 => TypeRef::Builtin("u32"),
+        Type::U64 => TypeRef::Builtin("u64"),
+        Type::S8 => TypeRef::Builtin("s8"),
+        Type::S16 => TypeRef::Builtin("s16"),
+        Type::S32 => TypeRef::Builtin("s32"),
+        Type::S64 => TypeRef::Builtin("s64"),
+        Type::F32 => TypeRef::Builtin("f32"),
+        Type::F64 => TypeRef::Builtin("f64"),
+        Type::Char => TypeRef::Builtin("char"),
+        Type::CChar => TypeRef::Builtin("c-char"),
+        Type::Usize => TypeRef::Builtin("usize"),
+        Type::Handle(r) => TypeRef::Handle {
+            handle: &iface.resources[*r].name,
+        },
+        Type::Id(id) => TypeRef::Id {
+            index: id.index(),
+            name: iface.types[*id].name.as_deref(),
+        },
+    }
+}
+
+/// Converts `value`, which the resolver has checked is in range for `ty`, to the
+/// 64-bit integer of `ty`'s signedness.
+fn constant_value(iface: &crate::Interface, ty: &crate::Type, value: i128) -> ConstantValue {
+    use crate::Type;
+    match ty {
+        Type::S8 | Type::S16 | Type::S32 | Type::S64 => ConstantValue::Signed(value as i64),
+        Type::Id(id) => match &iface.types[*id].kind {
+            TypeDefKind::Type(ty) => constant_value(iface, ty, value),
+            _ => unreachable!("constants have integer types"),
+        },
+        _ => ConstantValue::Unsigned(value as u64),
+    }
+}
+
+fn params<'a>(iface: &'a crate::Interface, params: &'a [(String, crate::Type)]) -> Vec<Param<'a>> {
+    params
+        .iter()
+        .map(|(name, ty)| Param {
+            name,
+            ty: type_ref(iface, ty),
+        })
+        .collect()
+}
+
+fn signature(sig: WasmSignature) -> Signature {
+    let types = |types: Vec<WasmType>| types.into_iter().map(|ty| ty.as_str()).collect();
+    Signature {
+        params: types(sig.params),
+        results: types(sig.results),
+        retptr: sig.retptr.map(types),
+    }
+}
+
+fn int(int: Int) -> &'static str {
+    match int {
+        Int::U8 => "u8",
+        Int::U16 => "u16",
+        Int::U32 => "u32",
+        Int::U64 => "u64",
+    }
+}
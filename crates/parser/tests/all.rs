@@ -83,6 +83,7 @@ fn find_tests() -> Vec<PathBuf> {
             match f.path().extension().and_then(|s| s.to_str()) {
                 Some("md") => {}
                 Some("wit") => {}
+                Some("witx") if cfg!(feature = "witx-compat") => {}
                 _ => continue,
             }
             tests.push(f.path());
@@ -115,16 +116,51 @@ impl Runner<'_> {
             }
         } else {
             let instance = result?;
+            if test.extension().and_then(|s| s.to_str()) == Some("wit") {
+                self.check_format(test, contents, &instance)?;
+            }
             to_json(&instance)
         };
 
-        let result_file = test.with_extension("wit.result");
+        self.check_expectation(test, &test.with_extension("wit.result"), &result)?;
+        return Ok(());
+    }
+
+    /// Checks that formatting `contents` doesn't change the interface it
+    /// defines and that formatting the result again is a no-op.
+    ///
+    /// If there's a `*.wit.formatted` file next to the test then the
+    /// formatted source is also compared against that.
+    fn check_format(&self, test: &Path, contents: &str, instance: &Interface) -> Result<()> {
+        let formatted = format(test, contents)?;
+        if format(test, &formatted)? != formatted {
+            bail!("formatting is not idempotent, first pass:\n{}", formatted);
+        }
+        let parent = test.parent().unwrap();
+        let reformatted = Interface::parse_with(test, &formatted, |name| {
+            let path = interface_paths(&[parent.to_path_buf()], name).remove(0);
+            let contents = fs::read_to_string(&path)?;
+            Ok((path, contents))
+        })
+        .context("failed to parse formatted source")?;
+        if to_json(&reformatted) != to_json(instance) {
+            bail!("formatting changed the interface:\n{}", formatted);
+        }
+
+        let formatted_file = test.with_extension("wit.formatted");
+        if formatted_file.exists() {
+            self.check_expectation(test, &formatted_file, &formatted)?;
+        }
+        Ok(())
+    }
+
+    fn check_expectation(&self, test: &Path, expected_file: &Path, result: &str) -> Result<()> {
         if env::var_os("BLESS").is_some() {
-            fs::write(&result_file, result)?;
+            fs::write(expected_file, result)?;
         } else {
-            let expected = fs::read_to_string(&result_file).context(format!(
+            let expected = fs::read_to_string(expected_file).context(format!(
                 "failed to read test expectation file {:?}\nthis can be fixed with BLESS=1",
-                result_file
+                expected_file
             ))?;
             let expected = normalize(test, &expected);
             if expected != result {
@@ -136,16 +172,7 @@ impl Runner<'_> {
             }
         }
         self.bump_ntests();
-        return Ok(());
-
-        fn normalize(test: &Path, s: &str) -> String {
-            s.replace(
-                &test.display().to_string(),
-                &test.display().to_string().replace("\\", "/"),
-            )
-            .replace("\\parse-fail\\", "/parse-fail/")
-            .replace("\r\n", "\n")
-        }
+        Ok(())
     }
 
     fn bump_ntests(&self) {
@@ -153,9 +180,20 @@ impl Runner<'_> {
     }
 }
 
+fn normalize(test: &Path, s: &str) -> String {
+    s.replace(
+        &test.display().to_string(),
+        &test.display().to_string().replace("\\", "/"),
+    )
+    .replace("\\parse-fail\\", "/parse-fail/")
+    .replace("\r\n", "\n")
+}
+
 fn to_json(i: &Interface) -> String {
     #[derive(Serialize)]
     struct Interface {
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        interfaces: Vec<NestedInterface>,
         #[serde(skip_serializing_if = "Vec::is_empty")]
         resources: Vec<Resource>,
         #[serde(skip_serializing_if = "Vec::is_empty")]
@@ -164,11 +202,24 @@ fn to_json(i: &Interface) -> String {
         functions: Vec<Function>,
         #[serde(skip_serializing_if = "Vec::is_empty")]
         globals: Vec<Global>,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        constants: Vec<Constant>,
+    }
+
+    #[derive(Serialize)]
+    struct NestedInterface {
+        name: String,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        attrs: Vec<String>,
+        #[serde(flatten)]
+        iface: Interface,
     }
 
     #[derive(Serialize)]
     struct Resource {
         name: String,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        attrs: Vec<String>,
         #[serde(skip_serializing_if = "Option::is_none")]
         foreign_module: Option<String>,
     }
@@ -178,6 +229,8 @@ fn to_json(i: &Interface) -> String {
         idx: usize,
         #[serde(skip_serializing_if = "Option::is_none")]
         name: Option<String>,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        attrs: Vec<String>,
         #[serde(flatten)]
         ty: Type,
         #[serde(skip_serializing_if = "Option::is_none")]
@@ -204,6 +257,8 @@ fn to_json(i: &Interface) -> String {
     #[derive(Serialize)]
     struct Function {
         name: String,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        attrs: Vec<String>,
         #[serde(rename = "async", skip_serializing_if = "Option::is_none")]
         is_async: Option<bool>,
         params: Vec<String>,
@@ -213,54 +268,105 @@ fn to_json(i: &Interface) -> String {
     #[derive(Serialize)]
     struct Global {
         name: String,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        attrs: Vec<String>,
         ty: String,
     }
 
-    let resources = i
-        .resources
-        .iter()
-        .map(|(_, r)| Resource {
-            name: r.name.clone(),
-            foreign_module: r.foreign_module.clone(),
-        })
-        .collect::<Vec<_>>();
+    #[derive(Serialize)]
+    struct Constant {
+        name: String,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        attrs: Vec<String>,
+        ty: String,
+        value: i128,
+    }
 
-    let types = i
-        .types
-        .iter()
-        .map(|(i, r)| TypeDef {
-            idx: i.index(),
-            name: r.name.clone(),
-            ty: translate_typedef(r),
-            foreign_module: r.foreign_module.clone(),
-        })
-        .collect::<Vec<_>>();
-    let functions = i
-        .functions
-        .iter()
-        .map(|f| Function {
-            name: f.name.clone(),
-            is_async: if f.is_async { Some(f.is_async) } else { None },
-            params: f.params.iter().map(|(_, ty)| translate_type(ty)).collect(),
-            results: f.results.iter().map(|(_, ty)| translate_type(ty)).collect(),
-        })
-        .collect::<Vec<_>>();
-    let globals = i
-        .globals
-        .iter()
-        .map(|g| Global {
-            name: g.name.clone(),
-            ty: translate_type(&g.ty),
-        })
-        .collect::<Vec<_>>();
+    return serde_json::to_string_pretty(&translate_interface(i)).unwrap();
+
+    fn translate_interface(i: &wit_parser::Interface) -> Interface {
+        let interfaces = i
+            .interfaces
+            .iter()
+            .map(|(_, i)| NestedInterface {
+                name: i.name.clone(),
+                attrs: translate_attrs(&i.attrs),
+                iface: translate_interface(i),
+            })
+            .collect::<Vec<_>>();
+        let resources = i
+            .resources
+            .iter()
+            .map(|(_, r)| Resource {
+                name: r.name.clone(),
+                attrs: translate_attrs(&r.attrs),
+                foreign_module: r.foreign_module.clone(),
+            })
+            .collect::<Vec<_>>();
 
-    let iface = Interface {
-        resources,
-        types,
-        functions,
-        globals,
-    };
-    return serde_json::to_string_pretty(&iface).unwrap();
+        let types = i
+            .types
+            .iter()
+            .map(|(i, r)| TypeDef {
+                idx: i.index(),
+                name: r.name.clone(),
+                attrs: translate_attrs(&r.attrs),
+                ty: translate_typedef(r),
+                foreign_module: r.foreign_module.clone(),
+            })
+            .collect::<Vec<_>>();
+        let functions = i
+            .functions
+            .iter()
+            .map(|f| Function {
+                name: f.name.clone(),
+                attrs: translate_attrs(&f.attrs),
+                is_async: if f.is_async { Some(f.is_async) } else { None },
+                params: f.params.iter().map(|(_, ty)| translate_type(ty)).collect(),
+                results: f.results.iter().map(|(_, ty)| translate_type(ty)).collect(),
+            })
+            .collect::<Vec<_>>();
+        let globals = i
+            .globals
+            .iter()
+            .map(|g| Global {
+                name: g.name.clone(),
+                attrs: translate_attrs(&g.attrs),
+                ty: translate_type(&g.ty),
+            })
+            .collect::<Vec<_>>();
+
+        let constants = i
+            .constants
+            .iter()
+            .map(|c| Constant {
+                name: c.name.clone(),
+                attrs: translate_attrs(&c.attrs),
+                ty: translate_type(&c.ty),
+                value: c.value,
+            })
+            .collect::<Vec<_>>();
+
+        Interface {
+            interfaces,
+            resources,
+            types,
+            functions,
+            globals,
+            constants,
+        }
+    }
+
+    fn translate_attrs(attrs: &wit_parser::Attributes) -> Vec<String> {
+        attrs
+            .list
+            .iter()
+            .map(|a| match &a.args {
+                Some(args) => format!("@{}({})", a.name, args),
+                None => format!("@{}", a.name),
+            })
+            .collect()
+    }
 
     fn translate_typedef(ty: &wit_parser::TypeDef) -> Type {
         match &ty.kind {
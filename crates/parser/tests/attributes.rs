@@ -0,0 +1,46 @@
+use wit_parser::Interface;
+
+#[test]
+fn deprecated_message_is_unescaped() {
+    let iface =
+        Interface::parse("foo", r#"@deprecated("use \"g\"\nor\\ h") f: function()"#).unwrap();
+    let attrs = &iface.functions[0].attrs;
+    assert_eq!(attrs.deprecated(), Some("use \"g\"\nor\\ h"));
+    assert_eq!(
+        attrs.get("deprecated").unwrap().args.as_deref(),
+        Some(r#""use \"g\"\nor\\ h""#)
+    );
+}
+
+#[test]
+fn deprecated_without_message() {
+    let iface = Interface::parse("foo", "@deprecated f: function()").unwrap();
+    assert_eq!(iface.functions[0].attrs.deprecated(), Some(""));
+}
+
+#[test]
+fn deprecated_requires_a_single_string() {
+    for args in ["x", "\"a\", \"b\"", "msg = \"a\"", ""] {
+        let wit = format!("@deprecated({}) f: function()", args);
+        assert!(Interface::parse("foo", &wit).is_err(), "{}", wit);
+    }
+}
+
+#[test]
+fn key_values() {
+    let iface = Interface::parse(
+        "foo",
+        r#"@rust(derive = "A\", b", flag, nested = (x, y), derive="C,D") f: function()"#,
+    )
+    .unwrap();
+    let attr = iface.functions[0].attrs.get("rust").unwrap();
+    assert_eq!(
+        attr.key_values(),
+        [
+            ("derive", "A\", b"),
+            ("flag", ""),
+            ("nested", "(x, y)"),
+            ("derive", "C,D"),
+        ]
+    );
+}
@@ -0,0 +1,191 @@
+use wit_parser::compat::{compare, ChangeKind, Compatibility, ItemKind};
+use wit_parser::Interface;
+
+/// Compares `old` and `new`, rendering each change as
+/// `<compatibility> <kind> <item>: <message>`.
+fn changes(old: &str, new: &str) -> Vec<String> {
+    let old = Interface::parse("foo", old).unwrap();
+    let new = Interface::parse("foo", new).unwrap();
+    compare(&old, &new)
+        .iter()
+        .map(|c| {
+            format!(
+                "{} {} {}: {}",
+                c.compatibility.as_str(),
+                c.kind.as_str(),
+                c.item,
+                c.message
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn identical() {
+    let wit = "
+        /// docs
+        record point { x: u32, y: u32 }
+        resource file { read: function() -> list<u8> }
+        f: function(p: point) -> option<file>
+        g: u32
+        const c: u8 = 1
+    ";
+    assert_eq!(changes(wit, wit), Vec::<String>::new());
+    // Documentation and attributes don't matter.
+    let documented = "
+        @since(1.2)
+        record point { x: u32, y: u32 }
+        resource file {
+            /// Reads the whole file.
+            @deprecated
+            read: function() -> list<u8>
+        }
+        /// Does things.
+        f: function(p: point) -> option<file>
+        g: u32
+        const c: u8 = 1
+    ";
+    assert_eq!(changes(wit, documented), Vec::<String>::new());
+}
+
+#[test]
+fn functions() {
+    assert_eq!(
+        changes(
+            "f: function()\ng: function()",
+            "f: function()\nh: function()"
+        ),
+        [
+            "breaking removed g: function `g` was removed",
+            "compatible added h: function `h` was added",
+        ]
+    );
+    assert_eq!(
+        changes("f: function(a: u32)", "f: function(b: u32)"),
+        ["compatible changed f: parameter `a` was renamed to `b`"]
+    );
+    assert_eq!(
+        changes("f: function(a: u32)", "f: function(a: s32)"),
+        ["breaking changed f: type of parameter `a` changed from `u32` to `s32`"]
+    );
+    assert_eq!(
+        changes("f: function(a: u32)", "f: function(a: u64)"),
+        [
+            "breaking changed f: type of parameter `a` changed from `u32` to `u64`",
+            "breaking abi-changed f: core wasm signature changed from `(i32)` to `(i64)`",
+        ]
+    );
+    assert_eq!(
+        changes("f: function() -> u32", "f: function() -> (u32, u32)"),
+        [
+            "breaking changed f: number of results changed from 1 to 2",
+            "breaking abi-changed f: core wasm signature changed from `() -> i32` to `(i32)`",
+        ]
+    );
+    // Aliases are transparent.
+    assert_eq!(
+        changes("type fd = u32\nf: function(a: fd)", "f: function(a: u32)"),
+        ["breaking removed fd: type `fd` was removed"]
+    );
+}
+
+#[test]
+fn records() {
+    assert_eq!(
+        changes("record r { a: u32, b: u32 }", "record r { a: u64, c: u32 }"),
+        [
+            "breaking changed r.a: type changed from `u32` to `u64`",
+            "breaking removed r.b: field `r.b` was removed",
+            "breaking added r.c: field `r.c` was added",
+        ]
+    );
+    assert_eq!(
+        changes("record r { a: u8, b: u32 }", "record r { b: u32, a: u8 }"),
+        [
+            "breaking layout-changed r.a: moved from offset 0 to offset 4",
+            "breaking layout-changed r.b: moved from offset 4 to offset 0",
+        ]
+    );
+    assert_eq!(
+        changes("record r { a: u32, b: u32 }", "record r { c: u32, a: u32 }"),
+        [
+            "breaking removed r.b: field `r.b` was removed",
+            "breaking added r.c: field `r.c` was added",
+        ]
+    );
+    assert_eq!(
+        changes("flags f { a, b }", "flags f { b, a }"),
+        [
+            "breaking layout-changed f.a: moved from bit 0 to bit 1",
+            "breaking layout-changed f.b: moved from bit 1 to bit 0",
+        ]
+    );
+    assert_eq!(
+        changes("record r { a: u32 }", "variant r { a(u32) }"),
+        ["breaking changed r: changed from a record to a variant"]
+    );
+}
+
+#[test]
+fn variants() {
+    assert_eq!(
+        changes("variant v { a(u32), b, c }", "variant v { a(u64), c, d }"),
+        [
+            "breaking changed v.a: payload changed from `u32` to `u64`",
+            "breaking removed v.b: case `v.b` was removed",
+            "breaking reordered v.c: discriminant changed from 2 to 1",
+            "breaking added v.d: case `v.d` was added",
+        ]
+    );
+    assert_eq!(
+        changes("enum e { a, b }", "enum e { a, b, c }"),
+        ["breaking added e.c: case `e.c` was added"]
+    );
+}
+
+#[test]
+fn other_items() {
+    assert_eq!(
+        changes(
+            "resource r\ng: u32\nconst c: u8 = 1",
+            "resource s\ng: u64\nconst c: u8 = 2\nconst d: u8 = 3"
+        ),
+        [
+            "breaking removed r: resource `r` was removed",
+            "compatible added s: resource `s` was added",
+            "breaking changed g: type changed from `u32` to `u64`",
+            "breaking changed c: value changed from 1 to 2",
+            "compatible added d: constant `d` was added",
+        ]
+    );
+    assert_eq!(
+        changes(
+            "resource r { f: function() }",
+            "resource r { f: function()\nstatic g: function() }"
+        ),
+        ["compatible added r::g: function `r::g` was added"]
+    );
+    assert_eq!(
+        changes(
+            "interface nested { f: function(a: u32) }",
+            "interface nested { f: function(a: string) }"
+        ),
+        [
+            "breaking changed nested/f: type of parameter `a` changed from `u32` to `string`",
+            "breaking abi-changed nested/f: core wasm signature changed from `(i32)` to `(i32, i32)`",
+        ]
+    );
+}
+
+#[test]
+fn classification() {
+    let old = Interface::parse("foo", "record r { a: u32 }\nf: function()").unwrap();
+    let new = Interface::parse("foo", "record r { a: u32 }\nf: function()\ng: function()").unwrap();
+    let changes = compare(&old, &new);
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].item_kind, ItemKind::Function);
+    assert_eq!(changes[0].kind, ChangeKind::Added);
+    assert_eq!(changes[0].compatibility, Compatibility::Compatible);
+    assert!(!changes[0].is_breaking());
+    assert_eq!(changes[0].span.line, 3);
+}
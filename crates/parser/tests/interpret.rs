@@ -0,0 +1,308 @@
+use wit_parser::abi::AbiVariant;
+use wit_parser::interpret::{call_interface, call_wasm, LinearMemory, Memory, Value, WasmValue};
+use wit_parser::Interface;
+
+/// Calls `name` in `wit` with `args` through both halves of the ABI in each
+/// variant, with `results` being returned by the implementation, and asserts
+/// that both arguments and results make it across unchanged.
+fn round_trip(wit: &str, name: &str, args: &[Value], results: &[Value]) {
+    let iface = Interface::parse("foo", wit).unwrap();
+    let func = iface.functions.iter().find(|f| f.name == name).unwrap();
+    for variant in [AbiVariant::GuestImport, AbiVariant::GuestExport] {
+        let mut memory = Memory::new();
+        let actual = call_wasm(&iface, variant, func, &mut memory, args, |memory, wasm| {
+            call_interface(&iface, variant, func, memory, wasm, |actual| {
+                assert_eq!(actual, args, "arguments of {:?}", variant);
+                Ok(results.to_vec())
+            })
+        })
+        .unwrap();
+        assert_eq!(actual, results, "results of {:?}", variant);
+    }
+}
+
+fn some(v: Value) -> Value {
+    Value::Variant {
+        case: 1,
+        payload: Some(Box::new(v)),
+    }
+}
+
+fn none() -> Value {
+    Value::Variant {
+        case: 0,
+        payload: None,
+    }
+}
+
+#[test]
+fn scalars() {
+    round_trip(
+        "f: function(a: u8, b: s8, c: u16, d: s16, e: u32, f: s32) -> (a: u64, b: s64)",
+        "f",
+        &[
+            Value::U8(255),
+            Value::S8(-128),
+            Value::U16(65535),
+            Value::S16(-2),
+            Value::U32(u32::MAX),
+            Value::S32(i32::MIN),
+        ],
+        &[Value::U64(u64::MAX), Value::S64(-1)],
+    );
+    round_trip(
+        "f: function(a: f32, b: f64, c: char, d: bool) -> char",
+        "f",
+        &[
+            Value::F32(1.5),
+            Value::F64(-0.25),
+            Value::Char('☃'),
+            Value::Bool(true),
+        ],
+        &[Value::Char('x')],
+    );
+    round_trip(
+        "resource r\nf: function(a: r) -> r",
+        "f",
+        &[Value::Handle(3)],
+        &[Value::Handle(7)],
+    );
+}
+
+#[test]
+fn records() {
+    let wit = "
+        record point { x: u8, y: f64 }
+        flags small { a, b, c }
+        flags big { b0, b1, b2, b3, b4, b5, b6, b7, b8, b9, b10, b11, b12, b13, b14, b15,
+                    b16, b17, b18, b19, b20, b21, b22, b23, b24, b25, b26, b27, b28, b29,
+                    b30, b31, b32, b33 }
+        f: function(a: point, b: tuple<u32, string>, c: small, d: big) -> point
+    ";
+    let mut big = vec![false; 34];
+    big[0] = true;
+    big[31] = true;
+    big[33] = true;
+    round_trip(
+        wit,
+        "f",
+        &[
+            Value::Record(vec![Value::U8(1), Value::F64(2.0)]),
+            Value::Record(vec![Value::U32(3), Value::String("four".to_string())]),
+            Value::Flags(vec![true, false, true]),
+            Value::Flags(big),
+        ],
+        &[Value::Record(vec![Value::U8(5), Value::F64(6.0)])],
+    );
+}
+
+#[test]
+fn variants() {
+    let wit = "
+        variant v { a(u8), b(f64), c(string), d }
+        enum e { x, y, z }
+        f: function(a: v, b: v, c: option<s64>, d: e) -> expected<list<u8>, v>
+    ";
+    round_trip(
+        wit,
+        "f",
+        &[
+            Value::Variant {
+                case: 1,
+                payload: Some(Box::new(Value::F64(1.0))),
+            },
+            Value::Variant {
+                case: 3,
+                payload: None,
+            },
+            some(Value::S64(-5)),
+            Value::Variant {
+                case: 2,
+                payload: None,
+            },
+        ],
+        &[Value::Variant {
+            case: 1,
+            payload: Some(Box::new(Value::Variant {
+                case: 2,
+                payload: Some(Box::new(Value::String("error".to_string()))),
+            })),
+        }],
+    );
+    round_trip(
+        "f: function(a: option<option<u32>>) -> expected<_, _>",
+        "f",
+        &[some(none())],
+        &[Value::Variant {
+            case: 0,
+            payload: None,
+        }],
+    );
+}
+
+#[test]
+fn lists() {
+    let wit = "
+        record entry { key: string, values: list<option<u16>> }
+        f: function(a: list<entry>, b: list<list<string>>, c: list<char>) -> list<entry>
+    ";
+    let entry = |key: &str, values: Vec<Value>| {
+        Value::Record(vec![Value::String(key.to_string()), Value::List(values)])
+    };
+    round_trip(
+        wit,
+        "f",
+        &[
+            Value::List(vec![
+                entry("a", vec![some(Value::U16(1)), none()]),
+                entry("", vec![]),
+            ]),
+            Value::List(vec![
+                Value::List(vec![Value::String("x".to_string())]),
+                Value::List(vec![]),
+            ]),
+            Value::String("chars".to_string()),
+        ],
+        &[Value::List(vec![entry("é", vec![none()])])],
+    );
+}
+
+#[test]
+fn layout() {
+    // Lowering a record through a return pointer writes each core wasm value
+    // into its own 8-byte slot.
+    let iface = Interface::parse("foo", "f: function() -> (a: u8, b: string)").unwrap();
+    let func = &iface.functions[0];
+    let mut memory = Memory::new();
+    let results = call_interface(
+        &iface,
+        AbiVariant::GuestExport,
+        func,
+        &mut memory,
+        &[],
+        |_| Ok(vec![Value::U8(9), Value::String("hi".to_string())]),
+    )
+    .unwrap();
+    let retptr = match results[..] {
+        [WasmValue::I32(ptr)] => ptr as usize,
+        _ => panic!("unexpected results {:?}", results),
+    };
+    let bytes = memory.bytes();
+    let word = |offset: usize| {
+        let mut buf = [0; 4];
+        buf.copy_from_slice(&bytes[retptr + offset..][..4]);
+        u32::from_le_bytes(buf) as usize
+    };
+    assert_eq!(word(0), 9);
+    assert_eq!(&bytes[word(8)..][..word(16)], b"hi");
+
+    // Invalid values coming from core wasm are errors.
+    let iface = Interface::parse("foo", "f: function(a: char, b: string)").unwrap();
+    let func = &iface.functions[0];
+    let mut memory = Memory::from(vec![0xff]);
+    let call = |memory: &mut Memory, args: &[WasmValue]| {
+        call_interface(&iface, AbiVariant::GuestExport, func, memory, args, |_| {
+            Ok(Vec::new())
+        })
+        .unwrap_err()
+        .to_string()
+    };
+    let args = [WasmValue::I32(0xd800), WasmValue::I32(0), WasmValue::I32(0)];
+    assert_eq!(
+        call(&mut memory, &args),
+        "invalid unicode scalar value 0xd800"
+    );
+    let args = [WasmValue::I32(0x61), WasmValue::I32(0), WasmValue::I32(1)];
+    assert_eq!(call(&mut memory, &args), "invalid utf-8 in string at 0x0");
+    let args = [WasmValue::I32(0x61), WasmValue::I32(0), WasmValue::I32(2)];
+    assert_eq!(
+        call(&mut memory, &args),
+        "list of 2 bytes at 0x0 is out of bounds"
+    );
+}
+
+/// A memory which records what's freed.
+#[derive(Default)]
+struct Tracking {
+    memory: Memory,
+    frees: Vec<(i32, usize, usize)>,
+}
+
+impl LinearMemory for Tracking {
+    fn read(&mut self, addr: i32, dst: &mut [u8]) -> anyhow::Result<()> {
+        self.memory.read(addr, dst)
+    }
+
+    fn write(&mut self, addr: i32, src: &[u8]) -> anyhow::Result<()> {
+        self.memory.write(addr, src)
+    }
+
+    fn alloc(&mut self, size: usize, align: usize) -> anyhow::Result<i32> {
+        self.memory.alloc(size, align)
+    }
+
+    fn free(&mut self, addr: i32, size: usize, align: usize) -> anyhow::Result<()> {
+        self.frees.push((addr, size, align));
+        Ok(())
+    }
+}
+
+#[test]
+fn frees() {
+    // Lists returned from an export are owned by the caller, which frees
+    // them once they're lifted.
+    let iface = Interface::parse("foo", "f: function() -> (a: string, b: list<u16>)").unwrap();
+    let func = &iface.functions[0];
+    let mut memory = Tracking::default();
+    let mut lowered = Vec::new();
+    let results = call_wasm(
+        &iface,
+        AbiVariant::GuestExport,
+        func,
+        &mut memory,
+        &[],
+        |memory, args| {
+            let results =
+                call_interface(&iface, AbiVariant::GuestExport, func, memory, args, |_| {
+                    Ok(vec![
+                        Value::String("abc".to_string()),
+                        Value::List(vec![Value::U16(1), Value::U16(2)]),
+                    ])
+                })?;
+            let retptr = match results[..] {
+                [WasmValue::I32(ptr)] => ptr,
+                _ => panic!("unexpected results {:?}", results),
+            };
+            for offset in [0, 16] {
+                let mut buf = [0; 4];
+                memory.read(retptr + offset, &mut buf)?;
+                lowered.push(i32::from_le_bytes(buf));
+            }
+            Ok(results)
+        },
+    )
+    .unwrap();
+    assert_eq!(results[0], Value::String("abc".to_string()));
+    assert_eq!(memory.frees, [(lowered[0], 3, 1), (lowered[1], 4, 2)]);
+
+    // Nothing is freed when an import's arguments are lifted, since they're
+    // only borrowed.
+    let iface = Interface::parse("foo", "f: function(a: string, b: list<u16>)").unwrap();
+    let func = &iface.functions[0];
+    let mut memory = Tracking::default();
+    let args = [Value::String("abc".to_string()), Value::List(Vec::new())];
+    call_wasm(
+        &iface,
+        AbiVariant::GuestImport,
+        func,
+        &mut memory,
+        &args,
+        |memory, args| {
+            call_interface(&iface, AbiVariant::GuestImport, func, memory, args, |_| {
+                Ok(Vec::new())
+            })
+        },
+    )
+    .unwrap();
+    assert_eq!(memory.frees, []);
+}
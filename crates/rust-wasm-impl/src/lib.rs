@@ -52,6 +52,7 @@ mod kw {
     syn::custom_keyword!(paths);
     syn::custom_keyword!(unchecked);
     syn::custom_keyword!(multi_module);
+    syn::custom_keyword!(mock);
 }
 
 impl Parse for Opts {
@@ -68,6 +69,7 @@ impl Parse for Opts {
                 match field.into_value() {
                     ConfigField::Unchecked => opts.unchecked = true,
                     ConfigField::MultiModule => opts.multi_module = true,
+                    ConfigField::Mock => opts.mock = true,
                     ConfigField::Interfaces(v) => interfaces = v,
                 }
             }
@@ -104,6 +106,7 @@ enum ConfigField {
     Interfaces(Vec<Interface>),
     Unchecked,
     MultiModule,
+    Mock,
 }
 
 impl Parse for ConfigField {
@@ -141,6 +144,9 @@ impl Parse for ConfigField {
         } else if l.peek(kw::multi_module) {
             input.parse::<kw::multi_module>()?;
             Ok(ConfigField::MultiModule)
+        } else if l.peek(kw::mock) {
+            input.parse::<kw::mock>()?;
+            Ok(ConfigField::Mock)
         } else {
             Err(l.error())
         }
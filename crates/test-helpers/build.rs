@@ -1,30 +1,43 @@
+use heck::*;
 use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use wit_bindgen_gen_core::{wit_parser::Interface, Generator};
+use wit_bindgen_gen_core::wit_parser::{Interface, Type, TypeDefKind};
+use wit_bindgen_gen_core::Generator;
+
+#[path = "src/echo.rs"]
+mod echo;
 
 fn main() {
     let out_dir = PathBuf::from(std::env::var_os("OUT_DIR").unwrap());
 
     let mut wasms = Vec::new();
+    let mut roundtrips = Vec::new();
 
     if cfg!(feature = "wasm-rust") {
-        let mut cmd = Command::new("cargo");
-        cmd.arg("build")
-            .current_dir("../test-rust-wasm")
-            .arg("--target=wasm32-wasi")
-            .env("CARGO_TARGET_DIR", &out_dir)
-            .env("CARGO_PROFILE_DEV_DEBUG", "1")
-            .env("RUSTFLAGS", "-Clink-args=--export-table")
-            .env_remove("CARGO_ENCODED_RUSTFLAGS");
-        let status = cmd.status().unwrap();
+        let cargo = || {
+            let mut cmd = Command::new("cargo");
+            cmd.arg("build")
+                .current_dir("../test-rust-wasm")
+                .arg("--target=wasm32-wasi")
+                .env("CARGO_TARGET_DIR", &out_dir)
+                .env("CARGO_PROFILE_DEV_DEBUG", "1")
+                .env("RUSTFLAGS", "-Clink-args=--export-table")
+                .env_remove("CARGO_ENCODED_RUSTFLAGS");
+            cmd
+        };
+        let status = cargo().status().unwrap();
         assert!(status.success());
         for file in out_dir.join("wasm32-wasi/debug").read_dir().unwrap() {
             let file = file.unwrap().path();
             if file.extension().and_then(|s| s.to_str()) != Some("wasm") {
                 continue;
             }
+            // Built below, once for each round-trip test.
+            if file.file_stem().and_then(|s| s.to_str()) == Some("roundtrip") {
+                continue;
+            }
             wasms.push((
                 "rust",
                 file.file_stem().unwrap().to_str().unwrap().to_string(),
@@ -44,6 +57,34 @@ fn main() {
             }
         }
         println!("cargo:rerun-if-changed=../test-rust-wasm/Cargo.toml");
+
+        // Each round-trip test gets a guest which exports its functions,
+        // built from the same `roundtrip` binary with generated source.
+        println!("cargo:rerun-if-changed=../../tests/roundtrip");
+        let roundtrip_dir = out_dir.join("roundtrip");
+        drop(fs::remove_dir_all(&roundtrip_dir));
+        fs::create_dir(&roundtrip_dir).unwrap();
+        for wit in fs::read_dir("../../tests/roundtrip").unwrap() {
+            let wit = wit.unwrap().path();
+            if wit.extension().and_then(|s| s.to_str()) != Some("wit") {
+                continue;
+            }
+            println!("cargo:rerun-if-changed={}", wit.display());
+            let name = wit.file_stem().unwrap().to_str().unwrap().to_string();
+            let guest = roundtrip_dir.join(format!("{}.rs", name));
+            fs::write(&guest, roundtrip_guest(&wit)).unwrap();
+
+            let status = cargo()
+                .arg("--bin=roundtrip")
+                .arg("--features=roundtrip")
+                .env("ROUNDTRIP_GUEST", &guest)
+                .status()
+                .unwrap();
+            assert!(status.success());
+            let wasm = roundtrip_dir.join(format!("{}.wasm", name));
+            fs::copy(out_dir.join("wasm32-wasi/debug/roundtrip.wasm"), &wasm).unwrap();
+            roundtrips.push((name, wasm.to_str().unwrap().to_string()));
+        }
     }
 
     if cfg!(feature = "wasm-c") {
@@ -97,6 +138,9 @@ fn main() {
                 .arg("-Werror")
                 .arg("-Wno-unused-parameter")
                 .arg("-mexec-model=reactor")
+                // Async imports are completed by the host calling back into
+                // the function table.
+                .arg("-Wl,--export-table")
                 .arg("-g")
                 .arg("-o")
                 .arg(&out_wasm);
@@ -163,6 +207,115 @@ fn main() {
         }
     }
 
-    let src = format!("const WASMS: &[(&str, &str, &str)] = &{:?};", wasms);
+    let src = format!(
+        "const WASMS: &[(&str, &str, &str)] = &{:?};\n\
+         #[allow(dead_code)]\n\
+         const ROUNDTRIPS: &[(&str, &str)] = &{:?};\n",
+        wasms, roundtrips,
+    );
     std::fs::write(out_dir.join("wasms.rs"), src).unwrap();
 }
+
+/// Returns the source of a Rust guest which exports the functions of the
+/// round-trip test at `wit`, each returning its arguments.
+fn roundtrip_guest(wit: &Path) -> String {
+    let iface = echo::interface(wit);
+    let mut files = Default::default();
+    wit_bindgen_gen_rust_wasm::Opts::default()
+        .build()
+        .generate_all(&[], std::slice::from_ref(&iface), &mut files);
+    let (_, bindings) = files
+        .iter()
+        .find(|(name, _)| *name == "bindings.rs")
+        .unwrap();
+    let mut src = String::from_utf8(bindings.to_vec()).unwrap();
+
+    let camel = iface.name.to_camel_case();
+    src.push_str(&format!("\nstruct {};\n\n", camel));
+    src.push_str(&format!(
+        "impl {}::{} for {} {{\n",
+        iface.name.to_snake_case(),
+        camel,
+        camel
+    ));
+    for func in iface.functions.iter() {
+        let params = func
+            .params
+            .iter()
+            .enumerate()
+            .map(|(i, (_, ty))| format!("a{}: {}", i, rust_type(&iface, ty)))
+            .collect::<Vec<_>>();
+        let results = (0..func.params.len())
+            .map(|i| format!("a{}", i))
+            .collect::<Vec<_>>();
+        let (ret, body) = match func.params.len() {
+            1 => (rust_type(&iface, &func.params[0].1), results[0].clone()),
+            _ => {
+                let types = func.params.iter().map(|(_, ty)| rust_type(&iface, ty));
+                (
+                    format!("({})", types.collect::<Vec<_>>().join(", ")),
+                    format!("({})", results.join(", ")),
+                )
+            }
+        };
+        src.push_str(&format!(
+            "    fn {}({}) -> {} {{\n        {}\n    }}\n",
+            wit_bindgen_gen_rust::to_rust_ident(&func.name),
+            params.join(", "),
+            ret,
+            body,
+        ));
+    }
+    src.push_str("}\n");
+    src
+}
+
+/// Returns the type of `ty` in the exports of a Rust guest, where everything
+/// is owned.
+fn rust_type(iface: &Interface, ty: &Type) -> String {
+    match ty {
+        Type::U8 => "u8".to_string(),
+        Type::S8 => "i8".to_string(),
+        Type::U16 => "u16".to_string(),
+        Type::S16 => "i16".to_string(),
+        Type::U32 => "u32".to_string(),
+        Type::S32 => "i32".to_string(),
+        Type::U64 => "u64".to_string(),
+        Type::S64 => "i64".to_string(),
+        Type::F32 => "f32".to_string(),
+        Type::F64 => "f64".to_string(),
+        Type::Char => "char".to_string(),
+        Type::Id(id) => {
+            let ty = &iface.types[*id];
+            if let Some(name) = &ty.name {
+                return format!("{}::{}", iface.name.to_snake_case(), name.to_camel_case());
+            }
+            match &ty.kind {
+                TypeDefKind::Type(t) => rust_type(iface, t),
+                TypeDefKind::List(Type::Char) => "String".to_string(),
+                TypeDefKind::List(t) => format!("Vec<{}>", rust_type(iface, t)),
+                TypeDefKind::Record(r) => {
+                    let fields = r.fields.iter().map(|f| rust_type(iface, &f.ty) + ",");
+                    format!("({})", fields.collect::<String>())
+                }
+                TypeDefKind::Variant(v) if v.is_bool() => "bool".to_string(),
+                TypeDefKind::Variant(v) => {
+                    if let Some(t) = v.as_option() {
+                        format!("Option<{}>", rust_type(iface, t))
+                    } else if let Some((ok, err)) = v.as_expected() {
+                        let unit = || "()".to_string();
+                        format!(
+                            "Result<{}, {}>",
+                            ok.map_or_else(unit, |t| rust_type(iface, t)),
+                            err.map_or_else(unit, |t| rust_type(iface, t)),
+                        )
+                    } else {
+                        unreachable!()
+                    }
+                }
+                _ => unreachable!(),
+            }
+        }
+        Type::Handle(_) | Type::CChar | Type::Usize => unreachable!(),
+    }
+}
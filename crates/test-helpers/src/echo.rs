@@ -0,0 +1,49 @@
+// Shared between the build script, which compiles a guest for each of these
+// interfaces, and the procedural macros, which generate the hosts calling it.
+
+use std::path::Path;
+use wit_bindgen_gen_core::wit_parser::{Interface, Type, TypeDefKind};
+
+/// Parses the `*.wit` file at `path` for round-trip testing, changing each of
+/// its functions to return its own parameters.
+pub fn interface(path: &Path) -> Interface {
+    let mut iface = Interface::parse_file(path).unwrap();
+    if !iface.resources.is_empty() {
+        panic!("{}: resources can't be round-tripped", path.display());
+    }
+    for func in iface.functions.iter() {
+        if func.is_async {
+            panic!("{}: `{}` can't be async", path.display(), func.name);
+        }
+        if !func.params.iter().all(|(_, ty)| supported(&iface, ty)) {
+            panic!(
+                "{}: `{}` has a parameter which can't be round-tripped",
+                path.display(),
+                func.name
+            );
+        }
+    }
+    for func in iface.functions.iter_mut() {
+        func.results = func.params.clone();
+    }
+    iface
+}
+
+fn supported(iface: &Interface, ty: &Type) -> bool {
+    match ty {
+        Type::Handle(_) | Type::CChar | Type::Usize => false,
+        Type::Id(id) => match &iface.types[*id].kind {
+            TypeDefKind::Type(t) | TypeDefKind::List(t) => supported(iface, t),
+            TypeDefKind::Record(r) => r.fields.iter().all(|f| supported(iface, &f.ty)),
+            TypeDefKind::Variant(v) => v
+                .cases
+                .iter()
+                .all(|c| c.ty.as_ref().map_or(true, |t| supported(iface, t))),
+            TypeDefKind::Pointer(_)
+            | TypeDefKind::ConstPointer(_)
+            | TypeDefKind::PushBuffer(_)
+            | TypeDefKind::PullBuffer(_) => false,
+        },
+        _ => true,
+    }
+}
@@ -6,6 +6,19 @@ use std::path::{Path, PathBuf};
 use std::time::{Duration, SystemTime};
 use wit_bindgen_gen_core::{Direction, Generator};
 
+#[cfg(any(
+    feature = "wit-bindgen-gen-wasmtime",
+    feature = "wit-bindgen-gen-js",
+    feature = "wit-bindgen-gen-wasmtime-py"
+))]
+mod echo;
+#[cfg(any(
+    feature = "wit-bindgen-gen-wasmtime",
+    feature = "wit-bindgen-gen-js",
+    feature = "wit-bindgen-gen-wasmtime-py"
+))]
+mod roundtrip;
+
 #[proc_macro]
 #[cfg(feature = "wit-bindgen-gen-rust-wasm")]
 pub fn codegen_rust_wasm_import(input: TokenStream) -> TokenStream {
@@ -27,6 +40,15 @@ pub fn codegen_rust_wasm_import(input: TokenStream) -> TokenStream {
                 },
                 |_| quote::quote!(),
             ),
+            (
+                "import-mock",
+                || {
+                    let mut opts = wit_bindgen_gen_rust_wasm::Opts::default();
+                    opts.mock = true;
+                    opts.build()
+                },
+                |_| quote::quote!(),
+            ),
         ],
     )
 }
@@ -62,12 +84,18 @@ pub fn codegen_rust_wasm_export(input: TokenStream) -> TokenStream {
 
     fn gen_extra(iface: &wit_parser::Interface) -> proc_macro2::TokenStream {
         let mut ret = quote::quote!();
-        if iface.resources.len() == 0 && iface.functions.len() == 0 {
+        for (_, nested) in iface.interfaces.iter() {
+            ret.extend(gen_extra(nested));
+        }
+        if iface.resources.len() == 0 && iface.functions.len() == 0 && iface.globals.len() == 0 {
             return ret;
         }
 
-        let snake = quote::format_ident!("{}", iface.name.to_snake_case());
+        let snake = module_path(iface);
         let camel = quote::format_ident!("{}", iface.name.to_camel_case());
+        // Nested interfaces are implemented by a type named after their full
+        // path.
+        let impl_ = quote::format_ident!("{}", iface.module().to_camel_case());
 
         for (_, r) in iface.resources.iter() {
             let name = quote::format_ident!("{}", r.name.to_camel_case());
@@ -78,7 +106,12 @@ pub fn codegen_rust_wasm_export(input: TokenStream) -> TokenStream {
         let mut resources = BTreeMap::new();
 
         let mut async_trait = quote::quote!();
-        for f in iface.functions.iter() {
+        let getters = iface
+            .globals
+            .iter()
+            .map(wit_bindgen_gen_core::global_getter)
+            .collect::<Vec<_>>();
+        for f in iface.functions.iter().chain(&getters) {
             let name = quote::format_ident!("{}", f.item_name().to_snake_case());
             let mut params = f
                 .params
@@ -118,10 +151,10 @@ pub fn codegen_rust_wasm_export(input: TokenStream) -> TokenStream {
             }
         }
         ret.extend(quote::quote! {
-            struct #camel;
+            struct #impl_;
 
             #async_trait
-            impl #snake::#camel for #camel {
+            impl #snake::#camel for #impl_ {
                 #(#methods)*
             }
         });
@@ -138,6 +171,15 @@ pub fn codegen_rust_wasm_export(input: TokenStream) -> TokenStream {
         ret
     }
 
+    /// Returns the path to the module generated for `iface`.
+    fn module_path(iface: &wit_parser::Interface) -> proc_macro2::TokenStream {
+        let segments = iface
+            .module()
+            .split("::")
+            .map(|s| quote::format_ident!("{}", s.to_snake_case()));
+        quote::quote! { #(#segments)::* }
+    }
+
     fn quote_ty(
         param: bool,
         iface: &wit_parser::Interface,
@@ -174,7 +216,11 @@ pub fn codegen_rust_wasm_export(input: TokenStream) -> TokenStream {
         let ty = &iface.types[id];
         if let Some(name) = &ty.name {
             let name = quote::format_ident!("{}", name.to_camel_case());
-            let module = quote::format_ident!("{}", iface.name.to_snake_case());
+            let module = module_path(iface);
+            let info = wit_bindgen_gen_core::Types::default().type_id_info(iface, id);
+            if info.has_push_buffer || info.has_pull_buffer {
+                return quote::quote! { #module::#name<'_> };
+            }
             return quote::quote! { #module::#name };
         }
         match &ty.kind {
@@ -195,8 +241,24 @@ pub fn codegen_rust_wasm_export(input: TokenStream) -> TokenStream {
                     quote::quote! { Vec<#t> }
                 }
             }
-            TypeDefKind::PushBuffer(_) => panic!("unimplemented push-buffer"),
-            TypeDefKind::PullBuffer(_) => panic!("unimplemented pull-buffer"),
+            TypeDefKind::PushBuffer(t) => {
+                let raw = iface.all_bits_valid(t);
+                let t = quote_ty(param, iface, t);
+                if raw {
+                    quote::quote! { wit_bindgen_rust::exports::OutBufferRaw<'_, #t> }
+                } else {
+                    quote::quote! { wit_bindgen_rust::exports::OutBuffer<'_, #t> }
+                }
+            }
+            TypeDefKind::PullBuffer(t) => {
+                let raw = iface.all_bits_valid(t);
+                let t = quote_ty(param, iface, t);
+                if raw {
+                    quote::quote! { wit_bindgen_rust::exports::InBufferRaw<'_, #t> }
+                } else {
+                    quote::quote! { wit_bindgen_rust::exports::InBuffer<'_, #t> }
+                }
+            }
             TypeDefKind::Record(r) => {
                 let fields = r.fields.iter().map(|f| quote_ty(param, iface, &f.ty));
                 quote::quote! { (#(#fields,)*) }
@@ -281,6 +343,15 @@ pub fn codegen_wasmtime_import(input: TokenStream) -> TokenStream {
                 },
                 |_| quote::quote!(),
             ),
+            (
+                "import-mock",
+                || {
+                    let mut opts = wit_bindgen_gen_wasmtime::Opts::default();
+                    opts.mock = true;
+                    opts.build()
+                },
+                |_| quote::quote!(),
+            ),
         ],
     )
 }
@@ -317,6 +388,16 @@ pub fn codegen_c_export(input: TokenStream) -> TokenStream {
     })
 }
 
+/// Generates a test for the JSON document of each interface, which doesn't
+/// depend on whether it's imported or exported.
+#[proc_macro]
+#[cfg(feature = "wit-bindgen-gen-json")]
+pub fn codegen_json(input: TokenStream) -> TokenStream {
+    gen_verify(input, Direction::Import, "json", || {
+        wit_bindgen_gen_json::Opts::default().build()
+    })
+}
+
 #[proc_macro]
 #[cfg(feature = "wit-bindgen-gen-wasmtime-py")]
 pub fn codegen_py_export(input: TokenStream) -> TokenStream {
@@ -576,3 +657,136 @@ pub fn runtime_tests_wasmtime(_input: TokenStream) -> TokenStream {
 
     (quote::quote!(#(#tests)*)).into()
 }
+
+/// Invoked as `roundtrip_tests_wasmtime!()` to call a guest that returns its
+/// arguments with random values for each `tests/roundtrip/*.wit` file, through
+/// bindings generated by `wit-bindgen-gen-wasmtime`.
+#[proc_macro]
+#[cfg(feature = "wit-bindgen-gen-wasmtime")]
+pub fn roundtrip_tests_wasmtime(_input: TokenStream) -> TokenStream {
+    let tests = generate_roundtrips(|iface, dir| {
+        let mut files = Default::default();
+        wit_bindgen_gen_wasmtime::Opts::default()
+            .build()
+            .generate_all(std::slice::from_ref(iface), &[], &mut files);
+        write_files(dir, &files);
+        write_old_file(dir.join("host.rs"), roundtrip::wasmtime_host(iface));
+    });
+    let tests = tests.iter().map(|(name, wasm, wit, dir)| {
+        let name = quote::format_ident!("roundtrip_{}", name);
+        let bindings = dir.join("bindings.rs").to_str().unwrap().to_string();
+        let host = dir.join("host.rs").to_str().unwrap().to_string();
+        quote::quote! {
+            mod #name {
+                const _: &str = include_str!(#wit);
+                include!(#bindings);
+                include!(#host);
+
+                #[test]
+                fn test() -> anyhow::Result<()> {
+                    run(#wasm)
+                }
+            }
+        }
+    });
+    (quote::quote!(#(#tests)*)).into()
+}
+
+/// Invoked as `roundtrip_tests_js!()` to define a test for each
+/// `tests/roundtrip/*.wit` file which calls a top-level `roundtrip` function
+/// with a directory containing bindings generated by `wit-bindgen-gen-js` and
+/// a `host.ts` which calls the guest with random values.
+#[proc_macro]
+#[cfg(feature = "wit-bindgen-gen-js")]
+pub fn roundtrip_tests_js(_input: TokenStream) -> TokenStream {
+    let tests = generate_roundtrips(|iface, dir| {
+        let mut files = Default::default();
+        wit_bindgen_gen_js::Opts::default().build().generate_all(
+            std::slice::from_ref(iface),
+            &[],
+            &mut files,
+        );
+        write_files(dir, &files);
+        write_old_file(dir.join("host.ts"), roundtrip::js_host(iface));
+    });
+    roundtrip_test_fns(&tests)
+}
+
+/// Invoked as `roundtrip_tests_py!()` to define a test for each
+/// `tests/roundtrip/*.wit` file which calls a top-level `roundtrip` function
+/// with a directory containing bindings generated by
+/// `wit-bindgen-gen-wasmtime-py`, in an `exports` package, and a `host.py`
+/// which calls the guest with random values.
+#[proc_macro]
+#[cfg(feature = "wit-bindgen-gen-wasmtime-py")]
+pub fn roundtrip_tests_py(_input: TokenStream) -> TokenStream {
+    let tests = generate_roundtrips(|iface, dir| {
+        let mut files = Default::default();
+        wit_bindgen_gen_wasmtime_py::Opts::default()
+            .build()
+            .generate_all(std::slice::from_ref(iface), &[], &mut files);
+        let exports = dir.join("exports");
+        fs::create_dir(&exports).unwrap();
+        write_files(&exports, &files);
+        write_old_file(exports.join("__init__.py"), "");
+        write_old_file(dir.join("host.py"), roundtrip::py_host(iface));
+    });
+    roundtrip_test_fns(&tests)
+}
+
+/// Calls `generate` with the interface of each round-trip test and a fresh
+/// directory for its host, returning the name of each test, the guest which
+/// implements it, its `*.wit` file and the directory.
+#[cfg(any(
+    feature = "wit-bindgen-gen-wasmtime",
+    feature = "wit-bindgen-gen-js",
+    feature = "wit-bindgen-gen-wasmtime-py"
+))]
+fn generate_roundtrips(
+    generate: impl Fn(&wit_parser::Interface, &Path),
+) -> Vec<(String, &'static str, String, PathBuf)> {
+    let mut out_dir = PathBuf::from(env::var_os("OUT_DIR").expect("OUT_DIR not set"));
+    out_dir.push("roundtrip");
+    let cwd = env::current_dir().unwrap();
+    let mut tests = Vec::new();
+    for (name, wasm) in ROUNDTRIPS {
+        let wit = cwd.join("tests/roundtrip").join(format!("{}.wit", name));
+        let iface = echo::interface(&wit);
+        let dir = out_dir.join(name);
+        drop(fs::remove_dir_all(&dir));
+        fs::create_dir_all(&dir).unwrap();
+        generate(&iface, &dir);
+        tests.push((name.to_string(), *wasm, wit.display().to_string(), dir));
+    }
+    tests
+}
+
+#[cfg(any(
+    feature = "wit-bindgen-gen-js",
+    feature = "wit-bindgen-gen-wasmtime-py"
+))]
+fn roundtrip_test_fns(tests: &[(String, &'static str, String, PathBuf)]) -> TokenStream {
+    let tests = tests.iter().map(|(name, wasm, wit, dir)| {
+        let test = quote::format_ident!("roundtrip_{}", name);
+        let dir = dir.display().to_string();
+        quote::quote! {
+            #[test]
+            fn #test() {
+                const _: &str = include_str!(#wit);
+                crate::roundtrip(#name, #dir.as_ref(), #wasm.as_ref())
+            }
+        }
+    });
+    (quote::quote!(#(#tests)*)).into()
+}
+
+#[cfg(any(
+    feature = "wit-bindgen-gen-wasmtime",
+    feature = "wit-bindgen-gen-js",
+    feature = "wit-bindgen-gen-wasmtime-py"
+))]
+fn write_files(dir: &Path, files: &wit_bindgen_gen_core::Files) {
+    for (file, contents) in files.iter() {
+        write_old_file(dir.join(file), contents);
+    }
+}
@@ -0,0 +1,699 @@
+//! Random arguments for the functions of round-trip tests, and the source of
+//! hosts in each language which pass them through a guest that returns them.
+//!
+//! Values are generated as `wit_parser::interpret::Value`s and then printed as
+//! literals of whatever type each host's bindings use for them, so every host
+//! is checked with the same values.
+
+use heck::*;
+use wit_bindgen_gen_core::wit_parser::interpret::Value;
+use wit_bindgen_gen_core::wit_parser::{Function, Interface, Type, TypeDefKind};
+
+/// The number of times each function is called, each time with different
+/// arguments.
+const CASES: usize = 10;
+
+/// A call to `func` with `args`, which the guest returns unchanged.
+pub struct Case<'a> {
+    pub func: &'a Function,
+    pub args: Vec<Value>,
+}
+
+/// Generates the calls made by the hosts of a round-trip test.
+///
+/// The generator is seeded with the name of the interface, so each test gets
+/// different values but the same ones on every run.
+pub fn cases(iface: &Interface) -> Vec<Case<'_>> {
+    let seed = iface.name.bytes().fold(0xcbf29ce484222325u64, |hash, b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x100000001b3)
+    });
+    let mut rng = Rng(seed);
+    let mut cases = Vec::new();
+    for func in iface.functions.iter() {
+        for _ in 0..CASES {
+            let args = func
+                .params
+                .iter()
+                .map(|(_, ty)| rng.value(iface, ty, 0))
+                .collect();
+            cases.push(Case { func, args });
+        }
+    }
+    cases
+}
+
+/// A splitmix64 generator, which is all the randomness needed here.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    /// Returns an integer of `bits` bits, which is more likely than chance to
+    /// be at the edges of the signed and unsigned ranges.
+    fn int(&mut self, bits: u32) -> u64 {
+        let sign = 1 << (bits - 1);
+        match self.below(6) {
+            0 => 0,
+            1 => u64::MAX,
+            2 => sign,
+            3 => sign - 1,
+            4 => self.below(256) as u64,
+            _ => self.next(),
+        }
+    }
+
+    // NaN isn't generated since hosts disagree on whether it's equal to
+    // itself, and on whether its payload is preserved.
+
+    fn f32(&mut self) -> f32 {
+        loop {
+            let f = match self.below(3) {
+                0 => [
+                    0.0,
+                    -0.0,
+                    1.0,
+                    f32::INFINITY,
+                    f32::NEG_INFINITY,
+                    f32::MAX,
+                    f32::MIN_POSITIVE,
+                    f32::from_bits(1),
+                ][self.below(8)],
+                1 => (self.below(2000) as f32 - 1000.0) / 8.0,
+                _ => f32::from_bits(self.next() as u32),
+            };
+            if !f.is_nan() {
+                return f;
+            }
+        }
+    }
+
+    fn f64(&mut self) -> f64 {
+        loop {
+            let f = match self.below(3) {
+                0 => [
+                    0.0,
+                    -0.0,
+                    1.0,
+                    f64::INFINITY,
+                    f64::NEG_INFINITY,
+                    f64::MAX,
+                    f64::MIN_POSITIVE,
+                    f64::from_bits(1),
+                ][self.below(8)],
+                1 => (self.below(2000) as f64 - 1000.0) / 8.0,
+                _ => f64::from_bits(self.next()),
+            };
+            if !f.is_nan() {
+                return f;
+            }
+        }
+    }
+
+    /// Returns a character which is equally likely to be encoded in each of
+    /// one through four bytes of UTF-8.
+    fn char(&mut self) -> char {
+        let end = [0x80, 0x800, 0x10000, 0x110000][self.below(4)];
+        loop {
+            if let Some(c) = std::char::from_u32(self.below(end) as u32) {
+                return c;
+            }
+        }
+    }
+
+    /// Returns the length of a list, with lists getting shorter the more
+    /// deeply they're nested.
+    fn len(&mut self, depth: usize) -> usize {
+        self.below(1 + (8 >> depth.min(3)))
+    }
+
+    fn value(&mut self, iface: &Interface, ty: &Type, depth: usize) -> Value {
+        match ty {
+            Type::U8 => Value::U8(self.int(8) as u8),
+            Type::S8 => Value::S8(self.int(8) as i8),
+            Type::U16 => Value::U16(self.int(16) as u16),
+            Type::S16 => Value::S16(self.int(16) as i16),
+            Type::U32 => Value::U32(self.int(32) as u32),
+            Type::S32 => Value::S32(self.int(32) as i32),
+            Type::U64 => Value::U64(self.int(64)),
+            Type::S64 => Value::S64(self.int(64) as i64),
+            Type::F32 => Value::F32(self.f32()),
+            Type::F64 => Value::F64(self.f64()),
+            Type::Char => Value::Char(self.char()),
+            Type::Id(id) => match &iface.types[*id].kind {
+                TypeDefKind::Type(t) => self.value(iface, t, depth),
+                TypeDefKind::List(Type::Char) => {
+                    let len = self.len(depth);
+                    Value::String((0..len).map(|_| self.char()).collect())
+                }
+                TypeDefKind::List(t) => {
+                    let len = self.len(depth);
+                    Value::List((0..len).map(|_| self.value(iface, t, depth + 1)).collect())
+                }
+                TypeDefKind::Record(r) if r.is_flags() => {
+                    Value::Flags(r.fields.iter().map(|_| self.below(2) == 1).collect())
+                }
+                TypeDefKind::Record(r) => Value::Record(
+                    r.fields
+                        .iter()
+                        .map(|f| self.value(iface, &f.ty, depth + 1))
+                        .collect(),
+                ),
+                TypeDefKind::Variant(v) if v.is_bool() => Value::Bool(self.below(2) == 1),
+                TypeDefKind::Variant(v) => {
+                    let case = self.below(v.cases.len());
+                    let payload = v.cases[case]
+                        .ty
+                        .as_ref()
+                        .map(|t| Box::new(self.value(iface, t, depth + 1)));
+                    Value::Variant { case, payload }
+                }
+                _ => unreachable!(),
+            },
+            Type::Handle(_) | Type::CChar | Type::Usize => unreachable!(),
+        }
+    }
+}
+
+fn flag_bits(flags: &[bool]) -> u64 {
+    flags
+        .iter()
+        .enumerate()
+        .filter(|(_, set)| **set)
+        .map(|(i, _)| 1 << i)
+        .sum()
+}
+
+/// Formats `f` such that it parses back to the same value in JS and Python.
+#[cfg(any(
+    feature = "wit-bindgen-gen-js",
+    feature = "wit-bindgen-gen-wasmtime-py"
+))]
+fn float(f: f64, inf: &str) -> String {
+    if f.is_infinite() {
+        format!("{}{}", if f < 0.0 { "-" } else { "" }, inf)
+    } else {
+        format!("{:?}", f)
+    }
+}
+
+/// Returns a string literal of `s` which only uses printable ASCII, escaping
+/// everything else with `escape`.
+#[cfg(any(
+    feature = "wit-bindgen-gen-js",
+    feature = "wit-bindgen-gen-wasmtime-py"
+))]
+fn string(s: &str, escape: fn(char) -> String) -> String {
+    let mut ret = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                ret.push('\\');
+                ret.push(c);
+            }
+            ' '..='~' => ret.push(c),
+            _ => ret.push_str(&escape(c)),
+        }
+    }
+    ret.push('"');
+    ret
+}
+
+/// Returns the source of the `run` function of a Rust host which calls the
+/// guest through bindings from `wit-bindgen-gen-wasmtime`.
+///
+/// Arguments are borrowed but results are owned, which for types containing
+/// lists means they're different types, so each result is compared with an
+/// owned copy of its argument. Not every type implements `PartialEq`, so
+/// they're compared by their `Debug` output.
+#[cfg(feature = "wit-bindgen-gen-wasmtime")]
+pub fn wasmtime_host(iface: &Interface) -> String {
+    let mut types = wit_bindgen_gen_core::Types::default();
+    types.analyze(iface);
+    let rust = Rust { iface, types };
+    let module = iface.name.to_snake_case();
+    let mut src = format!(
+        "fn run(wasm: &str) -> anyhow::Result<()> {{\n\
+         let (exports, mut store) = crate::instantiate::<(), _, _>(\n\
+             wasm,\n\
+             |_| Ok(()),\n\
+             |store, module, linker| {{\n\
+                 {}::{}::instantiate(store, module, linker, |cx| &mut cx.exports)\n\
+             }},\n\
+         )?;\n\
+         fn check<T: std::fmt::Debug>(actual: T, expected: T) {{\n\
+             assert_eq!(format!(\"{{:?}}\", actual), format!(\"{{:?}}\", expected));\n\
+         }}\n",
+        module,
+        iface.name.to_camel_case(),
+    );
+    for case in cases(iface) {
+        let values = |param| {
+            case.func
+                .params
+                .iter()
+                .zip(&case.args)
+                .map(|((_, ty), arg)| rust.value(ty, arg, param))
+                .collect::<Vec<_>>()
+        };
+        let expected = values(false);
+        let expected = match expected.len() {
+            1 => expected[0].clone(),
+            _ => format!("({})", expected.join(", ")),
+        };
+        src.push_str(&format!(
+            "check(exports.{}(&mut store, {})?, {});\n",
+            wit_bindgen_gen_rust::to_rust_ident(&case.func.name),
+            values(true).join(", "),
+            expected,
+        ));
+    }
+    src.push_str("Ok(())\n}\n");
+    src
+}
+
+#[cfg(feature = "wit-bindgen-gen-wasmtime")]
+struct Rust<'a> {
+    iface: &'a Interface,
+    types: wit_bindgen_gen_core::Types,
+}
+
+#[cfg(feature = "wit-bindgen-gen-wasmtime")]
+impl Rust<'_> {
+    /// Returns a literal of `val`, which is borrowed if it's a `param` and
+    /// owned otherwise.
+    fn value(&self, ty: &Type, val: &Value, param: bool) -> String {
+        let iface = self.iface;
+        let id = match (ty, val) {
+            (Type::Id(id), _) => *id,
+            (_, Value::U8(i)) => return format!("{}u8", i),
+            (_, Value::S8(i)) => return format!("{}i8", i),
+            (_, Value::U16(i)) => return format!("{}u16", i),
+            (_, Value::S16(i)) => return format!("{}i16", i),
+            (_, Value::U32(i)) => return format!("{}u32", i),
+            (_, Value::S32(i)) => return format!("{}i32", i),
+            (_, Value::U64(i)) => return format!("{}u64", i),
+            (_, Value::S64(i)) => return format!("{}i64", i),
+            (_, Value::F32(f)) => return format!("f32::from_bits({:#x})", f.to_bits()),
+            (_, Value::F64(f)) => return format!("f64::from_bits({:#x})", f.to_bits()),
+            (_, Value::Char(c)) => return format!("{:?}", c),
+            _ => unreachable!(),
+        };
+        let module = iface.name.to_snake_case();
+        let ty = &iface.types[id];
+        let name = || {
+            let info = self.types.get(id);
+            let suffix = match (info.has_list && info.param && info.result, param) {
+                (false, _) => "",
+                (true, true) => "Param",
+                (true, false) => "Result",
+            };
+            format!(
+                "{}::{}{}",
+                module,
+                ty.name.as_ref().unwrap().to_camel_case(),
+                suffix
+            )
+        };
+        match (&ty.kind, val) {
+            (TypeDefKind::Type(t), _) => self.value(t, val, param),
+            (TypeDefKind::List(_), Value::String(s)) if param => format!("{:?}", s),
+            (TypeDefKind::List(_), Value::String(s)) => format!("{:?}.to_string()", s),
+            (TypeDefKind::List(t), Value::List(vals)) => {
+                let vals = vals.iter().map(|v| self.value(t, v, param));
+                let vals = vals.collect::<Vec<_>>().join(", ");
+                if param {
+                    format!("&[{}][..]", vals)
+                } else {
+                    format!("vec![{}]", vals)
+                }
+            }
+            (TypeDefKind::Record(_), Value::Flags(flags)) => {
+                format!("{}::from_bits_truncate({})", name(), flag_bits(flags))
+            }
+            (TypeDefKind::Record(r), Value::Record(vals)) if r.is_tuple() => {
+                let vals = r.fields.iter().zip(vals);
+                let vals = vals.map(|(f, v)| self.value(&f.ty, v, param) + ",");
+                format!("({})", vals.collect::<String>())
+            }
+            (TypeDefKind::Record(r), Value::Record(vals)) => {
+                let vals = r.fields.iter().zip(vals).map(|(f, v)| {
+                    format!(
+                        "{}: {},",
+                        wit_bindgen_gen_rust::to_rust_ident(&f.name),
+                        self.value(&f.ty, v, param)
+                    )
+                });
+                format!("{} {{ {} }}", name(), vals.collect::<String>())
+            }
+            (TypeDefKind::Variant(_), Value::Bool(b)) => b.to_string(),
+            (TypeDefKind::Variant(v), Value::Variant { case, payload }) => {
+                let payload = match (&v.cases[*case].ty, payload) {
+                    (Some(t), Some(val)) => format!("({})", self.value(t, val, param)),
+                    _ => String::new(),
+                };
+                if v.as_option().is_some() {
+                    return match *case {
+                        0 => "None".to_string(),
+                        _ => format!("Some{}", payload),
+                    };
+                }
+                if v.as_expected().is_some() {
+                    let payload = if payload.is_empty() { "(())" } else { &payload };
+                    return match *case {
+                        0 => format!("Ok{}", payload),
+                        _ => format!("Err{}", payload),
+                    };
+                }
+                format!(
+                    "{}::{}{}",
+                    name(),
+                    wit_bindgen_gen_rust::case_name(&v.cases[*case].name),
+                    payload
+                )
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// Returns the source of a TypeScript host which calls the guest through
+/// bindings from `wit-bindgen-gen-js`.
+#[cfg(feature = "wit-bindgen-gen-js")]
+pub fn js_host(iface: &Interface) -> String {
+    let js = Js {
+        iface,
+        gen: wit_bindgen_gen_js::Js::new(),
+    };
+    let class = iface.name.to_camel_case();
+    let mut src = format!(
+        "import {{ {} }} from \"./{}.js\";\n\
+         import {{ getWasm, addWasiToImports }} from \"./helpers.js\";\n\
+         // @ts-ignore\n\
+         import * as assert from 'assert';\n\
+         \n\
+         async function run() {{\n\
+           const importObj = {{}};\n\
+           const wasi = addWasiToImports(importObj);\n\
+           const wasm = new {0}();\n\
+           await wasm.instantiate(getWasm(), importObj);\n\
+           wasi.start(wasm.instance);\n",
+        class,
+        iface.module().to_kebab_case(),
+    );
+    for case in cases(iface) {
+        let values = |param| {
+            case.func
+                .params
+                .iter()
+                .zip(&case.args)
+                .map(|((_, ty), arg)| js.value(ty, arg, param))
+                .collect::<Vec<_>>()
+        };
+        // Multiple results are returned in an object keyed by their names.
+        let expected = values(false);
+        let expected = match expected.len() {
+            0 => "undefined".to_string(),
+            1 => expected[0].clone(),
+            _ => {
+                let fields = case.func.results.iter().zip(&expected);
+                let fields =
+                    fields.map(|((name, _), val)| format!("{}: {}", name.to_mixed_case(), val));
+                format!("{{ {} }}", fields.collect::<Vec<_>>().join(", "))
+            }
+        };
+        src.push_str(&format!(
+            "  assert.deepStrictEqual(wasm.{}({}), {});\n",
+            case.func.name.to_mixed_case(),
+            values(true).join(", "),
+            expected,
+        ));
+    }
+    src.push_str("}\n\nawait run()\n");
+    src
+}
+
+#[cfg(feature = "wit-bindgen-gen-js")]
+struct Js<'a> {
+    iface: &'a Interface,
+    gen: wit_bindgen_gen_js::Js,
+}
+
+#[cfg(feature = "wit-bindgen-gen-js")]
+impl Js<'_> {
+    /// Returns a literal of `val`, which is being passed to the guest if it's
+    /// a `param` and returned from it otherwise.
+    fn value(&self, ty: &Type, val: &Value, param: bool) -> String {
+        let iface = self.iface;
+        let id = match (ty, val) {
+            (Type::Id(id), _) => *id,
+            (_, Value::U64(i)) => return format!("{}n", i),
+            (_, Value::S64(i)) => return format!("{}n", i),
+            (_, Value::F32(f)) => return float(f64::from(*f), "Infinity"),
+            (_, Value::F64(f)) => return float(*f, "Infinity"),
+            (_, Value::Char(c)) => return string(&c.to_string(), js_escape),
+            (_, val) => return int(val),
+        };
+        let ty = &iface.types[id];
+        match (&ty.kind, val) {
+            (TypeDefKind::Type(t), _) => self.value(t, val, param),
+            (TypeDefKind::List(_), Value::String(s)) => string(s, js_escape),
+            (TypeDefKind::List(t), Value::List(vals)) => {
+                let vals = vals.iter().map(|v| self.value(t, v, param));
+                let vals = vals.collect::<Vec<_>>().join(", ");
+                match array_ty(iface, t) {
+                    Some(array) => format!("new {}([{}])", array, vals),
+                    None => format!("[{}]", vals),
+                }
+            }
+            (TypeDefKind::Record(r), Value::Flags(flags)) => {
+                let suffix = match iface.flags_repr(r) {
+                    Some(wit_bindgen_gen_core::wit_parser::Int::U64) => "n",
+                    _ => "",
+                };
+                format!("{}{}", flag_bits(flags), suffix)
+            }
+            (TypeDefKind::Record(r), Value::Record(vals)) if r.is_tuple() => {
+                let vals = r
+                    .fields
+                    .iter()
+                    .zip(vals)
+                    .map(|(f, v)| self.value(&f.ty, v, param));
+                format!("[{}]", vals.collect::<Vec<_>>().join(", "))
+            }
+            (TypeDefKind::Record(r), Value::Record(vals)) => {
+                let vals = r.fields.iter().zip(vals).map(|(f, v)| {
+                    format!(
+                        "{}: {}",
+                        f.name.to_mixed_case(),
+                        self.value(&f.ty, v, param)
+                    )
+                });
+                format!("{{ {} }}", vals.collect::<Vec<_>>().join(", "))
+            }
+            (TypeDefKind::Variant(_), Value::Bool(b)) => b.to_string(),
+            (TypeDefKind::Variant(v), Value::Variant { case, payload }) => {
+                let payload = match (&v.cases[*case].ty, payload) {
+                    (Some(t), Some(val)) => Some(self.value(t, val, param)),
+                    _ => None,
+                };
+                if v.is_enum() && ty.name.is_some() {
+                    case.to_string()
+                } else if self.gen.is_nullable_option(iface, v) {
+                    payload.unwrap_or_else(|| "null".to_string())
+                } else {
+                    let tag = format!("tag: \"{}\"", v.cases[*case].name);
+                    // The cases of `expected` always have a `val` in their
+                    // types, but it isn't set when they're lifted without a
+                    // payload.
+                    match payload {
+                        Some(payload) => format!("{{ {}, val: {} }}", tag, payload),
+                        None if param && v.as_expected().is_some() => {
+                            format!("{{ {}, val: undefined }}", tag)
+                        }
+                        None => format!("{{ {} }}", tag),
+                    }
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// Returns the typed array which `wit-bindgen-gen-js` uses for lists of `ty`.
+#[cfg(feature = "wit-bindgen-gen-js")]
+fn array_ty(iface: &Interface, ty: &Type) -> Option<&'static str> {
+    match ty {
+        Type::U8 => Some("Uint8Array"),
+        Type::S8 => Some("Int8Array"),
+        Type::U16 => Some("Uint16Array"),
+        Type::S16 => Some("Int16Array"),
+        Type::U32 => Some("Uint32Array"),
+        Type::S32 => Some("Int32Array"),
+        Type::U64 => Some("BigUint64Array"),
+        Type::S64 => Some("BigInt64Array"),
+        Type::F32 => Some("Float32Array"),
+        Type::F64 => Some("Float64Array"),
+        Type::Id(id) => match &iface.types[*id].kind {
+            TypeDefKind::Type(t) => array_ty(iface, t),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(feature = "wit-bindgen-gen-js")]
+fn js_escape(c: char) -> String {
+    format!("\\u{{{:x}}}", c as u32)
+}
+
+/// Returns the source of a Python host which calls the guest through bindings
+/// from `wit-bindgen-gen-wasmtime-py`, imported as `exports.bindings`.
+#[cfg(feature = "wit-bindgen-gen-wasmtime-py")]
+pub fn py_host(iface: &Interface) -> String {
+    let mut src = format!(
+        "import exports.bindings as e
+import sys
+import wasmtime
+
+def run(wasm_file: str) -> None:
+    store = wasmtime.Store()
+    module = wasmtime.Module.from_file(store.engine, wasm_file)
+    linker = wasmtime.Linker(store.engine)
+    linker.define_wasi()
+    wasi = wasmtime.WasiConfig()
+    wasi.inherit_stdout()
+    wasi.inherit_stderr()
+    store.set_wasi(wasi)
+    wasm = e.{}(store, linker, module)
+
+",
+        iface.name.to_camel_case(),
+    );
+    for case in cases(iface) {
+        let values = case
+            .func
+            .params
+            .iter()
+            .zip(&case.args)
+            .map(|((_, ty), arg)| py_value(iface, ty, arg))
+            .collect::<Vec<_>>();
+        let expected = match values.len() {
+            0 => "None".to_string(),
+            1 => values[0].clone(),
+            _ => format!("({})", values.join(", ")),
+        };
+        let mut args = vec!["store".to_string()];
+        args.extend(values);
+        src.push_str(&format!(
+            "    assert wasm.{}({}) == {}\n",
+            case.func.name.to_snake_case(),
+            args.join(", "),
+            expected,
+        ));
+    }
+    src.push_str("\nif __name__ == '__main__':\n    run(sys.argv[1])\n");
+    src
+}
+
+#[cfg(feature = "wit-bindgen-gen-wasmtime-py")]
+fn py_value(iface: &Interface, ty: &Type, val: &Value) -> String {
+    let id = match (ty, val) {
+        (Type::Id(id), _) => *id,
+        (_, Value::F32(f)) => return float(f64::from(*f), "float('inf')"),
+        (_, Value::F64(f)) => return float(*f, "float('inf')"),
+        (_, Value::Char(c)) => return string(&c.to_string(), py_escape),
+        (_, val) => return int(val),
+    };
+    let ty = &iface.types[id];
+    let name = || ty.name.as_ref().unwrap().to_camel_case();
+    match (&ty.kind, val) {
+        (TypeDefKind::Type(t), _) => py_value(iface, t, val),
+        (TypeDefKind::List(_), Value::String(s)) => string(s, py_escape),
+        (TypeDefKind::List(t), Value::List(vals)) => {
+            let vals = vals.iter().map(|v| py_value(iface, t, v));
+            let vals = vals.collect::<Vec<_>>().join(", ");
+            match t {
+                Type::U8 => format!("bytes([{}])", vals),
+                _ => format!("[{}]", vals),
+            }
+        }
+        (TypeDefKind::Record(_), Value::Flags(flags)) => {
+            format!("e.{}({})", name(), flag_bits(flags))
+        }
+        (TypeDefKind::Record(r), Value::Record(vals)) if r.is_tuple() => {
+            if vals.is_empty() {
+                return "None".to_string();
+            }
+            let vals = r.fields.iter().zip(vals);
+            let vals = vals.map(|(f, v)| py_value(iface, &f.ty, v) + ",");
+            format!("({})", vals.collect::<String>())
+        }
+        (TypeDefKind::Record(r), Value::Record(vals)) => {
+            let vals = r
+                .fields
+                .iter()
+                .zip(vals)
+                .map(|(f, v)| py_value(iface, &f.ty, v));
+            format!("e.{}({})", name(), vals.collect::<Vec<_>>().join(", "))
+        }
+        (TypeDefKind::Variant(_), Value::Bool(true)) => "True".to_string(),
+        (TypeDefKind::Variant(_), Value::Bool(false)) => "False".to_string(),
+        (TypeDefKind::Variant(v), Value::Variant { case, payload }) => {
+            let payload = match (&v.cases[*case].ty, payload) {
+                (Some(t), Some(val)) => Some(py_value(iface, t, val)),
+                _ => None,
+            };
+            if v.is_enum() && ty.name.is_some() {
+                format!("e.{}({})", name(), case)
+            } else if v.as_option().is_some() {
+                payload.unwrap_or_else(|| "None".to_string())
+            } else if v.as_expected().is_some() {
+                let case = if *case == 0 { "Ok" } else { "Err" };
+                let payload = payload.unwrap_or_else(|| "None".to_string());
+                format!("e.{}({})", case, payload)
+            } else {
+                format!(
+                    "e.{}{}({})",
+                    name(),
+                    v.cases[*case].name.to_camel_case(),
+                    payload.unwrap_or_default()
+                )
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(feature = "wit-bindgen-gen-wasmtime-py")]
+fn py_escape(c: char) -> String {
+    format!("\\U{:08x}", c as u32)
+}
+
+/// Formats an integer in the decimal syntax common to JS and Python.
+#[cfg(any(
+    feature = "wit-bindgen-gen-js",
+    feature = "wit-bindgen-gen-wasmtime-py"
+))]
+fn int(val: &Value) -> String {
+    match val {
+        Value::U8(i) => i.to_string(),
+        Value::S8(i) => i.to_string(),
+        Value::U16(i) => i.to_string(),
+        Value::S16(i) => i.to_string(),
+        Value::U32(i) => i.to_string(),
+        Value::S32(i) => i.to_string(),
+        Value::U64(i) => i.to_string(),
+        Value::S64(i) => i.to_string(),
+        _ => unreachable!(),
+    }
+}
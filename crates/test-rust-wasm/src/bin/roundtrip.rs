@@ -0,0 +1,5 @@
+// A guest for one of the round-trip tests in `tests/roundtrip`, whose source
+// is generated by the build script of `test-helpers`.
+include!(env!("ROUNDTRIP_GUEST"));
+
+fn main() {}
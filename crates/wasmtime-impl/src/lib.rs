@@ -63,6 +63,7 @@ mod kw {
     syn::custom_keyword!(src);
     syn::custom_keyword!(paths);
     syn::custom_keyword!(custom_error);
+    syn::custom_keyword!(mock);
 }
 
 impl Parse for Opts {
@@ -82,6 +83,7 @@ impl Parse for Opts {
                     ConfigField::Interfaces(v) => interfaces = v,
                     ConfigField::Async(v) => opts.async_ = v,
                     ConfigField::CustomError(v) => opts.custom_error = v,
+                    ConfigField::Mock(v) => opts.mock = v,
                 }
             }
             if interfaces.is_empty() {
@@ -117,6 +119,7 @@ enum ConfigField {
     Interfaces(Vec<Interface>),
     Async(wit_bindgen_gen_wasmtime::Async),
     CustomError(bool),
+    Mock(bool),
 }
 
 impl Parse for ConfigField {
@@ -172,6 +175,10 @@ impl Parse for ConfigField {
             Ok(ConfigField::CustomError(
                 input.parse::<syn::LitBool>()?.value,
             ))
+        } else if l.peek(kw::mock) {
+            input.parse::<kw::mock>()?;
+            input.parse::<Token![:]>()?;
+            Ok(ConfigField::Mock(input.parse::<syn::LitBool>()?.value))
         } else {
             Err(l.error())
         }
@@ -0,0 +1,368 @@
+//! Calling the exports of an instance, and defining the functions it imports,
+//! through an `Interface` that's only known at runtime rather than through
+//! bindings generated by `import!` and `export!`.
+//!
+//! Arguments and results are passed as dynamically-typed [`Value`]s, which are
+//! lowered into and lifted out of the instance's memory by the canonical ABI
+//! interpreter in `wit_parser::interpret`.
+
+use crate::rt::RawMem;
+use crate::BorrowChecker;
+use anyhow::{anyhow, bail, Result};
+use std::convert::TryFrom;
+use std::sync::Arc;
+use wasmtime::{
+    AsContextMut, Caller, Func, FuncType, Instance, Linker, Memory, Trap, TypedFunc, Val, ValType,
+};
+use wit_parser::abi::{AbiVariant, WasmType};
+use wit_parser::interpret::{self, LinearMemory, WasmValue};
+use wit_parser::InterfaceId;
+
+pub use wit_parser::interpret::Value;
+pub use wit_parser::Interface;
+
+type Realloc = TypedFunc<(i32, i32, i32, i32), i32>;
+type Free = TypedFunc<(i32, i32, i32), ()>;
+
+/// The exports of an instance which implements an `Interface`.
+///
+/// Handles to resources defined by the instance are represented with
+/// `Value::Handle`, holding the instance's own index for the resource. Owned
+/// handles returned by the instance must eventually be passed to
+/// [`Exports::drop_handle`].
+pub struct Exports {
+    iface: Interface,
+    instance: Instance,
+    memory: Option<Memory>,
+    realloc: Option<Realloc>,
+    free: Option<Free>,
+}
+
+impl Exports {
+    /// Looks up the exports of `instance` needed to call the functions of
+    /// `iface` and of the interfaces nested within it, failing if any of the
+    /// functions aren't exported.
+    pub fn new(
+        mut store: impl AsContextMut,
+        instance: &Instance,
+        iface: Interface,
+    ) -> Result<Self> {
+        let mut store = store.as_context_mut();
+        for (_, iface) in interfaces(&iface) {
+            for func in iface.functions.iter() {
+                let name = iface.export_name(&func.name);
+                if instance.get_func(&mut store, &name).is_none() {
+                    bail!("`{}` export not available", name);
+                }
+            }
+        }
+        let memory = instance.get_memory(&mut store, "memory");
+        let realloc = instance
+            .get_typed_func(&mut store, "canonical_abi_realloc")
+            .ok();
+        let free = instance
+            .get_typed_func(&mut store, "canonical_abi_free")
+            .ok();
+        Ok(Exports {
+            iface,
+            instance: *instance,
+            memory,
+            realloc,
+            free,
+        })
+    }
+
+    pub fn interface(&self) -> &Interface {
+        &self.iface
+    }
+
+    /// Calls the function named `name` with `args`, returning its results.
+    ///
+    /// Functions of resources are named `resource::function`, and functions
+    /// of nested interfaces are named as they're exported, like
+    /// `outer::inner#function`.
+    pub fn call(
+        &self,
+        mut store: impl AsContextMut,
+        name: &str,
+        args: &[Value],
+    ) -> Result<Vec<Value>> {
+        let (iface, func) = interfaces(&self.iface)
+            .into_iter()
+            .find_map(|(_, iface)| {
+                let func = iface
+                    .functions
+                    .iter()
+                    .find(|f| iface.export_name(&f.name) == name)?;
+                Some((iface, func))
+            })
+            .ok_or_else(|| anyhow!("no function named `{}` in `{}`", name, self.iface.name))?;
+        let export = self.get_func(&mut store, &iface.export_name(&func.name))?;
+        let mut memory = InstanceMemory {
+            store: store.as_context_mut(),
+            memory: self.memory,
+            realloc: self.realloc.clone(),
+            free: self.free.clone(),
+        };
+        interpret::call_wasm(
+            iface,
+            AbiVariant::GuestExport,
+            func,
+            &mut memory,
+            args,
+            |memory, args| {
+                let params = args.iter().map(|arg| to_val(*arg)).collect::<Vec<_>>();
+                let mut results = vec![Val::I32(0); export.ty(&memory.store).results().len()];
+                export.call(&mut memory.store, &params, &mut results)?;
+                results.iter().map(from_val).collect()
+            },
+        )
+    }
+
+    /// Drops an owned handle to `resource`, which was returned by the
+    /// instance.
+    pub fn drop_handle(
+        &self,
+        mut store: impl AsContextMut,
+        resource: &str,
+        handle: u32,
+    ) -> Result<()> {
+        let name = format!("canonical_abi_drop_{}", resource);
+        let dtor = self
+            .instance
+            .get_typed_func::<i32, (), _>(&mut store, &name)
+            .map_err(|_| anyhow!("`{}` export not available", name))?;
+        dtor.call(&mut store, handle as i32)?;
+        Ok(())
+    }
+
+    fn get_func(&self, store: impl AsContextMut, name: &str) -> Result<Func> {
+        self.instance
+            .get_func(store, name)
+            .ok_or_else(|| anyhow!("`{}` export not available", name))
+    }
+}
+
+/// Defines each function of `iface` and of the interfaces nested within it in
+/// `linker` for instances to import, implemented by calling `imp` with the
+/// function's name and arguments.
+///
+/// Functions of resources are named `resource::function`, and functions of
+/// nested interfaces are named as they're exported, like
+/// `outer::inner#function`. Handles to resources are passed to and from `imp`
+/// as `Value::Handle` with whatever index `imp` chooses to give them. When the
+/// instance drops or clones a handle, `imp` is called as `resource::drop` or
+/// `resource::clone` with the handle, and in the latter case must return the
+/// new handle.
+pub fn add_to_linker<T>(
+    linker: &mut Linker<T>,
+    iface: Interface,
+    imp: impl Fn(&str, Vec<Value>) -> Result<Vec<Value>> + Send + Sync + 'static,
+) -> Result<()> {
+    let iface = Arc::new(iface);
+    let imp = Arc::new(imp);
+    for (path, nested) in interfaces(&iface) {
+        define(linker, &iface, path, nested, &imp)?;
+    }
+    Ok(())
+}
+
+/// Defines the functions and resources of the interface `nested`, found at
+/// `path` within `iface`, for `add_to_linker`.
+fn define<T, F>(
+    linker: &mut Linker<T>,
+    iface: &Arc<Interface>,
+    path: Vec<InterfaceId>,
+    nested: &Interface,
+    imp: &Arc<F>,
+) -> Result<()>
+where
+    F: Fn(&str, Vec<Value>) -> Result<Vec<Value>> + Send + Sync + 'static,
+{
+    let path = Arc::new(path);
+    for (i, func) in nested.functions.iter().enumerate() {
+        if func.is_async {
+            bail!("async function `{}` isn't supported", func.name);
+        }
+        let sig = nested.wasm_signature(AbiVariant::GuestImport, func);
+        let ty = FuncType::new(
+            sig.params.iter().map(|ty| val_type(*ty)),
+            sig.results.iter().map(|ty| val_type(*ty)),
+        );
+        let iface = iface.clone();
+        let path = path.clone();
+        let name = nested.export_name(&func.name).into_owned();
+        let imp = imp.clone();
+        linker.func_new(
+            nested.module(),
+            &func.name,
+            ty,
+            move |mut caller: Caller<'_, T>, params, results| {
+                let iface = lookup(&iface, &path);
+                let func = &iface.functions[i];
+                let memory = caller.get_export("memory").and_then(|e| e.into_memory());
+                let realloc = match caller.get_export("canonical_abi_realloc") {
+                    Some(realloc) => Some(
+                        realloc
+                            .into_func()
+                            .ok_or_else(|| {
+                                anyhow!("`canonical_abi_realloc` export not a function")
+                            })?
+                            .typed(&caller)?,
+                    ),
+                    None => None,
+                };
+                let mut memory = InstanceMemory {
+                    store: caller,
+                    memory,
+                    realloc,
+                    free: None,
+                };
+                let args = params.iter().map(from_val).collect::<Result<Vec<_>>>()?;
+                let ret = interpret::call_interface(
+                    iface,
+                    AbiVariant::GuestImport,
+                    func,
+                    &mut memory,
+                    &args,
+                    |args| imp(&name, args.to_vec()),
+                )?;
+                for (slot, val) in results.iter_mut().zip(ret) {
+                    *slot = to_val(val);
+                }
+                Ok(())
+            },
+        )?;
+    }
+    for (_, resource) in nested.resources.iter() {
+        let drop_name = nested
+            .export_name(&format!("{}::drop", resource.name))
+            .into_owned();
+        let imp2 = imp.clone();
+        linker.func_wrap(
+            "canonical_abi",
+            &format!("resource_drop_{}", resource.name),
+            move |idx: u32| -> Result<(), Trap> {
+                imp2(&drop_name, vec![Value::Handle(idx)])?;
+                Ok(())
+            },
+        )?;
+        let clone_name = nested
+            .export_name(&format!("{}::clone", resource.name))
+            .into_owned();
+        let imp2 = imp.clone();
+        linker.func_wrap(
+            "canonical_abi",
+            &format!("resource_clone_{}", resource.name),
+            move |idx: u32| -> Result<u32, Trap> {
+                match imp2(&clone_name, vec![Value::Handle(idx)])?.as_slice() {
+                    [Value::Handle(idx)] => Ok(*idx),
+                    other => Err(Trap::new(format!(
+                        "`{}` returned {:?} rather than a handle",
+                        clone_name, other
+                    ))),
+                }
+            },
+        )?;
+    }
+    Ok(())
+}
+
+/// Returns `iface` followed by all of the interfaces nested within it, each
+/// with the ids of the interfaces leading to it from `iface`.
+fn interfaces(iface: &Interface) -> Vec<(Vec<InterfaceId>, &Interface)> {
+    let mut ret = vec![(Vec::new(), iface)];
+    for (id, nested) in iface.interfaces.iter() {
+        ret.extend(interfaces(nested).into_iter().map(|(mut path, nested)| {
+            path.insert(0, id);
+            (path, nested)
+        }));
+    }
+    ret
+}
+
+/// Returns the interface nested within `iface` at `path`, as returned by
+/// `interfaces`.
+fn lookup<'a>(iface: &'a Interface, path: &[InterfaceId]) -> &'a Interface {
+    path.iter().fold(iface, |iface, id| &iface.interfaces[*id])
+}
+
+/// The memory of an instance, which allocates and frees with the instance's
+/// `canonical_abi_realloc` and `canonical_abi_free` exports.
+struct InstanceMemory<S> {
+    store: S,
+    memory: Option<Memory>,
+    realloc: Option<Realloc>,
+    free: Option<Free>,
+}
+
+impl<S> InstanceMemory<S> {
+    fn memory(&self) -> Result<Memory> {
+        self.memory
+            .ok_or_else(|| anyhow!("`memory` export not available"))
+    }
+}
+
+impl<S: AsContextMut> LinearMemory for InstanceMemory<S> {
+    fn read(&mut self, addr: i32, dst: &mut [u8]) -> Result<()> {
+        let memory = self.memory()?;
+        let mut bc = BorrowChecker::new(memory.data_mut(self.store.as_context_mut()));
+        dst.copy_from_slice(bc.slice(addr, i32::try_from(dst.len())?)?);
+        Ok(())
+    }
+
+    fn write(&mut self, addr: i32, src: &[u8]) -> Result<()> {
+        let memory = self.memory()?;
+        memory
+            .data_mut(self.store.as_context_mut())
+            .store_many(addr, src)?;
+        Ok(())
+    }
+
+    fn alloc(&mut self, size: usize, align: usize) -> Result<i32> {
+        let realloc = self
+            .realloc
+            .as_ref()
+            .ok_or_else(|| anyhow!("`canonical_abi_realloc` export not available"))?;
+        let size = i32::try_from(size)?;
+        Ok(realloc.call(self.store.as_context_mut(), (0, 0, align as i32, size))?)
+    }
+
+    fn free(&mut self, addr: i32, size: usize, align: usize) -> Result<()> {
+        let free = self
+            .free
+            .as_ref()
+            .ok_or_else(|| anyhow!("`canonical_abi_free` export not available"))?;
+        let size = i32::try_from(size)?;
+        free.call(self.store.as_context_mut(), (addr, size, align as i32))?;
+        Ok(())
+    }
+}
+
+fn val_type(ty: WasmType) -> ValType {
+    match ty {
+        WasmType::I32 => ValType::I32,
+        WasmType::I64 => ValType::I64,
+        WasmType::F32 => ValType::F32,
+        WasmType::F64 => ValType::F64,
+    }
+}
+
+fn to_val(value: WasmValue) -> Val {
+    match value {
+        WasmValue::I32(i) => Val::I32(i),
+        WasmValue::I64(i) => Val::I64(i),
+        WasmValue::F32(f) => Val::F32(f.to_bits()),
+        WasmValue::F64(f) => Val::F64(f.to_bits()),
+    }
+}
+
+fn from_val(val: &Val) -> Result<WasmValue> {
+    Ok(match val {
+        Val::I32(i) => WasmValue::I32(*i),
+        Val::I64(i) => WasmValue::I64(*i),
+        Val::F32(bits) => WasmValue::F32(f32::from_bits(*bits)),
+        Val::F64(bits) => WasmValue::F64(f64::from_bits(*bits)),
+        other => bail!("unexpected {:?} value", other.ty()),
+    })
+}
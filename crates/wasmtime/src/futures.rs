@@ -0,0 +1,195 @@
+//! Host-side support for `async` wit functions.
+//!
+//! Guest modules implement `async` functions with a callback-based ABI: async
+//! imports receive a function-table index and context to invoke once the
+//! host's results are ready, and async exports report their results through
+//! the `canonical_abi::async_export_done` intrinsic. The [`Async`] type here
+//! tracks both halves of that protocol for a store and is driven by the
+//! futures returned from generated bindings for async exports.
+
+use crate::slab::Slab;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use wasmtime::{AsContextMut, StoreContextMut, Trap};
+
+/// A boxed future, used as the return value of host implementations of `async`
+/// wit functions.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Work to perform on a store once the host future for an `async` import has
+/// resolved.
+///
+/// This lowers the host's results into the guest and then invokes the guest's
+/// completion callback for the import.
+pub struct ImportCompletion<T>(
+    Box<dyn for<'a> FnOnce(StoreContextMut<'a, T>) -> BoxFuture<'a, Result<(), Trap>> + Send>,
+);
+
+impl<T> ImportCompletion<T> {
+    pub fn new<F>(f: F) -> ImportCompletion<T>
+    where
+        F: for<'a> FnOnce(StoreContextMut<'a, T>) -> BoxFuture<'a, Result<(), Trap>>
+            + Send
+            + 'static,
+    {
+        ImportCompletion(Box::new(f))
+    }
+}
+
+/// State for `async` wit functions which must be stored within the data `T`
+/// of a `Store<T>`.
+///
+/// Generated bindings which use `async` functions require access to this
+/// structure through their `get` or `get_state` closures. The same `Async<T>`
+/// must be used for both the imports and exports of a module since host
+/// imports called from an async export are only completed while that export
+/// is being awaited.
+pub struct Async<T> {
+    imports: Vec<BoxFuture<'static, ImportCompletion<T>>>,
+    exports: Slab<Export>,
+}
+
+enum Export {
+    /// The export has been called but hasn't finished yet, and will produce
+    /// this many 8-byte result slots.
+    Pending(usize),
+    /// The export has finished and these are its raw results.
+    Done(Vec<u8>),
+}
+
+impl<T> Default for Async<T> {
+    fn default() -> Async<T> {
+        Async {
+            imports: Vec::new(),
+            exports: Slab::default(),
+        }
+    }
+}
+
+impl<T> Async<T> {
+    /// Enqueues the host's future for an async import, to be completed the
+    /// next time an async export is awaited.
+    pub fn spawn_import(
+        &mut self,
+        future: impl Future<Output = ImportCompletion<T>> + Send + 'static,
+    ) {
+        self.imports.push(Box::pin(future));
+    }
+
+    /// Allocates a context for a call to an async export which will produce
+    /// `results` result slots.
+    pub fn start_export(&mut self, results: usize) -> u32 {
+        self.exports.insert(Export::Pending(results))
+    }
+
+    /// Implementation of the `canonical_abi::async_export_done` intrinsic,
+    /// recording the results of the export call identified by `ctx` which are
+    /// located at `ptr` in `memory`.
+    pub fn export_done(&mut self, ctx: i32, memory: &[u8], ptr: i32) -> Result<(), Trap> {
+        let slot = self
+            .exports
+            .get_mut(ctx as u32)
+            .ok_or_else(|| Trap::new("invalid async export context"))?;
+        let results = match slot {
+            Export::Pending(results) => *results,
+            Export::Done(_) => return Err(Trap::new("async export completed twice")),
+        };
+        let bytes = memory
+            .get(ptr as u32 as usize..)
+            .and_then(|m| m.get(..results * 8))
+            .ok_or_else(|| Trap::new("out of bounds read"))?;
+        *slot = Export::Done(bytes.to_vec());
+        Ok(())
+    }
+
+    fn take_export(&mut self, ctx: u32) -> Option<Vec<u8>> {
+        match self.exports.get(ctx)? {
+            Export::Done(_) => {}
+            Export::Pending(_) => return None,
+        }
+        match self.exports.remove(ctx) {
+            Some(Export::Done(results)) => Some(results),
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// Waits for the async export call identified by `ctx` to finish, returning
+/// the raw bytes of its results.
+///
+/// While waiting this will drive all pending host futures for async imports,
+/// reentering the guest with their results as they complete.
+pub async fn wait_export<T>(
+    mut store: StoreContextMut<'_, T>,
+    get: impl Fn(&mut T) -> &mut Async<T>,
+    ctx: u32,
+) -> Result<Vec<u8>, Trap> {
+    let result = drive_export(&mut store, &get, ctx).await;
+    if result.is_err() {
+        get(store.data_mut()).exports.remove(ctx);
+    }
+    result
+}
+
+async fn drive_export<T>(
+    store: &mut StoreContextMut<'_, T>,
+    get: &impl Fn(&mut T) -> &mut Async<T>,
+    ctx: u32,
+) -> Result<Vec<u8>, Trap> {
+    loop {
+        if let Some(results) = get(store.data_mut()).take_export(ctx) {
+            return Ok(results);
+        }
+        let ready = ReadyImports {
+            store: &mut *store,
+            get,
+        }
+        .await?;
+        for completion in ready {
+            (completion.0)(store.as_context_mut()).await?;
+        }
+    }
+}
+
+/// A future which resolves to the completions of all host futures for async
+/// imports which are ready.
+struct ReadyImports<'a, 'b, T, G> {
+    store: &'a mut StoreContextMut<'b, T>,
+    get: &'a G,
+}
+
+impl<T, G> Future for ReadyImports<'_, '_, T, G>
+where
+    G: Fn(&mut T) -> &mut Async<T>,
+{
+    type Output = Result<Vec<ImportCompletion<T>>, Trap>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let me = self.get_mut();
+        let state = (me.get)(me.store.data_mut());
+        if state.imports.is_empty() {
+            // Nothing can ever complete the export at this point, so rather
+            // than hang forever report an error.
+            return Poll::Ready(Err(Trap::new(
+                "async export cannot complete: no pending async imports",
+            )));
+        }
+        let mut ready = Vec::new();
+        let mut i = 0;
+        while i < state.imports.len() {
+            match state.imports[i].as_mut().poll(cx) {
+                Poll::Ready(completion) => {
+                    state.imports.swap_remove(i);
+                    ready.push(completion);
+                }
+                Poll::Pending => i += 1,
+            }
+        }
+        if ready.is_empty() {
+            Poll::Pending
+        } else {
+            Poll::Ready(Ok(ready))
+        }
+    }
+}
@@ -1,13 +1,17 @@
 use crate::slab::Slab;
-use std::cell::RefCell;
 use std::convert::TryFrom;
 use std::mem;
-use std::rc::Rc;
-use wasmtime::{Memory, Trap};
+use std::sync::{Arc, Mutex};
+use wasmtime::{AsContextMut, Memory, StoreContextMut, Trap};
 
+/// Host-side state backing the `wit_canonical_buffer_abi` intrinsics.
+///
+/// Buffers passed to wasm exports are registered here for the duration of a
+/// call through a [`BufferTransaction`], and wasm then refers to them by
+/// handle when it reads from or writes to them.
 #[derive(Default, Clone)]
 pub struct BufferGlue {
-    inner: Rc<RefCell<Inner>>,
+    inner: Arc<Mutex<Inner>>,
 }
 
 #[derive(Default)]
@@ -16,6 +20,16 @@ struct Inner {
     out_buffers: Slab<Buffer<Output>>,
 }
 
+// SAFETY: the raw pointers in `Input` and `Output` are what keep `Inner` from
+// being `Send`. Each one is inserted by a `BufferTransaction` and borrows data
+// for that transaction's `'call` lifetime, and the transaction removes every
+// handle it inserted when it's dropped. The only places they're dereferenced
+// are `in_read` and `out_write`, which wasm reaches through the store that owns
+// this glue while the call that created the transaction is running, so they're
+// never touched from a thread other than the one driving that call and never
+// outlive the borrowed data.
+unsafe impl Send for Inner {}
+
 struct Buffer<T> {
     len: u32,
     kind: T,
@@ -24,7 +38,15 @@ struct Buffer<T> {
 enum Input {
     Bytes(*const u8, usize),
     General {
-        shim: unsafe fn([usize; 2], *const u8, &Memory, i32, u32, &mut u32) -> Result<(), Trap>,
+        shim: unsafe fn(
+            [usize; 2],
+            *const u8,
+            *mut u8,
+            &Memory,
+            i32,
+            u32,
+            &mut u32,
+        ) -> Result<(), Trap>,
         iterator: [usize; 2],
         serialize: *const u8,
     },
@@ -33,7 +55,7 @@ enum Input {
 enum Output {
     Bytes(*mut u8, usize),
     General {
-        shim: unsafe fn(*mut u8, *const u8, &Memory, i32, u32) -> Result<(), Trap>,
+        shim: unsafe fn(*mut u8, *const u8, *mut u8, &Memory, i32, u32) -> Result<(), Trap>,
         dst: *mut u8,
         deserialize: *const u8,
     },
@@ -47,8 +69,9 @@ impl BufferGlue {
         }
     }
 
+    /// Implementation of the canonical abi "in_len" function
     pub fn in_len(&self, handle: u32) -> Result<u32, Trap> {
-        let mut inner = self.inner.borrow_mut();
+        let mut inner = self.inner.lock().unwrap();
         let b = inner
             .in_buffers
             .get_mut(handle)
@@ -57,15 +80,22 @@ impl BufferGlue {
     }
 
     /// Implementation of the canonical abi "in_read" function
-    pub fn in_read(
+    ///
+    /// # Safety
+    ///
+    /// The `store` provided must be the same store, with the same data type,
+    /// that the transaction which created `handle` was used with. Buffers
+    /// registered with [`BufferTransaction::push_in`] cast `store` back to
+    /// that transaction's data type.
+    pub unsafe fn in_read<S: AsContextMut>(
         &self,
         handle: u32,
-        store: impl wasmtime::AsContextMut,
-        memory: &wasmtime::Memory,
+        mut store: S,
+        memory: &Memory,
         base: u32,
         len: u32,
     ) -> Result<(), Trap> {
-        let mut inner = self.inner.borrow_mut();
+        let mut inner = self.inner.lock().unwrap();
         let b = inner
             .in_buffers
             .get_mut(handle)
@@ -75,67 +105,76 @@ impl BufferGlue {
                 "more items requested from in-buffer than are available",
             ));
         }
-        unsafe {
-            match &mut b.kind {
-                Input::Bytes(ptr, elem_size) => {
-                    let write_size = (len as usize) * *elem_size;
-                    memory
-                        .write(
-                            store,
-                            base as usize,
-                            std::slice::from_raw_parts(*ptr, write_size),
-                        )
-                        .map_err(|_| Trap::new("out-of-bounds write while reading in-buffer"))?;
-                    *ptr = (*ptr).add(write_size);
-                    b.len -= len;
-                    Ok(())
-                }
-                &mut Input::General {
-                    shim,
+        match &mut b.kind {
+            Input::Bytes(ptr, elem_size) => {
+                let write_size = (len as usize) * *elem_size;
+                memory
+                    .write(
+                        store,
+                        base as usize,
+                        std::slice::from_raw_parts(*ptr, write_size),
+                    )
+                    .map_err(|_| Trap::new("out-of-bounds write while reading in-buffer"))?;
+                *ptr = (*ptr).add(write_size);
+                b.len -= len;
+                Ok(())
+            }
+            &mut Input::General {
+                shim,
+                iterator,
+                serialize,
+            } => {
+                drop(inner);
+                let mut processed = 0;
+                let mut cx = store.as_context_mut();
+                let res = shim(
                     iterator,
                     serialize,
-                } => {
-                    drop(inner);
-                    let mut processed = 0;
-                    let res = shim(
-                        iterator,
-                        serialize,
-                        memory,
-                        base as i32,
-                        len,
-                        &mut processed,
-                    );
-                    self.inner
-                        .borrow_mut()
-                        .in_buffers
-                        .get_mut(handle)
-                        .expect("should still be there")
-                        .len -= processed;
-                    res
-                }
+                    &mut cx as *mut StoreContextMut<'_, S::Data> as *mut u8,
+                    memory,
+                    base as i32,
+                    len,
+                    &mut processed,
+                );
+                self.inner
+                    .lock()
+                    .unwrap()
+                    .in_buffers
+                    .get_mut(handle)
+                    .expect("should still be there")
+                    .len -= processed;
+                res
             }
         }
     }
 
+    /// Implementation of the canonical abi "out_len" function
     pub fn out_len(&self, handle: u32) -> Result<u32, Trap> {
-        let mut inner = self.inner.borrow_mut();
+        let mut inner = self.inner.lock().unwrap();
         let b = inner
             .out_buffers
             .get_mut(handle)
-            .ok_or_else(|| Trap::new("out in-buffer handle"))?;
+            .ok_or_else(|| Trap::new("invalid out-buffer handle"))?;
         Ok(b.len)
     }
 
     /// Implementation of the canonical abi "out_write" function
-    pub fn out_write(
+    ///
+    /// # Safety
+    ///
+    /// The `store` provided must be the same store, with the same data type,
+    /// that the transaction which created `handle` was used with. Buffers
+    /// registered with [`BufferTransaction::push_out`] cast `store` back to
+    /// that transaction's data type.
+    pub unsafe fn out_write<S: AsContextMut>(
         &self,
         handle: u32,
-        store: impl wasmtime::AsContext,
-        memory: &wasmtime::Memory,
+        mut store: S,
+        memory: &Memory,
         base: u32,
         len: u32,
     ) -> Result<(), Trap> {
-        let mut inner = self.inner.borrow_mut();
+        let mut inner = self.inner.lock().unwrap();
         let b = inner
             .out_buffers
             .get_mut(handle)
@@ -145,30 +184,36 @@ impl BufferGlue {
                 "more items written to out-buffer than are available",
             ));
         }
-        unsafe {
-            match &mut b.kind {
-                Output::Bytes(ptr, elem_size) => {
-                    let read_size = (len as usize) * *elem_size;
-                    memory
-                        .read(
-                            &store,
-                            base as usize,
-                            std::slice::from_raw_parts_mut(*ptr, read_size),
-                        )
-                        .map_err(|_| Trap::new("out-of-bounds read while writing to out-buffer"))?;
-                    *ptr = (*ptr).add(read_size);
-                    b.len -= len;
-                    Ok(())
-                }
-                &mut Output::General {
-                    shim,
+        match &mut b.kind {
+            Output::Bytes(ptr, elem_size) => {
+                let read_size = (len as usize) * *elem_size;
+                memory
+                    .read(
+                        &store,
+                        base as usize,
+                        std::slice::from_raw_parts_mut(*ptr, read_size),
+                    )
+                    .map_err(|_| Trap::new("out-of-bounds read while writing to out-buffer"))?;
+                *ptr = (*ptr).add(read_size);
+                b.len -= len;
+                Ok(())
+            }
+            &mut Output::General {
+                shim,
+                dst,
+                deserialize,
+            } => {
+                b.len -= len;
+                drop(inner);
+                let mut cx = store.as_context_mut();
+                shim(
                     dst,
                     deserialize,
-                } => {
-                    shim(dst, deserialize, memory, base as i32, len)?;
-                    b.len -= len;
-                    Ok(())
-                }
+                    &mut cx as *mut StoreContextMut<'_, S::Data> as *mut u8,
+                    memory,
+                    base as i32,
+                    len,
+                )
             }
         }
     }
@@ -184,7 +229,7 @@ impl<'call> BufferTransaction<'call> {
     where
         'a: 'call,
     {
-        let mut inner = self.glue.inner.borrow_mut();
+        let mut inner = self.glue.inner.lock().unwrap();
         let handle = inner.in_buffers.insert(Buffer {
             len: u32::try_from(buffer.len()).unwrap(),
             kind: Input::Bytes(buffer.as_ptr() as *const u8, mem::size_of::<T>()),
@@ -193,20 +238,25 @@ impl<'call> BufferTransaction<'call> {
         return handle as i32;
     }
 
-    pub unsafe fn push_in<'a, T, F>(
+    /// Registers `iter` as an in-buffer whose items are serialized with
+    /// `write` as wasm reads them.
+    ///
+    /// `unsafe` because the `D` type here must match the data type of the
+    /// store that's later passed to [`BufferGlue::in_read`].
+    pub unsafe fn push_in<'a, T, F, D>(
         &mut self,
         iter: &'a mut (dyn ExactSizeIterator<Item = T> + 'a),
         write: &'a F,
     ) -> i32
     where
-        F: Fn(&Memory, i32, T) -> Result<i32, Trap> + 'a,
+        F: Fn(StoreContextMut<'_, D>, &Memory, i32, T) -> Result<i32, Trap> + 'a,
         'a: 'call,
     {
-        let mut inner = self.glue.inner.borrow_mut();
+        let mut inner = self.glue.inner.lock().unwrap();
         let handle = inner.in_buffers.insert(Buffer {
             len: u32::try_from(iter.len()).unwrap(),
             kind: Input::General {
-                shim: shim::<T, F>,
+                shim: shim::<T, F, D>,
                 iterator: mem::transmute(iter),
                 serialize: write as *const F as *const u8,
             },
@@ -214,22 +264,24 @@ impl<'call> BufferTransaction<'call> {
         self.handles.push((false, handle));
         return handle as i32;
 
-        unsafe fn shim<T, F>(
+        unsafe fn shim<T, F, D>(
             iter: [usize; 2],
             serialize: *const u8,
+            store: *mut u8,
             memory: &Memory,
             mut offset: i32,
             len: u32,
             processed: &mut u32,
         ) -> Result<(), Trap>
         where
-            F: Fn(&Memory, i32, T) -> Result<i32, Trap>,
+            F: Fn(StoreContextMut<'_, D>, &Memory, i32, T) -> Result<i32, Trap>,
         {
             let iter = mem::transmute::<_, &mut dyn ExactSizeIterator<Item = T>>(iter);
             let write = &*(serialize as *const F);
+            let store = &mut *(store as *mut StoreContextMut<'_, D>);
             for _ in 0..len {
                 let item = iter.next().unwrap();
-                offset += write(memory, offset, item)?;
+                offset += write(store.as_context_mut(), memory, offset, item)?;
                 *processed += 1;
             }
             Ok(())
@@ -240,7 +292,7 @@ impl<'call> BufferTransaction<'call> {
     where
         'a: 'call,
     {
-        let mut inner = self.glue.inner.borrow_mut();
+        let mut inner = self.glue.inner.lock().unwrap();
         let handle = inner.out_buffers.insert(Buffer {
             len: u32::try_from(buffer.len()).unwrap(),
             kind: Output::Bytes(buffer.as_mut_ptr() as *mut u8, mem::size_of::<T>()),
@@ -249,16 +301,21 @@ impl<'call> BufferTransaction<'call> {
         return handle as i32;
     }
 
-    pub unsafe fn push_out<'a, T, F>(&mut self, dst: &'a mut Vec<T>, read: &'a F) -> i32
+    /// Registers `dst` as an out-buffer whose items are deserialized with
+    /// `read` as wasm writes them.
+    ///
+    /// `unsafe` because the `D` type here must match the data type of the
+    /// store that's later passed to [`BufferGlue::out_write`].
+    pub unsafe fn push_out<'a, T, F, D>(&mut self, dst: &'a mut Vec<T>, read: &'a F) -> i32
     where
-        F: Fn(&Memory, i32) -> Result<(T, i32), Trap> + 'a,
+        F: Fn(StoreContextMut<'_, D>, &Memory, i32) -> Result<(T, i32), Trap> + 'a,
         'a: 'call,
     {
-        let mut inner = self.glue.inner.borrow_mut();
+        let mut inner = self.glue.inner.lock().unwrap();
         let handle = inner.out_buffers.insert(Buffer {
             len: u32::try_from(dst.capacity() - dst.len()).unwrap(),
             kind: Output::General {
-                shim: shim::<T, F>,
+                shim: shim::<T, F, D>,
                 dst: dst as *mut Vec<T> as *mut u8,
                 deserialize: read as *const F as *const u8,
             },
@@ -266,20 +323,22 @@ impl<'call> BufferTransaction<'call> {
         self.handles.push((true, handle));
         return handle as i32;
 
-        unsafe fn shim<T, F>(
+        unsafe fn shim<T, F, D>(
             dst: *mut u8,
             deserialize: *const u8,
+            store: *mut u8,
             memory: &Memory,
             mut offset: i32,
             len: u32,
         ) -> Result<(), Trap>
         where
-            F: Fn(&Memory, i32) -> Result<(T, i32), Trap>,
+            F: Fn(StoreContextMut<'_, D>, &Memory, i32) -> Result<(T, i32), Trap>,
         {
             let dst = &mut *(dst as *mut Vec<T>);
             let read = &*(deserialize as *const F);
+            let store = &mut *(store as *mut StoreContextMut<'_, D>);
             for _ in 0..len {
-                let (item, size) = read(memory, offset)?;
+                let (item, size) = read(store.as_context_mut(), memory, offset)?;
                 dst.push(item);
                 offset += size;
             }
@@ -290,7 +349,7 @@ impl<'call> BufferTransaction<'call> {
 
 impl Drop for BufferTransaction<'_> {
     fn drop(&mut self) {
-        let mut inner = self.glue.inner.borrow_mut();
+        let mut inner = self.glue.inner.lock().unwrap();
         for (out, handle) in self.handles.iter() {
             if *out {
                 inner.out_buffers.remove(*handle);
@@ -7,8 +7,11 @@ pub use tracing_lib as tracing;
 #[doc(hidden)]
 pub use {anyhow, bitflags, wasmtime};
 
+#[cfg(feature = "dynamic")]
+pub mod dynamic;
 mod error;
 pub mod exports;
+mod futures;
 pub mod imports;
 mod le;
 mod region;
@@ -16,6 +19,7 @@ mod slab;
 mod table;
 
 pub use error::GuestError;
+pub use futures::{Async, BoxFuture};
 pub use le::{Endian, Le};
 pub use region::{AllBytesValid, BorrowChecker, Region};
 pub use table::*;
@@ -32,6 +36,7 @@ unsafe impl Sync for RawMemory {}
 
 #[doc(hidden)]
 pub mod rt {
+    pub use crate::futures::{wait_export, ImportCompletion};
     use crate::slab::Slab;
     use crate::{Endian, Le};
     use std::mem;
@@ -129,6 +134,21 @@ pub mod rt {
         Ok(mem)
     }
 
+    pub fn get_table<T>(caller: &mut Caller<'_, T>, table: &str) -> Result<Table, wasmtime::Trap> {
+        let table = caller
+            .get_export(table)
+            .ok_or_else(|| {
+                let msg = format!("`{}` export not available", table);
+                Trap::new(msg)
+            })?
+            .into_table()
+            .ok_or_else(|| {
+                let msg = format!("`{}` export not a table", table);
+                Trap::new(msg)
+            })?;
+        Ok(table)
+    }
+
     pub fn bad_int(_: std::num::TryFromIntError) -> Trap {
         let msg = "out-of-bounds integer conversion";
         Trap::new(msg)
@@ -225,6 +245,14 @@ pub mod rt {
     #[derive(Debug, Copy, Clone)]
     pub struct ResourceIndex(u32);
 
+    impl ResourceIndex {
+        /// Creates an index which isn't in any `ResourceSlab`, for handles
+        /// returned by mocks of wasm exports.
+        pub fn mock(index: u32) -> ResourceIndex {
+            ResourceIndex(index)
+        }
+    }
+
     impl ResourceSlab {
         pub fn insert(&mut self, wasm: i32) -> ResourceIndex {
             ResourceIndex(self.slab.insert(Resource { wasm, refcnt: 1 }))
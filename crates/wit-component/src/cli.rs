@@ -30,11 +30,27 @@ fn parse_interface(name: Option<String>, path: &Path) -> Result<Interface> {
     let mut interface = Interface::parse_file(&path)
         .with_context(|| format!("failed to parse interface file `{}`", path.display()))?;
 
-    interface.name = name.unwrap_or_else(|| "".to_string());
+    match name {
+        Some(name) => {
+            set_module(&mut interface, &name);
+            interface.name = name;
+        }
+        None => interface.name = "".to_string(),
+    }
 
     Ok(interface)
 }
 
+/// Nested interfaces are imported and exported under their path from the
+/// top-level interface, so keep that path in sync with the interface's name.
+fn set_module(interface: &mut Interface, module: &str) {
+    for (_, nested) in interface.interfaces.iter_mut() {
+        let path = format!("{}::{}", module, nested.name);
+        set_module(nested, &path);
+        nested.module = Some(path);
+    }
+}
+
 /// WebAssembly component encoder.
 ///
 /// Encodes a WebAssembly component from a core WebAssembly module.
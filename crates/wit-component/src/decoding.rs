@@ -5,8 +5,8 @@ use wasmparser::{
     Validator, WasmFeatures,
 };
 use wit_parser::{
-    abi::Abi, validate_id, Case, Docs, Field, Function, FunctionKind, Int, Interface, Record,
-    RecordKind, Type, TypeDef, TypeDefKind, TypeId, Variant,
+    abi::Abi, validate_id, Attributes, Case, Docs, Field, Function, FunctionKind, Int, Interface,
+    Record, RecordKind, Span, Type, TypeDef, TypeDefKind, TypeId, Variant,
 };
 
 /// Represents information about a decoded WebAssembly component.
@@ -204,13 +204,13 @@ impl<'a> InterfaceDecoder<'a> {
             }
         }
 
-        for (name, index) in &self.info.exported_types {
-            let ty = match self.info.types.type_at(*index).unwrap() {
-                types::TypeDef::ComponentFunc(ty) => ty,
+        let info = self.info;
+        for (name, index) in &info.exported_types {
+            match info.types.type_at(*index).unwrap() {
+                types::TypeDef::ComponentFunc(ty) => self.add_function(name, ty)?,
+                types::TypeDef::Instance(ty) => self.add_interface(name, ty)?,
                 _ => continue,
-            };
-
-            self.add_function(name, ty)?;
+            }
         }
 
         for (name, index) in &self.info.exported_functions {
@@ -221,6 +221,56 @@ impl<'a> InterfaceDecoder<'a> {
         Ok(self.interface)
     }
 
+    fn add_interface(&mut self, name: &'a str, ty: &'a types::InstanceType) -> Result<()> {
+        validate_id(name)
+            .with_context(|| format!("interface name `{}` is not a valid identifier", name))?;
+
+        let info = self.info;
+
+        // Instance type exports are unordered, so sort them by name to keep
+        // the decoded interface deterministic.
+        let mut exports: Vec<_> = ty.exports(&info.types).iter().collect();
+        exports.sort_by_key(|(name, _)| name.as_str());
+
+        let mut decoder = InterfaceDecoder::new(info);
+        for (name, ty) in &exports {
+            if let types::ComponentEntityType::Type(id) = ty {
+                if let types::TypeDef::Interface(_) = info.types.type_from_id(*id).unwrap() {
+                    decoder.name_map.insert(*id, name.as_str());
+                }
+            }
+        }
+
+        decoder.interface.name = name.to_string();
+        decoder.interface.module = Some(match &self.interface.module {
+            Some(module) => format!("{}::{}", module, name),
+            None => name.to_string(),
+        });
+
+        for (name, ty) in &exports {
+            match ty {
+                types::ComponentEntityType::Func(id) => {
+                    match info.types.type_from_id(*id).unwrap() {
+                        types::TypeDef::ComponentFunc(ty) => decoder.add_function(name, ty)?,
+                        _ => unreachable!(),
+                    }
+                }
+                types::ComponentEntityType::Instance(id) => {
+                    match info.types.type_from_id(*id).unwrap() {
+                        types::TypeDef::Instance(ty) => decoder.add_interface(name, ty)?,
+                        _ => unreachable!(),
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let interface = decoder.interface;
+        let id = self.interface.interfaces.alloc(interface);
+        self.interface.interface_lookup.insert(name.to_string(), id);
+        Ok(())
+    }
+
     fn add_function(&mut self, func_name: &str, ty: &types::ComponentFuncType) -> Result<()> {
         validate_id(func_name)
             .with_context(|| format!("function name `{}` is not a valid identifier", func_name))?;
@@ -252,10 +302,12 @@ impl<'a> InterfaceDecoder<'a> {
             abi: Abi::Canonical,
             is_async: false,
             docs: Docs::default(),
+            attrs: Attributes::default(),
             name: func_name.to_string(),
             kind: FunctionKind::Freestanding,
             params,
             results,
+            span: Span::default(),
         });
 
         Ok(())
@@ -371,6 +423,7 @@ impl<'a> InterfaceDecoder<'a> {
                         docs: Docs::default(),
                         name: name.to_string(),
                         ty: self.decode_type(ty)?,
+                        span: Span::default(),
                     })
                 })
                 .collect::<Result<_>>()?,
@@ -412,6 +465,7 @@ impl<'a> InterfaceDecoder<'a> {
                             }
                             _ => Some(self.decode_type(&case.ty)?),
                         },
+                        span: Span::default(),
                     })
                 })
                 .collect::<Result<_>>()?,
@@ -438,6 +492,7 @@ impl<'a> InterfaceDecoder<'a> {
                         docs: Docs::default(),
                         name: i.to_string(),
                         ty: self.decode_type(ty)?,
+                        span: Span::default(),
                     })
                 })
                 .collect::<Result<_>>()?,
@@ -469,6 +524,7 @@ impl<'a> InterfaceDecoder<'a> {
                         docs: Docs::default(),
                         name: name.clone(),
                         ty: self.decode_primitive(PrimitiveInterfaceType::Bool)?,
+                        span: Span::default(),
                     })
                 })
                 .collect::<Result<_>>()?,
@@ -503,6 +559,7 @@ impl<'a> InterfaceDecoder<'a> {
                         docs: Docs::default(),
                         name: name.to_string(),
                         ty: None,
+                        span: Span::default(),
                     })
                 })
                 .collect::<Result<_>>()?,
@@ -529,6 +586,7 @@ impl<'a> InterfaceDecoder<'a> {
                         docs: Docs::default(),
                         name: i.to_string(),
                         ty: Some(self.decode_type(ty)?),
+                        span: Span::default(),
                     })
                 })
                 .collect::<Result<_>>()?,
@@ -551,11 +609,13 @@ impl<'a> InterfaceDecoder<'a> {
                     docs: Docs::default(),
                     name: "none".to_string(),
                     ty: None,
+                    span: Span::default(),
                 },
                 Case {
                     docs: Docs::default(),
                     name: "some".to_string(),
                     ty: Some(self.decode_type(ty)?),
+                    span: Span::default(),
                 },
             ],
             tag: Variant::infer_tag(2),
@@ -581,6 +641,7 @@ impl<'a> InterfaceDecoder<'a> {
                         types::InterfaceTypeRef::Primitive(PrimitiveInterfaceType::Unit) => None,
                         _ => Some(self.decode_type(ok)?),
                     },
+                    span: Span::default(),
                 },
                 Case {
                     docs: Docs::default(),
@@ -589,6 +650,7 @@ impl<'a> InterfaceDecoder<'a> {
                         types::InterfaceTypeRef::Primitive(PrimitiveInterfaceType::Unit) => None,
                         _ => Some(self.decode_type(error)?),
                     },
+                    span: Span::default(),
                 },
             ],
             tag: Variant::infer_tag(2),
@@ -602,9 +664,11 @@ impl<'a> InterfaceDecoder<'a> {
     fn alloc_type(&mut self, name: Option<String>, kind: TypeDefKind) -> TypeId {
         self.interface.types.alloc(TypeDef {
             docs: Docs::default(),
+            attrs: Attributes::default(),
             kind,
             name,
             foreign_module: None,
+            span: Span::default(),
         })
     }
 
@@ -618,11 +682,13 @@ impl<'a> InterfaceDecoder<'a> {
                             docs: Docs::default(),
                             name: "false".to_string(),
                             ty: None,
+                            span: Span::default(),
                         },
                         Case {
                             docs: Docs::default(),
                             name: "true".to_string(),
                             ty: None,
+                            span: Span::default(),
                         },
                     ],
                     tag: Int::U8,
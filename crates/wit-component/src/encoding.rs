@@ -1,5 +1,5 @@
 use crate::{
-    validation::{expected_export_name, validate_module},
+    validation::{expected_export_name, for_each_interface, location, validate_module},
     StringEncoding,
 };
 use anyhow::{bail, Context, Result};
@@ -13,8 +13,8 @@ use wasm_encoder::*;
 use wasmparser::{Validator, WasmFeatures};
 use wit_parser::{
     abi::{Abi, AbiVariant, WasmSignature, WasmType},
-    Docs, Field, Function, FunctionKind, Interface, Record, RecordKind, Type, TypeDef, TypeDefKind,
-    Variant,
+    Attributes, Docs, Field, Function, FunctionKind, Interface, Record, RecordKind, Span, Type,
+    TypeDef, TypeDefKind, Variant,
 };
 
 const INDIRECT_TABLE_NAME: &str = "$imports";
@@ -356,8 +356,9 @@ impl<'a> TypeEncoder<'a> {
             let (name, ty) = &func.results[0];
             if !name.is_empty() {
                 bail!(
-                    "unsupported function `{}`: a single return value cannot be named",
-                    func.name
+                    "unsupported function `{}`: a single return value cannot be named{}",
+                    func.name,
+                    location(&func.span)
                 );
             }
             self.encode_type(interface, instance, ty)?
@@ -372,18 +373,21 @@ impl<'a> TypeEncoder<'a> {
                         docs: Docs::default(),
                         name: i.to_string(),
                         ty: *ty,
+                        span: Span::default(),
                     })
                 })
                 .collect::<Result<Vec<_>>>()?;
 
             let def = TypeDef {
                 docs: Docs::default(),
+                attrs: Attributes::default(),
                 name: None,
                 kind: TypeDefKind::Record(Record {
                     fields,
                     kind: RecordKind::Tuple,
                 }),
                 foreign_module: None,
+                span: Span::default(),
             };
 
             InterfaceTypeRef::Type(
@@ -451,10 +455,16 @@ impl<'a> TypeEncoder<'a> {
                         }
                         TypeDefKind::Type(ty) => self.encode_type(interface, instance, ty)?,
                         TypeDefKind::Pointer(_) | TypeDefKind::ConstPointer(_) => {
-                            bail!("the use of pointers in interfaces is not supported")
+                            bail!(
+                                "the use of pointers in interfaces is not supported{}",
+                                location(&ty.span)
+                            )
                         }
                         TypeDefKind::PushBuffer(_) | TypeDefKind::PullBuffer(_) => {
-                            bail!("the use of buffers in interfaces is not currently supported")
+                            bail!(
+                                "the use of buffers in interfaces is not currently supported{}",
+                                location(&ty.span)
+                            )
                         }
                     };
 
@@ -744,6 +754,12 @@ enum Lowering<'a> {
 #[derive(Debug)]
 struct ImportedInterface<'a> {
     ty: u32,
+    // The import this interface is nested within and its name in that
+    // import's instance, or `None` if this is a top-level import.
+    parent: Option<(&'a str, &'a str)>,
+    // Whether the core module imports from this interface; interfaces that
+    // are only needed to reach nested interfaces aren't lowered.
+    required: bool,
     lowerings: Vec<Lowering<'a>>,
     // Stores indexes into `lowerings` that are indirect.
     indirect_lowerings: Vec<usize>,
@@ -769,11 +785,17 @@ struct ImportEncoder<'a> {
 }
 
 impl<'a> ImportEncoder<'a> {
-    fn import(&mut self, interface: &'a Interface, ty: u32) -> Result<()> {
-        match self.imports.entry(&interface.name) {
+    fn import(
+        &mut self,
+        interface: &'a Interface,
+        ty: u32,
+        parent: Option<&'a Interface>,
+        required: bool,
+    ) -> Result<()> {
+        match self.imports.entry(interface.module()) {
             indexmap::map::Entry::Occupied(e) => {
                 if e.get().ty != ty {
-                    bail!("duplicate import `{}`", interface.name)
+                    bail!("duplicate import `{}`", interface.module())
                 }
             }
             indexmap::map::Entry::Vacant(e) => {
@@ -781,6 +803,7 @@ impl<'a> ImportEncoder<'a> {
                 let lowerings = interface
                     .functions
                     .iter()
+                    .filter(|_| required)
                     .enumerate()
                     .map(|(i, f)| {
                         let sig = interface.wasm_signature(AbiVariant::GuestImport, f);
@@ -813,6 +836,8 @@ impl<'a> ImportEncoder<'a> {
 
                 e.insert(ImportedInterface {
                     ty,
+                    parent: parent.map(|p| (p.module(), interface.name.as_str())),
+                    required,
                     lowerings,
                     indirect_lowerings,
                 });
@@ -832,12 +857,30 @@ impl<'a> ImportEncoder<'a> {
 
     fn encode_imports(&self, component: &mut Component) {
         let mut imports = ComponentImportSection::default();
+        let mut aliases = AliasSection::new();
 
+        // Top-level interfaces always precede nested ones, so the instance
+        // index of every import is its index in `self.imports`.
         for (name, import) in &self.imports {
-            imports.import(name, import.ty);
+            match import.parent {
+                Some((parent, name)) => {
+                    aliases.instance_export(
+                        self.imports.get_index_of(parent).unwrap() as u32,
+                        AliasExportKind::Instance,
+                        name,
+                    );
+                }
+                None => {
+                    imports.import(name, import.ty);
+                }
+            }
         }
 
         component.section(&imports);
+
+        if aliases.len() > 0 {
+            component.section(&aliases);
+        }
     }
 
     fn create_shim_modules(&self) -> Option<(Module, Module)> {
@@ -968,7 +1011,9 @@ impl<'a> ImportEncoder<'a> {
             .imports
             .iter()
             .enumerate()
-            .map(|(instance_index, (name, import))| {
+            .filter(|(_, (_, import))| import.required)
+            .enumerate()
+            .map(|(arg_index, (instance_index, (name, import)))| {
                 let alias_start_index = *function_count + aliases.len();
 
                 let exports = import.lowerings.iter().enumerate().map(|(i, lowering)| {
@@ -1004,7 +1049,7 @@ impl<'a> ImportEncoder<'a> {
                 instances.export_core_items(exports);
                 (
                     *name,
-                    ModuleArg::Instance(*instance_count + instance_index as u32),
+                    ModuleArg::Instance(*instance_count + arg_index as u32),
                 )
             })
             .collect();
@@ -1215,8 +1260,11 @@ impl<'a> ComponentEncoder<'a> {
     }
 
     fn validate_interface(interface: &Interface) -> Result<()> {
-        if interface.resources.len() != 0 {
-            bail!("the use of resources in interfaces is not currently not supported");
+        if let Some((_, resource)) = interface.resources.iter().next() {
+            bail!(
+                "the use of resources in interfaces is not currently not supported{}",
+                location(&resource.span)
+            );
         }
 
         Ok(())
@@ -1224,27 +1272,33 @@ impl<'a> ComponentEncoder<'a> {
 
     fn validate_function(function: &Function) -> Result<()> {
         if function.name.is_empty() {
-            bail!("interface has an unnamed function");
+            bail!(
+                "interface has an unnamed function{}",
+                location(&function.span)
+            );
         }
 
         if !matches!(function.kind, FunctionKind::Freestanding) {
             bail!(
-                "unsupported function `{}`: only free-standing functions are currently supported",
-                function.name
+                "unsupported function `{}`: only free-standing functions are currently supported{}",
+                function.name,
+                location(&function.span)
             );
         }
 
         if !matches!(function.abi, Abi::Canonical) {
             bail!(
-                "unsupported function `{}`: only canonical functions are supported",
-                function.name
+                "unsupported function `{}`: only canonical functions are supported{}",
+                function.name,
+                location(&function.span)
             );
         }
 
         if function.is_async {
             bail!(
-                "unsupported function `{}`: only synchronous functions are currently supported",
-                function.name
+                "unsupported function `{}`: only synchronous functions are currently supported{}",
+                function.name,
+                location(&function.span)
             );
         }
 
@@ -1271,27 +1325,65 @@ impl<'a> ComponentEncoder<'a> {
         imports: &mut ImportEncoder<'a>,
         required_imports: &HashSet<&'a str>,
     ) -> Result<()> {
+        // Nested interfaces are aliased out of the instances they're nested
+        // within, so they're only recorded once all top-level imports are.
+        let mut nested = Vec::new();
+
         for import in self.imports {
-            if !required_imports.contains(import.name.as_str()) {
+            let mut required = false;
+            for_each_interface(import, &mut |i| {
+                required |= required_imports.contains(i.module());
+            });
+            if !required {
                 continue;
             }
 
-            Self::validate_interface(import)?;
+            let index = Self::encode_import_type(types, import, &mut nested)?;
+            imports.import(
+                import,
+                index,
+                None,
+                required_imports.contains(import.module()),
+            )?;
+        }
 
-            let mut instance = Some(InstanceTypeEncoder::default());
+        for (import, index, parent) in nested {
+            imports.import(
+                import,
+                index,
+                Some(parent),
+                required_imports.contains(import.module()),
+            )?;
+        }
 
-            for func in &import.functions {
-                Self::validate_function(func)?;
+        Ok(())
+    }
 
-                let index = types.encode_func_type(import, func, &mut instance)?;
-                types.export_type(&mut instance, &func.name, index)?;
-            }
+    fn encode_import_type(
+        types: &mut TypeEncoder<'a>,
+        import: &'a Interface,
+        nested: &mut Vec<(&'a Interface, u32, &'a Interface)>,
+    ) -> Result<u32> {
+        Self::validate_interface(import)?;
+
+        let mut instance = Some(InstanceTypeEncoder::default());
 
-            let index = types.encode_instance_type(&instance.as_ref().unwrap().ty);
-            imports.import(import, index)?;
+        for func in &import.functions {
+            Self::validate_function(func)?;
+
+            let index = types.encode_func_type(import, func, &mut instance)?;
+            types.export_type(&mut instance, &func.name, index)?;
         }
 
-        Ok(())
+        for (_, iface) in import.interfaces.iter() {
+            let mut inner = Vec::new();
+            let index = Self::encode_import_type(types, iface, &mut inner)?;
+            types.export_type(&mut instance, &iface.name, index)?;
+            nested.push((iface, index, import));
+            nested.extend(inner);
+        }
+
+        Ok(types.encode_instance_type(&instance.as_ref().unwrap().ty))
     }
 
     fn encode_export_types(&self, types: &mut TypeEncoder<'a>) -> Result<()> {
@@ -1302,8 +1394,6 @@ impl<'a> ComponentEncoder<'a> {
             .map(|i| (i, true))
             .chain(self.exports.iter().map(|i| (i, false)))
         {
-            Self::validate_interface(export)?;
-
             let mut instance = if is_default {
                 None
             } else {
@@ -1313,17 +1403,7 @@ impl<'a> ComponentEncoder<'a> {
                 Some(InstanceTypeEncoder::default())
             };
 
-            // TODO: stick interface documentation in a custom section?
-
-            for func in &export.functions {
-                Self::validate_function(func)?;
-
-                let index = types.encode_func_type(export, func, &mut instance)?;
-
-                if self.types_only {
-                    types.export_type(&mut instance, &func.name, index)?;
-                }
-            }
+            self.encode_export_type(types, export, &mut instance)?;
 
             match instance {
                 Some(instance) if self.types_only => {
@@ -1337,6 +1417,41 @@ impl<'a> ComponentEncoder<'a> {
         Ok(())
     }
 
+    fn encode_export_type(
+        &self,
+        types: &mut TypeEncoder<'a>,
+        export: &'a Interface,
+        instance: &mut Option<InstanceTypeEncoder<'a>>,
+    ) -> Result<()> {
+        Self::validate_interface(export)?;
+
+        // TODO: stick interface documentation in a custom section?
+
+        for func in &export.functions {
+            Self::validate_function(func)?;
+
+            let index = types.encode_func_type(export, func, instance)?;
+
+            if self.types_only {
+                types.export_type(instance, &func.name, index)?;
+            }
+        }
+
+        // Nested interfaces are always described by an instance type, which
+        // is exported from the enclosing interface.
+        for (_, nested) in export.interfaces.iter() {
+            let mut nested_instance = Some(InstanceTypeEncoder::default());
+            self.encode_export_type(types, nested, &mut nested_instance)?;
+
+            if self.types_only {
+                let index = types.encode_instance_type(&nested_instance.unwrap().ty);
+                types.export_type(instance, &nested.name, index)?;
+            }
+        }
+
+        Ok(())
+    }
+
     fn encode_core_instantiation(
         &mut self,
         component: &mut Component,
@@ -1415,12 +1530,6 @@ impl<'a> ComponentEncoder<'a> {
         types: &mut TypeEncoder<'a>,
         core_instance_index: u32,
     ) {
-        let encoding_options = [
-            self.encoding.into(),
-            CanonicalOption::Into(core_instance_index),
-        ];
-        let into_options = [CanonicalOption::Into(core_instance_index)];
-
         for (export, is_default) in self
             .interface
             .iter()
@@ -1428,74 +1537,124 @@ impl<'a> ComponentEncoder<'a> {
             .map(|i| (i, true))
             .chain(self.exports.iter().map(|i| (i, false)))
         {
-            let alias_start_index = self.function_count;
+            let exports = self.encode_interface_exports(
+                component,
+                types,
+                core_instance_index,
+                export,
+                (!is_default).then(|| export.name.as_str()),
+            );
 
-            // Alias the exports from the core module
-            let mut aliases = AliasSection::new();
-            for func in &export.functions {
-                let name =
-                    expected_export_name((!is_default).then(|| export.name.as_str()), &func.name);
+            if is_default {
+                // Directly export the lifted functions and nested instances
+                for (name, export) in exports {
+                    types.exports.export(name, export);
+                }
+            } else if !exports.is_empty() {
+                // Otherwise, export them as an instance
+                let mut instances = InstanceSection::new();
 
-                aliases.instance_export(
-                    core_instance_index,
-                    AliasExportKind::Function,
-                    name.as_ref(),
-                );
+                instances.export_items(exports);
+                types
+                    .exports
+                    .export(&export.name, ComponentExport::Instance(self.instance_count));
+                self.instance_count += 1;
 
-                self.function_count += 1;
+                component.section(&instances);
             }
+        }
+    }
 
-            component.section(&aliases);
+    /// Lifts the functions of `export`, and of its nested interfaces, out of
+    /// the core instance and returns the items which make up its instance.
+    ///
+    /// Nested interfaces are instantiated here and show up in the returned
+    /// items as instances of their own.
+    fn encode_interface_exports(
+        &mut self,
+        component: &mut Component,
+        types: &TypeEncoder<'a>,
+        core_instance_index: u32,
+        export: &'a Interface,
+        name: Option<&str>,
+    ) -> Vec<(&'a str, ComponentExport)> {
+        let encoding_options = [
+            self.encoding.into(),
+            CanonicalOption::Into(core_instance_index),
+        ];
+        let into_options = [CanonicalOption::Into(core_instance_index)];
 
-            let mut functions = ComponentFunctionSection::new();
-            let mut exports = Vec::new();
-            for (i, func) in export.functions.iter().enumerate() {
-                // The type should already have been encoded
-                let ty = *types
-                    .func_type_map
-                    .get(&FunctionKey {
-                        interface: export,
-                        func,
-                    })
-                    .expect("the type should be encoded");
+        let alias_start_index = self.function_count;
 
-                let options = match RequiredOptions::for_function(export, func) {
-                    RequiredOptions::None => &[] as &[CanonicalOption],
-                    RequiredOptions::Encoding => &encoding_options,
-                    RequiredOptions::Into => &into_options,
-                };
+        // Alias the exports from the core module
+        let mut aliases = AliasSection::new();
+        for func in &export.functions {
+            let name = expected_export_name(name, &func.name);
 
-                functions.lift(ty, alias_start_index + i as u32, options.iter().copied());
+            aliases.instance_export(
+                core_instance_index,
+                AliasExportKind::Function,
+                name.as_ref(),
+            );
 
-                if is_default {
-                    // Directly export the lifted function
-                    types
-                        .exports
-                        .export(&func.name, ComponentExport::Function(self.function_count));
-                } else {
-                    // Otherwise, add it to the list for later instantiation
-                    exports.push((
-                        func.name.as_str(),
-                        ComponentExport::Function(self.function_count),
-                    ));
-                }
+            self.function_count += 1;
+        }
 
-                self.function_count += 1;
-            }
+        component.section(&aliases);
 
-            component.section(&functions);
+        let mut functions = ComponentFunctionSection::new();
+        let mut exports = Vec::new();
+        for (i, func) in export.functions.iter().enumerate() {
+            // The type should already have been encoded
+            let ty = *types
+                .func_type_map
+                .get(&FunctionKey {
+                    interface: export,
+                    func,
+                })
+                .expect("the type should be encoded");
 
-            if !exports.is_empty() {
-                let mut instances = InstanceSection::new();
+            let options = match RequiredOptions::for_function(export, func) {
+                RequiredOptions::None => &[] as &[CanonicalOption],
+                RequiredOptions::Encoding => &encoding_options,
+                RequiredOptions::Into => &into_options,
+            };
 
-                instances.export_items(exports);
-                types
-                    .exports
-                    .export(&export.name, ComponentExport::Instance(self.instance_count));
-                self.instance_count += 1;
+            functions.lift(ty, alias_start_index + i as u32, options.iter().copied());
 
-                component.section(&instances);
+            exports.push((
+                func.name.as_str(),
+                ComponentExport::Function(self.function_count),
+            ));
+
+            self.function_count += 1;
+        }
+
+        component.section(&functions);
+
+        for (_, nested) in export.interfaces.iter() {
+            let nested_exports = self.encode_interface_exports(
+                component,
+                types,
+                core_instance_index,
+                nested,
+                Some(nested.module()),
+            );
+            if nested_exports.is_empty() {
+                continue;
             }
+
+            let mut instances = InstanceSection::new();
+            instances.export_items(nested_exports);
+            exports.push((
+                nested.name.as_str(),
+                ComponentExport::Instance(self.instance_count),
+            ));
+            self.instance_count += 1;
+
+            component.section(&instances);
         }
+
+        exports
     }
 }
@@ -40,6 +40,19 @@ impl InterfacePrinter {
             self.output.push_str("\n\n");
         }
 
+        for (_, nested) in interface.interfaces.iter() {
+            let body = InterfacePrinter::default().print(nested)?;
+            writeln!(&mut self.output, "interface {} {{", nested.name)?;
+            for line in body.trim_end().lines() {
+                if !line.is_empty() {
+                    self.output.push_str("  ");
+                    self.output.push_str(line);
+                }
+                self.output.push('\n');
+            }
+            self.output.push_str("}\n\n");
+        }
+
         self.declared.clear();
         Ok(std::mem::take(&mut self.output))
     }
@@ -9,7 +9,7 @@ use wasmparser::{
 };
 use wit_parser::{
     abi::{AbiVariant, WasmSignature, WasmType},
-    Interface,
+    Interface, Span,
 };
 
 fn is_wasi(name: &str) -> bool {
@@ -21,8 +21,9 @@ fn is_canonical_function(name: &str) -> bool {
 }
 
 pub fn expected_export_name<'a>(interface: Option<&str>, func: &'a str) -> Cow<'a, str> {
-    // TODO: wit-bindgen currently doesn't mangle its export names, so this
-    // only works with the default (i.e. `None`) interface.
+    // TODO: wit-bindgen currently only mangles the export names of nested
+    // interfaces, so this only works with the default (i.e. `None`) interface
+    // and the interfaces nested within it.
     match interface {
         Some(interface) => format!("{}#{}", interface, func).into(),
         None => func.into(),
@@ -67,7 +68,15 @@ pub fn validate_module<'a>(
     imports: &[Interface],
     exports: &[Interface],
 ) -> Result<HashSet<&'a str>> {
-    let imports: HashMap<&str, &Interface> = imports.iter().map(|i| (i.name.as_str(), i)).collect();
+    // Nested interfaces are imported by the core module under their full
+    // path, so flatten them into the map of imports.
+    let mut import_map = HashMap::new();
+    for import in imports {
+        for_each_interface(import, &mut |i| {
+            import_map.insert(i.module(), i);
+        });
+    }
+    let imports = import_map;
     let exports: HashMap<&str, &Interface> = exports.iter().map(|i| (i.name.as_str(), i)).collect();
 
     let mut validator = Validator::new();
@@ -155,6 +164,24 @@ pub fn validate_module<'a>(
     Ok(import_funcs.keys().copied().collect())
 }
 
+/// Formats the location of `span` as a trailing line for an error message,
+/// or returns an empty string if the location isn't known.
+pub fn location(span: &Span) -> String {
+    match &span.file {
+        Some(_) => format!("\n     --> {}", span),
+        None => String::new(),
+    }
+}
+
+/// Calls `f` with `interface` and then, recursively, with each of its nested
+/// interfaces.
+pub fn for_each_interface<'a>(interface: &'a Interface, f: &mut dyn FnMut(&'a Interface)) {
+    f(interface);
+    for (_, nested) in interface.interfaces.iter() {
+        for_each_interface(nested, f);
+    }
+}
+
 fn validate_imported_interface(
     interface: &Interface,
     name: &str,
@@ -178,13 +205,14 @@ fn validate_imported_interface(
         let ty = types.func_type_at(*ty).unwrap();
         if ty != &expected {
             bail!(
-                    "type mismatch for function `{}` on imported interface `{}`: expected `{:?} -> {:?}` but found `{:?} -> {:?}`",
+                    "type mismatch for function `{}` on imported interface `{}`: expected `{:?} -> {:?}` but found `{:?} -> {:?}`{}",
                     func_name,
                     name,
                     expected.params,
                     expected.returns,
                     ty.params,
-                    ty.returns
+                    ty.returns,
+                    location(&f.span),
                 );
         }
     }
@@ -208,31 +236,38 @@ fn validate_exported_interface(
                 if ty != &expected_ty {
                     match name {
                         Some(name) => bail!(
-                            "type mismatch for function `{}` from exported interface `{}`: expected `{:?} -> {:?}` but found `{:?} -> {:?}`",
+                            "type mismatch for function `{}` from exported interface `{}`: expected `{:?} -> {:?}` but found `{:?} -> {:?}`{}",
                             f.name,
                             name,
                             expected_ty.params,
                             expected_ty.returns,
                             ty.params,
-                            ty.returns
+                            ty.returns,
+                            location(&f.span),
                         ),
                         None => bail!(
-                            "type mismatch for default interface function `{}`: expected `{:?} -> {:?}` but found `{:?} -> {:?}`",
+                            "type mismatch for default interface function `{}`: expected `{:?} -> {:?}` but found `{:?} -> {:?}`{}",
                             f.name,
                             expected_ty.params,
                             expected_ty.returns,
                             ty.params,
-                            ty.returns
+                            ty.returns,
+                            location(&f.span),
                         )
                     }
                 }
             }
             None => bail!(
-                "module does not export required function `{}`",
-                expected_export
+                "module does not export required function `{}`{}",
+                expected_export,
+                location(&f.span),
             ),
         }
     }
 
+    for (_, nested) in interface.interfaces.iter() {
+        validate_exported_interface(nested, Some(nested.module()), exports, types)?;
+    }
+
     Ok(())
 }
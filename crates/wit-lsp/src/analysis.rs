@@ -0,0 +1,201 @@
+//! Queries over resolved interfaces for hover, go-to-definition and
+//! completion.
+
+use wit_parser::abi::AbiVariant;
+use wit_parser::*;
+
+/// A named item which can be referred to within an interface.
+pub enum Item<'a> {
+    Type(&'a Interface, TypeId),
+    Resource(&'a Interface, ResourceId),
+    Function(&'a Interface, &'a Function),
+    Global(&'a Interface, &'a Global),
+    Constant(&'a Interface, &'a Constant),
+    Interface(&'a Interface),
+}
+
+impl<'a> Item<'a> {
+    /// Finds the item called `name` in `iface`, or failing that in the
+    /// interfaces nested within it.
+    ///
+    /// Names introduced by `use` resolve to the item they refer to, so the
+    /// span of the returned item may be in another file.
+    pub fn find(iface: &'a Interface, name: &str) -> Option<Item<'a>> {
+        if let Some(id) = iface.resource_lookup.get(name) {
+            return Some(Item::Resource(iface, *id));
+        }
+        if let Some(id) = iface.type_lookup.get(name) {
+            return Some(Item::Type(iface, *id));
+        }
+        // Functions within resources are named `resource::function`.
+        let function = iface
+            .functions
+            .iter()
+            .find(|f| f.name == name || f.name.rsplit("::").next() == Some(name));
+        if let Some(f) = function {
+            return Some(Item::Function(iface, f));
+        }
+        if let Some(g) = iface.globals.iter().find(|g| g.name == name) {
+            return Some(Item::Global(iface, g));
+        }
+        if let Some(c) = iface.constants.iter().find(|c| c.name == name) {
+            return Some(Item::Constant(iface, c));
+        }
+        if let Some(id) = iface.interface_lookup.get(name) {
+            return Some(Item::Interface(&iface.interfaces[*id]));
+        }
+        iface
+            .interfaces
+            .iter()
+            .find_map(|(_, nested)| Item::find(nested, name))
+    }
+
+    pub fn span(&self) -> &'a Span {
+        match *self {
+            Item::Type(iface, id) => &iface.types[id].span,
+            Item::Resource(iface, id) => &iface.resources[id].span,
+            Item::Function(_, f) => &f.span,
+            Item::Global(_, g) => &g.span,
+            Item::Constant(_, c) => &c.span,
+            Item::Interface(i) => &i.span,
+        }
+    }
+
+    /// Renders markdown describing this item: its declaration, its
+    /// documentation, and for types and globals their canonical ABI size and
+    /// alignment.
+    pub fn hover(&self) -> String {
+        let (decl, docs, layout) = match *self {
+            Item::Type(iface, id) => {
+                let ty = &iface.types[id];
+                let name = ty.name.as_deref().unwrap_or("");
+                let decl = match &ty.kind {
+                    TypeDefKind::Type(Type::Handle(r)) => {
+                        format!("resource {}", iface.resources[*r].name)
+                    }
+                    TypeDefKind::Record(r) if r.is_flags() => format!("flags {}", name),
+                    TypeDefKind::Record(r) if r.is_tuple() => {
+                        format!("type {} = {}", name, iface.typedef_string(id))
+                    }
+                    TypeDefKind::Record(_) => format!("record {}", name),
+                    TypeDefKind::Variant(v) if v.is_enum() => format!("enum {}", name),
+                    TypeDefKind::Variant(v) if v.is_union() => format!("union {}", name),
+                    TypeDefKind::Variant(v)
+                        if v.as_option().is_none() && v.as_expected().is_none() && !v.is_bool() =>
+                    {
+                        format!("variant {}", name)
+                    }
+                    _ => format!("type {} = {}", name, iface.typedef_string(id)),
+                };
+                (decl, &ty.docs, layout(iface, &Type::Id(id)))
+            }
+            Item::Resource(iface, id) => {
+                let r = &iface.resources[id];
+                (format!("resource {}", r.name), &r.docs, None)
+            }
+            Item::Function(iface, f) => (function_string(iface, f), &f.docs, None),
+            Item::Global(iface, g) => (
+                format!("{}: {}", g.name, iface.type_string(&g.ty)),
+                &g.docs,
+                layout(iface, &g.ty),
+            ),
+            Item::Constant(iface, c) => (
+                format!(
+                    "const {}: {} = {}",
+                    c.name,
+                    iface.type_string(&c.ty),
+                    c.value
+                ),
+                &c.docs,
+                None,
+            ),
+            Item::Interface(i) => (format!("interface {}", i.name), &i.docs, None),
+        };
+        let mut hover = format!("```wit\n{}\n```", decl);
+        if let Some(docs) = &docs.contents {
+            hover.push_str("\n\n");
+            hover.push_str(docs.trim_end());
+        }
+        if let Some((size, align)) = layout {
+            hover.push_str(&format!("\n\nsize: {}, align: {}", size, align));
+        }
+        return hover;
+
+        fn layout(iface: &Interface, ty: &Type) -> Option<(usize, usize)> {
+            // Handles don't have a representation in linear memory.
+            if let Type::Handle(_) = ty {
+                return None;
+            }
+            let mut sizes = SizeAlign::default();
+            sizes.fill(AbiVariant::GuestImport, iface);
+            Some((sizes.size(ty), sizes.align(ty)))
+        }
+    }
+}
+
+/// Returns the names of all types and resources which can be referred to in
+/// `iface` or the interfaces nested within it, in sorted order.
+pub fn type_names(iface: &Interface) -> Vec<&str> {
+    let mut names = Vec::new();
+    collect(iface, &mut names);
+    names.sort_unstable();
+    names.dedup();
+    return names;
+
+    fn collect<'a>(iface: &'a Interface, names: &mut Vec<&'a str>) {
+        names.extend(iface.type_lookup.keys().map(|s| s.as_str()));
+        names.extend(iface.resource_lookup.keys().map(|s| s.as_str()));
+        for (_, nested) in iface.interfaces.iter() {
+            collect(nested, names);
+        }
+    }
+}
+
+fn function_string(iface: &Interface, f: &Function) -> String {
+    let name = f.name.rsplit("::").next().unwrap();
+    let mut params = f
+        .params
+        .iter()
+        .map(|(name, ty)| (name, ty))
+        .collect::<Vec<_>>();
+    let prefix = match &f.kind {
+        FunctionKind::Freestanding => "",
+        FunctionKind::Static { .. } => "static ",
+        FunctionKind::Method { .. } => {
+            // The implicit `self` parameter isn't written in the source.
+            params.remove(0);
+            ""
+        }
+    };
+    let mut ret = format!(
+        "{}{}: {}function(",
+        prefix,
+        name,
+        if f.is_async { "async " } else { "" }
+    );
+    for (i, (name, ty)) in params.iter().enumerate() {
+        if i > 0 {
+            ret.push_str(", ");
+        }
+        ret.push_str(&format!("{}: {}", name, iface.type_string(ty)));
+    }
+    ret.push(')');
+    match f.results.len() {
+        0 => {}
+        1 if f.results[0].0.is_empty() => {
+            ret.push_str(" -> ");
+            ret.push_str(&iface.type_string(&f.results[0].1));
+        }
+        _ => {
+            ret.push_str(" -> (");
+            for (i, (name, ty)) in f.results.iter().enumerate() {
+                if i > 0 {
+                    ret.push_str(", ");
+                }
+                ret.push_str(&format!("{}: {}", name, iface.type_string(ty)));
+            }
+            ret.push(')');
+        }
+    }
+    ret
+}
@@ -0,0 +1,20 @@
+use anyhow::Result;
+use std::io;
+use wit_lsp::{read_message, write_message, Server};
+
+fn main() -> Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut input = stdin.lock();
+    let mut output = stdout.lock();
+    let mut server = Server::default();
+    while let Some(msg) = read_message(&mut input)? {
+        for msg in server.handle(msg) {
+            write_message(&mut output, &msg)?;
+        }
+        if let Some(code) = server.exit_code() {
+            std::process::exit(code);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,465 @@
+//! A language server for `*.wit` files.
+//!
+//! The server speaks the Language Server Protocol over stdio and provides
+//! diagnostics, hover, go-to-definition and completion of type names. Files
+//! are parsed with `wit_parser`, loading the interfaces named in `use` items
+//! from open documents first and the filesystem otherwise.
+
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use wit_parser::{Interface, ParseError, Span};
+
+mod analysis;
+
+use analysis::Item;
+
+/// Types which are always in scope.
+const BUILTIN_TYPES: &[&str] = &[
+    "bool",
+    "char",
+    "expected",
+    "f32",
+    "f64",
+    "handle",
+    "list",
+    "option",
+    "pull-buffer",
+    "push-buffer",
+    "s16",
+    "s32",
+    "s64",
+    "s8",
+    "string",
+    "tuple",
+    "u16",
+    "u32",
+    "u64",
+    "u8",
+];
+
+/// The state of a language server session.
+#[derive(Default)]
+pub struct Server {
+    documents: HashMap<PathBuf, Document>,
+    shutdown: bool,
+    exit_code: Option<i32>,
+}
+
+struct Document {
+    uri: String,
+    text: String,
+    /// The most recent successful parse of `text`, which is kept around while
+    /// the document is being edited so that queries keep working.
+    interface: Option<Interface>,
+}
+
+impl Server {
+    /// Handles the JSON-RPC message `msg` from the client, returning the
+    /// messages to send back.
+    pub fn handle(&mut self, msg: Value) -> Vec<Value> {
+        let method = msg["method"].as_str().unwrap_or("");
+        let params = &msg["params"];
+        let id = match msg.get("id") {
+            Some(id) => id.clone(),
+            None => return self.notification(method, params).unwrap_or_default(),
+        };
+        let result = match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    // Full document sync
+                    "textDocumentSync": 1,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "completionProvider": {},
+                },
+                "serverInfo": { "name": "wit-lsp" },
+            })),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            }
+            "textDocument/hover" => self.hover(params),
+            "textDocument/definition" => self.definition(params),
+            "textDocument/completion" => self.completion(params),
+            _ => {
+                return vec![json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {
+                        "code": -32601,
+                        "message": format!("unknown method `{}`", method),
+                    },
+                })]
+            }
+        };
+        let response = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(e) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": -32602, "message": format!("{:#}", e) },
+            }),
+        };
+        vec![response]
+    }
+
+    /// Returns the code the server should exit with once the client has sent
+    /// the `exit` notification.
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) -> Result<Vec<Value>> {
+        match method {
+            "exit" => {
+                self.exit_code = Some(if self.shutdown { 0 } else { 1 });
+                Ok(Vec::new())
+            }
+            "textDocument/didOpen" => {
+                let doc = &params["textDocument"];
+                let uri = str_param(doc, "uri")?;
+                self.documents.insert(
+                    uri_to_path(uri)?,
+                    Document {
+                        uri: uri.to_string(),
+                        text: str_param(doc, "text")?.to_string(),
+                        interface: None,
+                    },
+                );
+                Ok(self.check_all())
+            }
+            "textDocument/didChange" => {
+                let path = uri_to_path(str_param(&params["textDocument"], "uri")?)?;
+                let change = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .context("missing `contentChanges`")?;
+                let text = str_param(change, "text")?.to_string();
+                if let Some(doc) = self.documents.get_mut(&path) {
+                    doc.text = text;
+                }
+                Ok(self.check_all())
+            }
+            "textDocument/didClose" => {
+                let uri = str_param(&params["textDocument"], "uri")?;
+                self.documents.remove(&uri_to_path(uri)?);
+                let mut msgs = vec![publish(uri, Vec::new())];
+                msgs.extend(self.check_all());
+                Ok(msgs)
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// Parses every open document, since any of them may `use` the one that
+    /// changed, and publishes their diagnostics.
+    fn check_all(&mut self) -> Vec<Value> {
+        let mut paths = self.documents.keys().cloned().collect::<Vec<_>>();
+        paths.sort();
+        let mut msgs = Vec::new();
+        for path in paths {
+            let doc = &self.documents[&path];
+            let result = Interface::parse_with(&path, &doc.text, |name| self.load(&path, name));
+            let diagnostics = match result {
+                Ok(iface) => {
+                    self.documents.get_mut(&path).unwrap().interface = Some(iface);
+                    Vec::new()
+                }
+                Err(e) => self.diagnostics(&path, &e),
+            };
+            msgs.push(publish(&self.documents[&path].uri, diagnostics));
+        }
+        msgs
+    }
+
+    /// Loads the interface `name` used by the document at `path`, preferring
+    /// open documents over the filesystem in each place it's searched for.
+    fn load(&self, path: &Path, name: &str) -> Result<(PathBuf, String)> {
+        let paths = dependency_paths(path, name);
+        for dep in paths.iter() {
+            if let Some(doc) = self.documents.get(dep) {
+                return Ok((dep.clone(), doc.text.clone()));
+            }
+            if dep.is_file() {
+                let contents = fs::read_to_string(dep)
+                    .with_context(|| format!("failed to read `{}`", dep.display()))?;
+                return Ok((dep.clone(), contents));
+            }
+        }
+        let mut msg = format!("interface `{}` not found, tried:", name);
+        for dep in paths.iter() {
+            msg.push_str(&format!("\n    {}", dep.display()));
+        }
+        bail!("{}", msg)
+    }
+
+    fn diagnostics(&self, path: &Path, err: &anyhow::Error) -> Vec<Value> {
+        let text = &self.documents[path].text;
+        let diagnostics = match err.downcast_ref::<ParseError>() {
+            Some(e) => e.diagnostics(),
+            None => {
+                return vec![json!({
+                    "range": range(text, &Span::default()),
+                    "severity": 1,
+                    "source": "wit",
+                    "message": format!("{:#}", err),
+                })]
+            }
+        };
+        diagnostics
+            .iter()
+            .map(|d| {
+                let mut message = d.message.clone();
+                for note in d.notes.iter() {
+                    message.push_str("\nnote: ");
+                    message.push_str(note);
+                }
+                // Problems in the interfaces this one uses are reported at
+                // the top of the file.
+                let span = match &d.span.file {
+                    Some(file) if **file != *path => {
+                        message = format!("{}: {}", d.span, message);
+                        Span::default()
+                    }
+                    _ => d.span.clone(),
+                };
+                let related = d
+                    .related
+                    .iter()
+                    .filter_map(|(span, label)| {
+                        let location = self.location(span)?;
+                        Some(json!({ "location": location, "message": label }))
+                    })
+                    .collect::<Vec<_>>();
+                json!({
+                    "range": range(text, &span),
+                    "severity": match d.severity {
+                        wit_parser::Severity::Error => 1,
+                        wit_parser::Severity::Warning => 2,
+                    },
+                    "source": "wit",
+                    "message": message,
+                    "relatedInformation": related,
+                })
+            })
+            .collect()
+    }
+
+    fn hover(&self, params: &Value) -> Result<Value> {
+        let (doc, word) = self.word_at(params)?;
+        let item = match doc.interface.as_ref().and_then(|i| Item::find(i, &word)) {
+            Some(item) => item,
+            None => return Ok(Value::Null),
+        };
+        Ok(json!({
+            "contents": { "kind": "markdown", "value": item.hover() },
+        }))
+    }
+
+    fn definition(&self, params: &Value) -> Result<Value> {
+        let (doc, word) = self.word_at(params)?;
+        if let Some(item) = doc.interface.as_ref().and_then(|i| Item::find(i, &word)) {
+            return Ok(self.location(item.span()).unwrap_or(Value::Null));
+        }
+        // The name of an interface in a `use` item refers to its file.
+        let path = uri_to_path(&doc.uri)?;
+        let dep = dependency_paths(&path, &word)
+            .into_iter()
+            .find(|dep| self.documents.contains_key(dep) || dep.is_file());
+        Ok(match dep {
+            Some(dep) => json!({
+                "uri": path_to_uri(&dep),
+                "range": range("", &Span::default()),
+            }),
+            None => Value::Null,
+        })
+    }
+
+    fn completion(&self, params: &Value) -> Result<Value> {
+        let doc = self.document(params)?;
+        let mut items = BUILTIN_TYPES
+            .iter()
+            .map(|name| json!({ "label": name, "kind": 14 }))
+            .collect::<Vec<_>>();
+        if let Some(iface) = &doc.interface {
+            items.extend(
+                analysis::type_names(iface)
+                    .into_iter()
+                    .map(|name| json!({ "label": name, "kind": 22 })),
+            );
+        }
+        Ok(json!(items))
+    }
+
+    fn document(&self, params: &Value) -> Result<&Document> {
+        let uri = str_param(&params["textDocument"], "uri")?;
+        match self.documents.get(&uri_to_path(uri)?) {
+            Some(doc) => Ok(doc),
+            None => bail!("document `{}` is not open", uri),
+        }
+    }
+
+    /// Returns the document that `params` refers to and the identifier at the
+    /// position within it.
+    fn word_at(&self, params: &Value) -> Result<(&Document, String)> {
+        let doc = self.document(params)?;
+        let position = &params["position"];
+        let line = position["line"].as_u64().context("missing `line`")? as usize;
+        let character = position["character"]
+            .as_u64()
+            .context("missing `character`")? as usize;
+        let text = doc.text.lines().nth(line).unwrap_or("");
+        let offset = utf16_to_byte(text, character);
+        // Dots are included to pick up paths such as `dir.module` in `use` items.
+        let is_id = |c: char| c.is_alphanumeric() || c == '-' || c == '_' || c == '.';
+        let start = text[..offset]
+            .rfind(|c| !is_id(c))
+            .map(|i| i + text[i..].chars().next().unwrap().len_utf8())
+            .unwrap_or(0);
+        let end = text[offset..]
+            .find(|c| !is_id(c))
+            .map(|i| offset + i)
+            .unwrap_or(text.len());
+        Ok((doc, text[start..end].to_string()))
+    }
+
+    fn location(&self, span: &Span) -> Option<Value> {
+        let file = span.file.as_ref()?;
+        let text = match self.documents.get(&**file) {
+            Some(doc) => doc.text.clone(),
+            None => fs::read_to_string(file).ok()?,
+        };
+        Some(json!({
+            "uri": path_to_uri(file),
+            "range": range(&text, span),
+        }))
+    }
+}
+
+/// Reads a message from the client, returning `None` at the end of `input`.
+pub fn read_message(input: &mut impl BufRead) -> Result<Option<Value>> {
+    let mut len = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            len = Some(value.trim().parse::<usize>()?);
+        }
+    }
+    let len = len.context("message is missing a `Content-Length` header")?;
+    let mut body = vec![0; len];
+    input.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+/// Writes `msg` to the client.
+pub fn write_message(output: &mut impl Write, msg: &Value) -> Result<()> {
+    let body = msg.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()?;
+    Ok(())
+}
+
+fn publish(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+fn str_param<'a>(value: &'a Value, name: &str) -> Result<&'a str> {
+    value[name]
+        .as_str()
+        .with_context(|| format!("missing `{}`", name))
+}
+
+/// Returns the files that `use` items in the file at `path` may load the
+/// interface `name` from, in the order `Interface::parse_file` tries them.
+fn dependency_paths(path: &Path, name: &str) -> Vec<PathBuf> {
+    wit_parser::interface_paths(&wit_parser::search_path(path, &[]), name)
+}
+
+/// Converts `span` into an LSP range within `text`, the contents of the file
+/// it points into. Spans without a file are placed at the start of `text`.
+///
+/// Spans of items are always within a single line, so only the start of the
+/// span is looked up.
+fn range(text: &str, span: &Span) -> Value {
+    let line = span.line.saturating_sub(1) as usize;
+    let line_text = text.lines().nth(line).unwrap_or("");
+    let start = (span.column.saturating_sub(1) as usize).min(line_text.len());
+    let mut end = (start + (span.end - span.start) as usize).min(line_text.len());
+    // Errors at a single position are widened to the character there so that
+    // editors have something to underline.
+    if end == start {
+        end += line_text
+            .get(start..)
+            .and_then(|s| s.chars().next())
+            .map_or(0, |c| c.len_utf8());
+    }
+    let position = |byte: usize| {
+        let character = line_text
+            .get(..byte)
+            .map_or(0, |s| s.encode_utf16().count());
+        json!({ "line": line, "character": character })
+    };
+    json!({ "start": position(start), "end": position(end) })
+}
+
+/// Converts an offset in UTF-16 code units within `line`, as used by LSP
+/// positions, into a byte offset.
+fn utf16_to_byte(line: &str, character: usize) -> usize {
+    let mut units = 0;
+    for (i, c) in line.char_indices() {
+        if units >= character {
+            return i;
+        }
+        units += c.len_utf16();
+    }
+    line.len()
+}
+
+fn uri_to_path(uri: &str) -> Result<PathBuf> {
+    let path = match uri.strip_prefix("file://") {
+        Some(path) => path,
+        None => bail!("unsupported URI `{}`", uri),
+    };
+    let mut bytes = Vec::new();
+    let mut rest = path.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        rest = tail;
+        if b == b'%' && rest.len() >= 2 {
+            if let Ok(byte) = u8::from_str_radix(std::str::from_utf8(&rest[..2])?, 16) {
+                bytes.push(byte);
+                rest = &rest[2..];
+                continue;
+            }
+        }
+        bytes.push(b);
+    }
+    Ok(PathBuf::from(String::from_utf8(bytes)?))
+}
+
+fn path_to_uri(path: &Path) -> String {
+    let mut uri = "file://".to_string();
+    for b in path.to_string_lossy().bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(b as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    uri
+}
@@ -0,0 +1,195 @@
+use serde_json::{json, Value};
+use wit_lsp::Server;
+
+const DEP: &str = "\
+/// A point in space.
+record point {
+  x: u32,
+  y: u32,
+}
+";
+
+const MAIN: &str = "\
+use { point } from dep
+
+/// Moves a point.
+move: function(p: point, dx: u32) -> point
+";
+
+fn open(server: &mut Server, uri: &str, text: &str) -> Vec<Value> {
+    server.handle(json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didOpen",
+        "params": {
+            "textDocument": { "uri": uri, "languageId": "wit", "version": 1, "text": text },
+        },
+    }))
+}
+
+fn request(server: &mut Server, method: &str, params: Value) -> Value {
+    let mut msgs = server.handle(json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": method,
+        "params": params,
+    }));
+    assert_eq!(msgs.len(), 1);
+    msgs.remove(0)["result"].take()
+}
+
+fn at(uri: &str, line: u32, character: u32) -> Value {
+    json!({
+        "textDocument": { "uri": uri },
+        "position": { "line": line, "character": character },
+    })
+}
+
+fn server() -> Server {
+    let mut server = Server::default();
+    let result = request(&mut server, "initialize", json!({}));
+    assert_eq!(result["capabilities"]["hoverProvider"], true);
+    open(&mut server, "file:///ws/dep.wit", DEP);
+    let msgs = open(&mut server, "file:///ws/main.wit", MAIN);
+    for msg in msgs {
+        assert_eq!(msg["params"]["diagnostics"], json!([]), "{}", msg);
+    }
+    server
+}
+
+#[test]
+fn diagnostics() {
+    let mut server = server();
+    let msgs = server.handle(json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didChange",
+        "params": {
+            "textDocument": { "uri": "file:///ws/main.wit", "version": 2 },
+            "contentChanges": [{ "text": "a: function(x: u32, y)\nb: function() -> list<>\n" }],
+        },
+    }));
+    let main = msgs
+        .iter()
+        .find(|m| m["params"]["uri"] == "file:///ws/main.wit")
+        .unwrap();
+    let diagnostics = main["params"]["diagnostics"].as_array().unwrap();
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(
+        diagnostics[0]["range"],
+        json!({
+            "start": { "line": 0, "character": 21 },
+            "end": { "line": 0, "character": 22 },
+        })
+    );
+    assert_eq!(diagnostics[1]["message"], "expected a type, found '>'");
+    assert_eq!(diagnostics[1]["range"]["start"]["line"], 1);
+}
+
+#[test]
+fn related_spans() {
+    let mut server = Server::default();
+    let msgs = open(&mut server, "file:///ws/dup.wit", "a: u32\na: u32\n");
+    let diagnostic = &msgs[0]["params"]["diagnostics"][0];
+    assert_eq!(diagnostic["range"]["start"]["line"], 1);
+    let related = &diagnostic["relatedInformation"][0];
+    assert_eq!(related["location"]["uri"], "file:///ws/dup.wit");
+    assert_eq!(related["location"]["range"]["start"]["line"], 0);
+}
+
+#[test]
+fn definition() {
+    let mut server = server();
+    let location = request(
+        &mut server,
+        "textDocument/definition",
+        at("file:///ws/main.wit", 3, 19),
+    );
+    assert_eq!(
+        location,
+        json!({
+            "uri": "file:///ws/dep.wit",
+            "range": {
+                "start": { "line": 1, "character": 7 },
+                "end": { "line": 1, "character": 12 },
+            },
+        })
+    );
+
+    let location = request(
+        &mut server,
+        "textDocument/definition",
+        at("file:///ws/main.wit", 0, 20),
+    );
+    assert_eq!(location["uri"], "file:///ws/dep.wit");
+}
+
+#[test]
+fn hover() {
+    let mut server = server();
+    let hover = request(
+        &mut server,
+        "textDocument/hover",
+        at("file:///ws/main.wit", 3, 20),
+    );
+    assert_eq!(
+        hover["contents"]["value"],
+        "```wit\nrecord point\n```\n\nA point in space.\n\nsize: 8, align: 4"
+    );
+
+    let hover = request(
+        &mut server,
+        "textDocument/hover",
+        at("file:///ws/main.wit", 3, 1),
+    );
+    assert_eq!(
+        hover["contents"]["value"],
+        "```wit\nmove: function(p: point, dx: u32) -> point\n```\n\nMoves a point."
+    );
+}
+
+#[test]
+fn completion() {
+    let mut server = server();
+    let items = request(
+        &mut server,
+        "textDocument/completion",
+        at("file:///ws/main.wit", 3, 0),
+    );
+    let labels = items
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|i| i["label"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert!(labels.contains(&"point"));
+    assert!(labels.contains(&"string"));
+}
+
+#[test]
+fn messages() {
+    let mut buf = Vec::new();
+    let msg = json!({ "jsonrpc": "2.0", "method": "exit" });
+    wit_lsp::write_message(&mut buf, &msg).unwrap();
+    let mut input = &buf[..];
+    assert_eq!(wit_lsp::read_message(&mut input).unwrap(), Some(msg));
+    assert_eq!(wit_lsp::read_message(&mut input).unwrap(), None);
+}
+
+#[test]
+fn dotted_use() {
+    let mut server = server();
+    open(&mut server, "file:///ws/sub/dep.wit", "type id = u64\n");
+    let msgs = open(
+        &mut server,
+        "file:///ws/nested.wit",
+        "use { id } from sub.dep\nget: function() -> id\n",
+    );
+    for msg in msgs {
+        assert_eq!(msg["params"]["diagnostics"], json!([]), "{}", msg);
+    }
+    let location = request(
+        &mut server,
+        "textDocument/definition",
+        at("file:///ws/nested.wit", 0, 18),
+    );
+    assert_eq!(location["uri"], "file:///ws/sub/dep.wit");
+}
@@ -1,13 +1,30 @@
-use anyhow::{Context, Result};
-use std::path::PathBuf;
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
 use structopt::StructOpt;
-use wit_bindgen_gen_core::{wit_parser, Files, Generator};
+use wit_bindgen_gen_core::{wit_parser, Direction, Files, Generator};
 use wit_parser::Interface;
 
 #[derive(Debug, StructOpt)]
 struct Opt {
+    /// Generate all bindings listed in the given TOML manifest instead of
+    /// running a single generator.
+    #[structopt(long = "config")]
+    config: Option<PathBuf>,
+
+    /// Check that the bindings are up-to-date instead of generating them, as
+    /// with a generator's `--check`. Applies to `--config`, a generator, or
+    /// `fmt`.
+    //
+    // Named apart from the `check` subcommand, which clap would otherwise
+    // report as this flag being present.
+    #[structopt(name = "check-flag", long = "check")]
+    check: bool,
+
     #[structopt(subcommand)]
-    command: Command,
+    command: Option<Command>,
 }
 
 #[derive(Debug, StructOpt)]
@@ -55,6 +72,62 @@ enum Command {
         #[structopt(flatten)]
         common: Common,
     },
+    /// Emit each interface as a JSON document describing its types,
+    /// functions and their ABI, for use by other tools.
+    Json {
+        #[structopt(flatten)]
+        opts: wit_bindgen_gen_json::Opts,
+        #[structopt(flatten)]
+        common: Common,
+    },
+    /// Validate `*.wit` files without generating any bindings, reporting
+    /// anything that a generator can't produce bindings for.
+    Check {
+        /// Only check support in the given generator, treating anything it
+        /// can't handle as an error. Can be specified multiple times.
+        #[structopt(long = "generator", short, number_of_values = 1)]
+        generators: Vec<String>,
+
+        /// Search the given directory for the interfaces named in `use`
+        /// items. Can be specified multiple times.
+        #[structopt(long = "include-dir", number_of_values = 1)]
+        include_dirs: Vec<PathBuf>,
+
+        /// The `*.wit` files to check.
+        #[structopt(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Compare two versions of a `*.wit` interface, reporting each change
+    /// and whether it breaks code built against the old version.
+    ///
+    /// Fails if any of the changes are breaking.
+    Compat {
+        /// Print the changes as a JSON document instead of as diagnostics.
+        #[structopt(long = "json")]
+        json: bool,
+
+        /// Search the given directory for the interfaces named in `use`
+        /// items. Can be specified multiple times.
+        #[structopt(long = "include-dir", number_of_values = 1)]
+        include_dirs: Vec<PathBuf>,
+
+        /// The old version of the interface.
+        old: PathBuf,
+
+        /// The new version of the interface.
+        new: PathBuf,
+    },
+    /// Format `*.wit` files in place, preserving comments.
+    Fmt {
+        /// Don't write any files, instead print a diff for every file which
+        /// isn't formatted and fail if there are any.
+        #[structopt(long = "check")]
+        check: bool,
+
+        /// The `*.wit` files to format.
+        #[structopt(required = true)]
+        files: Vec<PathBuf>,
+    },
 }
 
 #[derive(Debug, StructOpt)]
@@ -72,50 +145,622 @@ struct Common {
     /// specified multiple times.
     #[structopt(long = "export", short)]
     exports: Vec<PathBuf>,
+
+    /// Search the given directory for the interfaces named in `use` items,
+    /// after the directory of the file using them and before those listed in
+    /// `WIT_PATH`. Can be specified multiple times.
+    #[structopt(long = "include-dir", number_of_values = 1)]
+    include_dirs: Vec<PathBuf>,
+
+    /// Don't write any files, instead check that the files in `--out-dir` are
+    /// exactly what would be generated. A diff is printed for every file that
+    /// would change, be created, or be removed, and the command fails if there
+    /// are any.
+    ///
+    /// Every other file in `--out-dir` is assumed to be stale output.
+    #[structopt(long = "check")]
+    check: bool,
 }
 
 fn main() -> Result<()> {
     let opt = Opt::from_args();
-    let (mut generator, common): (Box<dyn Generator>, _) = match opt.command {
+    match (opt.config, opt.command) {
+        (Some(config), None) => generate_manifest(&config, opt.check),
+        (
+            None,
+            Some(Command::Check {
+                generators,
+                include_dirs,
+                files,
+            }),
+        ) if !opt.check => check(&generators, &include_dirs, &files),
+        (None, Some(Command::Fmt { check, files })) => fmt(&files, check || opt.check),
+        (
+            None,
+            Some(Command::Compat {
+                json,
+                include_dirs,
+                old,
+                new,
+            }),
+        ) if !opt.check => compat(&old, &new, &include_dirs, json),
+        (None, Some(Command::Check { .. })) | (None, Some(Command::Compat { .. })) => {
+            bail!("`--check` only applies to `--config`, generators and `fmt`")
+        }
+        (None, Some(command)) => {
+            let output = generate(command)?;
+            if opt.check || output.check {
+                report_stale(check_files(&[output])?)
+            } else {
+                output.write()
+            }
+        }
+        (Some(_), Some(_)) => bail!("cannot specify both `--config` and a generator"),
+        (None, None) => bail!("must specify either `--config` or a generator"),
+    }
+}
+
+/// The bindings generated for a single generator and where they belong.
+struct Output {
+    files: Files,
+    out_dir: Option<PathBuf>,
+    /// Whether the generator was asked to `--check` these files rather than
+    /// write them.
+    check: bool,
+}
+
+/// Generates the bindings for `command` without writing them anywhere.
+fn generate(command: Command) -> Result<Output> {
+    let (mut generator, common): (Box<dyn Generator>, _) = match command {
         Command::RustWasm { opts, common } => (Box::new(opts.build()), common),
         Command::Wasmtime { opts, common } => (Box::new(opts.build()), common),
         Command::WasmtimePy { opts, common } => (Box::new(opts.build()), common),
         Command::Js { opts, common } => (Box::new(opts.build()), common),
         Command::C { opts, common } => (Box::new(opts.build()), common),
         Command::Markdown { opts, common } => (Box::new(opts.build()), common),
+        Command::Json { opts, common } => (Box::new(opts.build()), common),
         Command::SpiderMonkey { opts, common } => {
             let js_source = std::fs::read_to_string(&opts.js)
                 .with_context(|| format!("failed to read {}", opts.js.display()))?;
             (Box::new(opts.build(js_source)), common)
         }
+        Command::Check { .. } => bail!("`check` is not a generator"),
+        Command::Fmt { .. } => bail!("`fmt` is not a generator"),
+        Command::Compat { .. } => bail!("`compat` is not a generator"),
     };
 
     let imports = common
         .imports
         .iter()
-        .map(|wit| Interface::parse_file(wit))
+        .map(|wit| Interface::parse_file_with_include_dirs(wit, &common.include_dirs))
         .collect::<Result<Vec<_>>>()?;
     let exports = common
         .exports
         .iter()
-        .map(|wit| Interface::parse_file(wit))
+        .map(|wit| Interface::parse_file_with_include_dirs(wit, &common.include_dirs))
         .collect::<Result<Vec<_>>>()?;
 
     let mut files = Files::default();
     generator.generate_all(&imports, &exports, &mut files);
+    Ok(Output {
+        files,
+        out_dir: common.out_dir,
+        check: common.check,
+    })
+}
 
-    for (name, contents) in files.iter() {
-        let dst = match &common.out_dir {
+impl Output {
+    fn path(&self, name: &str) -> PathBuf {
+        match &self.out_dir {
             Some(path) => path.join(name),
             None => name.into(),
+        }
+    }
+
+    fn write(&self) -> Result<()> {
+        for (name, contents) in self.files.iter() {
+            let dst = self.path(name);
+            println!("Generating {:?}", dst);
+            if let Some(parent) = dst.parent() {
+                std::fs::create_dir_all(parent)
+                    .with_context(|| format!("failed to create {:?}", parent))?;
+            }
+            std::fs::write(&dst, contents).with_context(|| format!("failed to write {:?}", dst))?;
+        }
+        Ok(())
+    }
+}
+
+fn report_stale(stale: usize) -> Result<()> {
+    if stale > 0 {
+        bail!("{} generated file(s) are out of date", stale);
+    }
+    Ok(())
+}
+
+/// Compares the files of `outputs` against the contents of their output
+/// directories, printing a unified diff for each file which differs, and
+/// returns how many files differ.
+///
+/// Outputs sharing a directory are compared against it together, so that
+/// each one's files aren't reported as stale by the others.
+fn check_files(outputs: &[Output]) -> Result<usize> {
+    let mut expected = BTreeMap::new();
+    for output in outputs {
+        for (name, contents) in output.files.iter() {
+            expected.insert(output.path(name), contents);
+        }
+    }
+
+    // Anything in an output directory that wouldn't be generated would be
+    // removed.
+    let mut actual = BTreeSet::new();
+    for out_dir in outputs.iter().filter_map(|o| o.out_dir.as_deref()) {
+        if out_dir.is_dir() {
+            walk(out_dir, &mut actual)?;
+        }
+    }
+    let extra = actual
+        .into_iter()
+        .filter(|path| !expected.contains_key(path))
+        .collect::<Vec<_>>();
+
+    let mut stale = 0;
+    for (path, contents) in expected {
+        let old = match std::fs::read(&path) {
+            Ok(old) => Some(old),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e).with_context(|| format!("failed to read {:?}", path)),
+        };
+        if old.as_deref() == Some(contents) {
+            continue;
+        }
+        stale += 1;
+        let name = path.display().to_string();
+        match &old {
+            Some(old) => print_diff(&name, old, &name, contents),
+            None => print_diff("/dev/null", &[], &name, contents),
+        }
+    }
+    for path in extra {
+        stale += 1;
+        let old = std::fs::read(&path).with_context(|| format!("failed to read {:?}", path))?;
+        print_diff(&path.display().to_string(), &old, "/dev/null", &[]);
+    }
+    Ok(stale)
+}
+
+fn walk(dir: &Path, files: &mut BTreeSet<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir).with_context(|| format!("failed to read {:?}", dir))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            walk(&path, files)?;
+        } else {
+            files.insert(path);
+        }
+    }
+    Ok(())
+}
+
+fn print_diff(old_name: &str, old: &[u8], new_name: &str, new: &[u8]) {
+    match (std::str::from_utf8(old), std::str::from_utf8(new)) {
+        (Ok(old), Ok(new)) => print!("{}", unified_diff(old_name, old, new_name, new)),
+        _ => println!("Binary files {} and {} differ", old_name, new_name),
+    }
+}
+
+/// Renders the difference between `old` and `new` in the unified format of
+/// `diff -u`.
+fn unified_diff(old_name: &str, old: &str, new_name: &str, new: &str) -> String {
+    const CONTEXT: usize = 3;
+
+    let lines = diff::lines(old, new);
+    let changes = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, diff::Result::Both(..)))
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+
+    let mut ret = format!("--- {}\n+++ {}\n", old_name, new_name);
+    let mut i = 0;
+    while i < changes.len() {
+        // Changes close enough together for their context to overlap go in
+        // the same hunk.
+        let mut j = i;
+        while j + 1 < changes.len() && changes[j + 1] - changes[j] <= 2 * CONTEXT + 1 {
+            j += 1;
+        }
+        let start = changes[i].saturating_sub(CONTEXT);
+        let end = (changes[j] + CONTEXT + 1).min(lines.len());
+
+        let mut old_start = 1;
+        let mut new_start = 1;
+        for line in lines[..start].iter() {
+            match line {
+                diff::Result::Left(_) => old_start += 1,
+                diff::Result::Right(_) => new_start += 1,
+                diff::Result::Both(..) => {
+                    old_start += 1;
+                    new_start += 1;
+                }
+            }
+        }
+
+        let mut body = String::new();
+        let mut old_len = 0;
+        let mut new_len = 0;
+        for line in lines[start..end].iter() {
+            match line {
+                diff::Result::Left(l) => {
+                    old_len += 1;
+                    body.push_str(&format!("-{}\n", l));
+                }
+                diff::Result::Right(r) => {
+                    new_len += 1;
+                    body.push_str(&format!("+{}\n", r));
+                }
+                diff::Result::Both(l, _) => {
+                    old_len += 1;
+                    new_len += 1;
+                    body.push_str(&format!(" {}\n", l));
+                }
+            }
+        }
+        ret.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            range(old_start, old_len),
+            range(new_start, new_len)
+        ));
+        ret.push_str(&body);
+        i = j + 1;
+    }
+    return ret;
+
+    fn range(start: usize, len: usize) -> String {
+        match len {
+            0 => format!("{},0", start - 1),
+            1 => start.to_string(),
+            _ => format!("{},{}", start, len),
+        }
+    }
+}
+
+/// Generators that `check` queries by default. The `spidermonkey` generator
+/// isn't included since it needs a JS module to generate bindings for.
+const CHECKED_GENERATORS: &[&str] = &[
+    "rust-wasm",
+    "wasmtime",
+    "wasmtime-py",
+    "js",
+    "c",
+    "markdown",
+];
+
+fn check_generator(name: &str) -> Result<Box<dyn Generator>> {
+    Ok(match name {
+        "rust-wasm" => Box::new(wit_bindgen_gen_rust_wasm::Opts::default().build()),
+        "wasmtime" => Box::new(wit_bindgen_gen_wasmtime::Opts::default().build()),
+        "wasmtime-py" => Box::new(wit_bindgen_gen_wasmtime_py::Opts::default().build()),
+        "js" => Box::new(wit_bindgen_gen_js::Opts::default().build()),
+        "c" => Box::new(wit_bindgen_gen_c::Opts::default().build()),
+        "markdown" => Box::new(wit_bindgen_gen_markdown::Opts::default().build()),
+        _ => bail!(
+            "cannot check generator `{}`, expected one of: {}",
+            name,
+            CHECKED_GENERATORS.join(", ")
+        ),
+    })
+}
+
+/// Parses each of `files`, resolving any `use` items, and then asks each
+/// generator which constructs it doesn't support in either direction.
+///
+/// Unsupported constructs are only warnings unless `generators` explicitly
+/// asked for the generator in question.
+fn check(generators: &[String], include_dirs: &[PathBuf], files: &[PathBuf]) -> Result<()> {
+    let explicit = !generators.is_empty();
+    let generators = if explicit {
+        generators.iter().map(|s| s.as_str()).collect()
+    } else {
+        CHECKED_GENERATORS.to_vec()
+    };
+    for name in generators.iter() {
+        check_generator(name)?;
+    }
+
+    let mut errors = 0;
+    for file in files {
+        let iface = match Interface::parse_file_with_include_dirs(file, include_dirs) {
+            Ok(iface) => iface,
+            Err(e) => {
+                eprintln!("error: {:#}\n", e);
+                errors += match e.downcast_ref::<wit_parser::ParseError>() {
+                    Some(e) => e.diagnostics().len(),
+                    None => 1,
+                };
+                continue;
+            }
         };
-        println!("Generating {:?}", dst);
-        if let Some(parent) = dst.parent() {
-            std::fs::create_dir_all(parent)
-                .with_context(|| format!("failed to create {:?}", parent))?;
+        for name in generators.iter() {
+            let gen = check_generator(name)?;
+            // Merge identical failures from both directions into one
+            // diagnostic.
+            let mut found: Vec<(wit_bindgen_gen_core::Unsupported, Vec<&str>)> = Vec::new();
+            for (dir, dir_name) in
+                [(Direction::Import, "import"), (Direction::Export, "export")].iter()
+            {
+                for u in wit_bindgen_gen_core::unsupported(&*gen, &iface, *dir) {
+                    match found
+                        .iter_mut()
+                        .find(|(prev, _)| prev.item == u.item && prev.message == u.message)
+                    {
+                        Some((_, dirs)) => dirs.push(dir_name),
+                        None => found.push((u, vec![dir_name])),
+                    }
+                }
+            }
+
+            let level = if explicit { "error" } else { "warning" };
+            for (u, dirs) in found {
+                let msg = format!(
+                    "{}: `{}` cannot generate {} bindings for `{}`: {}",
+                    level,
+                    name,
+                    dirs.join(" or "),
+                    u.item,
+                    u.message,
+                );
+                // Items `use`d from another file are highlighted in the file
+                // that they're declared in.
+                match &u.span.file {
+                    Some(path) => {
+                        let contents = std::fs::read_to_string(path)
+                            .with_context(|| format!("failed to read: {}", path.display()))?;
+                        eprintln!("{}\n", u.span.highlight(&contents, &msg))
+                    }
+                    None => eprintln!("{}: {}\n", file.display(), msg),
+                }
+                if explicit {
+                    errors += 1;
+                }
+            }
         }
-        std::fs::write(&dst, contents).with_context(|| format!("failed to write {:?}", dst))?;
     }
 
+    if errors > 0 {
+        bail!("check failed with {} error(s)", errors);
+    }
     Ok(())
 }
+
+/// Formats each of `files` in place, or with `check` prints a diff for each
+/// one that isn't already formatted.
+fn fmt(files: &[PathBuf], check: bool) -> Result<()> {
+    let mut unformatted = 0;
+    for file in files {
+        let contents = std::fs::read_to_string(file)
+            .with_context(|| format!("failed to read: {}", file.display()))?;
+        let formatted = wit_parser::format(file, &contents)?;
+        if formatted == contents {
+            continue;
+        }
+        if check {
+            unformatted += 1;
+            let name = file.display().to_string();
+            print_diff(&name, contents.as_bytes(), &name, formatted.as_bytes());
+        } else {
+            std::fs::write(file, formatted)
+                .with_context(|| format!("failed to write: {}", file.display()))?;
+        }
+    }
+    if unformatted > 0 {
+        bail!("{} file(s) are not formatted", unformatted);
+    }
+    Ok(())
+}
+
+/// Compares the interfaces in `old` and `new`, printing every change between
+/// them and failing if any are breaking.
+fn compat(old: &Path, new: &Path, include_dirs: &[PathBuf], json: bool) -> Result<()> {
+    let old = Interface::parse_file_with_include_dirs(old, include_dirs)?;
+    let new = Interface::parse_file_with_include_dirs(new, include_dirs)?;
+    let changes = wit_parser::compat::compare(&old, &new);
+    let breaking = changes.iter().filter(|c| c.is_breaking()).count();
+
+    if json {
+        let changes = changes
+            .iter()
+            .map(|c| {
+                let mut change = serde_json::json!({
+                    "item": c.item,
+                    "item-kind": c.item_kind.as_str(),
+                    "kind": c.kind.as_str(),
+                    "compatibility": c.compatibility.as_str(),
+                    "message": c.message,
+                });
+                if let Some(file) = &c.span.file {
+                    change["span"] = serde_json::json!({
+                        "file": file.display().to_string(),
+                        "line": c.span.line,
+                        "column": c.span.column,
+                    });
+                }
+                change
+            })
+            .collect::<Vec<_>>();
+        let doc = serde_json::json!({
+            "compatible": breaking == 0,
+            "changes": changes,
+        });
+        println!("{}", serde_json::to_string_pretty(&doc)?);
+    } else {
+        for change in changes.iter() {
+            let msg = format!(
+                "{}: `{}`: {}",
+                change.compatibility.as_str(),
+                change.item,
+                change.message
+            );
+            // Removed items point into the old file and everything else into
+            // the new one.
+            match &change.span.file {
+                Some(path) => {
+                    let contents = std::fs::read_to_string(path)
+                        .with_context(|| format!("failed to read: {}", path.display()))?;
+                    println!("{}\n", change.span.highlight(&contents, &msg));
+                }
+                None => println!("{}\n", msg),
+            }
+        }
+        println!(
+            "{} breaking and {} compatible change(s)",
+            breaking,
+            changes.len() - breaking
+        );
+    }
+
+    if breaking > 0 {
+        bail!("found {} breaking change(s)", breaking);
+    }
+    Ok(())
+}
+
+/// A manifest, passed with `--config`, listing bindings to generate.
+///
+/// ```toml
+/// [[generate]]
+/// generator = "rust-wasm"
+/// out-dir = "src/bindings"
+/// imports = ["wit/host.wit"]
+/// exports = ["wit/exports"]
+///
+/// [generate.options]
+/// unchecked = true
+/// ```
+///
+/// Paths are relative to the directory containing the manifest, and a
+/// directory in `imports` or `exports` stands for all the `*.wit` files within
+/// it. The `options` table holds the generator's command line flags, without
+/// their leading `--`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    #[serde(default)]
+    generate: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct ManifestEntry {
+    generator: String,
+    out_dir: Option<PathBuf>,
+    #[serde(default)]
+    imports: Vec<PathBuf>,
+    #[serde(default)]
+    exports: Vec<PathBuf>,
+    #[serde(default)]
+    options: toml::value::Table,
+}
+
+fn generate_manifest(path: &Path, check: bool) -> Result<()> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    let manifest: Manifest =
+        toml::from_str(&contents).with_context(|| format!("failed to parse {}", path.display()))?;
+    let base = path.parent().unwrap_or_else(|| Path::new(""));
+
+    // Validate every entry before generating anything so a typo in one entry
+    // doesn't leave the others half-regenerated.
+    let commands = manifest
+        .generate
+        .iter()
+        .map(|entry| {
+            entry.command(base).with_context(|| {
+                format!("invalid `{}` entry in {}", entry.generator, path.display())
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let outputs = commands
+        .into_iter()
+        .map(generate)
+        .collect::<Result<Vec<_>>>()?;
+    let (checked, written): (Vec<_>, Vec<_>) = outputs
+        .into_iter()
+        .partition(|output| check || output.check);
+    for output in written.iter() {
+        output.write()?;
+    }
+    report_stale(check_files(&checked)?)
+}
+
+impl ManifestEntry {
+    /// Translates this entry into the same `Command` that the equivalent
+    /// invocation on the command line would produce.
+    fn command(&self, base: &Path) -> Result<Command> {
+        let mut args: Vec<OsString> = vec!["wit-bindgen".into(), self.generator.clone().into()];
+        let out_dir = base.join(self.out_dir.as_deref().unwrap_or(Path::new("")));
+        if !out_dir.as_os_str().is_empty() {
+            args.push("--out-dir".into());
+            args.push(out_dir.into());
+        }
+        for (flag, paths) in [("--import", &self.imports), ("--export", &self.exports)].iter() {
+            for path in paths.iter() {
+                for wit in wit_files(&base.join(path))? {
+                    args.push((*flag).into());
+                    args.push(wit.into());
+                }
+            }
+        }
+        for (name, value) in self.options.iter() {
+            let values = match value {
+                toml::Value::Array(values) => values.iter().collect(),
+                value => vec![value],
+            };
+            for value in values {
+                let flag = format!("--{}", name);
+                match value {
+                    toml::Value::Boolean(true) => args.push(flag.into()),
+                    toml::Value::Boolean(false) => {}
+                    toml::Value::String(s) => {
+                        args.push(flag.into());
+                        args.push(s.into());
+                    }
+                    toml::Value::Integer(_) | toml::Value::Float(_) => {
+                        args.push(flag.into());
+                        args.push(value.to_string().into());
+                    }
+                    _ => bail!("unsupported value for option `{}`", name),
+                }
+            }
+        }
+
+        let opt = Opt::from_iter_safe(&args).map_err(|e| anyhow!("{}", e.message))?;
+        match opt.command {
+            Some(Command::Check { .. }) => bail!("`check` is not a generator"),
+            Some(Command::Fmt { .. }) => bail!("`fmt` is not a generator"),
+            Some(Command::Compat { .. }) => bail!("`compat` is not a generator"),
+            Some(command) => Ok(command),
+            None => bail!("`{}` is not a generator", self.generator),
+        }
+    }
+}
+
+/// Returns `path` itself if it's a file, or all the `*.wit` files directly
+/// within it, in sorted order, if it's a directory.
+fn wit_files(path: &Path) -> Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = Vec::new();
+    for entry in
+        std::fs::read_dir(path).with_context(|| format!("failed to read {}", path.display()))?
+    {
+        let path = entry?.path();
+        if path.extension().and_then(|s| s.to_str()) == Some("wit") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
@@ -0,0 +1,78 @@
+from exports.bindings import Exports
+from imports.bindings import add_imports_to_linker, Imports
+from typing import Optional
+import asyncio
+import sys
+import wasmtime
+
+class MyImports:
+    def __init__(self) -> None:
+        self.hit = False
+        self.entered = 0
+        self.all_entered = asyncio.Event()
+
+    async def thunk(self) -> None:
+        if self.hit:
+            print('second time in thunk, raising an error')
+            raise Exception('catch me')
+        print('first time in thunk')
+        await asyncio.sleep(0)
+        self.hit = True
+
+    async def concurrent1(self, a: int) -> int:
+        assert(a == 1)
+        await self.enter_concurrent()
+        return 11
+
+    async def concurrent2(self, a: int) -> int:
+        assert(a == 2)
+        await self.enter_concurrent()
+        return 12
+
+    async def concurrent3(self, a: int) -> int:
+        assert(a == 3)
+        await self.enter_concurrent()
+        return 13
+
+    # Only returns once all three `concurrent*` functions have been entered,
+    # ensuring that they're all in flight at the same time.
+    async def enter_concurrent(self) -> None:
+        self.entered += 1
+        if self.entered == 3:
+            self.all_entered.set()
+        await self.all_entered.wait()
+
+async def run(wasm_file: str) -> None:
+    store = wasmtime.Store()
+    module = wasmtime.Module.from_file(store.engine, wasm_file)
+    linker = wasmtime.Linker(store.engine)
+    linker.define_wasi()
+    wasi = wasmtime.WasiConfig()
+    wasi.inherit_stdout()
+    wasi.inherit_stderr()
+    store.set_wasi(wasi)
+
+    imports = MyImports()
+    add_imports_to_linker(linker, store, imports)
+    wasm = Exports(store, linker, module)
+
+    init_bytes = wasm.allocated_bytes(store)
+    await wasm.thunk(store)
+    assert(imports.hit)
+    assert(init_bytes == wasm.allocated_bytes(store))
+
+    # Make sure that errors on the host make their way back to whomever's
+    # doing the actual `await`
+    err: Optional[Exception] = None
+    try:
+        await wasm.thunk(store)
+    except Exception as e:
+        err = e
+    assert(err is not None)
+    assert('catch me' in str(err))
+
+    await wasm.test_concurrent(store)
+    assert(imports.entered == 3)
+
+if __name__ == '__main__':
+    asyncio.run(run(sys.argv[1]))
@@ -0,0 +1,148 @@
+wit_bindgen_wasmtime::export!("../../tests/runtime/async_functions/imports.wit");
+
+use anyhow::Result;
+use imports::*;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Poll, Wake, Waker};
+use std::thread;
+use wit_bindgen_wasmtime::{Async, BoxFuture};
+
+type Context = crate::Context<MyImports, MyExports>;
+
+#[derive(Default)]
+pub struct MyImports {
+    hit: bool,
+    concurrent: Arc<Mutex<Concurrent>>,
+}
+
+#[derive(Default)]
+struct Concurrent {
+    entered: usize,
+    wakers: Vec<Waker>,
+}
+
+impl Imports for MyImports {
+    fn thunk(&mut self) -> BoxFuture<'static, Result<(), wasmtime::Trap>> {
+        if self.hit {
+            println!("second time in thunk, returning an error");
+            return Box::pin(async { Err(wasmtime::Trap::new("catch me")) });
+        }
+        println!("first time in thunk");
+        self.hit = true;
+        Box::pin(async { Ok(()) })
+    }
+
+    fn concurrent1(&mut self, a: u32) -> BoxFuture<'static, Result<u32, wasmtime::Trap>> {
+        assert_eq!(a, 1);
+        let entered = self.enter_concurrent();
+        Box::pin(async move {
+            entered.await;
+            Ok(11)
+        })
+    }
+
+    fn concurrent2(&mut self, a: u32) -> BoxFuture<'static, Result<u32, wasmtime::Trap>> {
+        assert_eq!(a, 2);
+        let entered = self.enter_concurrent();
+        Box::pin(async move {
+            entered.await;
+            Ok(12)
+        })
+    }
+
+    fn concurrent3(&mut self, a: u32) -> BoxFuture<'static, Result<u32, wasmtime::Trap>> {
+        assert_eq!(a, 3);
+        let entered = self.enter_concurrent();
+        Box::pin(async move {
+            entered.await;
+            Ok(13)
+        })
+    }
+}
+
+impl MyImports {
+    /// Returns a future which resolves only once all three `concurrent*`
+    /// functions have been polled, ensuring that they're all in flight at the
+    /// same time.
+    fn enter_concurrent(&self) -> impl Future<Output = ()> {
+        let state = self.concurrent.clone();
+        let mut entered = false;
+        std::future::poll_fn(move |cx| {
+            let mut state = state.lock().unwrap();
+            if !entered {
+                entered = true;
+                state.entered += 1;
+            }
+            if state.entered < 3 {
+                state.wakers.push(cx.waker().clone());
+                return Poll::Pending;
+            }
+            for waker in state.wakers.drain(..) {
+                waker.wake();
+            }
+            Poll::Ready(())
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct MyExports {
+    data: exports::ExportsData,
+    async_: Async<Context>,
+}
+
+wit_bindgen_wasmtime::import!("../../tests/runtime/async_functions/exports.wit");
+
+fn run(wasm: &str) -> Result<()> {
+    let (exports, mut store) = crate::instantiate(
+        wasm,
+        |linker| {
+            imports::add_to_linker(linker, |cx| -> (&mut MyImports, &mut Async<Context>) {
+                (&mut cx.imports, &mut cx.exports.async_)
+            })
+        },
+        |store, module, linker| {
+            exports::Exports::instantiate(store, module, linker, |cx| {
+                (&mut cx.exports.data, &mut cx.exports.async_)
+            })
+        },
+    )?;
+
+    let init_bytes = exports.allocated_bytes(&mut store)?;
+    block_on(exports.thunk(&mut store))?;
+    assert!(store.data().imports.hit);
+    assert_eq!(init_bytes, exports.allocated_bytes(&mut store)?);
+
+    // Make sure that errors on the host make their way back to whomever's
+    // doing the actual `await`
+    let err = block_on(exports.thunk(&mut store)).unwrap_err();
+    assert!(err.to_string().contains("catch me"), "bad error: {}", err);
+
+    block_on(exports.test_concurrent(&mut store))?;
+    assert_eq!(store.data().imports.concurrent.lock().unwrap().entered, 3);
+
+    Ok(())
+}
+
+/// Minimal executor which runs `future` to completion on the current thread.
+fn block_on<F: Future>(future: F) -> F::Output {
+    struct ThreadWaker(thread::Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Arc::new(ThreadWaker(thread::current())).into();
+    let mut cx = std::task::Context::from_waker(&waker);
+    let mut future = Box::pin(future);
+    loop {
+        match Pin::as_mut(&mut future).poll(&mut cx) {
+            Poll::Ready(result) => break result,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
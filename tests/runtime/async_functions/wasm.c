@@ -0,0 +1,78 @@
+#include <assert.h>
+#include <exports.h>
+#include <imports.h>
+#include <stdlib.h>
+
+// "custom allocator" which just keeps track of allocated bytes
+
+static size_t ALLOCATED_BYTES = 0;
+
+__attribute__((export_name("canonical_abi_realloc")))
+void *canonical_abi_realloc( void *ptr, size_t orig_size, size_t orig_align, size_t new_size) {
+  void *ret = realloc(ptr, new_size);
+  if (!ret)
+    abort();
+  ALLOCATED_BYTES -= orig_size;
+  ALLOCATED_BYTES += new_size;
+  return ret;
+}
+
+__attribute__((export_name("canonical_abi_free")))
+void canonical_abi_free(void *ptr, size_t size, size_t align) {
+  if (size > 0) {
+    ALLOCATED_BYTES -= size;
+    free(ptr);
+  }
+}
+
+uint32_t exports_allocated_bytes(void) {
+  return ALLOCATED_BYTES;
+}
+
+static void thunk_done(void *ctx) {
+  exports_thunk_done(ctx);
+}
+
+void exports_thunk(void *ctx) {
+  imports_thunk(thunk_done, ctx);
+}
+
+// State shared between the three concurrent calls made by
+// `exports_test_concurrent`, which completes once all of them have.
+typedef struct {
+  void *ctx;
+  int remaining;
+} concurrent_t;
+
+static void concurrent_done(concurrent_t *state) {
+  state->remaining -= 1;
+  if (state->remaining > 0)
+    return;
+  void *ctx = state->ctx;
+  free(state);
+  exports_test_concurrent_done(ctx);
+}
+
+static void concurrent1_done(void *ctx, uint32_t ret) {
+  assert(ret == 11);
+  concurrent_done(ctx);
+}
+
+static void concurrent2_done(void *ctx, uint32_t ret) {
+  assert(ret == 12);
+  concurrent_done(ctx);
+}
+
+static void concurrent3_done(void *ctx, uint32_t ret) {
+  assert(ret == 13);
+  concurrent_done(ctx);
+}
+
+void exports_test_concurrent(void *ctx) {
+  concurrent_t *state = malloc(sizeof(concurrent_t));
+  state->ctx = ctx;
+  state->remaining = 3;
+  imports_concurrent1(1, concurrent1_done, state);
+  imports_concurrent2(2, concurrent2_done, state);
+  imports_concurrent3(3, concurrent3_done, state);
+}
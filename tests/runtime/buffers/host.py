@@ -1,8 +1,11 @@
+from exported_buffers.bindings import ExportedBuffers
 from exports.bindings import Exports
 from imports.bindings import add_imports_to_linker, Imports
 from typing import Tuple, List
+import exported_buffers.bindings as b
 import exports.bindings as e
 import imports.bindings as i
+import os
 import sys
 import wasmtime
 
@@ -83,9 +86,35 @@ def run(wasm_file: str) -> None:
 
     imports = MyImports()
     add_imports_to_linker(linker, store, imports)
-    wasm = Exports(store, linker, module)
 
+    # Only the Rust guest, built as `buffers.wasm`, implements
+    # `exported-buffers.wit` since other guests' bindings generators can't
+    # export buffers yet. Its buffer intrinsics are defined in `linker` while
+    # instantiating it, so this has to come first.
+    if os.path.basename(wasm_file) == 'buffers.wasm':
+        test_exported_buffers(store, ExportedBuffers(store, linker, module))
+
+    wasm = Exports(store, linker, module)
     wasm.test_imports(store)
 
+def test_exported_buffers(store: wasmtime.Store, wasm: ExportedBuffers) -> None:
+    out: b.PushBuffer[int] = b.PushBuffer(10)
+    assert(wasm.buffer_u8(store, b'\x00', out) == 3)
+    assert(out.items == [1, 2, 3])
+
+    out = b.PushBuffer(10)
+    assert(wasm.buffer_u32(store, [0], out) == 3)
+    assert(out.items == [1, 2, 3])
+
+    bools: b.PushBuffer[bool] = b.PushBuffer(0)
+    assert(wasm.buffer_bool(store, [], bools) == 0)
+    assert(bools.items == [])
+
+    bools = b.PushBuffer(4)
+    assert(wasm.buffer_bool(store, [True, False, True], bools) == 3)
+    assert(bools.items == [False, True, False])
+
+    wasm.buffer_typedef(store, b.PushBuffer(1), [], b.PushBuffer(0), [True, False])
+
 if __name__ == '__main__':
     run(sys.argv[1])
@@ -2,6 +2,8 @@ wit_bindgen_wasmtime::export!("../../tests/runtime/buffers/imports.wit");
 
 use anyhow::Result;
 use imports::*;
+use std::iter;
+use std::path::Path;
 use wit_bindgen_wasmtime::exports::{PullBuffer, PushBuffer};
 use wit_bindgen_wasmtime::Le;
 
@@ -116,43 +118,71 @@ impl Imports for MyImports {
 }
 
 wit_bindgen_wasmtime::import!("../../tests/runtime/buffers/exports.wit");
+wit_bindgen_wasmtime::import!("../../tests/runtime/buffers/exported-buffers.wit");
 
 fn run(wasm: &str) -> Result<()> {
+    use exported_buffers::{ExportedBuffers, ExportedBuffersData};
     use exports::*;
 
-    let (exports, mut store) = crate::instantiate(
-        wasm,
-        |linker| imports::add_to_linker(linker, |cx| -> &mut MyImports { &mut cx.imports }),
-        |store, module, linker| Exports::instantiate(store, module, linker, |cx| &mut cx.exports),
-    )?;
+    let ((exports, buffers), mut store) =
+        crate::instantiate::<_, (ExportsData, ExportedBuffersData), _>(
+            wasm,
+            |linker| imports::add_to_linker(linker, |cx| -> &mut MyImports { &mut cx.imports }),
+            |store, module, linker| {
+                ExportedBuffers::add_to_linker(linker, |cx| &mut cx.exports.1)?;
+                let (exports, instance) =
+                    Exports::instantiate(&mut *store, module, linker, |cx| &mut cx.exports.0)?;
+                // Only the Rust guest, built as `buffers.wasm`, implements
+                // `exported-buffers.wit` since other guests' bindings
+                // generators can't export buffers yet.
+                let buffers = if Path::new(wasm).file_stem().unwrap() == "buffers" {
+                    Some(ExportedBuffers::new(&mut *store, &instance, |cx| {
+                        &mut cx.exports.1
+                    })?)
+                } else {
+                    None
+                };
+                Ok(((exports, buffers), instance))
+            },
+        )?;
 
     exports.test_imports(&mut store)?;
-    // fn buffers(wasm: &Wasm<Context>) -> Result<()> {
-    //     let mut out = [0; 10];
-    //     let n = wasm.buffer_u8(&[0u8], &mut out)? as usize;
-    //     assert_eq!(n, 3);
-    //     assert_eq!(&out[..n], [1, 2, 3]);
-    //     assert!(out[n..].iter().all(|x| *x == 0));
 
-    //     let mut out = [0; 10];
-    //     let n = wasm.buffer_u32(&[0], &mut out)? as usize;
-    //     assert_eq!(n, 3);
-    //     assert_eq!(&out[..n], [1, 2, 3]);
-    //     assert!(out[n..].iter().all(|x| *x == 0));
+    if let Some(buffers) = buffers {
+        let mut out = [0; 10];
+        let n = buffers.buffer_u8(&mut store, &[0u8], &mut out)? as usize;
+        assert_eq!(n, 3);
+        assert_eq!(&out[..n], [1, 2, 3]);
+        assert!(out[n..].iter().all(|x| *x == 0));
+
+        let mut out = [0; 10];
+        let n = buffers.buffer_u32(&mut store, &[0], &mut out)? as usize;
+        assert_eq!(n, 3);
+        assert_eq!(&out[..n], [1, 2, 3]);
+        assert!(out[n..].iter().all(|x| *x == 0));
+
+        assert_eq!(
+            buffers.buffer_bool(&mut store, &mut iter::empty(), &mut Vec::new())?,
+            0
+        );
+
+        let mut bools = [true, false, true].iter().copied();
+        let mut out = Vec::with_capacity(4);
+        let n = buffers.buffer_bool(&mut store, &mut bools, &mut out)?;
+        assert_eq!(n, 3);
+        assert_eq!(out, [false, true, false]);
 
-    //     assert_eq!(wasm.buffer_bool(&mut iter::empty(), &mut Vec::new())?, 0);
+        let mut bools = [true, false].iter().copied();
+        buffers.buffer_typedef(&mut store, &mut [0], &[], &mut Vec::new(), &mut bools)?;
+    }
+
+    // fn buffers(wasm: &Wasm<Context>) -> Result<()> {
     //     assert_eq!(wasm.buffer_string(&mut iter::empty(), &mut Vec::new())?, 0);
     //     assert_eq!(
     //         wasm.buffer_list_bool(&mut iter::empty(), &mut Vec::new())?,
     //         0
     //     );
 
-    //     let mut bools = [true, false, true].iter().copied();
-    //     let mut out = Vec::with_capacity(4);
-    //     let n = wasm.buffer_bool(&mut bools, &mut out)?;
-    //     assert_eq!(n, 3);
-    //     assert_eq!(out, [false, true, false]);
-
     //     let mut strings = ["foo", "bar", "baz"].iter().copied();
     //     let mut out = Vec::with_capacity(3);
     //     let n = wasm.buffer_string(&mut strings, &mut out)?;
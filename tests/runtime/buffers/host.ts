@@ -66,6 +66,18 @@ async function run() {
   addImportsToImports(importObj, imports, name => instance.exports[name]);
   const wasi = addWasiToImports(importObj);
 
+  // Some guests also implement `exported-buffers.wit`, which these bindings
+  // can't call yet, so its buffer intrinsics only need to exist to link.
+  const unsupported = () => { throw new Error('exported buffers are not supported'); };
+  Object.assign(importObj, {
+    wit_canonical_buffer_abi: {
+      in_len: unsupported,
+      in_read: unsupported,
+      out_len: unsupported,
+      out_write: unsupported,
+    },
+  });
+
   const wasm = new Exports();
   await wasm.instantiate(getWasm(), importObj);
   wasi.start(wasm.instance);
@@ -1,7 +1,9 @@
 wit_bindgen_rust::import!("../../tests/runtime/buffers/imports.wit");
 wit_bindgen_rust::export!("../../tests/runtime/buffers/exports.wit");
+wit_bindgen_rust::export!("../../tests/runtime/buffers/exported-buffers.wit");
 
 use std::iter;
+use wit_bindgen_rust::exports::{InBuffer, InBufferRaw, OutBuffer, OutBufferRaw};
 
 struct Exports;
 
@@ -101,37 +103,6 @@ impl exports::Exports for Exports {
         }
     }
 
-    // fn buffer_u8( in_: InBufferRaw<'_, u8>, out: OutBufferRaw<'_, u8>) -> u32 {
-    //     assert_eq!(in_.len(), 1);
-    //     let mut input = [0];
-    //     in_.copy(&mut input);
-    //     assert_eq!(input, [0]);
-
-    //     assert_eq!(out.capacity(), 10);
-    //     out.write(&[1, 2, 3]);
-    //     3
-    // }
-
-    // fn buffer_u32( in_: InBufferRaw<'_, u32>, out: OutBufferRaw<'_, u32>) -> u32 {
-    //     assert_eq!(in_.len(), 1);
-    //     let mut input = [0];
-    //     in_.copy(&mut input);
-    //     assert_eq!(input, [0]);
-
-    //     assert_eq!(out.capacity(), 10);
-    //     out.write(&[1, 2, 3]);
-    //     3
-    // }
-
-    // fn buffer_bool( in_: InBuffer<'_, bool>, out: OutBuffer<'_, bool>) -> u32 {
-    //     assert!(in_.len() <= out.capacity());
-    //     let len = in_.len();
-    //     let mut storage = vec![0; in_.len() * in_.element_size()];
-    //     let items = in_.iter(&mut storage).map(|b| !b).collect::<Vec<_>>();
-    //     out.write(&mut storage, items.into_iter());
-    //     len as u32
-    // }
-
     // fn buffer_string( in_: InBuffer<'_, String>, out: OutBuffer<'_, String>) -> u32 {
     //     assert!(in_.len() <= out.capacity());
     //     let len = in_.len();
@@ -194,12 +165,51 @@ impl exports::Exports for Exports {
     // }
 
     // fn buffer_in_record( _: BufferInRecord<'_>) {}
-    // fn buffer_typedef(
-    //
-    //     _: ParamInBufferU8<'_>,
-    //     _: ParamOutBufferU8<'_>,
-    //     _: ParamInBufferBool<'_>,
-    //     _: ParamOutBufferBool<'_>,
-    // ) {
-    // }
+}
+
+struct ExportedBuffers;
+
+impl exported_buffers::ExportedBuffers for ExportedBuffers {
+    fn buffer_u8(a: InBufferRaw<'_, u8>, b: OutBufferRaw<'_, u8>) -> u32 {
+        assert_eq!(a.len(), 1);
+        let mut input = [1];
+        a.copy(&mut input);
+        assert_eq!(input, [0]);
+
+        assert_eq!(b.capacity(), 10);
+        b.write(&[1, 2, 3]);
+        3
+    }
+
+    fn buffer_u32(a: InBufferRaw<'_, u32>, b: OutBufferRaw<'_, u32>) -> u32 {
+        assert_eq!(a.len(), 1);
+        let mut input = [1];
+        a.copy(&mut input);
+        assert_eq!(input, [0]);
+
+        assert_eq!(b.capacity(), 10);
+        b.write(&[1, 2, 3]);
+        3
+    }
+
+    fn buffer_bool(a: InBuffer<'_, bool>, b: OutBuffer<'_, bool>) -> u32 {
+        assert!(a.len() <= b.capacity());
+        let len = a.len();
+        let mut storage = vec![0; a.len() * a.element_size()];
+        let items = a.iter(&mut storage).map(|b| !b).collect::<Vec<_>>();
+        b.write(&mut storage, items.into_iter());
+        len as u32
+    }
+
+    fn buffer_typedef(
+        a: exported_buffers::ParamInBufferU8<'_>,
+        b: exported_buffers::ParamOutBufferU8<'_>,
+        c: exported_buffers::ParamInBufferBool<'_>,
+        d: exported_buffers::ParamOutBufferBool<'_>,
+    ) {
+        assert_eq!(a.capacity(), 1);
+        assert_eq!(b.len(), 0);
+        assert_eq!(c.capacity(), 0);
+        assert_eq!(d.len(), 2);
+    }
 }
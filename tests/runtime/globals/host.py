@@ -0,0 +1,32 @@
+from exports.bindings import Exports
+from imports.bindings import add_imports_to_linker, Imports
+import sys
+import wasmtime
+
+class MyImports:
+    def answer(self) -> int:
+        return 42
+
+    def greeting(self) -> str:
+        return 'hello from the host'
+
+def run(wasm_file: str) -> None:
+    store = wasmtime.Store()
+    module = wasmtime.Module.from_file(store.engine, wasm_file)
+    linker = wasmtime.Linker(store.engine)
+    linker.define_wasi()
+    wasi = wasmtime.WasiConfig()
+    wasi.inherit_stdout()
+    wasi.inherit_stderr()
+    store.set_wasi(wasi)
+
+    imports = MyImports()
+    add_imports_to_linker(linker, store, imports)
+    wasm = Exports(store, linker, module)
+
+    wasm.test_imports(store)
+    assert(wasm.answer(store) == 42)
+    assert(wasm.greeting(store) == 'hello from the guest')
+
+if __name__ == '__main__':
+    run(sys.argv[1])
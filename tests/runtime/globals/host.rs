@@ -0,0 +1,34 @@
+use anyhow::Result;
+
+wit_bindgen_wasmtime::export!("../../tests/runtime/globals/imports.wit");
+
+#[derive(Default)]
+pub struct MyImports;
+
+impl imports::Imports for MyImports {
+    fn answer(&mut self) -> u32 {
+        42
+    }
+
+    fn greeting(&mut self) -> String {
+        "hello from the host".to_string()
+    }
+}
+
+wit_bindgen_wasmtime::import!("../../tests/runtime/globals/exports.wit");
+
+fn run(wasm: &str) -> Result<()> {
+    let (exports, mut store) = crate::instantiate(
+        wasm,
+        |linker| imports::add_to_linker(linker, |cx| -> &mut MyImports { &mut cx.imports }),
+        |store, module, linker| {
+            exports::Exports::instantiate(store, module, linker, |cx| &mut cx.exports)
+        },
+    )?;
+
+    exports.test_imports(&mut store)?;
+    assert_eq!(exports.answer(&mut store)?, 42);
+    assert_eq!(exports.greeting(&mut store)?, "hello from the guest");
+
+    Ok(())
+}
@@ -0,0 +1,24 @@
+import { addImportsToImports, Imports } from "./imports.js";
+import { Exports } from "./exports.js";
+import { getWasm, addWasiToImports } from "./helpers.js";
+// @ts-ignore
+import * as assert from 'assert';
+
+async function run() {
+  const importObj = {};
+  addImportsToImports(importObj, {
+    answer() { return 42; },
+    greeting() { return "hello from the host"; },
+  });
+  const wasi = addWasiToImports(importObj);
+
+  const wasm = new Exports();
+  await wasm.instantiate(getWasm(), importObj);
+  wasi.start(wasm.instance);
+
+  wasm.testImports();
+  assert.strictEqual(wasm.answer(), 42);
+  assert.strictEqual(wasm.greeting(), "hello from the guest");
+}
+
+await run()
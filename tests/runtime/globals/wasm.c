@@ -0,0 +1,22 @@
+#include <assert.h>
+#include <imports.h>
+#include <exports.h>
+#include <string.h>
+
+void exports_test_imports() {
+  assert(imports_answer() == 42);
+
+  imports_string_t greeting;
+  imports_greeting(&greeting);
+  assert(greeting.len == strlen("hello from the host"));
+  assert(memcmp(greeting.ptr, "hello from the host", greeting.len) == 0);
+  imports_string_free(&greeting);
+}
+
+uint32_t exports_answer() {
+  return imports_answer();
+}
+
+void exports_greeting(exports_string_t *ret) {
+  exports_string_dup(ret, "hello from the guest");
+}
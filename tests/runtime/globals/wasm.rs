@@ -0,0 +1,19 @@
+wit_bindgen_rust::import!("../../tests/runtime/globals/imports.wit");
+wit_bindgen_rust::export!("../../tests/runtime/globals/exports.wit");
+
+struct Exports;
+
+impl exports::Exports for Exports {
+    fn test_imports() {
+        assert_eq!(imports::answer(), 42);
+        assert_eq!(imports::greeting(), "hello from the host");
+    }
+
+    fn answer() -> u32 {
+        imports::answer()
+    }
+
+    fn greeting() -> String {
+        "hello from the guest".to_string()
+    }
+}
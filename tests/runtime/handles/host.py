@@ -129,7 +129,11 @@ def run(wasm_file: str) -> None:
     assert(wasm.wasm_state2_saw_close(store) == False)
     s2: e.WasmState2 = wasm.wasm_state2_create(store)
     assert(wasm.wasm_state2_saw_close(store) == False)
+    s3 = s2.clone()
     s2.drop(store)
+    # ... but only once every clone has been dropped
+    assert(wasm.wasm_state2_saw_close(store) == False)
+    s3.drop(store)
     assert(wasm.wasm_state2_saw_close(store) == True)
 
     arg1 = wasm.wasm_state_create(store)
@@ -171,10 +175,10 @@ def run(wasm_file: str) -> None:
     s.drop(store)
 
     md = e.Markdown.create(store, wasm)
-    if md:
-        md.append(store, "red is the best color")
-        assert(md.render(store) == "green is the best color")
-        md.drop(store)
+    assert(md is not None)
+    md.append(store, "red is the best color")
+    assert(md.render(store) == "green is the best color")
+    md.drop(store)
 
 if __name__ == '__main__':
     run(sys.argv[1])
@@ -3,6 +3,9 @@ wit_bindgen_wasmtime::export!("../../tests/runtime/handles/imports.wit");
 use anyhow::Result;
 use imports::*;
 use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use wit_bindgen_wasmtime::dynamic::{self, Interface, Value};
 
 #[derive(Default)]
 pub struct MyImports {
@@ -87,6 +90,129 @@ impl Imports for MyImports {
     fn odd_name_frob_the_odd(&mut self, _: &()) {}
 }
 
+/// The resources behind the handles given out by `DynamicImports`.
+enum DynamicResource {
+    HostState(u32),
+    HostState2,
+    Markdown(Mutex<String>),
+    OddName,
+}
+
+/// Implements the same imports as `MyImports`, but for an interface loaded at
+/// runtime, where handles are indices into `handles`.
+#[derive(Default)]
+struct DynamicImports {
+    handles: HashMap<u32, Arc<DynamicResource>>,
+    next_handle: u32,
+    host_state2_closed: bool,
+}
+
+impl DynamicImports {
+    fn insert(&mut self, resource: DynamicResource) -> Value {
+        self.insert_arc(Arc::new(resource))
+    }
+
+    fn insert_arc(&mut self, resource: Arc<DynamicResource>) -> Value {
+        self.next_handle += 1;
+        self.handles.insert(self.next_handle, resource);
+        Value::Handle(self.next_handle)
+    }
+
+    fn get(&self, handle: &Value) -> Result<&Arc<DynamicResource>> {
+        match handle {
+            Value::Handle(idx) => self
+                .handles
+                .get(idx)
+                .ok_or_else(|| anyhow::anyhow!("invalid handle {}", idx)),
+            other => anyhow::bail!("expected a handle, got {:?}", other),
+        }
+    }
+
+    fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Vec<Value>> {
+        let some = |value| Value::Variant {
+            case: 1,
+            payload: Some(Box::new(value)),
+        };
+        let case0 = |value| Value::Variant {
+            case: 0,
+            payload: Some(Box::new(value)),
+        };
+        Ok(match name {
+            "host-state-create" => vec![self.insert(DynamicResource::HostState(100))],
+            "host-state-get" => match &**self.get(&args[0])? {
+                DynamicResource::HostState(val) => vec![Value::U32(*val)],
+                _ => anyhow::bail!("not a `host-state`"),
+            },
+            "host-state2-create" => vec![self.insert(DynamicResource::HostState2)],
+            "host-state2-saw-close" => vec![Value::Bool(self.host_state2_closed)],
+            "two-host-states" => {
+                self.get(&args[0])?;
+                self.get(&args[1])?;
+                vec![
+                    self.insert(DynamicResource::HostState(2)),
+                    self.insert(DynamicResource::HostState2),
+                ]
+            }
+            "host-state2-param-record"
+            | "host-state2-param-tuple"
+            | "host-state2-param-option"
+            | "host-state2-param-result"
+            | "host-state2-param-variant"
+            | "host-state2-param-list" => Vec::new(),
+            "host-state2-result-record" | "host-state2-result-tuple" => {
+                vec![Value::Record(
+                    vec![self.insert(DynamicResource::HostState2)],
+                )]
+            }
+            "host-state2-result-option" => vec![some(self.insert(DynamicResource::HostState2))],
+            "host-state2-result-result" | "host-state2-result-variant" => {
+                vec![case0(self.insert(DynamicResource::HostState2))]
+            }
+            "host-state2-result-list" => vec![Value::List(vec![
+                self.insert(DynamicResource::HostState2),
+                self.insert(DynamicResource::HostState2),
+            ])],
+            "markdown2::create" => {
+                vec![self.insert(DynamicResource::Markdown(Mutex::default()))]
+            }
+            "markdown2::append" => match (&**self.get(&args[0])?, &args[1]) {
+                (DynamicResource::Markdown(md), Value::String(buf)) => {
+                    md.lock().unwrap().push_str(buf);
+                    Vec::new()
+                }
+                _ => anyhow::bail!("invalid arguments to `markdown2::append`"),
+            },
+            "markdown2::render" => match &**self.get(&args[0])? {
+                DynamicResource::Markdown(md) => {
+                    vec![Value::String(md.lock().unwrap().replace("red", "green"))]
+                }
+                _ => anyhow::bail!("not a `markdown2`"),
+            },
+            "odd-name::create" => vec![self.insert(DynamicResource::OddName)],
+            "odd-name::frob-the-odd" => Vec::new(),
+            _ if name.ends_with("::clone") => {
+                let resource = self.get(&args[0])?.clone();
+                vec![self.insert_arc(resource)]
+            }
+            _ if name.ends_with("::drop") => {
+                let idx = match args[0] {
+                    Value::Handle(idx) => idx,
+                    _ => anyhow::bail!("expected a handle"),
+                };
+                let resource = self
+                    .handles
+                    .remove(&idx)
+                    .ok_or_else(|| anyhow::anyhow!("invalid handle {}", idx))?;
+                if let Ok(DynamicResource::HostState2) = Arc::try_unwrap(resource) {
+                    self.host_state2_closed = true;
+                }
+                Vec::new()
+            }
+            _ => anyhow::bail!("unknown function `{}`", name),
+        })
+    }
+}
+
 wit_bindgen_wasmtime::import!("../../tests/runtime/handles/exports.wit");
 
 fn run(wasm: &str) -> Result<()> {
@@ -152,5 +278,28 @@ fn run(wasm: &str) -> Result<()> {
     for s in exports.wasm_state2_result_list(&mut store)? {
         exports.drop_wasm_state2(&mut store, s)?;
     }
+
+    let md = exports.markdown_create(&mut store)?.unwrap();
+    exports.markdown_append(&mut store, &md, "red is the best color")?;
+    assert_eq!(
+        exports.markdown_render(&mut store, &md)?,
+        "green is the best color"
+    );
+    exports.drop_markdown(&mut store, md)?;
+
+    // The imports, and the handles they return, can also be defined for an
+    // interface loaded at runtime.
+    let (exports, mut store) = crate::instantiate::<MyImports, _, _>(
+        wasm,
+        |linker| {
+            let iface = Interface::parse_file("../../tests/runtime/handles/imports.wit")?;
+            let imports = Mutex::new(DynamicImports::default());
+            dynamic::add_to_linker(linker, iface, move |name, args| {
+                imports.lock().unwrap().call(name, args)
+            })
+        },
+        |store, module, linker| Exports::instantiate(store, module, linker, |cx| &mut cx.exports),
+    )?;
+    exports.test_imports(&mut store)?;
     Ok(())
 }
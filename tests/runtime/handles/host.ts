@@ -117,11 +117,11 @@ async function run() {
   s.drop();
 
   const md = exports.Markdown.create(wasm);
-  if (md) {
-    md.append("red is the best color");
-    assert.strictEqual(md.render(), "green is the best color");
-    md.drop();
-  }
+  if (md === null)
+    throw new Error('should be some');
+  md.append("red is the best color");
+  assert.strictEqual(md.render(), "green is the best color");
+  md.drop();
 }
 
 await run()
@@ -210,14 +210,51 @@ void exports_wasm_state2_result_list(exports_list_wasm_state2_t *ret0) {
   ret0->ptr[1] = exports_wasm_state2_new((void*) 888);
 }
 
+typedef struct {
+  char *ptr;
+  size_t len;
+} markdown_t;
+
 bool exports_markdown_create(exports_markdown_t *md) {
-  return false;
+  *md = exports_markdown_new(calloc(1, sizeof(markdown_t)));
+  return true;
+}
+
+void exports_markdown_dtor(void *data) {
+  markdown_t *md = data;
+  free(md->ptr);
+  free(md);
 }
 
 void exports_markdown_append(exports_markdown_t md, exports_string_t *s) {
-  abort();
+  markdown_t *data = exports_markdown_get(&md);
+  data->ptr = realloc(data->ptr, data->len + s->len);
+  memcpy(data->ptr + data->len, s->ptr, s->len);
+  data->len += s->len;
+  exports_string_free(s);
+  exports_markdown_free(&md);
 }
 
 void exports_markdown_render(exports_markdown_t md, exports_string_t *ret) {
-  abort();
+  markdown_t *data = exports_markdown_get(&md);
+
+  // Renders the buffer with every "red" replaced by "green".
+  size_t reds = 0;
+  for (size_t i = 0; i + 3 <= data->len; i++) {
+    if (memcmp(data->ptr + i, "red", 3) == 0)
+      reds++;
+  }
+  ret->len = data->len + 2 * reds;
+  ret->ptr = malloc(ret->len);
+  size_t j = 0;
+  for (size_t i = 0; i < data->len;) {
+    if (i + 3 <= data->len && memcmp(data->ptr + i, "red", 3) == 0) {
+      memcpy(ret->ptr + j, "green", 5);
+      i += 3;
+      j += 5;
+    } else {
+      ret->ptr[j++] = data->ptr[i++];
+    }
+  }
+  exports_markdown_free(&md);
 }
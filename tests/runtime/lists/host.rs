@@ -3,6 +3,7 @@ use anyhow::Result;
 wit_bindgen_wasmtime::export!("../../tests/runtime/lists/imports.wit");
 
 use imports::*;
+use wit_bindgen_wasmtime::dynamic::{self, Interface, Value};
 use wit_bindgen_wasmtime::Le;
 
 #[derive(Default)]
@@ -126,15 +127,85 @@ impl Imports for MyImports {
     }
 }
 
+fn string(s: &str) -> Value {
+    Value::String(s.to_string())
+}
+
+fn strings(s: &[&str]) -> Value {
+    Value::List(s.iter().map(|s| string(s)).collect())
+}
+
+/// Implements the same imports as `MyImports`, but for an interface loaded at
+/// runtime.
+/// Returns `len` flags with only the flag at `set` set.
+fn flags(len: usize, set: usize) -> Value {
+    Value::Flags((0..len).map(|i| i == set).collect())
+}
+
+fn dynamic_imports(name: &str, args: Vec<Value>) -> Result<Vec<Value>> {
+    Ok(match name {
+        "list-param" => {
+            assert_eq!(args, [Value::List((1..5).map(Value::U8).collect())]);
+            Vec::new()
+        }
+        "list-param2" => {
+            assert_eq!(args, [string("foo")]);
+            Vec::new()
+        }
+        "list-param3" => {
+            assert_eq!(args, [strings(&["foo", "bar", "baz"])]);
+            Vec::new()
+        }
+        "list-param4" => {
+            let expected = Value::List(vec![strings(&["foo", "bar"]), strings(&["baz"])]);
+            assert_eq!(args, [expected]);
+            Vec::new()
+        }
+        "list-result" => vec![Value::List((1..6).map(Value::U8).collect())],
+        "list-result2" => vec![string("hello!")],
+        "list-result3" => vec![strings(&["hello,", "world!"])],
+        "list-minmax8" | "list-minmax16" | "list-minmax32" | "list-minmax64"
+        | "list-minmax-float" | "list-roundtrip" | "string-roundtrip" => args,
+        "unaligned-roundtrip1" => {
+            let expected = [
+                Value::List(vec![Value::U16(1)]),
+                Value::List(vec![Value::U32(2)]),
+                Value::List(vec![Value::U64(3)]),
+                Value::List(vec![flags(32, 8)]),
+                Value::List(vec![flags(64, 9)]),
+            ];
+            assert_eq!(args, expected);
+            Vec::new()
+        }
+        "unaligned-roundtrip2" => {
+            let expected = [
+                Value::List(vec![Value::Record(vec![Value::U32(10), Value::U64(11)])]),
+                Value::List(vec![Value::F32(100.0)]),
+                Value::List(vec![Value::F64(101.0)]),
+                strings(&["foo"]),
+                Value::List(vec![Value::List(vec![Value::U8(102)])]),
+            ];
+            assert_eq!(args, expected);
+            Vec::new()
+        }
+        _ => anyhow::bail!("unknown function `{}`", name),
+    })
+}
+
 wit_bindgen_wasmtime::import!("../../tests/runtime/lists/exports.wit");
 
 fn run(wasm: &str) -> Result<()> {
     use exports::*;
 
+    let mut instance = None;
     let (exports, mut store) = crate::instantiate(
         wasm,
         |linker| imports::add_to_linker(linker, |cx| -> &mut MyImports { &mut cx.imports }),
-        |store, module, linker| Exports::instantiate(store, module, linker, |cx| &mut cx.exports),
+        |store, module, linker| {
+            let ret = Exports::instantiate(store, module, linker, |cx| &mut cx.exports)?;
+            instance = Some(ret.1);
+            Ok(ret)
+        },
     )?;
 
     let bytes = exports.allocated_bytes(&mut store)?;
@@ -155,5 +226,62 @@ fn run(wasm: &str) -> Result<()> {
     // Ensure that we properly called `free` everywhere in all the glue that we
     // needed to.
     assert_eq!(bytes, exports.allocated_bytes(&mut store)?);
+
+    // The same exports can be called through an interface loaded at runtime.
+    let iface = Interface::parse_file("../../tests/runtime/lists/exports.wit")?;
+    let dynamic = dynamic::Exports::new(&mut store, &instance.unwrap(), iface)?;
+    dynamic.call(
+        &mut store,
+        "list-param",
+        &[Value::List((1..5).map(Value::U8).collect())],
+    )?;
+    dynamic.call(&mut store, "list-param2", &[string("foo")])?;
+    dynamic.call(
+        &mut store,
+        "list-param3",
+        &[strings(&["foo", "bar", "baz"])],
+    )?;
+    dynamic.call(
+        &mut store,
+        "list-param4",
+        &[Value::List(vec![
+            strings(&["foo", "bar"]),
+            strings(&["baz"]),
+        ])],
+    )?;
+    assert_eq!(
+        dynamic.call(&mut store, "list-result", &[])?,
+        [Value::List((1..6).map(Value::U8).collect())]
+    );
+    assert_eq!(
+        dynamic.call(&mut store, "list-result2", &[])?,
+        [string("hello!")]
+    );
+    assert_eq!(
+        dynamic.call(&mut store, "list-result3", &[])?,
+        [strings(&["hello,", "world!"])]
+    );
+    assert_eq!(
+        dynamic.call(&mut store, "string-roundtrip", &[string("hello ⚑ world")])?,
+        [string("hello ⚑ world")]
+    );
+    assert!(dynamic
+        .call(&mut store, "list-result", &[string("x")])
+        .is_err());
+    assert!(dynamic
+        .call(&mut store, "list-param2", &[Value::U8(1)])
+        .is_err());
+    assert_eq!(bytes, exports.allocated_bytes(&mut store)?);
+
+    // ... and the imports can be defined for one too.
+    let (exports, mut store) = crate::instantiate::<MyImports, _, _>(
+        wasm,
+        |linker| {
+            let iface = Interface::parse_file("../../tests/runtime/lists/imports.wit")?;
+            dynamic::add_to_linker(linker, iface, dynamic_imports)
+        },
+        |store, module, linker| Exports::instantiate(store, module, linker, |cx| &mut cx.exports),
+    )?;
+    exports.test_imports(&mut store)?;
     Ok(())
 }